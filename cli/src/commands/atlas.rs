@@ -1,31 +1,57 @@
-use anyhow::{Result, anyhow};
-use atlas::types::OfficialAtlas;
-use clap::Subcommand;
-use image::ImageReader;
+use super::resources::{load_manifest, save_manifest};
+use anyhow::{Context, Result, anyhow};
+use atlas::types::{OfficialAtlas, PathOrPaths, Resource};
+use atlas::{
+    PackHeuristic, PackerConfig, frame_grid, join_frames, pack_official_atlas, split_frames,
+};
+use clap::{Args, Subcommand};
+use image::{DynamicImage, ImageReader, RgbaImage};
+use pvz2_resources::{
+    MSubgroupWrapper, PathDef, ResourceGroup, ShellSubgroupData, SlotMode, renumber_slots,
+};
+use rsb::ptx::{
+    decoder::PtxDecoder,
+    dither::PtxEncodeOptions,
+    encoder::PtxEncoder,
+    mipmap::{MipFilter, level_data_size, surface_layout},
+    registry::{PtxPlatform, PtxRegistry},
+    types::PtxFormat,
+};
+use rsb::schema::types::{ManifestRes, RsbManifest};
 use std::fs;
 use std::path::{Path, PathBuf};
-use texture_packer::TexturePackerConfig;
-use texture_packer::exporter::ImageExporter;
-use texture_packer::importer::ImageImporter;
 
 #[derive(Subcommand)]
 pub enum AtlasCommand {
     /// Split an Atlas into individual sprites
     Split {
-        /// Input Atlas JSON file
+        /// Input Atlas JSON file, or an unpacked RSB directory to split every atlas group
         json_path: PathBuf,
         /// Input Image file (optional, defaults to json name + .png)
         #[arg(short, long)]
         image: Option<PathBuf>,
-        /// Output directory (optional, defaults to json name + .sprite/media)
+        /// Output directory (optional, defaults to json name + .sprite/media, or
+        /// `atlases` inside an RSB directory)
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Resources manifest of an RSB directory (optional, defaults to the
+        /// PROPERTIES/RESOURCES file found in its packets)
+        #[arg(short, long)]
+        manifest: Option<PathBuf>,
+        /// Is target platform PowerVR (iOS) -> PVRTC textures?
+        #[arg(short, long)]
+        powervr: bool,
+        /// Encryption seed of the resources manifest, for encrypted RTONs
+        #[arg(long)]
+        seed: Option<String>,
     },
     /// Merge individual sprites into an Atlas
     Merge {
-        /// Input Atlas JSON file (used as layout definition)
+        /// Input Atlas JSON file (used as layout definition), or an unpacked RSB directory
+        /// to re-pack every split atlas group
         json_path: PathBuf,
-        /// Input directory containing sprites (optional, defaults to json name + .sprite/media)
+        /// Input directory containing sprites (optional, defaults to json name + .sprite/media,
+        /// or `atlases` inside an RSB directory)
         #[arg(short, long)]
         input: Option<PathBuf>,
         /// Output image file (optional, defaults to json name + .png)
@@ -34,26 +60,145 @@ pub enum AtlasCommand {
         /// Output updated JSON file (optional, defaults to overwriting input json)
         #[arg(long)]
         output_json: Option<PathBuf>,
+        /// Resources manifest of an RSB directory to update (optional, defaults to every
+        /// PROPERTIES/RESOURCES file found in its packets)
+        #[arg(short, long)]
+        manifest: Option<PathBuf>,
+        /// Is target platform PowerVR (iOS) -> PVRTC textures?
+        #[arg(short, long)]
+        powervr: bool,
+        /// Encryption seed of the resources manifest, for encrypted RTONs
+        #[arg(long)]
+        seed: Option<String>,
+        #[command(flatten)]
+        packing: PackingArgs,
     },
 }
 
+/// Atlas packing options shared by commands that build atlases
+#[derive(Args, Debug, Clone)]
+pub struct PackingArgs {
+    /// Maximum atlas page width
+    #[arg(long, default_value_t = 8192)]
+    pub max_width: u32,
+    /// Maximum atlas page height
+    #[arg(long, default_value_t = 8192)]
+    pub max_height: u32,
+    /// Transparent pixels between sprites
+    #[arg(long, default_value_t = 0)]
+    pub padding: u32,
+    /// Transparent pixels around the page edge
+    #[arg(long, default_value_t = 0)]
+    pub border: u32,
+    /// Edge pixels repeated around each sprite to stop bleeding when filtered
+    #[arg(long, default_value_t = 0)]
+    pub extrusion: u32,
+    /// Keep the packed page size instead of rounding up to powers of two
+    #[arg(long)]
+    pub no_pot: bool,
+    /// Trim transparent borders off sprites, moving the offset into x/y
+    #[arg(long)]
+    pub trim: bool,
+    /// Spill sprites that do not fit onto extra atlas pages
+    #[arg(long)]
+    pub multi_page: bool,
+    /// Placement heuristic: short-side, long-side, area, bottom-left or contact
+    #[arg(long, default_value = "short-side")]
+    pub heuristic: String,
+}
+
+impl PackingArgs {
+    pub fn to_config(&self) -> Result<PackerConfig> {
+        Ok(PackerConfig {
+            max_width: self.max_width,
+            max_height: self.max_height,
+            padding: self.padding,
+            border: self.border,
+            extrusion: self.extrusion,
+            power_of_two: !self.no_pot,
+            trim: self.trim,
+            allow_rotation: false,
+            multi_page: self.multi_page,
+            heuristic: parse_heuristic(&self.heuristic)?,
+        })
+    }
+}
+
+pub fn parse_heuristic(heuristic: &str) -> Result<PackHeuristic> {
+    match heuristic.to_lowercase().as_str() {
+        "short-side" | "bssf" => Ok(PackHeuristic::BestShortSideFit),
+        "long-side" | "blsf" => Ok(PackHeuristic::BestLongSideFit),
+        "area" | "baf" => Ok(PackHeuristic::BestAreaFit),
+        "bottom-left" | "bl" => Ok(PackHeuristic::BottomLeft),
+        "contact" | "cp" => Ok(PackHeuristic::ContactPoint),
+        _ => Err(anyhow!(
+            "Unknown heuristic: {}, must be short-side, long-side, area, bottom-left or contact",
+            heuristic
+        )),
+    }
+}
+
+/// Page 0 uses `path` itself; later pages get a `_01`, `_02`, ... suffix.
+pub fn page_image_path(path: &Path, page: usize) -> PathBuf {
+    if page == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}_{:02}.{}", stem, page, ext))
+}
+
 pub fn handle(cmd: AtlasCommand) -> Result<()> {
     match cmd {
+        AtlasCommand::Split {
+            json_path,
+            output,
+            manifest,
+            powervr,
+            seed,
+            ..
+        } if is_rsb_project(&json_path) => split_rsb_project(
+            &json_path,
+            manifest.as_deref(),
+            output.as_deref(),
+            powervr,
+            seed.as_deref(),
+        ),
         AtlasCommand::Split {
             json_path,
             image,
             output,
+            ..
         } => split_atlas(&json_path, image.as_deref(), output.as_deref()),
+        AtlasCommand::Merge {
+            json_path,
+            input,
+            manifest,
+            powervr,
+            seed,
+            packing,
+            ..
+        } if is_rsb_project(&json_path) => merge_rsb_project(
+            &json_path,
+            manifest.as_deref(),
+            input.as_deref(),
+            powervr,
+            seed.as_deref(),
+            &packing.to_config()?,
+        ),
         AtlasCommand::Merge {
             json_path,
             input,
             output_image,
             output_json,
+            packing,
+            ..
         } => merge_atlas(
             &json_path,
             input.as_deref(),
             output_image.as_deref(),
             output_json.as_deref(),
+            &packing.to_config()?,
         ),
     }
 }
@@ -90,8 +235,22 @@ pub fn split_atlas(
 
     println!("Using Image: {:?}", img_path);
 
-    // 3. Open Image
-    let img = ImageReader::open(&img_path)?.decode()?;
+    // 3. Open Images, one per atlas page; later pages carry a `_01`, `_02`, ... suffix
+    let page_ids: Vec<&str> = atlas
+        .resources
+        .iter()
+        .filter(|r| r.atlas == Some(true))
+        .map(|r| r.id.as_str())
+        .collect();
+    let mut pages = vec![ImageReader::open(&img_path)?.decode()?];
+    for page in 1..page_ids.len() {
+        let page_path = page_image_path(&img_path, page);
+        if !page_path.exists() {
+            eprintln!("Warning: Atlas page not found: {:?}", page_path);
+            break;
+        }
+        pages.push(ImageReader::open(&page_path)?.decode()?);
+    }
 
     // 4. Determine Output Directory
     let out_dir = if let Some(p) = output_dir {
@@ -114,8 +273,22 @@ pub fn split_atlas(
 
     // 5. Iterate and Split
     let mut count = 0;
-    for res in atlas.resources {
+    for res in &atlas.resources {
         if let (Some(ax), Some(ay), Some(aw), Some(ah)) = (res.ax, res.ay, res.aw, res.ah) {
+            // Sprites without a parent live on the first page
+            let page = match &res.parent {
+                Some(parent) => page_ids.iter().position(|id| id == parent),
+                None => Some(0),
+            };
+            let Some(img) = page.and_then(|page| pages.get(page)) else {
+                eprintln!(
+                    "Warning: Atlas page {} of resource '{}' is not available. Skipping.",
+                    res.parent.as_deref().unwrap_or_default(),
+                    res.id
+                );
+                continue;
+            };
+
             // Check bounds to avoid panics or errors
             if ax + aw > img.width() || ay + ah > img.height() {
                 eprintln!(
@@ -131,13 +304,10 @@ pub fn split_atlas(
                 continue;
             }
 
-            let sub_img = img.crop_imm(ax, ay, aw, ah);
-            // Default to ID for filename.
-            // Sanitize ID?
-            let filename = format!("{}.png", res.id);
-            let save_path = out_dir.join(&filename);
-
-            sub_img.save(&save_path)?;
+            let sub_img = img.crop_imm(ax, ay, aw, ah).to_rgba8();
+            let grid = frame_grid(res.cols, res.rows)
+                .with_context(|| format!("Failed to split sprite '{}'", res.id))?;
+            save_sprite(&sub_img, &res.id, grid, &out_dir)?;
             count += 1;
         }
     }
@@ -152,6 +322,7 @@ pub fn merge_atlas(
     input_dir: Option<&Path>,
     output_image: Option<&Path>,
     output_json: Option<&Path>,
+    config: &PackerConfig,
 ) -> Result<()> {
     // 1. Read JSON
     let json_content = fs::read_to_string(json_path)?;
@@ -183,107 +354,1017 @@ pub fn merge_atlas(
 
     println!("Using Input Directory: {:?}", in_dir);
 
-    // 3. Configure Texture Packer
-    // Use a large maximum size, e.g., 4096x4096 or 8192x8192
-    let config = TexturePackerConfig {
-        max_width: 8192,
-        max_height: 8192,
-        allow_rotation: false,
-        texture_outlines: false,
-        border_padding: 0,
-        texture_padding: 0,
-        trim: false, // We rely on original JSON for coordinates
-        texture_extrusion: 0,
-        force_max_dimensions: false,
+    // 3. Load Sprites
+    let sprites = load_sprites(&atlas, &in_dir)?;
+
+    // 4. Pack and update coordinates in JSON
+    let pages = pack_official_atlas(&mut atlas, &sprites, config)?;
+    println!(
+        "Successfully packed {} sprites into {} page(s).",
+        sprites.len(),
+        pages.len()
+    );
+
+    // 5. Save Images
+    let out_img_path = if let Some(p) = output_image {
+        p.to_path_buf()
+    } else {
+        json_path.with_extension("png")
+    };
+
+    for (index, page) in pages.iter().enumerate() {
+        let page_path = page_image_path(&out_img_path, index);
+        page.save(&page_path)?;
+        println!(
+            "Saved atlas page {}x{} to {:?}",
+            page.width(),
+            page.height(),
+            page_path
+        );
+    }
+
+    // 6. Save JSON
+    let out_json_path = if let Some(p) = output_json {
+        p.to_path_buf()
+    } else {
+        json_path.to_path_buf()
     };
 
-    let mut packer = texture_packer::TexturePacker::new_skyline(config);
+    let updated_json = serde_json::to_string_pretty(&atlas)?;
+    fs::write(&out_json_path, updated_json)?;
+    println!("Saved updated atlas JSON to {:?}", out_json_path);
 
-    // 4. Load Images and Pack
-    let mut packed_count = 0;
+    Ok(())
+}
+
+/// Save a sprite as `<id>.png`, or an animation strip as `<id>/00.png`, `<id>/01.png`, ...
+fn save_sprite(
+    sprite: &RgbaImage,
+    id: &str,
+    grid: Option<(u32, u32)>,
+    out_dir: &Path,
+) -> Result<()> {
+    let Some((cols, rows)) = grid else {
+        sprite.save(out_dir.join(format!("{}.png", id)))?;
+        return Ok(());
+    };
+    let frame_dir = out_dir.join(id);
+    fs::create_dir_all(&frame_dir)?;
+    for (index, frame) in split_frames(sprite, cols, rows)
+        .with_context(|| format!("Failed to split {} into frames", id))?
+        .iter()
+        .enumerate()
+    {
+        frame.save(frame_dir.join(format!("{:02}.png", index)))?;
+    }
+    Ok(())
+}
+
+/// Load a sprite saved by [`save_sprite`], re-assembling strips from their frames.
+/// A strip given as a single `<id>.png` is used as-is.
+fn load_sprite(id: &str, grid: Option<(u32, u32)>, in_dir: &Path) -> Result<Option<RgbaImage>> {
+    let frame_dir = in_dir.join(id);
+    if let Some((cols, rows)) = grid.filter(|_| frame_dir.is_dir()) {
+        let frames = (0..cols * rows)
+            .map(|index| {
+                let path = frame_dir.join(format!("{:02}.png", index));
+                Ok(ImageReader::open(&path)
+                    .with_context(|| format!("Frame not found: {:?}", path))?
+                    .with_guessed_format()?
+                    .decode()?
+                    .to_rgba8())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        return Ok(Some(join_frames(&frames, cols, rows)?));
+    }
+
+    let sprite_path = in_dir.join(format!("{}.png", id));
+    if !sprite_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(
+        ImageReader::open(&sprite_path)?
+            .with_guessed_format()?
+            .decode()?
+            .to_rgba8(),
+    ))
+}
+
+/// Load every sprite of the atlas. A missing sprite is an error, since its resource
+/// would keep a rectangle in a page that no longer holds it.
+fn load_sprites(atlas: &OfficialAtlas, in_dir: &Path) -> Result<Vec<(String, RgbaImage)>> {
+    let mut sprites = Vec::new();
     for res in &atlas.resources {
-        let filename = format!("{}.png", res.id);
-        let sprite_path = in_dir.join(&filename);
-
-        if sprite_path.exists() {
-            if let Ok(texture) = ImageImporter::import_from_file(&sprite_path) {
-                if let Err(e) = packer.pack_own(res.id.clone(), texture) {
-                    eprintln!("Warning: Failed to pack '{}': {:?}", res.id, e);
-                } else {
-                    packed_count += 1;
-                }
-            } else {
-                eprintln!("Warning: Failed to load image '{}'", filename);
+        if res.atlas == Some(true) {
+            continue;
+        }
+        let grid = frame_grid(res.cols, res.rows)
+            .with_context(|| format!("Failed to load sprite '{}'", res.id))?;
+        let sprite = load_sprite(&res.id, grid, in_dir)
+            .with_context(|| format!("Failed to load sprite '{}'", res.id))?
+            .ok_or_else(|| anyhow!("Sprite not found: {:?}", in_dir.join(&res.id)))?;
+        sprites.push((res.id.clone(), sprite));
+    }
+
+    Ok(sprites)
+}
+
+/// An unpacked RSB directory, as written by `rsb unpack`
+pub fn is_rsb_project(path: &Path) -> bool {
+    path.is_dir() && path.join("rsb_manifest.json").is_file()
+}
+
+/// Position of a file in `rsb_manifest.json`: group, subgroup and resource index
+type FileLocation = (usize, usize, usize);
+
+/// The textures of an unpacked RSB and the `rsb_manifest.json` describing them
+struct RsbProject {
+    dir: PathBuf,
+    manifest: RsbManifest,
+}
+
+impl RsbProject {
+    fn open(dir: &Path) -> Result<Self> {
+        let manifest_path = dir.join("rsb_manifest.json");
+        let manifest = serde_json::from_str(
+            &fs::read_to_string(&manifest_path)
+                .with_context(|| format!("Failed to read {:?}", manifest_path))?,
+        )
+        .with_context(|| format!("Failed to parse {:?}", manifest_path))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            manifest,
+        })
+    }
+
+    fn locations(&self) -> impl Iterator<Item = (FileLocation, &ManifestRes)> {
+        self.manifest
+            .group
+            .iter()
+            .enumerate()
+            .flat_map(|(g, group)| {
+                group.subgroup.iter().enumerate().flat_map(move |(s, sub)| {
+                    sub.packet_info
+                        .res
+                        .iter()
+                        .enumerate()
+                        .map(move |(r, res)| ((g, s, r), res))
+                })
+            })
+    }
+
+    fn res(&self, (g, s, r): FileLocation) -> &ManifestRes {
+        &self.manifest.group[g].subgroup[s].packet_info.res[r]
+    }
+
+    fn res_mut(&mut self, (g, s, r): FileLocation) -> &mut ManifestRes {
+        &mut self.manifest.group[g].subgroup[s].packet_info.res[r]
+    }
+
+    fn file_path(&self, (g, s, r): FileLocation) -> PathBuf {
+        let sub = &self.manifest.group[g].subgroup[s];
+        self.dir
+            .join(&sub.name_packet)
+            .join(sub.packet_info.res[r].path.replace('\\', "/"))
+    }
+
+    /// Find the PTX a manifest path refers to; manifest paths omit the extension
+    fn find_texture(&self, path: &PathDef) -> Option<FileLocation> {
+        let wanted = normalize_rsb_path(&path.to_joined());
+        self.locations()
+            .find(|(_, res)| res.ptx_info.is_some() && normalize_rsb_path(&res.path) == wanted)
+            .map(|(location, _)| location)
+    }
+
+    /// `PROPERTIES\RESOURCES.RTON`, `.NEWTON` or `.JSON` files present on disk
+    fn resource_manifests(&self) -> Vec<PathBuf> {
+        self.locations()
+            .filter(|(_, res)| {
+                let path = res.path.replace('\\', "/").to_uppercase();
+                let path = Path::new(&path);
+                path.file_stem().is_some_and(|stem| stem == "RESOURCES")
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext == "RTON" || ext == "NEWTON" || ext == "JSON")
+            })
+            .map(|(location, _)| self.file_path(location))
+            .filter(|path| path.is_file())
+            .collect()
+    }
+
+    /// Decode a texture from its PTX, falling back to the PNG left next to it by unpack
+    fn load_texture(&self, location: FileLocation, is_powervr: bool) -> Result<DynamicImage> {
+        let res = self.res(location);
+        let path = self.file_path(location);
+        match &res.ptx_info {
+            Some(ptx) if path.is_file() => {
+                let (width, height) = texture_size(res);
+                PtxDecoder::decode(
+                    &fs::read(&path)?,
+                    width,
+                    height,
+                    ptx.format,
+                    ptx.alpha_size,
+                    ptx.alpha_format,
+                    is_powervr,
+                )
+                .with_context(|| format!("Failed to decode {:?}", path))
+            }
+            _ => {
+                let png_path = path.with_extension("png");
+                Ok(ImageReader::open(&png_path)
+                    .with_context(|| format!("Texture not found: {:?}", path))?
+                    .decode()?)
             }
-        } else {
-            eprintln!("Warning: Sprite not found: {:?}", sprite_path);
         }
     }
 
-    println!("Successfully packed {} sprites.", packed_count);
+    /// Layout of the texture's PTX on disk. Ids shared by several layouts (30, 147) are
+    /// told apart by the size of the base level, as when decoding.
+    fn texture_format(&self, location: FileLocation, is_powervr: bool) -> Result<PtxFormat> {
+        let res = self.res(location);
+        let path = self.file_path(location);
+        let Some(ptx) = &res.ptx_info else {
+            return Err(anyhow!("{} is not a PTX texture", res.path));
+        };
+        if !path.is_file() {
+            return Ok(PtxFormat::from(ptx.format));
+        }
+        let (width, height) = texture_size(res);
+        let level_sizes = PtxDecoder::mip_level_sizes(
+            fs::metadata(&path)?.len() as usize,
+            width,
+            height,
+            ptx.format,
+            ptx.alpha_format,
+            is_powervr,
+            ptx.mip_count,
+        )
+        .with_context(|| format!("Failed to read {:?}", path))?;
+        let codec = PtxRegistry::global()
+            .decoder_for(
+                ptx.format,
+                PtxPlatform::from_powervr(is_powervr),
+                level_sizes[0],
+                width,
+                height,
+            )
+            .with_context(|| format!("Failed to read {:?}", path))?;
+        Ok(codec.format())
+    }
 
-    // 5. Update Coordinates in JSON
-    for res in &mut atlas.resources {
-        if let Some(frame) = packer.get_frame(&res.id) {
-            res.ax = Some(frame.frame.x);
-            res.ay = Some(frame.frame.y);
-            res.aw = Some(frame.frame.w);
-            res.ah = Some(frame.frame.h);
+    /// Encode `image` as `format` and record the texture's new size and layout
+    fn encode_texture(
+        &mut self,
+        location: FileLocation,
+        image: &RgbaImage,
+        format: PtxFormat,
+        is_powervr: bool,
+    ) -> Result<Vec<u8>> {
+        let path = self.file_path(location);
+        let res = self.res_mut(location);
+        let Some(ptx) = res.ptx_info.as_mut() else {
+            return Err(anyhow!("{} is not a PTX texture", res.path));
+        };
+        let (width, height) = (image.width(), image.height());
+        let data = PtxEncoder::encode_mips(
+            &DynamicImage::ImageRgba8(image.clone()),
+            format,
+            is_powervr,
+            &PtxEncodeOptions::default(),
+            ptx.mip_count.unwrap_or(1),
+            MipFilter::Box,
+        )
+        .with_context(|| format!("Failed to encode {:?}", path))?;
+
+        // Pitch and alpha plane size follow from the codec's layout of the base level
+        let codec = PtxRegistry::global()
+            .encoder_for(format, PtxPlatform::from_powervr(is_powervr))
+            .with_context(|| format!("Failed to encode {:?}", path))?;
+        let level_size = level_data_size(codec.as_ref(), ptx.alpha_format, width, height);
+        let (pitch, alpha_size) = surface_layout(format, width, height, level_size);
+        ptx.pitch = pitch;
+        ptx.alpha_size = ptx.alpha_size.map(|_| alpha_size);
+        ptx.width = width as i32;
+        ptx.height = height as i32;
+        let alpha_size = ptx.alpha_size;
+        if let Some(property) = res.ptx_property.as_mut() {
+            property.pitch = pitch;
+            property.alpha_size = alpha_size;
+        }
+        if let Some(part1) = res.part1_info.as_mut() {
+            part1.width = width;
+            part1.height = height;
         }
+        Ok(data)
     }
 
-    // 6. Calculate Final Dimensions (Nearest Power of 2)
-    let frames = packer.get_frames();
-    let mut max_x: u32 = 0;
-    let mut max_y: u32 = 0;
-    for frame in frames.values() {
-        if frame.frame.x + frame.frame.w > max_x {
-            max_x = frame.frame.x + frame.frame.w;
+    /// Write encoded texture data, and the PNG `rsb pack` prefers over it
+    fn write_texture(&self, location: FileLocation, data: &[u8], image: &RgbaImage) -> Result<()> {
+        let path = self.file_path(location);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, data)?;
+        // `rsb pack` encodes the PNG when it exists, so keep it in step with the PTX
+        image.save(path.with_extension("png"))?;
+        if let Some(sidecar) = super::ptx::container_sidecar(&path) {
+            eprintln!(
+                "Warning: {:?} takes precedence over the new texture when packing",
+                sidecar
+            );
+        }
+        Ok(())
+    }
+
+    /// Add a texture for an extra atlas page to the packet of `template`
+    fn add_texture(&mut self, template: FileLocation, path: &PathDef) -> FileLocation {
+        let (g, s, _) = template;
+        let mut res = self.res(template).clone();
+        let extension = Path::new(&res.path.replace('\\', "/"))
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        res.path = format!("{}{}", path.to_joined(), extension);
+
+        let packet = &mut self.manifest.group[g].subgroup[s].packet_info.res;
+        let next_id = packet
+            .iter()
+            .filter_map(|r| r.part1_info.as_ref().map(|p| p.id + 1))
+            .max()
+            .unwrap_or(0);
+        if let Some(part1) = res.part1_info.as_mut() {
+            let delta = next_id as i32 - part1.id as i32;
+            part1.id = next_id;
+            if let Some(ptx) = res.ptx_info.as_mut() {
+                ptx.ptx_index += delta;
+            }
+        }
+        packet.push(res);
+        (g, s, packet.len() - 1)
+    }
+
+    /// Write `rsb_manifest.json` and the per-packet `manifest.json` files
+    fn save(&self) -> Result<()> {
+        fs::write(
+            self.dir.join("rsb_manifest.json"),
+            serde_json::to_string_pretty(&self.manifest)?,
+        )?;
+        for group in &self.manifest.group {
+            for sub in &group.subgroup {
+                let packet_manifest = self.dir.join(&sub.name_packet).join("manifest.json");
+                if packet_manifest.is_file() {
+                    fs::write(
+                        packet_manifest,
+                        serde_json::to_string_pretty(&sub.packet_info.res)?,
+                    )?;
+                }
+            }
         }
-        if frame.frame.y + frame.frame.h > max_y {
-            max_y = frame.frame.y + frame.frame.h;
+        Ok(())
+    }
+}
+
+/// Upper-case, backslash-separated path without its extension
+fn normalize_rsb_path(path: &str) -> String {
+    let path = path.replace('/', "\\").to_uppercase();
+    match path.rsplit_once('.') {
+        Some((stem, ext)) if !ext.contains('\\') => stem.to_string(),
+        _ => path,
+    }
+}
+
+/// Texture size, preferring the RSG's own dimensions over the RSB canvas size
+fn texture_size(res: &ManifestRes) -> (u32, u32) {
+    match (&res.part1_info, &res.ptx_info) {
+        (Some(p1), _) if p1.width > 0 && p1.height > 0 => (p1.width, p1.height),
+        (_, Some(ptx)) => (ptx.width as u32, ptx.height as u32),
+        _ => (0, 0),
+    }
+}
+
+/// Atlas images of a manifest group, or `None` when it holds no atlas
+fn group_atlases(group: &ShellSubgroupData) -> Option<Vec<&MSubgroupWrapper>> {
+    let atlases: Vec<_> = group
+        .resources
+        .as_ref()?
+        .iter()
+        .filter(|res| res.atlas == Some(true))
+        .collect();
+    (!atlases.is_empty()).then_some(atlases)
+}
+
+/// Atlas a sprite is cut from: its `parent`, or the only atlas of the group
+fn sprite_atlas<'a>(
+    res: &'a MSubgroupWrapper,
+    atlases: &[&'a MSubgroupWrapper],
+) -> Option<&'a str> {
+    if res.atlas == Some(true) || res.ax.is_none() {
+        return None;
+    }
+    match (&res.parent, atlases) {
+        (Some(parent), _) => Some(parent),
+        (None, [only]) => Some(&only.id),
+        _ => None,
+    }
+}
+
+fn resolve_manifests(project: &RsbProject, manifest: Option<&Path>) -> Result<Vec<PathBuf>> {
+    if let Some(path) = manifest {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let found = project.resource_manifests();
+    if found.is_empty() {
+        return Err(anyhow!(
+            "No PROPERTIES/RESOURCES manifest found in {:?}, pass one with --manifest",
+            project.dir
+        ));
+    }
+    Ok(found)
+}
+
+/// Split every atlas group of an unpacked RSB into `<output>/<group>.sprite/media`.
+pub fn split_rsb_project(
+    rsb_dir: &Path,
+    manifest: Option<&Path>,
+    output_dir: Option<&Path>,
+    is_powervr: bool,
+    seed: Option<&str>,
+) -> Result<()> {
+    let project = RsbProject::open(rsb_dir)?;
+    let manifest_path = resolve_manifests(&project, manifest)?.remove(0);
+    let (resources, format) = load_manifest(&manifest_path, None, seed)?;
+    println!("Using {} manifest: {:?}", format.name(), manifest_path);
+
+    let out_root = output_dir
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| rsb_dir.join("atlases"));
+
+    let mut group_count = 0;
+    let mut sprite_count = 0;
+    for group in &resources.groups {
+        let Some(atlases) = group_atlases(group) else {
+            continue;
+        };
+        let out_dir = out_root.join(format!("{}.sprite", group.id)).join("media");
+        fs::create_dir_all(&out_dir)?;
+
+        let mut count = 0;
+        for atlas in &atlases {
+            let Some(location) = project.find_texture(&atlas.path) else {
+                eprintln!(
+                    "Warning: Texture {} of {} not found in the RSB. Skipping.",
+                    atlas.path.to_joined(),
+                    atlas.id
+                );
+                continue;
+            };
+            let image = project.load_texture(location, is_powervr)?;
+
+            for res in group.resources.iter().flatten() {
+                if sprite_atlas(res, &atlases) != Some(atlas.id.as_str()) {
+                    continue;
+                }
+                let (ax, ay) = (res.ax.unwrap_or(0), res.ay.unwrap_or(0));
+                let (aw, ah) = (res.aw.unwrap_or(0), res.ah.unwrap_or(0));
+                if ax + aw > image.width() || ay + ah > image.height() {
+                    eprintln!(
+                        "Warning: Resource '{}' is out of bounds (x: {}, y: {}, w: {}, h: {}) for {} size {}x{}. Skipping.",
+                        res.id,
+                        ax,
+                        ay,
+                        aw,
+                        ah,
+                        atlas.id,
+                        image.width(),
+                        image.height()
+                    );
+                    continue;
+                }
+                let grid = frame_grid(res.cols, res.rows)
+                    .with_context(|| format!("Failed to split sprite '{}'", res.id))?;
+                save_sprite(
+                    &image.crop_imm(ax, ay, aw, ah).to_rgba8(),
+                    &res.id,
+                    grid,
+                    &out_dir,
+                )?;
+                count += 1;
+            }
         }
+
+        println!("{}: extracted {} sprites", group.id, count);
+        group_count += 1;
+        sprite_count += count;
     }
 
-    let final_width = max_x.next_power_of_two();
-    let final_height = max_y.next_power_of_two();
+    println!(
+        "Successfully extracted {} sprites from {} atlas groups into {:?}.",
+        sprite_count, group_count, out_root
+    );
+    Ok(())
+}
+
+/// Re-pack every atlas group split by [`split_rsb_project`], re-encoding the PTX in its
+/// original format and updating the resources manifests and `rsb_manifest.json`.
+///
+/// Every group is packed and every page encoded before anything is written, so a
+/// missing sprite or an unsuitable texture format leaves the project untouched.
+pub fn merge_rsb_project(
+    rsb_dir: &Path,
+    manifest: Option<&Path>,
+    input_dir: Option<&Path>,
+    is_powervr: bool,
+    seed: Option<&str>,
+    config: &PackerConfig,
+) -> Result<()> {
+    let mut project = RsbProject::open(rsb_dir)?;
+    let mut manifests = Vec::new();
+    for path in resolve_manifests(&project, manifest)? {
+        let (resources, format) = load_manifest(&path, None, seed)?;
+        println!("Using {} manifest: {:?}", format.name(), path);
+        manifests.push((path, resources, format));
+    }
 
-    println!("Final Atlas Size: {}x{}", final_width, final_height);
+    let in_root = input_dir
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| rsb_dir.join("atlases"));
 
-    // We might need to resize the final exported image to power of 2,
-    // texture_packer usually outputs the minimum bounding box.
-    // ImageExporter exports what is provided by texture_packer.
-    let exported_image = ImageExporter::export(&packer, None)
-        .map_err(|e| anyhow!("Failed to export packed image: {}", e))?;
+    // Pack against the first manifest, then apply the same layout to all of them
+    let mut packed = Vec::new();
+    for group in &manifests[0].1.groups {
+        let Some(atlases) = group_atlases(group) else {
+            continue;
+        };
+        let in_dir = in_root.join(format!("{}.sprite", group.id)).join("media");
+        if !in_dir.is_dir() {
+            continue;
+        }
+        let Some(template) = project.find_texture(&atlases[0].path) else {
+            eprintln!(
+                "Warning: Texture {} of {} not found in the RSB. Skipping.",
+                atlases[0].path.to_joined(),
+                group.id
+            );
+            continue;
+        };
 
-    // Create a new image with power-of-two dimensions and copy the packed image into it
-    let mut final_image = image::DynamicImage::new_rgba8(final_width, final_height);
-    if let image::DynamicImage::ImageRgba8(ref mut buf) = final_image {
-        if let image::DynamicImage::ImageRgba8(ref packed_buf) = exported_image {
-            image::imageops::overlay(buf, packed_buf, 0, 0);
+        // PVRTC only stores power-of-two textures
+        if !config.power_of_two {
+            let pvrtc = atlases
+                .iter()
+                .filter_map(|atlas| project.find_texture(&atlas.path))
+                .filter_map(|location| project.res(location).ptx_info.as_ref())
+                .any(|ptx| {
+                    matches!(
+                        PtxFormat::from(ptx.format),
+                        PtxFormat::Pvrtc4BppRgba | PtxFormat::Pvrtc4BppRgbaA8
+                    )
+                });
+            if pvrtc {
+                return Err(anyhow!(
+                    "{} uses PVRTC textures, which need power-of-two sizes; drop --no-pot",
+                    group.id
+                ));
+            }
         }
+
+        let mut atlas = OfficialAtlas {
+            id: group.id.clone(),
+            parent: group.parent.clone().unwrap_or_default(),
+            res: group.res.clone().unwrap_or_default(),
+            type_: group.r#type.clone(),
+            resources: group
+                .resources
+                .iter()
+                .flatten()
+                .filter(|res| res.atlas == Some(true) || sprite_atlas(res, &atlases).is_some())
+                .map(to_atlas_resource)
+                .collect(),
+        };
+        let sprites = load_sprites(&atlas, &in_dir)
+            .with_context(|| format!("Failed to load the sprites of {}", group.id))?;
+        let pages = pack_official_atlas(&mut atlas, &sprites, config)
+            .with_context(|| format!("Failed to pack {}", group.id))?;
+        // Read before the template is re-encoded, for pages that copy its entry
+        let template_format = project.texture_format(template, is_powervr)?;
+        packed.push((atlas, pages, template, template_format));
     }
 
-    // 7. Save Image
-    let out_img_path = if let Some(p) = output_image {
-        p.to_path_buf()
-    } else {
-        json_path.with_extension("png")
-    };
+    let mut encoded = Vec::new();
+    for (atlas, pages, template, template_format) in &packed {
+        let page_resources = atlas.resources.iter().filter(|r| r.atlas == Some(true));
+        for (page, res) in pages.iter().zip(page_resources) {
+            let path = res
+                .path
+                .as_ref()
+                .map(from_atlas_path)
+                .ok_or_else(|| anyhow!("Atlas image {} has no path", res.id))?;
+            let (location, format) = match project.find_texture(&path) {
+                Some(location) => (location, project.texture_format(location, is_powervr)?),
+                None => (project.add_texture(*template, &path), *template_format),
+            };
+            let data = project.encode_texture(location, page, format, is_powervr)?;
+            encoded.push((atlas, res, page, location, data));
+        }
+    }
 
-    final_image.save(&out_img_path)?;
-    println!("Saved merged atlas to {:?}", out_img_path);
+    for (atlas, res, page, location, data) in &encoded {
+        project.write_texture(*location, data, page)?;
+        println!(
+            "{}: saved {} {}x{} as {}",
+            atlas.id,
+            res.id,
+            page.width(),
+            page.height(),
+            project.res(*location).path
+        );
+    }
 
-    // 8. Save JSON
-    let out_json_path = if let Some(p) = output_json {
-        p.to_path_buf()
-    } else {
-        json_path.to_path_buf()
+    for (path, resources, format) in &mut manifests {
+        for (atlas, _, _, _) in &packed {
+            apply_atlas_layout(resources, atlas);
+        }
+        renumber_slots(resources, SlotMode::Append);
+        save_manifest(resources, path, *format, "array", seed)?;
+        println!("Updated {:?}", path);
+    }
+    project.save()?;
+
+    println!("Successfully packed {} atlas groups.", packed.len());
+    Ok(())
+}
+
+pub fn to_atlas_resource(res: &MSubgroupWrapper) -> Resource {
+    Resource {
+        id: res.id.clone(),
+        type_: Some(res.r#type.clone()),
+        path: Some(match &res.path {
+            PathDef::String(path) => PathOrPaths::Single(path.clone()),
+            PathDef::Array(parts) => PathOrPaths::Multiple(parts.clone()),
+        }),
+        width: res.width,
+        height: res.height,
+        ax: res.ax,
+        ay: res.ay,
+        aw: res.aw,
+        ah: res.ah,
+        x: res.x,
+        y: res.y,
+        cols: res.cols,
+        rows: res.rows,
+        atlas: res.atlas,
+        parent: res.parent.clone(),
+        runtime: res.runtime,
+    }
+}
+
+fn from_atlas_path(path: &PathOrPaths) -> PathDef {
+    match path {
+        PathOrPaths::Single(path) => PathDef::String(path.clone()),
+        PathOrPaths::Multiple(parts) => PathDef::Array(parts.clone()),
+    }
+}
+
+/// Copy packed coordinates back into the manifest group with the atlas' id, adding
+/// resources without a slot for any extra pages after the group's last atlas image.
+pub fn apply_atlas_layout(resources: &mut ResourceGroup, atlas: &OfficialAtlas) {
+    let Some(group) = resources.groups.iter_mut().find(|g| g.id == atlas.id) else {
+        return;
+    };
+    let Some(entries) = group.resources.as_mut() else {
+        return;
     };
+    for packed in &atlas.resources {
+        if let Some(res) = entries.iter_mut().find(|r| r.id == packed.id) {
+            res.width = packed.width.or(res.width);
+            res.height = packed.height.or(res.height);
+            res.ax = packed.ax;
+            res.ay = packed.ay;
+            res.aw = packed.aw;
+            res.ah = packed.ah;
+            res.x = packed.x;
+            res.y = packed.y;
+            res.parent = packed.parent.clone();
+            continue;
+        }
+        let Some(last_atlas) = entries.iter().rposition(|r| r.atlas == Some(true)) else {
+            continue;
+        };
+        let mut page = entries[last_atlas].clone();
+        page.id = packed.id.clone();
+        // Numbered by `renumber_slots` once every layout is applied
+        page.slot = None;
+        if let Some(path) = &packed.path {
+            page.path = from_atlas_path(path);
+        }
+        page.width = packed.width;
+        page.height = packed.height;
+        entries.insert(last_atlas + 1, page);
+    }
+}
 
-    let updated_json = serde_json::to_string_pretty(&atlas)?;
-    fs::write(&out_json_path, updated_json)?;
-    println!("Saved updated atlas JSON to {:?}", out_json_path);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::resources::ManifestFormat;
+    use serde_json::json;
 
-    Ok(())
+    const SEED: &str = "atlas_seed";
+
+    /// An unpacked RSB with one 32x32 texture of `format`, an encrypted resources RTON
+    /// and two 16x16 sprites split from it
+    fn sample_project(name: &str, format: i32) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("atlas_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("P1/PROPERTIES")).unwrap();
+
+        let manifest = json!({
+            "version": 4,
+            "ptx_info_size": 16,
+            "path": { "rsgs": ["P1"], "packet_path": "packet" },
+            "group": [{
+                "name": "G",
+                "is_composite": true,
+                "subgroup": [{
+                    "name_packet": "P1",
+                    "category": ["1536", ""],
+                    "packet_info": {
+                        "version": 3,
+                        "compression_flags": 0,
+                        "res": [
+                            {
+                                "path": "ATLASES\\G_1536_00.PTX",
+                                "part1_info": { "id": 0, "width": 32, "height": 32 },
+                                "ptx_info": {
+                                    "ptx_index": 0,
+                                    "width": 32,
+                                    "height": 32,
+                                    "pitch": 128,
+                                    "format": format,
+                                    "alpha_size": null,
+                                    "alpha_format": null
+                                }
+                            },
+                            { "path": "PROPERTIES\\RESOURCES.RTON" }
+                        ]
+                    }
+                }]
+            }]
+        });
+        fs::write(
+            dir.join("rsb_manifest.json"),
+            serde_json::to_string_pretty(&manifest).unwrap(),
+        )
+        .unwrap();
+
+        let resources: ResourceGroup = serde_json::from_value(json!({
+            "slot_count": 3,
+            "groups": [{
+                "id": "G_1536",
+                "type": "simple",
+                "res": "1536",
+                "resources": [
+                    {
+                        "type": "Image",
+                        "slot": 0,
+                        "id": "ATLAS_G_1536_00",
+                        "path": "atlases\\G_1536_00",
+                        "atlas": true,
+                        "width": 32,
+                        "height": 32
+                    },
+                    {
+                        "type": "Image",
+                        "slot": 1,
+                        "id": "IMAGE_ONE",
+                        "path": "images\\one",
+                        "parent": "ATLAS_G_1536_00",
+                        "ax": 0, "ay": 0, "aw": 16, "ah": 16
+                    },
+                    {
+                        "type": "Image",
+                        "slot": 2,
+                        "id": "IMAGE_TWO",
+                        "path": "images\\two",
+                        "parent": "ATLAS_G_1536_00",
+                        "ax": 16, "ay": 0, "aw": 16, "ah": 16
+                    }
+                ]
+            }]
+        }))
+        .unwrap();
+        save_manifest(
+            &resources,
+            &dir.join("P1/PROPERTIES/RESOURCES.RTON"),
+            ManifestFormat::Rton,
+            "array",
+            Some(SEED),
+        )
+        .unwrap();
+
+        let media = dir.join("atlases/G_1536.sprite/media");
+        fs::create_dir_all(&media).unwrap();
+        for (id, shade) in [("IMAGE_ONE", 64), ("IMAGE_TWO", 192)] {
+            RgbaImage::from_pixel(16, 16, image::Rgba([shade, shade, shade, 255]))
+                .save(media.join(format!("{}.png", id)))
+                .unwrap();
+        }
+        dir
+    }
+
+    fn load_resources(dir: &Path) -> ResourceGroup {
+        let path = dir.join("P1/PROPERTIES/RESOURCES.RTON");
+        load_manifest(&path, None, Some(SEED)).unwrap().0
+    }
+
+    fn resource<'a>(resources: &'a ResourceGroup, id: &str) -> &'a MSubgroupWrapper {
+        resources.groups[0]
+            .resources
+            .iter()
+            .flatten()
+            .find(|res| res.id == id)
+            .unwrap()
+    }
+
+    #[test]
+    fn merge_rsb_project_repacks_with_encrypted_manifest() {
+        let dir = sample_project("merge", 0);
+        let config = PackerConfig {
+            max_width: 64,
+            max_height: 16,
+            ..PackerConfig::default()
+        };
+        merge_rsb_project(&dir, None, None, false, Some(SEED), &config).unwrap();
+
+        let resources = load_resources(&dir);
+        let one = resource(&resources, "IMAGE_ONE");
+        let two = resource(&resources, "IMAGE_TWO");
+        assert_eq!(
+            (one.aw, one.ah, two.aw, two.ah),
+            (Some(16), Some(16), Some(16), Some(16))
+        );
+        assert_eq!(one.ay, Some(0));
+        assert_ne!(one.ax, two.ax);
+        let page = resource(&resources, "ATLAS_G_1536_00");
+        assert_eq!((page.width, page.height), (Some(32), Some(16)));
+
+        let project = RsbProject::open(&dir).unwrap();
+        let location = project.find_texture(&page.path).unwrap();
+        let texture = project.load_texture(location, false).unwrap();
+        assert_eq!((texture.width(), texture.height()), (32, 16));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_rsb_project_gives_extra_pages_new_slots() {
+        let dir = sample_project("extra_page", 0);
+        let config = PackerConfig {
+            max_width: 16,
+            max_height: 16,
+            multi_page: true,
+            ..PackerConfig::default()
+        };
+        merge_rsb_project(&dir, None, None, false, Some(SEED), &config).unwrap();
+
+        let resources = load_resources(&dir);
+        assert_eq!(resources.slot_count, 4);
+        let extra = resources.groups[0]
+            .resources
+            .iter()
+            .flatten()
+            .filter(|res| res.atlas == Some(true))
+            .nth(1)
+            .unwrap();
+        assert_eq!(extra.slot, Some(3));
+
+        let project = RsbProject::open(&dir).unwrap();
+        let location = project.find_texture(&extra.path).unwrap();
+        assert!(project.file_path(location).is_file());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_rsb_project_rejects_missing_sprites() {
+        let dir = sample_project("missing_sprite", 0);
+        fs::remove_file(dir.join("atlases/G_1536.sprite/media/IMAGE_TWO.png")).unwrap();
+        let manifest = fs::read(dir.join("P1/PROPERTIES/RESOURCES.RTON")).unwrap();
+
+        let err = merge_rsb_project(
+            &dir,
+            None,
+            None,
+            false,
+            Some(SEED),
+            &PackerConfig::default(),
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("IMAGE_TWO"));
+        assert_eq!(
+            fs::read(dir.join("P1/PROPERTIES/RESOURCES.RTON")).unwrap(),
+            manifest
+        );
+        assert!(!dir.join("P1/ATLASES/G_1536_00.PTX").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_rsb_project_rejects_non_pot_pvrtc() {
+        let dir = sample_project("pvrtc", 30);
+        let config = PackerConfig {
+            power_of_two: false,
+            ..PackerConfig::default()
+        };
+
+        let err = merge_rsb_project(&dir, None, None, true, Some(SEED), &config).unwrap_err();
+        assert!(err.to_string().contains("PVRTC"));
+        assert!(!dir.join("P1/ATLASES/G_1536_00.PTX").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Rewrite the RSB manifest of a sample project
+    fn edit_rsb_manifest(dir: &Path, edit: impl FnOnce(&mut serde_json::Value)) {
+        let path = dir.join("rsb_manifest.json");
+        let mut manifest: serde_json::Value =
+            serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        edit(&mut manifest);
+        fs::write(&path, serde_json::to_string_pretty(&manifest).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn merge_rsb_project_records_codec_layout() {
+        // Format 30 on Android is ETC1 with palette alpha, told apart from PVRTC by size
+        let dir = sample_project("palette", 30);
+        edit_rsb_manifest(&dir, |manifest| {
+            manifest["ptx_info_size"] = json!(20);
+            let res = &mut manifest["group"][0]["subgroup"][0]["packet_info"]["res"][0];
+            res["ptx_info"]["pitch"] = json!(16);
+            res["ptx_info"]["alpha_size"] = json!(1 + 16 + 512);
+        });
+        let old = DynamicImage::new_rgba8(32, 32);
+        let data = PtxEncoder::encode(&old, PtxFormat::Etc1Palette, false).unwrap();
+        fs::create_dir_all(dir.join("P1/ATLASES")).unwrap();
+        fs::write(dir.join("P1/ATLASES/G_1536_00.PTX"), data).unwrap();
+
+        let config = PackerConfig {
+            max_width: 64,
+            max_height: 16,
+            ..PackerConfig::default()
+        };
+        merge_rsb_project(&dir, None, None, false, Some(SEED), &config).unwrap();
+
+        let project = RsbProject::open(&dir).unwrap();
+        let location = project.find_texture(&PathDef::String("atlases\\G_1536_00".into()));
+        let location = location.unwrap();
+        let ptx = project.res(location).ptx_info.as_ref().unwrap();
+        assert_eq!((ptx.width, ptx.height), (32, 16));
+        // 8 ETC1 blocks of 8 bytes per row of blocks; palette and 4-bit indices after them
+        assert_eq!(ptx.pitch, 16);
+        assert_eq!(ptx.alpha_size, Some(1 + 16 + 256));
+        let data = fs::read(project.file_path(location)).unwrap();
+        assert_eq!(data.len(), 256 + 1 + 16 + 256);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn merge_rsb_project_encodes_every_page_before_writing() {
+        let dir = sample_project("atomic", 0);
+        // The second page's texture uses a format nothing can encode
+        edit_rsb_manifest(&dir, |manifest| {
+            let res = &mut manifest["group"][0]["subgroup"][0]["packet_info"]["res"];
+            let mut page = res[0].clone();
+            page["path"] = json!("ATLASES\\G_1536_01.PTX");
+            page["part1_info"]["id"] = json!(1);
+            page["ptx_info"]["ptx_index"] = json!(1);
+            page["ptx_info"]["format"] = json!(99);
+            res.as_array_mut().unwrap().insert(1, page);
+        });
+        let ptx_path = dir.join("P1/ATLASES/G_1536_00.PTX");
+        fs::create_dir_all(ptx_path.parent().unwrap()).unwrap();
+        let old = PtxEncoder::encode(&DynamicImage::new_rgba8(32, 32), PtxFormat::Rgba8888, false)
+            .unwrap();
+        fs::write(&ptx_path, &old).unwrap();
+        let manifest = fs::read(dir.join("P1/PROPERTIES/RESOURCES.RTON")).unwrap();
+        let rsb_manifest = fs::read(dir.join("rsb_manifest.json")).unwrap();
+
+        let config = PackerConfig {
+            max_width: 16,
+            max_height: 16,
+            multi_page: true,
+            ..PackerConfig::default()
+        };
+        let err = merge_rsb_project(&dir, None, None, false, Some(SEED), &config).unwrap_err();
+        assert!(format!("{:#}", err).contains("G_1536_01"));
+        assert_eq!(fs::read(&ptx_path).unwrap(), old);
+        assert!(!ptx_path.with_extension("png").exists());
+        assert_eq!(
+            fs::read(dir.join("P1/PROPERTIES/RESOURCES.RTON")).unwrap(),
+            manifest
+        );
+        assert_eq!(
+            fs::read(dir.join("rsb_manifest.json")).unwrap(),
+            rsb_manifest
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,6 +1,6 @@
 use anyhow::{Context, Result};
 use clap::Subcommand;
-use newton::{MResourceGroup, decode_newton, encode_newton};
+use newton::{MResourceGroup, decode_newton, decode_newton_strict, encode_newton, to_json};
 use std::fs;
 use std::path::{Path, PathBuf};
 
@@ -13,6 +13,9 @@ pub enum NewtonCommands {
         /// Output JSON file (optional)
         #[arg(short, long)]
         output: Option<PathBuf>,
+        /// Fail on resource types newer than this tool instead of preserving them
+        #[arg(long)]
+        strict: bool,
     },
     /// Encode JSON to Newton
     Encode {
@@ -26,16 +29,25 @@ pub enum NewtonCommands {
 
 pub fn handle(cmd: NewtonCommands) -> Result<()> {
     match cmd {
-        NewtonCommands::Decode { input, output } => newton_decode(&input, &output),
+        NewtonCommands::Decode {
+            input,
+            output,
+            strict,
+        } => newton_decode(&input, &output, strict),
         NewtonCommands::Encode { input, output } => newton_encode(&input, &output),
     }
 }
 
-pub fn newton_decode(input: &Path, output: &Option<PathBuf>) -> Result<()> {
+pub fn newton_decode(input: &Path, output: &Option<PathBuf>, strict: bool) -> Result<()> {
     // Decode NTON -> JSON
     let mut file = fs::File::open(input)
         .with_context(|| format!("Failed to open Newton file: {:?}", input))?;
-    let root = decode_newton(&mut file).with_context(|| "Failed to parse Newton format")?;
+    let root = if strict {
+        decode_newton_strict(&mut file)
+    } else {
+        decode_newton(&mut file)
+    }
+    .with_context(|| "Failed to parse Newton format")?;
 
     let out_path = match output {
         Some(p) => p.clone(),
@@ -43,7 +55,7 @@ pub fn newton_decode(input: &Path, output: &Option<PathBuf>) -> Result<()> {
     };
 
     let mut file = fs::File::create(&out_path)?;
-    serde_json::to_writer_pretty(&mut file, &root)
+    serde_json::to_writer_pretty(&mut file, &to_json(&root)?)
         .with_context(|| "Failed to write JSON format")?;
     println!("Decoded NTON to {:?}", out_path);
     Ok(())
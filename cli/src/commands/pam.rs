@@ -1,9 +1,88 @@
+use super::atlas::page_image_path;
 use anyhow::{Context, Result};
-use clap::Subcommand;
-use pam::{convert_to_fla, decode_pam, encode_pam};
+use atlas::OfficialAtlas;
+use clap::{Args, Subcommand};
+use image::RgbaImage;
+use pam::media::media_name;
+use pam::{
+    MediaSource, PamInfo, PamRenderer, PreviewFormat, RenderOptions, SenAnimation, Severity,
+    SpineSkeleton, SpriteRef, convert_from_sen, convert_from_spine, convert_to_fla, convert_to_sen,
+    convert_to_spine, decode_pam, encode_pam, write_preview,
+};
 use std::fs;
+use std::ops::Range;
 use std::path::{Path, PathBuf};
 
+/// Where to find the bitmaps of the PAM images
+#[derive(Args)]
+pub struct MediaArgs {
+    /// Folder with the image bitmaps, named by file or resource id (default: <input dir>/media)
+    #[arg(short, long)]
+    media: Option<PathBuf>,
+    /// OfficialAtlas JSON to crop the bitmaps from; its pages are <json>.png, <json>_01.png, ...
+    #[arg(long, conflicts_with = "media")]
+    atlas: Option<PathBuf>,
+}
+
+/// Bitmaps loaded for a `MediaSource`, which only borrows them
+enum LoadedMedia {
+    Directory(PathBuf),
+    Atlas(OfficialAtlas, Vec<RgbaImage>),
+    None,
+}
+
+impl LoadedMedia {
+    fn source(&self) -> Option<MediaSource<'_>> {
+        match self {
+            LoadedMedia::Directory(dir) => Some(MediaSource::Directory(dir)),
+            LoadedMedia::Atlas(atlas, pages) => Some(MediaSource::Atlas { atlas, pages }),
+            LoadedMedia::None => None,
+        }
+    }
+}
+
+impl MediaArgs {
+    /// Resolve the media for `input`; the default `media` folder next to it is only
+    /// used when it exists
+    fn load(&self, input: &Path) -> Result<LoadedMedia> {
+        if let Some(json_path) = &self.atlas {
+            let content = fs::read_to_string(json_path).context("Failed to read atlas JSON")?;
+            let atlas: OfficialAtlas =
+                serde_json::from_str(&content).context("Failed to parse atlas JSON")?;
+            let first = json_path.with_extension("png");
+            let page_count = atlas
+                .resources
+                .iter()
+                .filter(|r| r.atlas == Some(true))
+                .count()
+                .max(1);
+            let mut pages = Vec::new();
+            for page in 0..page_count {
+                let page_path = page_image_path(&first, page);
+                if !page_path.exists() {
+                    eprintln!("Warning: Atlas page not found: {:?}", page_path);
+                    break;
+                }
+                let img = image::open(&page_path)
+                    .with_context(|| format!("Failed to open atlas page {:?}", page_path))?;
+                pages.push(img.to_rgba8());
+            }
+            return Ok(LoadedMedia::Atlas(atlas, pages));
+        }
+        let dir = match &self.media {
+            Some(dir) => dir.clone(),
+            None => input.parent().unwrap_or(Path::new(".")).join("media"),
+        };
+        if dir.is_dir() {
+            Ok(LoadedMedia::Directory(dir))
+        } else if self.media.is_some() {
+            anyhow::bail!("Media folder not found: {:?}", dir)
+        } else {
+            Ok(LoadedMedia::None)
+        }
+    }
+}
+
 #[derive(Subcommand)]
 pub enum PamCommands {
     /// Decode PAM to JSON
@@ -16,13 +95,19 @@ pub enum PamCommands {
         /// FLA scale resolution (default 1200)
         #[arg(short, long, default_value = "1200")]
         resolution: i32,
-        /// Explicit input format: json or fla
+        /// Explicit output format: json, fla or spine
         #[arg(short, long)]
         format: String,
+        /// Bitmaps to embed in the FLA or copy next to the Spine skeleton
+        #[command(flatten)]
+        media: MediaArgs,
+        /// JSON layout: native or sen (Sen's .pam.json)
+        #[arg(long, default_value = "native")]
+        schema: String,
     },
-    /// Encode JSON/FLA to PAM
+    /// Encode JSON/FLA/Spine to PAM
     Encode {
-        /// Input JSON or FLA directory
+        /// Input JSON, FLA (.fla archive or XFL folder) or Spine skeleton JSON
         input: PathBuf,
         /// Output PAM file (optional)
         #[arg(short, long)]
@@ -30,9 +115,76 @@ pub enum PamCommands {
         /// FLA scale resolution (default 1200)
         #[arg(short, long, default_value = "1200")]
         resolution: i32,
-        /// Explicit input format: json or fla
+        /// Explicit input format: json, fla or spine
+        #[arg(short, long)]
+        format: String,
+        /// JSON layout: native or sen (Sen's .pam.json)
+        #[arg(long, default_value = "native")]
+        schema: String,
+    },
+    /// Render PAM/JSON frames to PNG images or an animated preview
+    Render {
+        /// Input PAM or JSON file
+        input: PathBuf,
+        /// Output directory for png frames, or the animated file
+        /// (default: <input>_frames or <input>.<ext>)
         #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Output format: png (one file per frame), apng, gif or webp
+        #[arg(short, long, default_value = "png")]
         format: String,
+        /// Bitmaps to draw
+        #[command(flatten)]
+        media: MediaArgs,
+        /// Render only this label of the main sprite
+        #[arg(short, long)]
+        label: Option<String>,
+        /// First frame to render
+        #[arg(long)]
+        start: Option<usize>,
+        /// Last frame to render (inclusive)
+        #[arg(long)]
+        end: Option<usize>,
+        /// Output pixels per animation unit
+        #[arg(short, long, default_value = "1.0")]
+        scale: f64,
+        /// Texture resolution of the bitmaps (default 1200)
+        #[arg(short, long, default_value = "1200")]
+        resolution: i32,
+    },
+    /// Edit labels, frame rate and sprites of a PAM/JSON file
+    Edit {
+        /// Input PAM or JSON file
+        input: PathBuf,
+        /// Output PAM or JSON file, picked by extension (default: overwrite the input)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Copy the main sprite of another PAM/JSON file in as a new sprite
+        #[arg(long)]
+        merge: Vec<PathBuf>,
+        /// Label a frame of the main sprite: FRAME=NAME
+        #[arg(long)]
+        add_label: Vec<String>,
+        /// Rename a label: OLD=NEW
+        #[arg(long)]
+        rename_label: Vec<String>,
+        /// Remove a label
+        #[arg(long)]
+        remove_label: Vec<String>,
+        /// Delete sprites and images the animation never shows
+        #[arg(long)]
+        strip: bool,
+        /// Resample every sprite to a new frame rate
+        #[arg(long)]
+        frame_rate: Option<i32>,
+        /// JSON layout of the output: native or sen (Sen's .pam.json)
+        #[arg(long, default_value = "native")]
+        schema: String,
+    },
+    /// Check a PAM/JSON file for broken layer and resource references
+    Validate {
+        /// Input PAM or JSON file
+        input: PathBuf,
     },
 }
 
@@ -43,13 +195,78 @@ pub fn handle(cmd: PamCommands) -> Result<()> {
             output,
             resolution,
             format,
-        } => pam_decode(&input, &output, resolution, &format),
+            media,
+            schema,
+        } => pam_decode(
+            &input,
+            &output,
+            resolution,
+            &format,
+            &media,
+            parse_json_schema(&schema)?,
+        ),
         PamCommands::Encode {
             input,
             output,
             resolution,
             format,
-        } => pam_encode(&input, &output, resolution, &format),
+            schema,
+        } => pam_encode(
+            &input,
+            &output,
+            resolution,
+            &format,
+            parse_json_schema(&schema)?,
+        ),
+        PamCommands::Render {
+            input,
+            output,
+            format,
+            media,
+            label,
+            start,
+            end,
+            scale,
+            resolution,
+        } => {
+            let options = RenderOptions {
+                scale,
+                resolution,
+                ..Default::default()
+            };
+            pam_render(
+                &input,
+                &output,
+                parse_preview_format(&format)?,
+                &media,
+                label.as_deref(),
+                start,
+                end,
+                &options,
+            )
+        }
+        PamCommands::Edit {
+            input,
+            output,
+            merge,
+            add_label,
+            rename_label,
+            remove_label,
+            strip,
+            frame_rate,
+            schema,
+        } => pam_edit(
+            &input,
+            &output,
+            &merge,
+            &add_label,
+            &rename_label,
+            &remove_label,
+            strip,
+            frame_rate,
+            parse_json_schema(&schema)?,
+        ),
+        PamCommands::Validate { input } => pam_validate(&input),
     }
 }
 
@@ -58,8 +275,10 @@ pub fn pam_decode(
     output: &Option<PathBuf>,
     resolution: i32,
     format: &str,
+    media: &MediaArgs,
+    schema: JsonSchema,
 ) -> Result<()> {
-    // Decode PAM -> JSON/FLA
+    // Decode PAM -> JSON/FLA/Spine
     let mut file = fs::File::open(input).context("Failed to open input file")?;
     let pam_value = decode_pam(&mut file).context("Failed to decode PAM")?;
 
@@ -70,9 +289,41 @@ pub fn pam_decode(
             Some(p) => p.clone(),
             None => input.with_extension("fla"),
         };
-        convert_to_fla(&pam_value, &out_path, resolution)
+        let media = media.load(input)?;
+        convert_to_fla(&pam_value, &out_path, resolution, media.source().as_ref())
             .context("Failed to generate FLA file")?;
         println!("Decoded PAM to FLA file at {:?}", out_path);
+    } else if format_str == "spine" {
+        let out_path = match output {
+            Some(p) => p.clone(),
+            None => input.with_extension("spine.json"),
+        };
+        let out_dir = out_path.parent().unwrap_or(Path::new("."));
+        fs::create_dir_all(out_dir).context("Failed to create output directory")?;
+
+        let skeleton =
+            convert_to_spine(&pam_value, resolution).context("Failed to convert PAM to Spine")?;
+        fs::write(
+            &out_path,
+            serde_json::to_string_pretty(&skeleton).context("Failed to serialize to JSON")?,
+        )
+        .context("Failed to write output file")?;
+
+        // Spine looks the attachments up as <images>/<path or name>.png
+        let media = media.load(input)?;
+        if let Some(source) = media.source() {
+            let images_dir = out_dir.join("images");
+            fs::create_dir_all(&images_dir).context("Failed to create images directory")?;
+            for (info, bitmap) in pam_value.image.iter().zip(source.load(&pam_value)) {
+                if let Some(bitmap) = bitmap {
+                    let path = images_dir.join(format!("{}.png", media_name(info)));
+                    bitmap
+                        .save(&path)
+                        .with_context(|| format!("Failed to write {:?}", path))?;
+                }
+            }
+        }
+        println!("Decoded PAM to Spine skeleton at {:?}", out_path);
     } else {
         let out_path = match output {
             Some(p) => p.clone(),
@@ -83,11 +334,8 @@ pub fn pam_decode(
             fs::create_dir_all(parent).context("Failed to create output directory")?;
         }
 
-        fs::write(
-            &out_path,
-            serde_json::to_string_pretty(&pam_value).context("Failed to serialize to JSON")?,
-        )
-        .context("Failed to write output file")?;
+        fs::write(&out_path, pam_to_json(&pam_value, schema)?)
+            .context("Failed to write output file")?;
         println!("Decoded PAM to {:?}", out_path);
     }
 
@@ -99,15 +347,21 @@ pub fn pam_encode(
     output: &Option<PathBuf>,
     resolution: i32,
     format: &str,
+    schema: JsonSchema,
 ) -> Result<()> {
-    // Encode JSON/FLA -> PAM
+    // Encode JSON/FLA/Spine -> PAM
     let format_str = format.to_lowercase();
 
     let pam_value = if format_str == "json" {
         let content = fs::read_to_string(input).context("Failed to read input file")?;
-        serde_json::from_str(&content).context("Failed to parse JSON")?
+        pam_from_json(&content, schema)?
     } else if format_str == "fla" {
         pam::convert_from_fla(input, resolution).context("Failed to parse FLA file")?
+    } else if format_str == "spine" {
+        let content = fs::read_to_string(input).context("Failed to read input file")?;
+        let skeleton: SpineSkeleton =
+            serde_json::from_str(&content).context("Failed to parse Spine JSON")?;
+        convert_from_spine(&skeleton, resolution).context("Failed to convert Spine to PAM")?
     } else {
         anyhow::bail!("Unsupported input format for pam encode: {}", format_str);
     };
@@ -122,3 +376,286 @@ pub fn pam_encode(
     println!("Encoded PAM to {:?}", out_path);
     Ok(())
 }
+
+/// Layout of PAM JSON files
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JsonSchema {
+    /// Serde layout of `PamInfo`
+    Native,
+    /// Sen's `.pam.json`
+    Sen,
+}
+
+fn parse_json_schema(schema: &str) -> Result<JsonSchema> {
+    match schema.to_lowercase().as_str() {
+        "native" => Ok(JsonSchema::Native),
+        "sen" => Ok(JsonSchema::Sen),
+        _ => anyhow::bail!("Unsupported JSON schema: {} (use native or sen)", schema),
+    }
+}
+
+pub fn pam_from_json(content: &str, schema: JsonSchema) -> Result<PamInfo> {
+    match schema {
+        JsonSchema::Native => serde_json::from_str(content).context("Failed to parse JSON"),
+        JsonSchema::Sen => {
+            let sen: SenAnimation =
+                serde_json::from_str(content).context("Failed to parse Sen PAM JSON")?;
+            Ok(convert_from_sen(&sen))
+        }
+    }
+}
+
+pub fn pam_to_json(pam: &PamInfo, schema: JsonSchema) -> Result<String> {
+    match schema {
+        JsonSchema::Native => serde_json::to_string_pretty(pam),
+        JsonSchema::Sen => serde_json::to_string_pretty(&convert_to_sen(pam)),
+    }
+    .context("Failed to serialize to JSON")
+}
+
+/// Load a PAM from its binary or JSON form, picked by extension. JSON in either
+/// schema is accepted.
+pub fn load_pam(input: &Path) -> Result<PamInfo> {
+    let is_json = input
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    if is_json {
+        let content = fs::read_to_string(input).context("Failed to read input file")?;
+        pam_from_json(&content, JsonSchema::Native)
+            .or_else(|err| pam_from_json(&content, JsonSchema::Sen).map_err(|_| err))
+    } else {
+        let mut file = fs::File::open(input).context("Failed to open input file")?;
+        decode_pam(&mut file).context("Failed to decode PAM")
+    }
+}
+
+/// Narrow `range`, the frames of a label or animation out of `count`, to an explicit
+/// inclusive `start` and `end`
+pub fn select_frames(
+    range: Range<usize>,
+    count: usize,
+    start: Option<usize>,
+    end: Option<usize>,
+) -> Result<Range<usize>> {
+    let first = start.unwrap_or(range.start).max(range.start);
+    let last = end.map_or(range.end, |e| e + 1).min(range.end).min(count);
+    if first >= last {
+        anyhow::bail!("No frames to render in {}..{}", first, last);
+    }
+    Ok(first..last)
+}
+
+/// Render `frames` to one animated preview in `format`, or to numbered PNGs in a
+/// `<input>_frames` folder when there is no format
+pub fn write_rendered_frames(
+    input: &Path,
+    output: &Option<PathBuf>,
+    format: Option<PreviewFormat>,
+    frames: Range<usize>,
+    frame_rate: f64,
+    render: impl Fn(usize) -> Result<RgbaImage>,
+) -> Result<()> {
+    if let Some(format) = format {
+        let out_path = match output {
+            Some(p) => p.clone(),
+            None => input.with_extension(format.extension()),
+        };
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent).context("Failed to create output directory")?;
+        }
+        let images = frames.map(render).collect::<Result<Vec<_>>>()?;
+        let file = fs::File::create(&out_path).context("Failed to create output file")?;
+        write_preview(&images, frame_rate, format, std::io::BufWriter::new(file))?;
+        println!(
+            "Rendered {} frames at {} fps to {:?}",
+            images.len(),
+            frame_rate,
+            out_path
+        );
+        return Ok(());
+    }
+
+    let out_dir = match output {
+        Some(p) => p.clone(),
+        None => {
+            let stem = input.file_stem().unwrap_or_default().to_string_lossy();
+            input.with_file_name(format!("{}_frames", stem))
+        }
+    };
+    fs::create_dir_all(&out_dir).context("Failed to create output directory")?;
+
+    for frame in frames.clone() {
+        render(frame)?
+            .save(out_dir.join(format!("frame_{:04}.png", frame)))
+            .with_context(|| format!("Failed to write frame {}", frame))?;
+    }
+    println!(
+        "Rendered {} frames ({}..={}) to {:?}",
+        frames.len(),
+        frames.start,
+        frames.end - 1,
+        out_dir
+    );
+    Ok(())
+}
+
+/// `None` renders separate PNG frames
+pub fn parse_preview_format(format: &str) -> Result<Option<PreviewFormat>> {
+    match format.to_lowercase().as_str() {
+        "png" | "frames" => Ok(None),
+        "apng" => Ok(Some(PreviewFormat::Apng)),
+        "gif" => Ok(Some(PreviewFormat::Gif)),
+        "webp" => Ok(Some(PreviewFormat::Webp)),
+        _ => anyhow::bail!(
+            "Unsupported render format: {} (use png, apng, gif or webp)",
+            format
+        ),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pam_render(
+    input: &Path,
+    output: &Option<PathBuf>,
+    format: Option<PreviewFormat>,
+    media: &MediaArgs,
+    label: Option<&str>,
+    start: Option<usize>,
+    end: Option<usize>,
+    options: &RenderOptions,
+) -> Result<()> {
+    let pam_value = load_pam(input)?;
+    let images = match media.load(input)?.source() {
+        Some(source) => source.load(&pam_value),
+        None => {
+            eprintln!("Warning: no media folder found, rendering without bitmaps");
+            vec![None; pam_value.image.len()]
+        }
+    };
+    let renderer = PamRenderer::new(&pam_value, images);
+    let count = renderer.frame_count();
+    let range = match label {
+        Some(label) => renderer.label_range(label).with_context(|| {
+            let labels: Vec<String> = renderer.labels().into_iter().map(|(l, _)| l).collect();
+            format!(
+                "Label '{}' not found (available: {})",
+                label,
+                labels.join(", ")
+            )
+        })?,
+        None => 0..count,
+    };
+    let frames = select_frames(range, count, start, end)?;
+    write_rendered_frames(
+        input,
+        output,
+        format,
+        frames,
+        pam_value.frame_rate as f64,
+        |frame| renderer.render_frame(frame, options),
+    )
+}
+
+/// Split a `KEY=VALUE` edit argument
+fn parse_pair<'a>(arg: &'a str, what: &str) -> Result<(&'a str, &'a str)> {
+    arg.split_once('=')
+        .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+        .with_context(|| format!("Invalid {} '{}'", what, arg))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn pam_edit(
+    input: &Path,
+    output: &Option<PathBuf>,
+    merge: &[PathBuf],
+    add_label: &[String],
+    rename_label: &[String],
+    remove_label: &[String],
+    strip: bool,
+    frame_rate: Option<i32>,
+    schema: JsonSchema,
+) -> Result<()> {
+    let mut pam_value = load_pam(input)?;
+
+    for path in merge {
+        let other = load_pam(path)?;
+        if other.frame_rate != pam_value.frame_rate {
+            eprintln!(
+                "Warning: {:?} plays at {} fps, {:?} at {} fps",
+                path, other.frame_rate, input, pam_value.frame_rate
+            );
+        }
+        let merged = pam_value
+            .merge_sprites(&other, &[SpriteRef::Main])
+            .with_context(|| format!("Failed to merge {:?}", path))?;
+        println!("Merged {:?} as sprite {}", path, merged[0]);
+    }
+    for label in remove_label {
+        if pam_value.remove_label(label).is_none() {
+            anyhow::bail!("Label '{}' not found", label);
+        }
+    }
+    for arg in rename_label {
+        let (old, new) = parse_pair(arg, "label rename, expected OLD=NEW")?;
+        pam_value.rename_label(old, new)?;
+    }
+    for arg in add_label {
+        let (frame, label) = parse_pair(arg, "label, expected FRAME=NAME")?;
+        let frame = frame
+            .parse()
+            .with_context(|| format!("Invalid frame number '{}'", frame))?;
+        pam_value.insert_label(frame, label)?;
+    }
+    if strip {
+        let sprites = pam_value.remove_unused_sprites();
+        let images = pam_value.remove_unused_images();
+        println!(
+            "Removed {} unused sprites and {} unused images",
+            sprites, images
+        );
+    }
+    if let Some(frame_rate) = frame_rate {
+        pam_value.set_frame_rate(frame_rate)?;
+        println!(
+            "Resampled to {} fps ({} frames)",
+            frame_rate,
+            pam_value.main_sprite.frame.len()
+        );
+    }
+
+    let out_path = output.as_deref().unwrap_or(input);
+    let is_json = out_path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    if is_json {
+        fs::write(out_path, pam_to_json(&pam_value, schema)?)
+            .context("Failed to write output file")?;
+    } else {
+        let mut file = fs::File::create(out_path).context("Failed to create output file")?;
+        encode_pam(&pam_value, &mut file).context("Failed to encode PAM")?;
+    }
+    println!("Edited PAM written to {:?}", out_path);
+    Ok(())
+}
+
+pub fn pam_validate(input: &Path) -> Result<()> {
+    let pam_value = load_pam(input)?;
+    let issues = pam_value.validate();
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        anyhow::bail!(
+            "{:?} has {} errors and {} warnings",
+            input,
+            errors,
+            issues.len() - errors
+        );
+    }
+    println!("{:?} is valid ({} warnings)", input, issues.len());
+    Ok(())
+}
@@ -1,8 +1,12 @@
 use anyhow::{Result, anyhow};
 use clap::Subcommand;
-use rsb::ptx::{PtxDecoder, PtxEncoder, PtxFormat};
+use rsb::ptx::container::{ImportedPtx, join_ptx, read_container, split_ptx, write_container};
+use rsb::ptx::{
+    ContainerKind, DetectHint, DitherMode, MipFilter, PtxDecoder, PtxDetector, PtxEncodeOptions,
+    PtxEncoder, PtxFormat, PtxRegistry, mipmap,
+};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Subcommand)]
 pub enum PtxCommands {
@@ -13,15 +17,15 @@ pub enum PtxCommands {
         /// Output PNG file path (optional)
         #[arg(short, long)]
         output: Option<PathBuf>,
-        /// Width of the texture (required if not derivable from header/size)
+        /// Width of the texture (guessed from rsb_manifest.json or data size if omitted)
         #[arg(long)]
         width: Option<u32>,
-        /// Height of the texture (required if not derivable from header/size)
+        /// Height of the texture (guessed from rsb_manifest.json or data size if omitted)
         #[arg(long)]
         height: Option<u32>,
-        /// Texture format ID (147, 30, etc.)
+        /// Texture format ID (147, 30, etc.; guessed if omitted)
         #[arg(short, long)]
-        format: i32,
+        format: Option<i32>,
         /// Optional alpha size for specific sub-formats
         #[arg(long)]
         alpha_size: Option<i32>,
@@ -31,6 +35,29 @@ pub enum PtxCommands {
         /// Was it encoded with PowerVR? (Flips RGB/BGR for certain ETC1 formats)
         #[arg(long, default_value_t = false)]
         powervr: bool,
+        /// Number of mip levels in the data (inferred from the data size if omitted)
+        #[arg(long)]
+        mip_count: Option<u32>,
+        /// Also save every mip level as <output>_mipN.png
+        #[arg(long, default_value_t = false)]
+        save_mips: bool,
+    },
+    /// Guess format and dimensions of a loose PTX file
+    Detect {
+        /// Input PTX file path
+        input: PathBuf,
+        /// Known width, narrows down the guesses
+        #[arg(long)]
+        width: Option<u32>,
+        /// Known height, narrows down the guesses
+        #[arg(long)]
+        height: Option<u32>,
+        /// Known format ID, narrows down the guesses
+        #[arg(short, long)]
+        format: Option<i32>,
+        /// Maximum number of candidates to list
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
     },
     /// Encode a PNG or compatible image into a PTX file
     Encode {
@@ -44,7 +71,177 @@ pub enum PtxCommands {
         /// Are we targeting PowerVR engines (changes element order in encoded block buffer)
         #[arg(long, default_value_t = false)]
         powervr: bool,
+        /// Dithering for 16-bit formats: none, floyd-steinberg, ordered
+        #[arg(long, default_value = "none")]
+        dither: String,
+        /// Premultiply RGB by alpha before encoding
+        #[arg(long, default_value_t = false)]
+        premultiply_alpha: bool,
+        /// Number of mip levels to generate (0 for a full chain down to 1x1)
+        #[arg(long, default_value_t = 1)]
+        mips: u32,
+        /// Mip downsampling filter: box, lanczos
+        #[arg(long, default_value = "box")]
+        mip_filter: String,
     },
+    /// Wrap raw PTX data in a KTX, KTX2, DDS or PVR container without re-encoding
+    Export {
+        /// Input PTX file path
+        input: PathBuf,
+        /// Output container path (defaults to the input with the container extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Container: ktx, ktx2, dds, pvr
+        #[arg(short, long, default_value = "ktx")]
+        container: String,
+        /// Width of the texture (guessed from rsb_manifest.json or data size if omitted)
+        #[arg(long)]
+        width: Option<u32>,
+        /// Height of the texture (guessed from rsb_manifest.json or data size if omitted)
+        #[arg(long)]
+        height: Option<u32>,
+        /// Texture format ID (147, 30, etc.; guessed if omitted)
+        #[arg(short, long)]
+        format: Option<i32>,
+        /// Optional alpha format override (100 for ETC1-compressed alpha)
+        #[arg(long)]
+        alpha_format: Option<i32>,
+        /// Was it encoded with PowerVR? (RGBA8888 is stored as BGRA)
+        #[arg(long, default_value_t = false)]
+        powervr: bool,
+        /// Number of mip levels in the data (inferred from the data size if omitted)
+        #[arg(long)]
+        mip_count: Option<u32>,
+    },
+    /// Build a PTX file from a KTX, KTX2, DDS or PVR container without re-encoding
+    Import {
+        /// Input container file path
+        input: PathBuf,
+        /// Output PTX file path (defaults to the input with a .ptx extension)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Separate alpha texture (defaults to <input>_alpha.<ext> if present)
+        #[arg(long)]
+        alpha: Option<PathBuf>,
+    },
+    /// List the PTX formats known to the codec registry
+    Formats {
+        /// Width used to show the data size of each format
+        #[arg(long, default_value_t = 256)]
+        width: u32,
+        /// Height used to show the data size of each format
+        #[arg(long, default_value_t = 256)]
+        height: u32,
+    },
+}
+
+pub fn parse_mip_filter(filter: &str) -> Result<MipFilter> {
+    match filter.to_lowercase().as_str() {
+        "box" => Ok(MipFilter::Box),
+        "lanczos" | "lanczos3" => Ok(MipFilter::Lanczos3),
+        _ => Err(anyhow!(
+            "Unknown mip filter: {}, must be box or lanczos",
+            filter
+        )),
+    }
+}
+
+pub fn parse_dither(dither: &str) -> Result<DitherMode> {
+    match dither.to_lowercase().as_str() {
+        "none" => Ok(DitherMode::None),
+        "floyd-steinberg" | "fs" => Ok(DitherMode::FloydSteinberg),
+        "ordered" | "bayer" => Ok(DitherMode::Ordered),
+        _ => Err(anyhow!(
+            "Unknown dither mode: {}, must be none, floyd-steinberg or ordered",
+            dither
+        )),
+    }
+}
+
+/// Fill in missing dimensions and format from rsb_manifest.json or the data size.
+#[allow(clippy::type_complexity)]
+fn resolve_layout(
+    data: &[u8],
+    input: &Path,
+    width: Option<u32>,
+    height: Option<u32>,
+    format: Option<i32>,
+    alpha_size: Option<i32>,
+    alpha_format: Option<i32>,
+) -> Result<(u32, u32, i32, Option<i32>, Option<i32>)> {
+    Ok(match (width, height, format) {
+        (Some(w), Some(h), Some(format)) => (w, h, format, alpha_size, alpha_format),
+        _ => {
+            let hint = DetectHint {
+                width,
+                height,
+                format_code: format,
+            };
+            let (candidates, warnings) = PtxDetector::detect(data, Some(input), hint)?;
+            for warning in warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            let best = candidates.into_iter().next().ok_or_else(|| {
+                anyhow!(
+                    "Could not guess PTX layout for {} bytes; pass --format, --width and --height",
+                    data.len()
+                )
+            })?;
+            println!(
+                "Guessed {:?} (ID {}) {}x{} from {:?}",
+                best.format, best.format_code, best.width, best.height, best.source
+            );
+            (
+                width.unwrap_or(best.width),
+                height.unwrap_or(best.height),
+                format.unwrap_or(best.format_code),
+                alpha_size.or(best.alpha_size),
+                alpha_format.or(best.alpha_format),
+            )
+        }
+    })
+}
+
+pub fn parse_container(container: &str) -> Result<ContainerKind> {
+    match container.to_lowercase().as_str() {
+        "ktx" => Ok(ContainerKind::Ktx),
+        "ktx2" => Ok(ContainerKind::Ktx2),
+        "dds" => Ok(ContainerKind::Dds),
+        "pvr" => Ok(ContainerKind::Pvr),
+        _ => Err(anyhow!(
+            "Unknown container: {}, must be ktx, ktx2, dds or pvr",
+            container
+        )),
+    }
+}
+
+/// Path of the separate alpha texture exported next to `color`.
+fn alpha_path(color: &Path) -> PathBuf {
+    let stem = color.file_stem().unwrap_or_default().to_string_lossy();
+    let ext = color.extension().unwrap_or_default().to_string_lossy();
+    color.with_file_name(format!("{}_alpha.{}", stem, ext))
+}
+
+/// Container file next to an unpacked PTX (same stem, any supported extension).
+pub fn container_sidecar(ptx_path: &Path) -> Option<PathBuf> {
+    ["ktx2", "ktx", "dds", "pvr"]
+        .iter()
+        .map(|ext| ptx_path.with_extension(ext))
+        .find(|path| path.is_file())
+}
+
+/// Read a colour container and its `_alpha` sibling (or `alpha` if given) back into PTX data.
+pub fn import_container(color: &Path, alpha: Option<&Path>) -> Result<ImportedPtx> {
+    let color_texture = read_container(&fs::read(color)?)?;
+    let alpha_file = match alpha {
+        Some(path) => Some(path.to_path_buf()),
+        None => Some(alpha_path(color)).filter(|path| path.is_file()),
+    };
+    let alpha_texture = match &alpha_file {
+        Some(path) => Some(read_container(&fs::read(path)?)?),
+        None => None,
+    };
+    Ok(join_ptx(&color_texture, alpha_texture.as_ref())?)
 }
 
 pub fn handle(cmd: PtxCommands) -> Result<()> {
@@ -58,23 +255,83 @@ pub fn handle(cmd: PtxCommands) -> Result<()> {
             alpha_size,
             alpha_format,
             powervr,
+            mip_count,
+            save_mips,
         } => {
             let data = fs::read(&input)?;
 
-            // Assume width/height needs to be passed in for flat PTX files without a container providing them
-            let w = width.ok_or_else(|| {
-                anyhow!("Width must be provided for raw PTX decoding without RSG context")
-            })?;
-            let h = height.ok_or_else(|| {
-                anyhow!("Height must be provided for raw PTX decoding without RSG context")
-            })?;
+            let (w, h, format, alpha_size, alpha_format) = resolve_layout(
+                &data,
+                &input,
+                width,
+                height,
+                format,
+                alpha_size,
+                alpha_format,
+            )?;
 
             println!("Decoding PTX with Format ID: {}", format);
-            let img = PtxDecoder::decode(&data, w, h, format, alpha_size, alpha_format, powervr)?;
+            let levels = PtxDecoder::decode_mips(
+                &data,
+                w,
+                h,
+                format,
+                alpha_size,
+                alpha_format,
+                powervr,
+                mip_count,
+            )?;
 
             let out_path = output.unwrap_or_else(|| input.with_extension("png"));
-            img.save(&out_path)?;
+            levels[0].save(&out_path)?;
             println!("Decoded PTX saved to {:?}", out_path);
+
+            if levels.len() > 1 {
+                println!("Found {} mip levels", levels.len());
+            }
+            if save_mips {
+                let stem = out_path.file_stem().unwrap_or_default().to_string_lossy();
+                for (i, level) in levels.iter().enumerate().skip(1) {
+                    let mip_path = out_path.with_file_name(format!("{}_mip{}.png", stem, i));
+                    level.save(&mip_path)?;
+                    println!("Saved mip level {} to {:?}", i, mip_path);
+                }
+            }
+            Ok(())
+        }
+        PtxCommands::Detect {
+            input,
+            width,
+            height,
+            format,
+            limit,
+        } => {
+            let data = fs::read(&input)?;
+            let hint = DetectHint {
+                width,
+                height,
+                format_code: format,
+            };
+            let (candidates, warnings) = PtxDetector::detect(&data, Some(&input), hint)?;
+            for warning in warnings {
+                eprintln!("Warning: {}", warning);
+            }
+            if candidates.is_empty() {
+                println!("No matching layout for {} bytes", data.len());
+                return Ok(());
+            }
+
+            println!("Candidates for {:?} ({} bytes):", input, data.len());
+            for c in candidates.iter().take(limit) {
+                print!(
+                    "  [{:>4}] {:?} (ID {}) {}x{}",
+                    c.score, c.format, c.format_code, c.width, c.height
+                );
+                if let Some(alpha_format) = c.alpha_format {
+                    print!(" alpha_format={}", alpha_format);
+                }
+                println!(" <- {:?}", c.source);
+            }
             Ok(())
         }
         PtxCommands::Encode {
@@ -82,8 +339,16 @@ pub fn handle(cmd: PtxCommands) -> Result<()> {
             output,
             format,
             powervr,
+            dither,
+            premultiply_alpha,
+            mips,
+            mip_filter,
         } => {
             let img = image::open(&input)?;
+            let options = PtxEncodeOptions {
+                dither: parse_dither(&dither)?,
+                premultiply_alpha,
+            };
 
             // Map string format to enum
             let fmt = match format.to_lowercase().as_str() {
@@ -107,10 +372,100 @@ pub fn handle(cmd: PtxCommands) -> Result<()> {
             };
 
             println!("Encoding Image as {:?}", fmt);
-            let ptx_data = PtxEncoder::encode(&img, fmt, powervr)?;
+            let mip_count = if mips == 0 {
+                mipmap::full_mip_count(img.width(), img.height())
+            } else {
+                mips
+            };
+            let ptx_data = PtxEncoder::encode_mips(
+                &img,
+                fmt,
+                powervr,
+                &options,
+                mip_count,
+                parse_mip_filter(&mip_filter)?,
+            )?;
             fs::write(&output, ptx_data)?;
             println!("Encoded PTX saved to {:?}", output);
             Ok(())
         }
+        PtxCommands::Export {
+            input,
+            output,
+            container,
+            width,
+            height,
+            format,
+            alpha_format,
+            powervr,
+            mip_count,
+        } => {
+            let kind = parse_container(&container)?;
+            let data = fs::read(&input)?;
+            let (w, h, format, _, alpha_format) =
+                resolve_layout(&data, &input, width, height, format, None, alpha_format)?;
+
+            let surfaces = split_ptx(&data, w, h, format, alpha_format, powervr, mip_count)?;
+            let out_path = output.unwrap_or_else(|| input.with_extension(kind.extension()));
+            fs::write(&out_path, write_container(kind, &surfaces.color)?)?;
+            println!(
+                "Exported {:?} {}x{} ({} levels) to {:?}",
+                surfaces.color.format,
+                w,
+                h,
+                surfaces.color.levels.len(),
+                out_path
+            );
+
+            if let Some(alpha) = &surfaces.alpha {
+                let alpha_out = alpha_path(&out_path);
+                fs::write(&alpha_out, write_container(kind, alpha)?)?;
+                println!("Exported {:?} alpha to {:?}", alpha.format, alpha_out);
+            }
+            Ok(())
+        }
+        PtxCommands::Import {
+            input,
+            output,
+            alpha,
+        } => {
+            let imported = import_container(&input, alpha.as_deref())?;
+            let out_path = output.unwrap_or_else(|| input.with_extension("ptx"));
+            fs::write(&out_path, &imported.data)?;
+
+            println!(
+                "Imported {:?} (ID {}) {}x{} to {:?}",
+                imported.format, imported.format_code, imported.width, imported.height, out_path
+            );
+            if let Some(alpha_format) = imported.alpha_format {
+                println!("  alpha_format: {}", alpha_format);
+            }
+            if imported.mip_count > 1 {
+                println!("  mip_count: {}", imported.mip_count);
+            }
+            if imported.is_powervr {
+                println!("  BGRA data, pack with --powervr");
+            }
+            Ok(())
+        }
+        PtxCommands::Formats { width, height } => {
+            let registry = PtxRegistry::global();
+            println!(
+                "{:>5}  {:<8} {:<16} {:>6}  Size at {}x{}",
+                "ID", "Platform", "Codec", "Block", width, height
+            );
+            for entry in registry.entries() {
+                let (bw, bh) = entry.codec.block_dims();
+                println!(
+                    "{:>5}  {:<8} {:<16} {:>6}  {}",
+                    entry.format_code,
+                    format!("{:?}", entry.platform),
+                    entry.codec.name(),
+                    format!("{}x{}", bw, bh),
+                    entry.codec.data_size(width, height)
+                );
+            }
+            Ok(())
+        }
     }
 }
@@ -1,27 +1,32 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Subcommand;
+use pam::PreviewFormat;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use reanim::{ReanimVersion, decode, decode_xfl, encode, encode_xfl};
+use super::pam::{parse_preview_format, select_frames, write_rendered_frames};
+use reanim::{
+    Reanim, ReanimRenderer, ReanimVersion, RenderOptions, decode, decode_xfl, decode_xml, encode,
+    encode_xfl, encode_xml, load_images,
+};
 
 #[derive(Subcommand)]
 pub enum ReanimCommands {
-    /// Decode a Reanim binary file to JSON or XFL directory
+    /// Decode a Reanim binary file to JSON, .reanim text or XFL directory
     Decode {
         #[arg(required = true, help = "Input Reanim file")]
         input: PathBuf,
         #[arg(
             required = true,
-            help = "Output JSON file or XFL directory (must end in .xfl)"
+            help = "Output JSON file, .reanim text file or XFL directory (must end in .xfl)"
         )]
         output: PathBuf,
     },
-    /// Encode a JSON file or XFL directory to a Reanim binary file
+    /// Encode a JSON file, .reanim text file or XFL directory to a Reanim binary file
     Encode {
         #[arg(
             required = true,
-            help = "Input JSON file or XFL directory (must end in .xfl)"
+            help = "Input JSON file, .reanim text file or XFL directory (must end in .xfl)"
         )]
         input: PathBuf,
         #[arg(required = true, help = "Output Reanim file")]
@@ -29,6 +34,60 @@ pub enum ReanimCommands {
         #[arg(long, default_value = "pc", help = "Version: pc, phone32, phone64")]
         version: String,
     },
+    /// Render Reanim frames to PNG images or an animated preview
+    Render {
+        #[arg(
+            required = true,
+            help = "Input Reanim binary, JSON, .reanim text file or XFL directory"
+        )]
+        input: PathBuf,
+        #[arg(
+            short,
+            long,
+            help = "Output directory for png frames, or the animated file (default: <input>_frames or <input>.<ext>)"
+        )]
+        output: Option<PathBuf>,
+        #[arg(
+            short,
+            long,
+            default_value = "png",
+            help = "Output format: png (one file per frame), apng, gif or webp"
+        )]
+        format: String,
+        #[arg(
+            short,
+            long,
+            help = "Folder with the track images, named by id with or without IMAGE_REANIM_ (default: <input dir>/media)"
+        )]
+        media: Option<PathBuf>,
+        #[arg(
+            short,
+            long,
+            help = "Render only the frames shown by this anim_ track, e.g. anim_idle"
+        )]
+        anim: Option<String>,
+        #[arg(long, help = "First frame to render")]
+        start: Option<usize>,
+        #[arg(long, help = "Last frame to render (inclusive)")]
+        end: Option<usize>,
+        #[arg(
+            long,
+            help = "Draw only the tracks matching this name, * matches anything (repeatable)"
+        )]
+        show: Vec<String>,
+        #[arg(
+            long,
+            help = "Skip the tracks matching this name, * matches anything (repeatable)"
+        )]
+        hide: Vec<String>,
+        #[arg(
+            short,
+            long,
+            default_value = "1.0",
+            help = "Output pixels per animation unit"
+        )]
+        scale: f64,
+    },
 }
 
 pub fn handle(cmd: ReanimCommands) -> Result<()> {
@@ -37,13 +96,17 @@ pub fn handle(cmd: ReanimCommands) -> Result<()> {
             let data = fs::read(&input)?;
             let reanim = decode(&data)?;
 
-            if output.extension().and_then(|e| e.to_str()) == Some("xfl") {
+            let extension = output.extension().and_then(|e| e.to_str());
+            if extension == Some("xfl") {
                 encode_xfl(&reanim, &output)?;
                 println!(
                     "Extracted {} to XFL directory {}",
                     input.display(),
                     output.display()
                 );
+            } else if extension == Some("reanim") {
+                fs::write(&output, encode_xml(&reanim))?;
+                println!("Decoded {} to {}", input.display(), output.display());
             } else {
                 let json = serde_json::to_string_pretty(&reanim)?;
                 fs::write(&output, json)?;
@@ -55,8 +118,11 @@ pub fn handle(cmd: ReanimCommands) -> Result<()> {
             output,
             version,
         } => {
-            let reanim = if input.extension().and_then(|e| e.to_str()) == Some("xfl") {
+            let extension = input.extension().and_then(|e| e.to_str());
+            let reanim = if extension == Some("xfl") {
                 decode_xfl(&input)?
+            } else if extension == Some("reanim") {
+                decode_xml(&fs::read_to_string(&input)?)?
             } else {
                 let json = fs::read_to_string(&input)?;
                 serde_json::from_str(&json)?
@@ -76,6 +142,95 @@ pub fn handle(cmd: ReanimCommands) -> Result<()> {
                 ver
             );
         }
+        ReanimCommands::Render {
+            input,
+            output,
+            format,
+            media,
+            anim,
+            start,
+            end,
+            show,
+            hide,
+            scale,
+        } => {
+            let options = RenderOptions {
+                scale,
+                show,
+                hide,
+                ..Default::default()
+            };
+            reanim_render(
+                &input,
+                &output,
+                parse_preview_format(&format)?,
+                &media,
+                anim.as_deref(),
+                start,
+                end,
+                &options,
+            )?;
+        }
     }
     Ok(())
 }
+
+/// Read a Reanim from any of the formats `decode` and `encode` handle, picked by
+/// extension
+fn load_reanim(input: &Path) -> Result<Reanim> {
+    match input.extension().and_then(|e| e.to_str()) {
+        Some("xfl") => decode_xfl(input),
+        Some("reanim") => Ok(decode_xml(&fs::read_to_string(input)?)?),
+        Some("json") => Ok(serde_json::from_str(&fs::read_to_string(input)?)?),
+        _ => Ok(decode(&fs::read(input)?)?),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn reanim_render(
+    input: &Path,
+    output: &Option<PathBuf>,
+    format: Option<PreviewFormat>,
+    media: &Option<PathBuf>,
+    anim: Option<&str>,
+    start: Option<usize>,
+    end: Option<usize>,
+    options: &RenderOptions,
+) -> Result<()> {
+    let reanim = load_reanim(input)?;
+    let media_dir = match media {
+        Some(dir) => dir.clone(),
+        None => input.parent().unwrap_or(Path::new(".")).join("media"),
+    };
+    let images = if media_dir.is_dir() {
+        load_images(&reanim, &media_dir)?
+    } else if media.is_some() {
+        anyhow::bail!("Media folder not found: {:?}", media_dir)
+    } else {
+        eprintln!("Warning: no media folder found, rendering without images");
+        Default::default()
+    };
+    let renderer = ReanimRenderer::new(&reanim, images);
+    let missing = renderer.missing_images();
+    if !missing.is_empty() && media_dir.is_dir() {
+        eprintln!("Warning: images not found: {}", missing.join(", "));
+    }
+
+    let count = renderer.frame_count();
+    let range = match anim {
+        Some(anim) => renderer.anim_range(anim).with_context(|| {
+            let anims: Vec<String> = renderer.anims().into_iter().map(|(a, _)| a).collect();
+            format!(
+                "Track '{}' not found or never shown (anims: {})",
+                anim,
+                anims.join(", ")
+            )
+        })?,
+        None => 0..count,
+    };
+    let frames = select_frames(range, count, start, end)?;
+    let bounds = renderer.bounds(frames.clone(), options);
+    write_rendered_frames(input, output, format, frames, reanim.fps as f64, |frame| {
+        Ok(renderer.render_frame(frame, bounds, options)?)
+    })
+}
@@ -1,26 +1,404 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, anyhow};
 use clap::Parser;
 use pvz2_resources::{
-    ResInfo, ResourceGroup, convert_res_info_to_resource_group, convert_resource_group_to_res_info,
+    ConflictPolicy, Dimension, MergeEntry, PackedFiles, ResInfo, ResourceGroup, ScaffoldOptions,
+    ScaffoldSprite, SlotMode, convert_res_info_to_resource_group,
+    convert_resource_group_to_res_info, group_losses, merge_resource_groups, rename_resource_id,
+    renumber_slots, res_info_losses, scaffold_resources, validate_resource_group,
 };
+use rsb::schema::types::RsbManifest;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
-use std::path::PathBuf;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 pub enum ResourcesCommands {
-    /// Convert between grouped ResourceGroup and flat ResInfo formats
+    /// Convert a manifest between Newton, resources.rton, grouped resources.json and flat res.json
     Convert {
-        /// Input JSON file (either res.json or resources.json format)
+        /// Input manifest (Newton binary, RTON, resources.json or res.json)
         #[arg(short, long)]
         input: PathBuf,
-        /// Output JSON file
+        /// Output manifest
         #[arg(short, long)]
         output: PathBuf,
+        /// Input format: newton, rton, json or res-json (detected from the content when omitted)
+        #[arg(long)]
+        from: Option<String>,
+        /// Output format: newton, rton, json or res-json. When omitted, JSON input is
+        /// converted between the grouped and flat layouts and other inputs become grouped JSON.
+        #[arg(long)]
+        to: Option<String>,
         /// Force output expand_path structure ("string" or "array") for flat -> grouped conversion or grouped -> flat.
         /// When omitted, defaults to "array" when generating flat.
         #[arg(long, default_value = "array")]
         expand_path: String,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+        /// Write the output even when its format cannot hold everything in the input,
+        /// such as slots and `runtime` flags in res.json or the version in Newton
+        #[arg(long)]
+        lossy: bool,
     },
+    /// Check a manifest for broken references, optionally against an unpacked RSB
+    Validate {
+        /// Manifest to check (Newton binary, RTON, resources.json or res.json)
+        #[arg(short, long)]
+        input: PathBuf,
+        /// Unpacked RSB directory containing rsb_manifest.json
+        #[arg(long)]
+        rsb: Option<PathBuf>,
+        /// Input format: newton, rton, json or res-json (detected from the content when omitted)
+        #[arg(long)]
+        from: Option<String>,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+    },
+    /// Reassign resource slots and update slot_count
+    RenumberSlots {
+        /// Manifest to renumber (Newton binary, RTON, resources.json or res.json)
+        #[arg(short, long)]
+        input: PathBuf,
+        /// Output manifest (optional, overwrites the input when omitted)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// "compact" numbers every resource from 0, "append" keeps existing slots and
+        /// numbers new or colliding resources after the current maximum
+        #[arg(long, default_value = "append")]
+        mode: String,
+        /// Input format: newton, rton, json or res-json (detected from the content when omitted)
+        #[arg(long)]
+        from: Option<String>,
+        /// Output format (defaults to the input format)
+        #[arg(long)]
+        to: Option<String>,
+        /// Force output expand_path structure ("string" or "array") when writing res.json
+        #[arg(long, default_value = "array")]
+        expand_path: String,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+    },
+    /// Merge mod manifests onto a base manifest, later files taking precedence
+    Merge {
+        /// Base manifest followed by the mod manifests, in layering order
+        #[arg(required = true, num_args = 2..)]
+        inputs: Vec<PathBuf>,
+        /// Output manifest
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Conflict policy for ids defined more than once: override, keep or error
+        #[arg(long, default_value = "override")]
+        policy: String,
+        /// Write the merge report as JSON
+        #[arg(long)]
+        report: Option<PathBuf>,
+        /// Output format (defaults to the base manifest's format)
+        #[arg(long)]
+        to: Option<String>,
+        /// Force output expand_path structure ("string" or "array") when writing res.json
+        #[arg(long, default_value = "array")]
+        expand_path: String,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+    },
+    /// Generate Image entries for a folder of PNGs and add them to a manifest
+    Scaffold {
+        /// Folder of PNGs, scanned recursively
+        input: PathBuf,
+        /// Manifest to add the group to (updated when it exists, created otherwise)
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Group id (defaults to the folder name)
+        #[arg(short, long)]
+        group: Option<String>,
+        /// Prefix of the generated resource ids
+        #[arg(long, default_value = "IMAGE_")]
+        prefix: String,
+        /// Path segments prepended to every resource path, such as images/1536/full
+        #[arg(long, default_value = "images")]
+        path_prefix: String,
+        /// Resolution of the group, such as 1536
+        #[arg(long)]
+        res: Option<String>,
+        /// Composite group to register the new group in
+        #[arg(long)]
+        parent: Option<String>,
+        /// Pack the images into an atlas and write its pages
+        #[arg(long)]
+        atlas: bool,
+        /// Directory for the atlas pages (defaults to `atlases` next to the manifest)
+        #[arg(long)]
+        atlas_dir: Option<PathBuf>,
+        #[command(flatten)]
+        packing: super::atlas::PackingArgs,
+        /// Output format (defaults to the existing manifest's format, or grouped JSON)
+        #[arg(long)]
+        to: Option<String>,
+        /// Force output expand_path structure ("string" or "array") when writing res.json
+        #[arg(long, default_value = "array")]
+        expand_path: String,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+    },
+    /// Index where each resource id is defined and used across a project folder
+    Index {
+        /// Folder holding manifests, PAM, reanim, RTON and JSON files
+        input: PathBuf,
+        /// Only list the locations of this id
+        #[arg(long)]
+        id: Option<String>,
+        /// Write the full index as JSON
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+    },
+    /// Rename a resource id in every manifest, PAM, reanim, RTON and JSON file of a project folder
+    RenameId {
+        /// Folder holding manifests, PAM, reanim, RTON and JSON files
+        input: PathBuf,
+        /// Current resource id
+        old_id: String,
+        /// New resource id
+        new_id: String,
+        /// List the files that would change without writing them
+        #[arg(long)]
+        dry_run: bool,
+        /// Encryption Seed (for encrypted RTONs)
+        #[arg(long)]
+        seed: Option<String>,
+    },
+}
+
+/// The on-disk encodings of a resource manifest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestFormat {
+    /// Newton binary (`resources.newton`)
+    Newton,
+    /// `resources.rton`
+    Rton,
+    /// Grouped `resources.json`
+    Json,
+    /// Flat `res.json` (ResInfo)
+    ResJson,
+}
+
+impl ManifestFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ManifestFormat::Newton => "Newton",
+            ManifestFormat::Rton => "RTON",
+            ManifestFormat::Json => "ResourceGroup JSON",
+            ManifestFormat::ResJson => "ResInfo JSON",
+        }
+    }
+}
+
+pub fn parse_manifest_format(format: &str) -> Result<ManifestFormat> {
+    match format.to_lowercase().as_str() {
+        "newton" | "nton" => Ok(ManifestFormat::Newton),
+        "rton" => Ok(ManifestFormat::Rton),
+        "json" | "resources" | "resources-json" => Ok(ManifestFormat::Json),
+        "res-json" | "res" | "resinfo" => Ok(ManifestFormat::ResJson),
+        _ => Err(anyhow!(
+            "Unknown manifest format: {}, must be newton, rton, json or res-json",
+            format
+        )),
+    }
+}
+
+pub fn parse_slot_mode(mode: &str) -> Result<SlotMode> {
+    match mode.to_lowercase().as_str() {
+        "compact" => Ok(SlotMode::Compact),
+        "append" => Ok(SlotMode::Append),
+        _ => Err(anyhow!(
+            "Unknown slot mode: {}, must be compact or append",
+            mode
+        )),
+    }
+}
+
+pub fn parse_conflict_policy(policy: &str) -> Result<ConflictPolicy> {
+    match policy.to_lowercase().as_str() {
+        "override" => Ok(ConflictPolicy::Override),
+        "keep" => Ok(ConflictPolicy::Keep),
+        "error" => Ok(ConflictPolicy::Error),
+        _ => Err(anyhow!(
+            "Unknown conflict policy: {}, must be override, keep or error",
+            policy
+        )),
+    }
+}
+
+/// Guess the manifest format from its content.
+pub fn detect_manifest_format(data: &[u8]) -> ManifestFormat {
+    if data.starts_with(b"RTON") || data.starts_with(&[0x10, 0x00]) {
+        return ManifestFormat::Rton;
+    }
+    let first = data.iter().find(|b| !b.is_ascii_whitespace());
+    if first == Some(&b'{') {
+        if serde_json::from_slice::<ResInfo>(data).is_ok() {
+            return ManifestFormat::ResJson;
+        }
+        return ManifestFormat::Json;
+    }
+    ManifestFormat::Newton
+}
+
+/// Read a manifest in any supported format into the shared ResourceGroup model.
+pub fn load_manifest(
+    path: &Path,
+    format: Option<ManifestFormat>,
+    seed: Option<&str>,
+) -> Result<(ResourceGroup, ManifestFormat)> {
+    let data = fs::read(path).with_context(|| format!("Failed to read manifest: {:?}", path))?;
+    let format = format.unwrap_or_else(|| detect_manifest_format(&data));
+    Ok((parse_manifest(&data, format, seed)?, format))
+}
+
+fn parse_manifest(
+    data: &[u8],
+    format: ManifestFormat,
+    seed: Option<&str>,
+) -> Result<ResourceGroup> {
+    let group = match format {
+        ManifestFormat::Newton => {
+            newton::decode_newton(Cursor::new(data)).context("Failed to parse Newton format")?
+        }
+        ManifestFormat::Rton => {
+            let value: rton::RtonValue =
+                rton::from_bytes(data, seed).context("Failed to parse RTON format")?;
+            serde_json::from_value(serde_json::to_value(value)?)
+                .context("RTON does not contain a ResourceGroup")?
+        }
+        ManifestFormat::Json => {
+            serde_json::from_slice(data).context("Failed to parse ResourceGroup JSON")?
+        }
+        ManifestFormat::ResJson => {
+            let res_info: ResInfo =
+                serde_json::from_slice(data).context("Failed to parse ResInfo JSON")?;
+            convert_res_info_to_resource_group(&res_info)
+                .context("Failed to convert ResInfo to ResourceGroup")?
+        }
+    };
+    Ok(group)
+}
+
+/// Write the shared ResourceGroup model in the requested format.
+pub fn save_manifest(
+    group: &ResourceGroup,
+    path: &Path,
+    format: ManifestFormat,
+    expand_path: &str,
+    seed: Option<&str>,
+) -> Result<()> {
+    let data = encode_manifest(group, format, expand_path, seed)?;
+    fs::write(path, data).with_context(|| format!("Failed to write manifest to {:?}", path))
+}
+
+fn encode_manifest(
+    group: &ResourceGroup,
+    format: ManifestFormat,
+    expand_path: &str,
+    seed: Option<&str>,
+) -> Result<Vec<u8>> {
+    // The game needs a slot for every resource; only JSON may still leave them open
+    if matches!(format, ManifestFormat::Newton | ManifestFormat::Rton) {
+        let resources = group.groups.iter().filter_map(|g| g.resources.as_ref());
+        if let Some(res) = resources.flatten().find(|res| res.slot.is_none()) {
+            anyhow::bail!(
+                "Resource {} has no slot; run `resources renumber-slots` first",
+                res.id
+            );
+        }
+    }
+
+    let data = match format {
+        ManifestFormat::Newton => {
+            let mut out = Vec::new();
+            newton::encode_newton(group, &mut out).context("Failed to write Newton format")?;
+            out
+        }
+        ManifestFormat::Rton => {
+            let value: rton::RtonValue = serde_json::from_value(serde_json::to_value(group)?)?;
+            let mut out = Vec::new();
+            rton::to_writer(&mut out, &value, seed).context("Failed to write RTON format")?;
+            out
+        }
+        ManifestFormat::Json => serde_json::to_string_pretty(group)?.into_bytes(),
+        ManifestFormat::ResJson => {
+            let res_info = convert_resource_group_to_res_info(group, expand_path)
+                .context("Failed to convert ResourceGroup to ResInfo")?;
+            serde_json::to_string_pretty(&res_info)?.into_bytes()
+        }
+    };
+    Ok(data)
+}
+
+/// Describe what writing `group` as `format` would drop or change, found by writing
+/// it and reading it back. Grouped JSON is the model itself and keeps everything.
+pub fn conversion_losses(
+    group: &ResourceGroup,
+    format: ManifestFormat,
+    expand_path: &str,
+    seed: Option<&str>,
+) -> Result<Vec<String>> {
+    match format {
+        ManifestFormat::Json => Ok(Vec::new()),
+        ManifestFormat::ResJson => Ok(res_info_losses(group, expand_path)?),
+        ManifestFormat::Newton | ManifestFormat::Rton => {
+            let data = encode_manifest(group, format, expand_path, seed)?;
+            let round_trip = parse_manifest(&data, format, seed)?;
+            Ok(group_losses(group, &round_trip, None)?)
+        }
+    }
+}
+
+/// Index the files of an unpacked RSB that are still present on disk.
+pub fn load_packed_files(rsb_dir: &Path) -> Result<PackedFiles> {
+    let manifest_path = rsb_dir.join("rsb_manifest.json");
+    let manifest: RsbManifest = serde_json::from_str(
+        &fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read {:?}", manifest_path))?,
+    )
+    .with_context(|| format!("Failed to parse {:?}", manifest_path))?;
+
+    let mut files = PackedFiles::new();
+    for group in &manifest.group {
+        for subgroup in &group.subgroup {
+            let packet_dir = rsb_dir.join(&subgroup.name_packet);
+            for res in &subgroup.packet_info.res {
+                let file_path = packet_dir.join(res.path.replace('\\', "/"));
+                // Textures may have been left only as their decoded PNG
+                if !file_path.exists() && !file_path.with_extension("png").exists() {
+                    continue;
+                }
+                let dimension = res
+                    .part1_info
+                    .as_ref()
+                    .filter(|p| p.width > 0 && p.height > 0)
+                    .map(|p| Dimension {
+                        width: p.width,
+                        height: p.height,
+                    })
+                    .or_else(|| {
+                        res.ptx_info.as_ref().map(|p| Dimension {
+                            width: p.width as u32,
+                            height: p.height as u32,
+                        })
+                    });
+                files.insert(&res.path, dimension);
+            }
+        }
+    }
+    Ok(files)
 }
 
 pub fn handle(cmd: ResourcesCommands) -> Result<()> {
@@ -28,40 +406,1145 @@ pub fn handle(cmd: ResourcesCommands) -> Result<()> {
         ResourcesCommands::Convert {
             input,
             output,
+            from,
+            to,
             expand_path,
+            seed,
+            lossy,
         } => {
-            let json_str = fs::read_to_string(&input)
-                .with_context(|| format!("Failed to read input JSON: {:?}", input))?;
+            let from = from.as_deref().map(parse_manifest_format).transpose()?;
+            let to = to.as_deref().map(parse_manifest_format).transpose()?;
+
+            let (group, from) = load_manifest(&input, from, seed.as_deref())?;
+            println!("Detected {} manifest.", from.name());
 
-            // Try to parse as ResInfo first (flat)
-            if let Ok(res_info) = serde_json::from_str::<ResInfo>(&json_str) {
-                println!("Detected flat ResInfo structure. Converting to ResourceGroup...");
-                let group = convert_res_info_to_resource_group(&res_info)
-                    .context("Failed to convert ResInfo to ResourceGroup")?;
+            let to = to.unwrap_or(match from {
+                ManifestFormat::Json => ManifestFormat::ResJson,
+                _ => ManifestFormat::Json,
+            });
 
-                let out_str = serde_json::to_string_pretty(&group)?;
-                fs::write(&output, out_str)
-                    .with_context(|| format!("Failed to write output JSON to {:?}", output))?;
-                println!("Successfully wrote ResourceGroup to {:?}", output);
-                return Ok(());
+            if to != from {
+                // res.json stores no versions and one path shape for the whole file, which
+                // `--expand-path` brings back; neither is the input's to lose
+                let mut checked = group.clone();
+                if from == ManifestFormat::ResJson {
+                    checked.version = None;
+                    checked.content_version = None;
+                    let resources = checked.groups.iter_mut().flat_map(|g| &mut g.resources);
+                    for res in resources.flatten() {
+                        res.path = res.path.expanded("string");
+                        res.srcpath = res.srcpath.take().map(|p| p.expanded("string"));
+                    }
+                }
+                let losses = conversion_losses(&checked, to, &expand_path, seed.as_deref())?;
+                if !losses.is_empty() {
+                    for loss in losses.iter().take(10) {
+                        println!("  {} cannot keep {}", to.name(), loss);
+                    }
+                    if losses.len() > 10 {
+                        println!("  ... and {} more", losses.len() - 10);
+                    }
+                    if !lossy {
+                        anyhow::bail!(
+                            "Converting to {} would lose {} value(s); pass --lossy to convert anyway",
+                            to.name(),
+                            losses.len()
+                        );
+                    }
+                    println!(
+                        "Warning: writing {} without {} value(s).",
+                        to.name(),
+                        losses.len()
+                    );
+                }
             }
 
-            // Fallback: try parsing as ResourceGroup (hierarchical)
-            if let Ok(group) = serde_json::from_str::<ResourceGroup>(&json_str) {
-                println!("Detected hierarchical ResourceGroup structure. Converting to ResInfo...");
-                let res_info = convert_resource_group_to_res_info(&group, &expand_path)
-                    .context("Failed to convert ResourceGroup to ResInfo")?;
+            save_manifest(&group, &output, to, &expand_path, seed.as_deref())?;
+            println!("Successfully wrote {} to {:?}", to.name(), output);
+            Ok(())
+        }
+        ResourcesCommands::Validate {
+            input,
+            rsb,
+            from,
+            seed,
+        } => {
+            let from = from.as_deref().map(parse_manifest_format).transpose()?;
+            let (group, from) = load_manifest(&input, from, seed.as_deref())?;
+            println!("Detected {} manifest.", from.name());
 
-                let out_str = serde_json::to_string_pretty(&res_info)?;
-                fs::write(&output, out_str)
-                    .with_context(|| format!("Failed to write output JSON to {:?}", output))?;
-                println!("Successfully wrote ResInfo to {:?}", output);
-                return Ok(());
+            let files = rsb.as_deref().map(load_packed_files).transpose()?;
+            if let Some(files) = &files {
+                println!("Indexed {} files from unpacked RSB.", files.len());
             }
 
-            anyhow::bail!(
-                "Input file format was not recognized as a valid ResInfo or ResourceGroup JSON."
+            let issues = validate_resource_group(&group, files.as_ref());
+            for issue in &issues {
+                println!("{}", issue);
+            }
+            if !issues.is_empty() {
+                anyhow::bail!("Manifest has {} problem(s)", issues.len());
+            }
+            println!("Manifest is valid.");
+            Ok(())
+        }
+        ResourcesCommands::RenumberSlots {
+            input,
+            output,
+            mode,
+            from,
+            to,
+            expand_path,
+            seed,
+        } => {
+            let mode = parse_slot_mode(&mode)?;
+            let from = from.as_deref().map(parse_manifest_format).transpose()?;
+            let to = to.as_deref().map(parse_manifest_format).transpose()?;
+
+            let (mut group, from) = load_manifest(&input, from, seed.as_deref())?;
+            println!("Detected {} manifest.", from.name());
+
+            let changed = renumber_slots(&mut group, mode);
+            println!(
+                "Reassigned {} slot(s), slot_count is now {}.",
+                changed, group.slot_count
+            );
+
+            let output = output.unwrap_or(input);
+            let to = to.unwrap_or(from);
+            save_manifest(&group, &output, to, &expand_path, seed.as_deref())?;
+            println!("Successfully wrote {} to {:?}", to.name(), output);
+            Ok(())
+        }
+        ResourcesCommands::Merge {
+            inputs,
+            output,
+            policy,
+            report,
+            to,
+            expand_path,
+            seed,
+        } => {
+            let policy = parse_conflict_policy(&policy)?;
+            let to = to.as_deref().map(parse_manifest_format).transpose()?;
+
+            let (mut base, base_format) = load_manifest(&inputs[0], None, seed.as_deref())?;
+            let mut layers = Vec::with_capacity(inputs.len() - 1);
+            for path in &inputs[1..] {
+                let (layer, _) = load_manifest(path, None, seed.as_deref())?;
+                layers.push((path.display().to_string(), layer));
+            }
+
+            let merge_report = merge_resource_groups(&mut base, &layers, policy)
+                .context("Failed to merge manifests")?;
+
+            let print_entries = |label: &str, entries: &[MergeEntry]| {
+                for e in entries {
+                    match &e.id {
+                        Some(id) => println!("  {} {}/{} ({})", label, e.group, id, e.source),
+                        None => println!("  {} group {} ({})", label, e.group, e.source),
+                    }
+                }
+            };
+            println!(
+                "Merged {} layer(s): {} group(s) added, {} member(s) added, {} overridden, {} kept.",
+                layers.len(),
+                merge_report.added_groups.len(),
+                merge_report.added.len(),
+                merge_report.overridden.len(),
+                merge_report.kept.len()
+            );
+            print_entries("overridden", &merge_report.overridden);
+            print_entries("kept", &merge_report.kept);
+            println!(
+                "Reassigned {} slot(s), slot_count is now {}.",
+                merge_report.reassigned_slots, base.slot_count
+            );
+
+            if let Some(report_path) = report {
+                fs::write(&report_path, serde_json::to_string_pretty(&merge_report)?)
+                    .with_context(|| format!("Failed to write report to {:?}", report_path))?;
+                println!("Wrote merge report to {:?}", report_path);
+            }
+
+            let to = to.unwrap_or(base_format);
+            save_manifest(&base, &output, to, &expand_path, seed.as_deref())?;
+            println!("Successfully wrote {} to {:?}", to.name(), output);
+            Ok(())
+        }
+        ResourcesCommands::Scaffold {
+            input,
+            output,
+            group,
+            prefix,
+            path_prefix,
+            res,
+            parent,
+            atlas,
+            atlas_dir,
+            packing,
+            to,
+            expand_path,
+            seed,
+        } => {
+            let to = to.as_deref().map(parse_manifest_format).transpose()?;
+            let group = match group {
+                Some(group) => group,
+                None => input
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .ok_or_else(|| anyhow!("Cannot derive a group id from {:?}", input))?,
+            };
+            let options = ScaffoldOptions {
+                group,
+                id_prefix: prefix,
+                path_prefix: path_prefix
+                    .split(['/', '\\'])
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect(),
+                res,
+                parent,
+                atlas,
+            };
+            let atlas_dir = atlas_dir
+                .unwrap_or_else(|| output.parent().unwrap_or(Path::new("")).join("atlases"));
+            let layer = scaffold_folder(&input, &options, &atlas_dir, &packing)?;
+            let count = layer
+                .groups
+                .iter()
+                .filter_map(|g| g.resources.as_ref())
+                .map(Vec::len)
+                .sum::<usize>();
+
+            let (mut base, format) = if output.exists() {
+                let (base, format) = load_manifest(&output, None, seed.as_deref())?;
+                println!("Detected {} manifest.", format.name());
+                (base, format)
+            } else {
+                let base = ResourceGroup {
+                    version: Some(1),
+                    content_version: Some(1),
+                    slot_count: 0,
+                    groups: Vec::new(),
+                };
+                (base, ManifestFormat::Json)
+            };
+
+            let merge_report = merge_resource_groups(
+                &mut base,
+                &[(input.display().to_string(), layer)],
+                ConflictPolicy::Override,
+            )
+            .context("Failed to add the scaffolded group")?;
+            println!(
+                "Scaffolded group {} with {} resource(s), {} replacing existing entries; slot_count is now {}.",
+                options.group,
+                count,
+                merge_report.overridden.len(),
+                base.slot_count
+            );
+
+            let to = to.unwrap_or(format);
+            save_manifest(&base, &output, to, &expand_path, seed.as_deref())?;
+            println!("Successfully wrote {} to {:?}", to.name(), output);
+            Ok(())
+        }
+        ResourcesCommands::Index {
+            input,
+            id,
+            output,
+            seed,
+        } => {
+            let index = build_id_index(&input, seed.as_deref())?;
+            let files: HashSet<&str> = index
+                .values()
+                .flat_map(|e| e.definitions.iter().chain(&e.references))
+                .map(|r| r.file.as_str())
+                .collect();
+            println!("Indexed {} id(s) in {} file(s).", index.len(), files.len());
+
+            if let Some(id) = &id {
+                let entry = index
+                    .get(id)
+                    .ok_or_else(|| anyhow!("{} is not used in {:?}", id, input))?;
+                for r in &entry.definitions {
+                    println!("  defined    {} ({} {})", r.file, r.kind, r.location);
+                }
+                for r in &entry.references {
+                    println!("  referenced {} ({} {})", r.file, r.kind, r.location);
+                }
+            } else {
+                for (id, entry) in &index {
+                    if entry.definitions.is_empty() {
+                        let r = &entry.references[0];
+                        println!(
+                            "  [undefined] {} used in {} ({} {})",
+                            id, r.file, r.kind, r.location
+                        );
+                    }
+                }
+            }
+
+            if let Some(output) = output {
+                fs::write(&output, serde_json::to_string_pretty(&index)?)
+                    .with_context(|| format!("Failed to write index to {:?}", output))?;
+                println!("Wrote index to {:?}", output);
+            }
+            Ok(())
+        }
+        ResourcesCommands::RenameId {
+            input,
+            old_id,
+            new_id,
+            dry_run,
+            seed,
+        } => {
+            let index = build_id_index(&input, seed.as_deref())?;
+            let changes = rename_id(&input, &index, &old_id, &new_id, seed.as_deref(), dry_run)?;
+            for (file, count) in &changes {
+                println!("  {} ({} reference(s))", file, count);
+            }
+            let total: usize = changes.iter().map(|(_, count)| count).sum();
+            if dry_run {
+                println!(
+                    "Would rename {} reference(s) in {} file(s).",
+                    total,
+                    changes.len()
+                );
+            } else {
+                println!(
+                    "Renamed {} to {}: {} reference(s) in {} file(s).",
+                    old_id,
+                    new_id,
+                    total,
+                    changes.len()
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Scan `input` for PNGs and build the manifest layer for them, packing and saving
+/// the atlas pages into `atlas_dir` when [`ScaffoldOptions::atlas`] is set.
+pub fn scaffold_folder(
+    input: &Path,
+    options: &ScaffoldOptions,
+    atlas_dir: &Path,
+    packing: &super::atlas::PackingArgs,
+) -> Result<ResourceGroup> {
+    let mut files: Vec<PathBuf> = walkdir::WalkDir::new(input)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(anyhow!("No PNG files found in {:?}", input));
+    }
+
+    let mut sprites = Vec::with_capacity(files.len());
+    let mut images = Vec::new();
+    for path in &files {
+        let name = path
+            .strip_prefix(input)
+            .unwrap_or(path)
+            .with_extension("")
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        let (width, height) = if options.atlas {
+            let image = image::open(path)
+                .with_context(|| format!("Failed to read {:?}", path))?
+                .to_rgba8();
+            let size = image.dimensions();
+            images.push(image);
+            size
+        } else {
+            image::image_dimensions(path).with_context(|| format!("Failed to read {:?}", path))?
+        };
+        sprites.push(ScaffoldSprite {
+            name,
+            width,
+            height,
+        });
+    }
+
+    let mut layer = scaffold_resources(&sprites, options)?;
+    if options.atlas {
+        let group = layer
+            .groups
+            .iter()
+            .find(|g| g.id == options.group)
+            .ok_or_else(|| anyhow!("Scaffolded group {} is missing", options.group))?;
+        let resources = group.resources.iter().flatten();
+        let mut atlas = atlas::types::OfficialAtlas {
+            id: group.id.clone(),
+            parent: group.parent.clone().unwrap_or_default(),
+            res: group.res.clone().unwrap_or_default(),
+            type_: group.r#type.clone(),
+            resources: resources.map(super::atlas::to_atlas_resource).collect(),
+        };
+        // Sprites follow the atlas image in the same order as the files
+        let keyed: Vec<_> = atlas.resources[1..]
+            .iter()
+            .map(|r| r.id.clone())
+            .zip(images)
+            .collect();
+        let pages = atlas::pack_official_atlas(&mut atlas, &keyed, &packing.to_config()?)?;
+
+        fs::create_dir_all(atlas_dir)?;
+        let page_resources = atlas.resources.iter().filter(|r| r.atlas == Some(true));
+        for (page, res) in pages.iter().zip(page_resources) {
+            let name = match &res.path {
+                Some(atlas::types::PathOrPaths::Multiple(parts)) => parts.last().cloned(),
+                Some(atlas::types::PathOrPaths::Single(path)) => {
+                    path.rsplit(['/', '\\']).next().map(str::to_string)
+                }
+                None => None,
+            }
+            .unwrap_or_else(|| res.id.clone());
+            let page_path = atlas_dir.join(format!("{}.png", name));
+            page.save(&page_path)?;
+            println!(
+                "Saved atlas page {}x{} to {:?}",
+                page.width(),
+                page.height(),
+                page_path
             );
         }
+        super::atlas::apply_atlas_layout(&mut layer, &atlas);
+    }
+    Ok(layer)
+}
+
+/// Files that can define or reference resource ids, recognised by name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum IdSource {
+    /// `resources.*`, `res.json` or any `.newton`
+    Manifest,
+    Pam,
+    PamJson,
+    Reanim,
+    ReanimJson,
+    Rton,
+    Json,
+}
+
+fn classify_id_source(path: &Path) -> Option<IdSource> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    let (stem, ext) = name.split_once('.').unwrap_or((&name, ""));
+    let source = match ext {
+        "pam.json" => IdSource::PamJson,
+        "reanim.json" => IdSource::ReanimJson,
+        "pam" => IdSource::Pam,
+        "reanim" | "reanim.compiled" => IdSource::Reanim,
+        "newton" => IdSource::Manifest,
+        "rton" | "json" if stem == "resources" || stem == "res" => IdSource::Manifest,
+        "rton" => IdSource::Rton,
+        "json" => IdSource::Json,
+        _ => return None,
+    };
+    Some(source)
+}
+
+/// One place a resource id appears
+#[derive(Debug, Clone, Serialize)]
+pub struct IdReference {
+    /// Path relative to the indexed folder
+    pub file: String,
+    pub kind: &'static str,
+    /// Where in the file, e.g. `group/resource`, `image 3` or a JSON pointer
+    pub location: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IdIndexEntry {
+    /// Manifest resources declaring the id
+    pub definitions: Vec<IdReference>,
+    pub references: Vec<IdReference>,
+}
+
+pub type IdIndex = BTreeMap<String, IdIndexEntry>;
+
+/// Index every resource id defined or used under `root`.
+///
+/// Manifests, PAM and reanim files are read by their structure. Strings in other RTON
+/// and JSON files are only indexed when they exactly match an id found that way.
+pub fn build_id_index(root: &Path, seed: Option<&str>) -> Result<IdIndex> {
+    let mut index = IdIndex::new();
+    let mut strings = Vec::new();
+
+    let mut files: Vec<PathBuf> = walkdir::WalkDir::new(root)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect();
+    files.sort();
+
+    for path in &files {
+        let Some(source) = classify_id_source(path) else {
+            continue;
+        };
+        let file = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        let mut add = |id: &str, kind: &'static str, location: String, definition: bool| {
+            let entry = index.entry(id.to_string()).or_default();
+            let list = if definition {
+                &mut entry.definitions
+            } else {
+                &mut entry.references
+            };
+            list.push(IdReference {
+                file: file.clone(),
+                kind,
+                location,
+            });
+        };
+
+        let result = (|| -> Result<()> {
+            match source {
+                IdSource::Manifest => {
+                    let (group, _) = load_manifest(path, None, seed)?;
+                    for g in &group.groups {
+                        for res in g.resources.iter().flatten() {
+                            add(&res.id, "resource", format!("{}/{}", g.id, res.id), true);
+                            if let Some(parent) = &res.parent {
+                                add(
+                                    parent,
+                                    "atlas parent",
+                                    format!("{}/{}", g.id, res.id),
+                                    false,
+                                );
+                            }
+                        }
+                    }
+                }
+                IdSource::Pam | IdSource::PamJson => {
+                    let pam = read_pam(path, source)?;
+                    for (i, image) in pam.image.iter().enumerate() {
+                        add(image.resource_id(), "pam", format!("image {}", i), false);
+                    }
+                }
+                IdSource::Reanim | IdSource::ReanimJson => {
+                    let (reanim, _) = read_reanim(path, source)?;
+                    for track in &reanim.tracks {
+                        let mut seen = HashSet::new();
+                        for t in &track.transforms {
+                            for id in [&t.i, &t.i2].into_iter().flatten() {
+                                if is_reanim_image_id(id) && seen.insert(id) {
+                                    add(id, "reanim", format!("track {}", track.name), false);
+                                }
+                            }
+                        }
+                    }
+                }
+                IdSource::Rton | IdSource::Json => {
+                    let data = fs::read(path)?;
+                    let value: serde_json::Value = if source == IdSource::Rton {
+                        serde_json::to_value(rton::from_bytes::<rton::RtonValue>(&data, seed)?)?
+                    } else {
+                        serde_json::from_slice(&data)?
+                    };
+                    let kind = if source == IdSource::Rton {
+                        "rton"
+                    } else {
+                        "json"
+                    };
+                    collect_json_strings(&value, String::new(), &mut |pointer, text| {
+                        // RTIDs name the object they point to, `RTID(<id>@<sheet>)`
+                        if text.starts_with("RTID(")
+                            && let Ok(rtid) = text.parse::<rton::Rtid>()
+                        {
+                            for name in rtid.names() {
+                                strings.push((
+                                    name.to_string(),
+                                    kind,
+                                    file.clone(),
+                                    pointer.clone(),
+                                ));
+                            }
+                        } else {
+                            strings.push((text.to_string(), kind, file.clone(), pointer));
+                        }
+                    });
+                }
+            }
+            Ok(())
+        })();
+        if let Err(e) = result {
+            eprintln!("Warning: skipping {:?}: {}", path, e);
+        }
+    }
+
+    for (text, kind, file, location) in strings {
+        if let Some(entry) = index.get_mut(&text) {
+            entry.references.push(IdReference {
+                file,
+                kind,
+                location,
+            });
+        }
+    }
+    Ok(index)
+}
+
+/// Reanim `i` values are image ids, except for the numeric indices some versions store
+fn is_reanim_image_id(id: &str) -> bool {
+    !id.is_empty() && id.parse::<i64>().is_err()
+}
+
+/// Visit every string value and object key with its JSON pointer
+fn collect_json_strings(
+    value: &serde_json::Value,
+    pointer: String,
+    visit: &mut dyn FnMut(String, &str),
+) {
+    match value {
+        serde_json::Value::String(text) => visit(pointer, text),
+        serde_json::Value::Array(values) => {
+            for (i, v) in values.iter().enumerate() {
+                collect_json_strings(v, format!("{}/{}", pointer, i), visit);
+            }
+        }
+        serde_json::Value::Object(map) => {
+            for (key, v) in map {
+                let child = format!("{}/{}", pointer, key);
+                visit(child.clone(), key);
+                collect_json_strings(v, child, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn read_pam(path: &Path, source: IdSource) -> Result<pam::PamInfo> {
+    if source == IdSource::PamJson {
+        return Ok(serde_json::from_slice(&fs::read(path)?)?);
+    }
+    pam::decode_pam(&mut Cursor::new(fs::read(path)?))
+}
+
+/// Read a reanim and the compiled version it was stored in; `None` for the XML text
+/// of `.reanim` files and for JSON
+fn read_reanim(
+    path: &Path,
+    source: IdSource,
+) -> Result<(reanim::Reanim, Option<reanim::ReanimVersion>)> {
+    let data = fs::read(path)?;
+    if source == IdSource::ReanimJson {
+        return Ok((serde_json::from_slice(&data)?, None));
+    }
+    if is_xml_text(&data) {
+        let text =
+            std::str::from_utf8(&data).with_context(|| format!("{:?} is not UTF-8 text", path))?;
+        return Ok((reanim::decode_xml(text)?, None));
+    }
+    let (reanim, version) = reanim::decode_versioned(&data)?;
+    Ok((reanim, Some(version)))
+}
+
+/// Whether `data` starts like XML rather than a compiled file
+fn is_xml_text(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    data.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'<')
+}
+
+/// Rewrite every file under `root` that the index lists for `old_id`. Returns each
+/// changed file with its number of replaced references.
+pub fn rename_id(
+    root: &Path,
+    index: &IdIndex,
+    old_id: &str,
+    new_id: &str,
+    seed: Option<&str>,
+    dry_run: bool,
+) -> Result<Vec<(String, usize)>> {
+    if new_id.is_empty() || new_id == old_id {
+        return Err(anyhow!("The new id must differ from {}", old_id));
+    }
+    if index.contains_key(new_id) {
+        return Err(anyhow!(
+            "{} is already used in {:?}; renaming would merge two resources",
+            new_id,
+            root
+        ));
+    }
+    let entry = index
+        .get(old_id)
+        .ok_or_else(|| anyhow!("{} is not used in {:?}", old_id, root))?;
+
+    let mut files: Vec<&str> = entry
+        .definitions
+        .iter()
+        .chain(&entry.references)
+        .map(|r| r.file.as_str())
+        .collect();
+    files.sort();
+    files.dedup();
+
+    let mut changes = Vec::new();
+    for file in files {
+        let path = root.join(file);
+        let Some(source) = classify_id_source(&path) else {
+            continue;
+        };
+        let data = fs::read(&path)?;
+        let is_rton = data.starts_with(b"RTON") || data.starts_with(&[0x10, 0x00]);
+
+        let (count, output) = match source {
+            IdSource::Manifest if detect_manifest_format(&data) == ManifestFormat::Newton => {
+                let (mut group, _) = load_manifest(&path, Some(ManifestFormat::Newton), None)?;
+                let count = rename_resource_id(&mut group, old_id, new_id);
+                let mut out = Vec::new();
+                newton::encode_newton(&group, &mut out)?;
+                (count, out)
+            }
+            IdSource::Manifest | IdSource::Rton if is_rton => {
+                let mut value: rton::RtonValue = rton::from_bytes(&data, seed)?;
+                let count = value.replace_string(old_id, new_id);
+                let mut out = Vec::new();
+                // Only re-encrypt files that were encrypted
+                let seed = seed.filter(|_| !data.starts_with(b"RTON"));
+                rton::to_writer(&mut out, &value, seed)?;
+                (count, out)
+            }
+            // Binary PAM is spliced so a rename never re-encodes the animation
+            IdSource::Pam => {
+                let (out, count) = pam::rename_image_id(&data, old_id, new_id)?;
+                (count, out)
+            }
+            IdSource::PamJson => {
+                let mut pam = read_pam(&path, source)?;
+                let mut count = 0;
+                for image in &mut pam.image {
+                    if image.resource_id() == old_id {
+                        image.set_resource_id(new_id);
+                        count += 1;
+                    }
+                }
+                (count, serde_json::to_string_pretty(&pam)?.into_bytes())
+            }
+            IdSource::Reanim => {
+                let (mut reanim, version) = read_reanim(&path, source)?;
+                let mut count = 0;
+                for t in reanim.tracks.iter_mut().flat_map(|t| &mut t.transforms) {
+                    for id in [&mut t.i, &mut t.i2].into_iter().flatten() {
+                        if id == old_id {
+                            *id = new_id.to_string();
+                            count += 1;
+                        }
+                    }
+                }
+                let out = match version {
+                    Some(version) => reanim::encode(&reanim, version)?,
+                    None => reanim::encode_xml(&reanim).into_bytes(),
+                };
+                (count, out)
+            }
+            // JSON is edited as text so formatting and key order survive; ids never
+            // need escaping, so an exact match is the quoted id or an RTID name
+            _ => {
+                let mut text = String::from_utf8(data)
+                    .with_context(|| format!("{:?} is not UTF-8 JSON", path))?;
+                let mut count = 0;
+                for (from, to) in [
+                    (format!("\"{}\"", old_id), format!("\"{}\"", new_id)),
+                    (format!("\"RTID({}@", old_id), format!("\"RTID({}@", new_id)),
+                    (format!("@{})\"", old_id), format!("@{})\"", new_id)),
+                ] {
+                    count += text.matches(&from).count();
+                    text = text.replace(&from, &to);
+                }
+                (count, text.into_bytes())
+            }
+        };
+
+        if count == 0 {
+            continue;
+        }
+        if !dry_run {
+            fs::write(&path, output).with_context(|| format!("Failed to write {:?}", path))?;
+        }
+        changes.push((file.to_string(), count));
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pvz2_resources::PathDef;
+    use serde_json::{Value, json};
+
+    const FORMATS: [ManifestFormat; 4] = [
+        ManifestFormat::Newton,
+        ManifestFormat::Rton,
+        ManifestFormat::Json,
+        ManifestFormat::ResJson,
+    ];
+
+    /// Backslash paths in the order and with the slots a res.json reads back as, so
+    /// every format can hold it
+    fn sample_group() -> ResourceGroup {
+        serde_json::from_value(json!({
+            "slot_count": 3,
+            "groups": [
+                {
+                    "id": "Global",
+                    "type": "simple",
+                    "resources": [
+                        { "type": "File", "slot": 0, "id": "FILE_A", "path": "data\\a.rton" }
+                    ]
+                },
+                {
+                    "id": "UI",
+                    "type": "composite",
+                    "subgroups": [{ "id": "UI_1536", "res": "1536" }]
+                },
+                {
+                    "id": "UI_1536",
+                    "type": "simple",
+                    "res": "1536",
+                    "parent": "UI",
+                    "resources": [
+                        {
+                            "type": "Image",
+                            "slot": 1,
+                            "id": "ATLAS_UI_1536_00",
+                            "path": "atlases\\UI_1536_00",
+                            "atlas": true,
+                            "runtime": true,
+                            "width": 512,
+                            "height": 256
+                        },
+                        {
+                            "type": "Image",
+                            "slot": 2,
+                            "id": "IMAGE_UI_BUTTON",
+                            "path": "images\\button",
+                            "parent": "ATLAS_UI_1536_00",
+                            "x": -4,
+                            "ax": 0, "ay": 0, "aw": 64, "ah": 32
+                        }
+                    ]
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    /// Newton does not store the versions and res.json always reads back version 1
+    fn without_versions(group: &ResourceGroup) -> Value {
+        let mut group = group.clone();
+        group.version = None;
+        group.content_version = None;
+        serde_json::to_value(group).unwrap()
+    }
+
+    #[test]
+    fn test_convert_every_format_pair() {
+        let dir = std::env::temp_dir().join(format!("manifest_pairs_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let expected = without_versions(&sample_group());
+
+        for from in FORMATS {
+            let input = dir.join("input");
+            save_manifest(&sample_group(), &input, from, "string", None).unwrap();
+            let (group, detected) = load_manifest(&input, None, None).unwrap();
+            assert_eq!(detected, from);
+            assert_eq!(without_versions(&group), expected, "{}", from.name());
+
+            for to in FORMATS {
+                let output = dir.join("output");
+                save_manifest(&group, &output, to, "string", None).unwrap();
+                let (back, _) = load_manifest(&output, Some(to), None).unwrap();
+                assert_eq!(
+                    without_versions(&back),
+                    expected,
+                    "{} -> {}",
+                    from.name(),
+                    to.name()
+                );
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_conversion_losses() {
+        // res.json reads back version 1, which the sample does not set
+        for to in [
+            ManifestFormat::Newton,
+            ManifestFormat::Rton,
+            ManifestFormat::Json,
+        ] {
+            let losses = conversion_losses(&sample_group(), to, "string", None).unwrap();
+            assert!(losses.is_empty(), "{}: {:?}", to.name(), losses);
+        }
+
+        let mut group = sample_group();
+        group.version = Some(1);
+        let file = &mut group.groups[0].resources.as_mut().unwrap()[0];
+        file.runtime = Some(true);
+        file.path = PathDef::Array(vec!["data".to_string(), "a.rton".to_string()]);
+
+        let losses = conversion_losses(&group, ManifestFormat::Newton, "array", None).unwrap();
+        assert_eq!(
+            losses,
+            [
+                "manifest version",
+                "`path` of `FILE_A` in `Global`",
+                "`runtime` of `FILE_A` in `Global`"
+            ]
+        );
+        assert!(
+            conversion_losses(&group, ManifestFormat::Rton, "array", None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_convert_refuses_lossy_newton() {
+        let dir = std::env::temp_dir().join(format!("manifest_lossy_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("resources.json");
+        let output = dir.join("RESOURCES.NEWTON");
+        let mut group = sample_group();
+        group.groups[0].resources.as_mut().unwrap()[0].runtime = Some(true);
+        save_manifest(&group, &input, ManifestFormat::Json, "string", None).unwrap();
+
+        let convert = |lossy| {
+            handle(ResourcesCommands::Convert {
+                input: input.clone(),
+                output: output.clone(),
+                from: None,
+                to: Some("newton".to_string()),
+                expand_path: "array".to_string(),
+                seed: None,
+                lossy,
+            })
+        };
+        let refused = convert(false);
+        let written_without_lossy = output.exists();
+        let forced = convert(true);
+        let written_with_lossy = output.exists();
+
+        // A res.json input has nothing Newton can't keep
+        fs::remove_file(&output).unwrap();
+        save_manifest(
+            &sample_group(),
+            &input,
+            ManifestFormat::ResJson,
+            "array",
+            None,
+        )
+        .unwrap();
+        let from_res_json = convert(false);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert!(refused.unwrap_err().to_string().contains("--lossy"));
+        assert!(!written_without_lossy);
+        forced.unwrap();
+        assert!(written_with_lossy);
+        from_res_json.unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_rton_round_trip() {
+        let dir = std::env::temp_dir().join(format!("manifest_rton_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("RESOURCES.RTON");
+        let seed = Some("test_seed");
+
+        save_manifest(&sample_group(), &path, ManifestFormat::Rton, "string", seed).unwrap();
+        let (group, _) = load_manifest(&path, Some(ManifestFormat::Rton), seed).unwrap();
+        assert_eq!(without_versions(&group), without_versions(&sample_group()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rename_id_round_trip() {
+        let dir = std::env::temp_dir().join(format!("rename_id_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rtid = |name: &str| {
+            rton::RtonValue::Rtid(rton::Rtid::Raw {
+                name: name.to_string(),
+                parent: "UIProps".to_string(),
+            })
+        };
+        let props = |id: &str| {
+            rton::RtonValue::Object(vec![
+                ("button".to_string(), rtid(id)),
+                ("icon".to_string(), rtid("IMAGE_UI_ICON")),
+            ])
+        };
+
+        save_manifest(
+            &sample_group(),
+            &dir.join("resources.json"),
+            ManifestFormat::Json,
+            "string",
+            None,
+        )
+        .unwrap();
+        let pam = pam::PamInfo {
+            version: 6,
+            frame_rate: 30,
+            position: [0.0, 0.0],
+            size: [64.0, 32.0],
+            image: vec![pam::types::ImageInfo {
+                name: "button|IMAGE_UI_BUTTON".to_string(),
+                size: [64, 32],
+                transform: vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
+            }],
+            sprite: Vec::new(),
+            main_sprite: pam::types::SpriteInfo {
+                frame: vec![pam::types::FrameInfo::default()],
+                ..Default::default()
+            },
+        };
+        let mut data = Vec::new();
+        pam::encode_pam(&pam, &mut data).unwrap();
+        fs::write(dir.join("button.pam"), data).unwrap();
+        let reanim = reanim::Reanim {
+            fps: 12.0,
+            tracks: vec![reanim::ReanimTrack {
+                name: "button".to_string(),
+                transforms: vec![reanim::ReanimTransform {
+                    i: Some("IMAGE_UI_BUTTON".to_string()),
+                    ..Default::default()
+                }],
+            }],
+            ..Default::default()
+        };
+        fs::write(
+            dir.join("button.reanim"),
+            reanim::encode(&reanim, reanim::ReanimVersion::PC).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("props.rton"),
+            rton::to_bytes(&props("IMAGE_UI_BUTTON"), None).unwrap(),
+        )
+        .unwrap();
+        fs::write(
+            dir.join("props.json"),
+            "{ \"button\": \"RTID(IMAGE_UI_BUTTON@UIProps)\" }",
+        )
+        .unwrap();
+
+        let read_all = || {
+            let mut files: Vec<_> = fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect();
+            files.sort();
+            files
+                .into_iter()
+                .map(|path| (path.clone(), fs::read(path).unwrap()))
+                .collect::<Vec<_>>()
+        };
+        let before = read_all();
+
+        let index = build_id_index(&dir, None).unwrap();
+        let kinds: Vec<_> = index["IMAGE_UI_BUTTON"]
+            .references
+            .iter()
+            .map(|r| r.kind)
+            .collect();
+        assert_eq!(kinds, ["pam", "reanim", "json", "rton"]);
+
+        let changes =
+            rename_id(&dir, &index, "IMAGE_UI_BUTTON", "IMAGE_UI_OK", None, false).unwrap();
+        assert_eq!(changes.len(), 5);
+        let renamed = read_pam(&dir.join("button.pam"), IdSource::Pam).unwrap();
+        assert_eq!(renamed.image[0].name, "button|IMAGE_UI_OK");
+        let renamed: rton::RtonValue =
+            rton::from_bytes(&fs::read(dir.join("props.rton")).unwrap(), None).unwrap();
+        assert_eq!(renamed, props("IMAGE_UI_OK"));
+        assert_eq!(
+            fs::read_to_string(dir.join("props.json")).unwrap(),
+            "{ \"button\": \"RTID(IMAGE_UI_OK@UIProps)\" }"
+        );
+
+        // Renaming back must restore every file byte for byte
+        let index = build_id_index(&dir, None).unwrap();
+        rename_id(&dir, &index, "IMAGE_UI_OK", "IMAGE_UI_BUTTON", None, false).unwrap();
+        assert_eq!(read_all(), before);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_rename_id_in_text_reanim() {
+        let dir = std::env::temp_dir().join(format!("rename_reanim_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let reanim = reanim::Reanim {
+            fps: 12.0,
+            tracks: vec![reanim::ReanimTrack {
+                name: "blink".to_string(),
+                transforms: vec![
+                    reanim::ReanimTransform {
+                        i: Some("IMAGE_REANIM_EYE".to_string()),
+                        ..Default::default()
+                    },
+                    reanim::ReanimTransform {
+                        i2: Some("IMAGE_REANIM_EYE".to_string()),
+                        ..Default::default()
+                    },
+                ],
+            }],
+            ..Default::default()
+        };
+        let path = dir.join("eye.reanim");
+        let text = reanim::encode_xml(&reanim);
+        fs::write(&path, &text).unwrap();
+
+        let index = build_id_index(&dir, None).unwrap();
+        let references = &index["IMAGE_REANIM_EYE"].references;
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].kind, "reanim");
+        assert_eq!(references[0].location, "track blink");
+
+        let changes = rename_id(
+            &dir,
+            &index,
+            "IMAGE_REANIM_EYE",
+            "IMAGE_REANIM_PUPIL",
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(changes.len(), 1);
+        let renamed = reanim::decode_xml(&fs::read_to_string(&path).unwrap()).unwrap();
+        let transforms = &renamed.tracks[0].transforms;
+        assert_eq!(transforms[0].i.as_deref(), Some("IMAGE_REANIM_PUPIL"));
+        assert_eq!(transforms[1].i2.as_deref(), Some("IMAGE_REANIM_PUPIL"));
+
+        let index = build_id_index(&dir, None).unwrap();
+        rename_id(
+            &dir,
+            &index,
+            "IMAGE_REANIM_PUPIL",
+            "IMAGE_REANIM_EYE",
+            None,
+            false,
+        )
+        .unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), text);
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -1,10 +1,16 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow, bail};
 use clap::Subcommand;
 use rsb::{
     Rsb,
     io::writer::RsbWriter,
     ptx::decoder::PtxDecoder,
-    rsg::{pack_rsg, types::UnpackedFile, unpack_rsg},
+    ptx::dither::PtxEncodeOptions,
+    ptx::mipmap::MipFilter,
+    rsg::{
+        pack_rsg,
+        types::{Part1Extra, UnpackedFile},
+        unpack_rsg,
+    },
     schema::types::*,
 };
 use std::collections::HashMap;
@@ -37,6 +43,15 @@ pub enum RsbCommands {
         /// Apply Palette compression trick (experimental)
         #[arg(long)]
         use_palette: bool,
+        /// Dithering for 16-bit formats: none, floyd-steinberg, ordered
+        #[arg(long, default_value = "none")]
+        dither: String,
+        /// Premultiply RGB by alpha before encoding textures
+        #[arg(long)]
+        premultiply_alpha: bool,
+        /// Use a .ktx2/.ktx/.dds/.pvr next to a PTX as-is instead of encoding its PNG
+        #[arg(long)]
+        use_sidecars: bool,
     },
 }
 
@@ -52,11 +67,96 @@ pub fn handle(cmd: RsbCommands) -> Result<()> {
             output,
             powervr,
             use_palette,
-        } => pack_rsb(&input, &output, powervr, use_palette),
+            dither,
+            premultiply_alpha,
+            use_sidecars,
+        } => {
+            let options = PtxEncodeOptions {
+                dither: super::ptx::parse_dither(&dither)?,
+                premultiply_alpha,
+            };
+            pack_rsb(
+                &input,
+                &output,
+                powervr,
+                use_palette,
+                use_sidecars,
+                &options,
+            )
+        }
+    }
+}
+
+/// Rebuild a PTX from a container sidecar, which must match what the manifest expects.
+fn import_sidecar(
+    path: &Path,
+    ptx_info: Option<&RsbPtxInfo>,
+    part1_info: Option<&Part1Extra>,
+) -> Result<Vec<u8>> {
+    let imported = super::ptx::import_container(path, None)
+        .map_err(|e| anyhow!("Failed to import {:?}: {}", path, e))?;
+
+    let expected_format = ptx_info.map(|p| p.format).unwrap_or(imported.format_code);
+    let (expected_w, expected_h) = match (part1_info, ptx_info) {
+        (Some(p1), _) if p1.width > 0 && p1.height > 0 => (p1.width, p1.height),
+        (_, Some(ptx)) => (ptx.width as u32, ptx.height as u32),
+        _ => (imported.width, imported.height),
+    };
+    if imported.format_code != expected_format
+        || (imported.width, imported.height) != (expected_w, expected_h)
+    {
+        bail!(
+            "{:?}: format {} {}x{} does not match manifest format {} {}x{}",
+            path,
+            imported.format_code,
+            imported.width,
+            imported.height,
+            expected_format,
+            expected_w,
+            expected_h
+        );
+    }
+
+    // Only ETC1-compressed alpha (100) changes the layout; other values mean an 8-bit plane
+    let etc1_alpha = |alpha_format: Option<i32>| alpha_format == Some(100);
+    let expected_alpha = ptx_info
+        .map(|p| p.alpha_format)
+        .unwrap_or(imported.alpha_format);
+    if etc1_alpha(imported.alpha_format) != etc1_alpha(expected_alpha) {
+        bail!(
+            "{:?}: alpha_format {:?} does not match manifest alpha_format {:?}",
+            path,
+            imported.alpha_format,
+            expected_alpha
+        );
+    }
+
+    let expected_mips = ptx_info
+        .and_then(|p| p.mip_count)
+        .unwrap_or(if ptx_info.is_some() {
+            1
+        } else {
+            imported.mip_count
+        });
+    if imported.mip_count != expected_mips {
+        bail!(
+            "{:?}: {} mip levels do not match manifest mip_count {}",
+            path,
+            imported.mip_count,
+            expected_mips
+        );
     }
+    Ok(imported.data)
 }
 
-pub fn pack_rsb(input: &Path, output: &Path, is_powervr: bool, use_palette: bool) -> Result<()> {
+pub fn pack_rsb(
+    input: &Path,
+    output: &Path,
+    is_powervr: bool,
+    use_palette: bool,
+    use_sidecars: bool,
+    options: &PtxEncodeOptions,
+) -> Result<()> {
     // Read Global Manifest
     let rsb_manifest_path = input.join("rsb_manifest.json");
     let rsb_manifest_content = fs::read_to_string(&rsb_manifest_path)?;
@@ -119,7 +219,19 @@ pub fn pack_rsb(input: &Path, output: &Path, is_powervr: bool, use_palette: bool
                             .eq_ignore_ascii_case("ptx")
                         {
                             let png_path = file_path.with_extension("png");
-                            if png_path.exists() {
+                            let sidecar = if use_sidecars {
+                                super::ptx::container_sidecar(&file_path)
+                            } else {
+                                None
+                            };
+                            if let Some(sidecar) = sidecar {
+                                // Pre-compressed blocks from an external tool, used as-is
+                                data = import_sidecar(
+                                    &sidecar,
+                                    res.ptx_info.as_ref(),
+                                    res.part1_info.as_ref(),
+                                )?;
+                            } else if png_path.exists() {
                                 // Encode PNG back to PTX
                                 if let Ok(img) = image::open(&png_path) {
                                     let ptx_fmt =
@@ -131,8 +243,20 @@ pub fn pack_rsb(input: &Path, output: &Path, is_powervr: bool, use_palette: bool
                                         format = rsb::ptx::types::PtxFormat::Etc1Palette;
                                     }
 
-                                    if let Ok(encoded) = rsb::ptx::encoder::PtxEncoder::encode(
-                                        &img, format, is_powervr,
+                                    // Rebuild the mip chain recorded at unpack time
+                                    let mip_count = res
+                                        .ptx_info
+                                        .as_ref()
+                                        .and_then(|p| p.mip_count)
+                                        .unwrap_or(1);
+
+                                    if let Ok(encoded) = rsb::ptx::encoder::PtxEncoder::encode_mips(
+                                        &img,
+                                        format,
+                                        is_powervr,
+                                        options,
+                                        mip_count,
+                                        MipFilter::Box,
                                     ) {
                                         data = encoded;
                                     }
@@ -507,6 +631,7 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                                 pitch: global_ptx.pitch,
                                                 alpha_size: global_ptx.alpha_size,
                                                 alpha_format: global_ptx.alpha_format,
+                                                mip_count: None,
                                             });
                                         }
                                     }
@@ -527,6 +652,7 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                     }
 
                                     // Decode PTX if applicable
+                                    let mut mip_count = None;
                                     if out_file_path
                                         .extension()
                                         .unwrap_or_default()
@@ -547,7 +673,7 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                         }
 
                                         if width > 0 && height > 0 {
-                                            match PtxDecoder::decode(
+                                            match PtxDecoder::decode_mips(
                                                 &file.data,
                                                 width,
                                                 height,
@@ -555,8 +681,13 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                                 ptx.alpha_size,
                                                 ptx.alpha_format,
                                                 is_powervr,
+                                                None,
                                             ) {
-                                                Ok(img) => {
+                                                Ok(levels) => {
+                                                    if levels.len() > 1 {
+                                                        mip_count = Some(levels.len() as u32);
+                                                    }
+                                                    let img = &levels[0];
                                                     let png_path =
                                                         out_file_path.with_extension("png");
                                                     if let Err(e) = img.save(&png_path) {
@@ -576,6 +707,13 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                         }
                                     }
 
+                                    if let Some(info) = ptx_info.as_mut() {
+                                        info.mip_count = mip_count;
+                                    }
+                                    if let Some(property) = ptx_property.as_mut() {
+                                        property.mip_count = mip_count;
+                                    }
+
                                     ManifestRes {
                                         path: file.path.clone(),
                                         part1_info: file.part1_info.clone(),
@@ -649,6 +787,7 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                         pitch: global_ptx.pitch,
                                         alpha_size: global_ptx.alpha_size,
                                         alpha_format: global_ptx.alpha_format,
+                                        mip_count: None,
                                     });
                                 }
                             }
@@ -665,6 +804,7 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                             }
 
                             // Decode PTX if applicable
+                            let mut mip_count = None;
                             if out_file_path
                                 .extension()
                                 .unwrap_or_default()
@@ -685,7 +825,7 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                 }
 
                                 if width > 0 && height > 0 {
-                                    match PtxDecoder::decode(
+                                    match PtxDecoder::decode_mips(
                                         &file.data,
                                         width,
                                         height,
@@ -693,8 +833,13 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                         ptx.alpha_size,
                                         ptx.alpha_format,
                                         is_powervr,
+                                        None,
                                     ) {
-                                        Ok(img) => {
+                                        Ok(levels) => {
+                                            if levels.len() > 1 {
+                                                mip_count = Some(levels.len() as u32);
+                                            }
+                                            let img = &levels[0];
                                             let png_path = out_file_path.with_extension("png");
                                             if let Err(e) = img.save(&png_path) {
                                                 eprintln!(
@@ -713,6 +858,13 @@ pub fn unpack_rsb(input: &Path, output: &Option<PathBuf>, is_powervr: bool) -> R
                                 }
                             }
 
+                            if let Some(info) = ptx_info.as_mut() {
+                                info.mip_count = mip_count;
+                            }
+                            if let Some(property) = ptx_property.as_mut() {
+                                property.mip_count = mip_count;
+                            }
+
                             ManifestRes {
                                 path: file.path.clone(),
                                 part1_info: file.part1_info.clone(),
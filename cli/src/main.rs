@@ -54,7 +54,7 @@ enum Commands {
     /// SMF Operations (PopCap Zlib)
     #[command(subcommand)]
     Smf(smf::SmfCommands),
-    /// Atlas Operations (Split)
+    /// Atlas Operations (Split, Merge)
     #[command(subcommand)]
     Atlas(atlas::AtlasCommand),
     /// Resources Operations (Convert res.json to resources.json and vice versa)
@@ -63,7 +63,7 @@ enum Commands {
     /// PAK Operations (Unpack/Pack PvZ PAK archives)
     #[command(subcommand)]
     Pak(pak::PakCommands),
-    /// Reanim Operations (Decode/Encode Animations)
+    /// Reanim Operations (Decode/Encode/Render Animations)
     #[command(subcommand)]
     Reanim(reanim::ReanimCommands),
     /// Particles Operations (Decode/Encode Particles)
@@ -10,6 +10,10 @@ pub enum AtlasError {
     Image(#[from] image::ImageError),
     #[error("Atlas error: {0}")]
     Generic(String),
+    #[error("Sprite `{0}` ({1}x{2}) does not fit in a {3}x{4} atlas")]
+    SpriteTooLarge(String, u32, u32, u32, u32),
+    #[error("Sprites do not fit in one {0}x{1} atlas page")]
+    PageOverflow(u32, u32),
 }
 
 pub type Result<T> = std::result::Result<T, AtlasError>;
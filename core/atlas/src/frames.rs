@@ -0,0 +1,118 @@
+use crate::error::{AtlasError, Result};
+use image::{imageops, RgbaImage};
+
+/// `(cols, rows)` of an animation strip, or `None` for a single image. Fails when the
+/// grid has more frames than fit in a `u32`.
+pub fn frame_grid(cols: Option<u32>, rows: Option<u32>) -> Result<Option<(u32, u32)>> {
+    let (cols, rows) = (cols.unwrap_or(1).max(1), rows.unwrap_or(1).max(1));
+    let count = frame_count(cols, rows)?;
+    Ok((count > 1).then_some((cols, rows)))
+}
+
+fn frame_count(cols: u32, rows: u32) -> Result<u32> {
+    cols.checked_mul(rows)
+        .ok_or_else(|| AtlasError::Generic(format!("{}x{} frame grid is too large", cols, rows)))
+}
+
+/// Cut a strip into `cols * rows` equally sized frames, row by row. Fails when the
+/// strip does not divide evenly into the grid.
+pub fn split_frames(strip: &RgbaImage, cols: u32, rows: u32) -> Result<Vec<RgbaImage>> {
+    if cols == 0
+        || rows == 0
+        || !strip.width().is_multiple_of(cols)
+        || !strip.height().is_multiple_of(rows)
+    {
+        return Err(AtlasError::Generic(format!(
+            "{}x{} strip does not divide into {}x{} frames",
+            strip.width(),
+            strip.height(),
+            cols,
+            rows
+        )));
+    }
+    let (frame_width, frame_height) = (strip.width() / cols, strip.height() / rows);
+    let mut frames = Vec::with_capacity(frame_count(cols, rows)? as usize);
+    for row in 0..rows {
+        for col in 0..cols {
+            frames.push(
+                imageops::crop_imm(
+                    strip,
+                    col * frame_width,
+                    row * frame_height,
+                    frame_width,
+                    frame_height,
+                )
+                .to_image(),
+            );
+        }
+    }
+    Ok(frames)
+}
+
+/// Lay frames out row by row in a `cols x rows` grid. Cells take the size of the
+/// largest frame; smaller frames sit in the top left of their cell.
+pub fn join_frames(frames: &[RgbaImage], cols: u32, rows: u32) -> Result<RgbaImage> {
+    let count = frame_count(cols, rows)?;
+    if frames.len() != count as usize {
+        return Err(AtlasError::Generic(format!(
+            "{}x{} strip needs {} frames, got {}",
+            cols,
+            rows,
+            count,
+            frames.len()
+        )));
+    }
+    let frame_width = frames.iter().map(RgbaImage::width).max().unwrap_or(0);
+    let frame_height = frames.iter().map(RgbaImage::height).max().unwrap_or(0);
+    let (Some(width), Some(height)) = (
+        frame_width.checked_mul(cols),
+        frame_height.checked_mul(rows),
+    ) else {
+        return Err(AtlasError::Generic(format!(
+            "{}x{} strip of {}x{} frames is too large",
+            cols, rows, frame_width, frame_height
+        )));
+    };
+    let mut strip = RgbaImage::new(width, height);
+    for (index, frame) in frames.iter().enumerate() {
+        let (col, row) = (index as u32 % cols, index as u32 / cols);
+        imageops::replace(
+            &mut strip,
+            frame,
+            (col * frame_width) as i64,
+            (row * frame_height) as i64,
+        );
+    }
+    Ok(strip)
+}
+
+/// Trim the transparent border shared by every frame of a strip.
+///
+/// Each frame loses the same inset so the grid stays regular. Returns the inset as
+/// `(x, y)` and the rebuilt strip; a plain image is a `1 x 1` strip.
+pub fn trim_frames(strip: &RgbaImage, cols: u32, rows: u32) -> Result<(u32, u32, RgbaImage)> {
+    let frames = split_frames(strip, cols, rows)?;
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for frame in &frames {
+        for (x, y, pixel) in frame.enumerate_pixels() {
+            if pixel[3] != 0 {
+                min_x = min_x.min(x);
+                min_y = min_y.min(y);
+                max_x = max_x.max(x);
+                max_y = max_y.max(y);
+            }
+        }
+    }
+    if min_x == u32::MAX {
+        // Fully transparent: keep a single pixel per frame so the sprite still has a frame
+        (min_x, min_y, max_x, max_y) = (0, 0, 0, 0);
+    }
+    let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+    let frame_width = width.min(strip.width() / cols);
+    let frame_height = height.min(strip.height() / rows);
+    let trimmed: Vec<RgbaImage> = frames
+        .iter()
+        .map(|frame| imageops::crop_imm(frame, min_x, min_y, frame_width, frame_height).to_image())
+        .collect();
+    Ok((min_x, min_y, join_frames(&trimmed, cols, rows)?))
+}
@@ -1,5 +1,243 @@
 pub mod error;
+pub mod frames;
+pub mod packer;
 pub mod types;
 
 pub use error::{AtlasError, Result};
+pub use frames::*;
+pub use packer::*;
 pub use types::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{imageops, Rgba, RgbaImage};
+
+    fn sprite(key: &str, w: u32, h: u32) -> (String, RgbaImage) {
+        let seed = key.len() as u8;
+        let image = RgbaImage::from_fn(w, h, |x, y| Rgba([seed, x as u8, y as u8, 255]));
+        (key.to_string(), image)
+    }
+
+    fn sprites() -> Vec<(String, RgbaImage)> {
+        (0..24)
+            .map(|i| sprite(&format!("S{:02}", i), 10 + i * 7 % 40, 8 + i * 13 % 50))
+            .collect()
+    }
+
+    /// Placement of each sprite grown by `margin` on every side
+    fn margins(packed: &PackedAtlas, margin: u32) -> Vec<(usize, i64, i64, i64, i64)> {
+        packed
+            .sprites
+            .iter()
+            .map(|s| {
+                let m = margin as i64;
+                (
+                    s.page,
+                    s.x as i64 - m,
+                    s.y as i64 - m,
+                    (s.x + s.width) as i64 + m,
+                    (s.y + s.height) as i64 + m,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_pack_no_overlap() {
+        for heuristic in [
+            PackHeuristic::BestShortSideFit,
+            PackHeuristic::BestLongSideFit,
+            PackHeuristic::BestAreaFit,
+            PackHeuristic::BottomLeft,
+            PackHeuristic::ContactPoint,
+        ] {
+            let config = PackerConfig {
+                padding: 2,
+                extrusion: 1,
+                heuristic,
+                ..PackerConfig::default()
+            };
+            let sprites = sprites();
+            let packed = pack_sprites(&sprites, &config).unwrap();
+            assert_eq!(packed.pages.len(), 1);
+
+            // Extruded edges plus half the padding never meet
+            let rects = margins(&packed, 2);
+            for (i, a) in rects.iter().enumerate() {
+                for b in &rects[i + 1..] {
+                    let apart = a.0 != b.0 || a.3 <= b.1 || b.3 <= a.1 || a.4 <= b.2 || b.4 <= a.2;
+                    assert!(apart, "{:?} overlaps {:?}", a, b);
+                }
+            }
+
+            // Every sprite is copied unchanged
+            let page = &packed.pages[0];
+            for (s, (key, image)) in packed.sprites.iter().zip(&sprites) {
+                assert_eq!(&s.key, key);
+                let copied = imageops::crop_imm(page, s.x, s.y, s.width, s.height).to_image();
+                assert_eq!(&copied, image);
+            }
+        }
+    }
+
+    #[test]
+    fn test_pack_within_max_size() {
+        // 300 is not a power of two, so pages may not grow past 256
+        let config = PackerConfig {
+            max_width: 300,
+            max_height: 300,
+            padding: 1,
+            border: 3,
+            multi_page: true,
+            ..PackerConfig::default()
+        };
+        let packed = pack_sprites(&sprites(), &config).unwrap();
+        for page in &packed.pages {
+            assert!(page.width() <= 256 && page.height() <= 256);
+            assert!(page.width().is_power_of_two() && page.height().is_power_of_two());
+        }
+        for s in &packed.sprites {
+            let page = &packed.pages[s.page];
+            assert!(s.x >= 3 && s.y >= 3);
+            assert!(s.x + s.width <= page.width() - 3 && s.y + s.height <= page.height() - 3);
+        }
+
+        let config = PackerConfig {
+            power_of_two: false,
+            ..config
+        };
+        let packed = pack_sprites(&sprites(), &config).unwrap();
+        for page in &packed.pages {
+            assert!(page.width() <= 300 && page.height() <= 300);
+        }
+
+        let config = PackerConfig {
+            max_width: 100,
+            max_height: 100,
+            ..PackerConfig::default()
+        };
+        assert!(matches!(
+            pack_sprites(&[sprite("BIG", 101, 10)], &config),
+            Err(AtlasError::SpriteTooLarge(..))
+        ));
+    }
+
+    #[test]
+    fn test_pack_rotation() {
+        // The strip only fits the page on its side
+        let (key, image) = sprite("STRIP", 100, 20);
+        let config = PackerConfig {
+            max_width: 32,
+            max_height: 128,
+            power_of_two: false,
+            allow_rotation: true,
+            ..PackerConfig::default()
+        };
+        let packed = pack_sprites(&[(key.clone(), image.clone())], &config).unwrap();
+        let s = &packed.sprites[0];
+        assert!(s.rotated);
+        assert_eq!((s.width, s.height), (20, 100));
+        assert_eq!((s.source_width, s.source_height), (100, 20));
+        let copied = imageops::crop_imm(&packed.pages[0], s.x, s.y, s.width, s.height).to_image();
+        assert_eq!(copied, imageops::rotate90(&image));
+
+        let config = PackerConfig {
+            allow_rotation: false,
+            ..config
+        };
+        assert!(matches!(
+            pack_sprites(&[(key.clone(), image.clone())], &config),
+            Err(AtlasError::SpriteTooLarge(..))
+        ));
+
+        // Official atlases cannot say a sprite is rotated
+        let mut atlas = OfficialAtlas {
+            id: "UI".to_string(),
+            parent: String::new(),
+            res: String::new(),
+            type_: "simple".to_string(),
+            resources: Vec::new(),
+        };
+        let config = PackerConfig {
+            allow_rotation: true,
+            ..PackerConfig::default()
+        };
+        assert!(pack_official_atlas(&mut atlas, &[(key, image)], &config).is_err());
+    }
+
+    #[test]
+    fn test_pack_multi_page() {
+        let sprites: Vec<_> = (0..5).map(|i| sprite(&format!("P{}", i), 60, 60)).collect();
+        let config = PackerConfig {
+            max_width: 128,
+            max_height: 128,
+            ..PackerConfig::default()
+        };
+        assert!(matches!(
+            pack_sprites(&sprites, &config),
+            Err(AtlasError::PageOverflow(128, 128))
+        ));
+
+        let config = PackerConfig {
+            multi_page: true,
+            ..config
+        };
+        let packed = pack_sprites(&sprites, &config).unwrap();
+        assert_eq!(packed.pages.len(), 2);
+        assert_eq!(packed.sprites.iter().filter(|s| s.page == 0).count(), 4);
+        assert_eq!(packed.sprites.iter().filter(|s| s.page == 1).count(), 1);
+    }
+
+    #[test]
+    fn test_pack_empty_sprite() {
+        let config = PackerConfig {
+            padding: 2,
+            extrusion: 2,
+            ..PackerConfig::default()
+        };
+        let sprites = [
+            sprite("EMPTY", 0, 0),
+            sprite("FLAT", 5, 0),
+            sprite("A", 4, 4),
+        ];
+        let packed = pack_sprites(&sprites, &config).unwrap();
+        assert_eq!(packed.sprites[0].width, 0);
+        assert_eq!(packed.sprites[1].height, 0);
+        assert_eq!(packed.sprites[2].width, 4);
+    }
+
+    #[test]
+    fn test_split_join_round_trip() {
+        let (_, strip) = sprite("STRIP", 30, 20);
+        let frames = split_frames(&strip, 3, 2).unwrap();
+        assert_eq!(frames.len(), 6);
+        assert!(frames.iter().all(|f| f.dimensions() == (10, 10)));
+        assert_eq!(
+            frames[4],
+            imageops::crop_imm(&strip, 10, 10, 10, 10).to_image()
+        );
+        assert_eq!(join_frames(&frames, 3, 2).unwrap(), strip);
+    }
+
+    #[test]
+    fn test_split_uneven_strip() {
+        let (_, strip) = sprite("STRIP", 31, 20);
+        assert!(split_frames(&strip, 3, 2).is_err());
+        assert!(split_frames(&strip, 1, 3).is_err());
+        assert!(split_frames(&strip, 0, 1).is_err());
+        assert!(trim_frames(&strip, 3, 2).is_err());
+        assert!(join_frames(&[strip], 3, 2).is_err());
+    }
+
+    #[test]
+    fn test_frame_grid() {
+        assert_eq!(frame_grid(None, None).unwrap(), None);
+        assert_eq!(frame_grid(Some(0), Some(1)).unwrap(), None);
+        assert_eq!(frame_grid(Some(3), None).unwrap(), Some((3, 1)));
+        assert_eq!(frame_grid(Some(3), Some(2)).unwrap(), Some((3, 2)));
+        assert!(frame_grid(Some(70000), Some(70000)).is_err());
+        assert!(split_frames(&RgbaImage::new(1, 1), u32::MAX, u32::MAX).is_err());
+        assert!(join_frames(&[], u32::MAX, 2).is_err());
+    }
+}
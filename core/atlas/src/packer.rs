@@ -0,0 +1,585 @@
+use crate::error::{AtlasError, Result};
+use crate::frames::{frame_grid, trim_frames};
+use crate::types::{OfficialAtlas, PathOrPaths, Resource};
+use image::{imageops, Rgba, RgbaImage};
+
+/// Rule used to choose between free spaces when placing a sprite
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackHeuristic {
+    /// Minimise the shorter leftover side of the chosen space
+    BestShortSideFit,
+    /// Minimise the longer leftover side of the chosen space
+    BestLongSideFit,
+    /// Choose the smallest space the sprite fits in
+    BestAreaFit,
+    /// Place as low, then as far left, as possible
+    BottomLeft,
+    /// Touch as many placed sprites and page edges as possible
+    ContactPoint,
+}
+
+#[derive(Debug, Clone)]
+pub struct PackerConfig {
+    pub max_width: u32,
+    pub max_height: u32,
+    /// Transparent pixels between sprites
+    pub padding: u32,
+    /// Transparent pixels around the page edge
+    pub border: u32,
+    /// Pixels of each sprite's edge repeated outwards, to stop filtering bleed
+    pub extrusion: u32,
+    /// Round page sizes up to powers of two, never past the largest one within the
+    /// maximum size
+    pub power_of_two: bool,
+    /// Cut fully transparent borders off sprites before packing
+    pub trim: bool,
+    /// Allow 90 degree clockwise rotation. PvZ2 manifests cannot describe rotated
+    /// sprites, so this is only useful for other consumers of the packer.
+    pub allow_rotation: bool,
+    /// Open a new page when sprites do not fit, instead of failing
+    pub multi_page: bool,
+    pub heuristic: PackHeuristic,
+}
+
+/// The defaults lay pages out like the packer `atlas merge` used before: up to
+/// 8192x8192, no padding or extrusion, power-of-two sizes
+impl Default for PackerConfig {
+    fn default() -> Self {
+        Self {
+            max_width: 8192,
+            max_height: 8192,
+            padding: 0,
+            border: 0,
+            extrusion: 0,
+            power_of_two: true,
+            trim: false,
+            allow_rotation: false,
+            multi_page: false,
+            heuristic: PackHeuristic::BestShortSideFit,
+        }
+    }
+}
+
+/// Where a sprite ended up
+#[derive(Debug, Clone)]
+pub struct PackedSprite {
+    pub key: String,
+    pub page: usize,
+    /// Placement in the page, excluding padding and extrusion
+    pub x: u32,
+    pub y: u32,
+    /// Size in the page; swapped relative to the trimmed image when rotated
+    pub width: u32,
+    pub height: u32,
+    pub rotated: bool,
+    /// Offset of the trimmed area inside the source image
+    pub trim_x: u32,
+    pub trim_y: u32,
+    pub source_width: u32,
+    pub source_height: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PackedAtlas {
+    pub pages: Vec<RgbaImage>,
+    /// In the order the sprites were given
+    pub sprites: Vec<PackedSprite>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    x: u32,
+    y: u32,
+    w: u32,
+    h: u32,
+}
+
+impl Rect {
+    fn right(&self) -> u32 {
+        self.x + self.w
+    }
+
+    fn bottom(&self) -> u32 {
+        self.y + self.h
+    }
+
+    fn contains(&self, other: &Rect) -> bool {
+        other.x >= self.x
+            && other.y >= self.y
+            && other.right() <= self.right()
+            && other.bottom() <= self.bottom()
+    }
+
+    fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.right()
+            && other.x < self.right()
+            && self.y < other.bottom()
+            && other.y < self.bottom()
+    }
+}
+
+/// One page of the MaxRects bin packer
+struct Page {
+    width: u32,
+    height: u32,
+    free: Vec<Rect>,
+    used: Vec<Rect>,
+}
+
+impl Page {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            free: vec![Rect {
+                x: 0,
+                y: 0,
+                w: width,
+                h: height,
+            }],
+            used: Vec::new(),
+        }
+    }
+
+    /// Best placement as (rect, rotated), or `None` when nothing fits
+    fn find(
+        &self,
+        w: u32,
+        h: u32,
+        allow_rotation: bool,
+        heuristic: PackHeuristic,
+    ) -> Option<(Rect, bool)> {
+        let mut best: Option<((i64, i64), Rect, bool)> = None;
+        for free in &self.free {
+            for (rw, rh, rotated) in [(w, h, false), (h, w, true)] {
+                if rotated && (!allow_rotation || w == h) {
+                    continue;
+                }
+                if rw > free.w || rh > free.h {
+                    continue;
+                }
+                let rect = Rect {
+                    x: free.x,
+                    y: free.y,
+                    w: rw,
+                    h: rh,
+                };
+                let score = self.score(free, &rect, heuristic);
+                if best.as_ref().is_none_or(|(s, _, _)| score < *s) {
+                    best = Some((score, rect, rotated));
+                }
+            }
+        }
+        best.map(|(_, rect, rotated)| (rect, rotated))
+    }
+
+    /// Lower is better
+    fn score(&self, free: &Rect, rect: &Rect, heuristic: PackHeuristic) -> (i64, i64) {
+        let leftover_w = (free.w - rect.w) as i64;
+        let leftover_h = (free.h - rect.h) as i64;
+        match heuristic {
+            PackHeuristic::BestShortSideFit => {
+                (leftover_w.min(leftover_h), leftover_w.max(leftover_h))
+            }
+            PackHeuristic::BestLongSideFit => {
+                (leftover_w.max(leftover_h), leftover_w.min(leftover_h))
+            }
+            PackHeuristic::BestAreaFit => (
+                free.w as i64 * free.h as i64 - rect.w as i64 * rect.h as i64,
+                leftover_w.min(leftover_h),
+            ),
+            PackHeuristic::BottomLeft => (rect.bottom() as i64, rect.x as i64),
+            PackHeuristic::ContactPoint => (-(self.contact(rect) as i64), rect.y as i64),
+        }
+    }
+
+    fn contact(&self, rect: &Rect) -> u32 {
+        let overlap = |a1: u32, a2: u32, b1: u32, b2: u32| a2.min(b2).saturating_sub(a1.max(b1));
+        let mut score = 0;
+        if rect.x == 0 || rect.right() == self.width {
+            score += rect.h;
+        }
+        if rect.y == 0 || rect.bottom() == self.height {
+            score += rect.w;
+        }
+        for used in &self.used {
+            if used.x == rect.right() || used.right() == rect.x {
+                score += overlap(used.y, used.bottom(), rect.y, rect.bottom());
+            }
+            if used.y == rect.bottom() || used.bottom() == rect.y {
+                score += overlap(used.x, used.right(), rect.x, rect.right());
+            }
+        }
+        score
+    }
+
+    fn place(&mut self, rect: Rect) {
+        let mut next = Vec::with_capacity(self.free.len() + 4);
+        for free in &self.free {
+            if !free.intersects(&rect) {
+                next.push(*free);
+                continue;
+            }
+            if rect.x > free.x {
+                next.push(Rect {
+                    w: rect.x - free.x,
+                    ..*free
+                });
+            }
+            if rect.right() < free.right() {
+                next.push(Rect {
+                    x: rect.right(),
+                    w: free.right() - rect.right(),
+                    ..*free
+                });
+            }
+            if rect.y > free.y {
+                next.push(Rect {
+                    h: rect.y - free.y,
+                    ..*free
+                });
+            }
+            if rect.bottom() < free.bottom() {
+                next.push(Rect {
+                    y: rect.bottom(),
+                    h: free.bottom() - rect.bottom(),
+                    ..*free
+                });
+            }
+        }
+
+        // Drop free spaces contained in others
+        let mut pruned: Vec<Rect> = Vec::with_capacity(next.len());
+        for (i, a) in next.iter().enumerate() {
+            let redundant = next
+                .iter()
+                .enumerate()
+                .any(|(j, b)| i != j && b.contains(a) && (!a.contains(b) || j < i));
+            if !redundant {
+                pruned.push(*a);
+            }
+        }
+        self.free = pruned;
+        self.used.push(rect);
+    }
+}
+
+/// Bounding box of the non-transparent pixels as (x, y, w, h)
+fn trim_bounds(image: &RgbaImage) -> (u32, u32, u32, u32) {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if pixel[3] != 0 {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+    if min_x == u32::MAX {
+        // Fully transparent: keep a single pixel so the sprite still has a frame
+        return (0, 0, 1.min(image.width()), 1.min(image.height()));
+    }
+    (min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+}
+
+/// Pack sprites into one or more atlas pages.
+pub fn pack_sprites(sprites: &[(String, RgbaImage)], config: &PackerConfig) -> Result<PackedAtlas> {
+    let border = config.border;
+    let margin = config.padding + config.extrusion * 2;
+    // Rounding up a page must not take it past the maximum size
+    let (max_width, max_height) = if config.power_of_two {
+        (
+            prev_power_of_two(config.max_width),
+            prev_power_of_two(config.max_height),
+        )
+    } else {
+        (config.max_width, config.max_height)
+    };
+    // Padding is only needed between sprites, so the bin gets one padding of slack
+    // on the far edges
+    let bin_width = (max_width.saturating_sub(border * 2) + config.padding).max(1);
+    let bin_height = (max_height.saturating_sub(border * 2) + config.padding).max(1);
+
+    let trimmed: Vec<(u32, u32, u32, u32)> = sprites
+        .iter()
+        .map(|(_, image)| {
+            if config.trim {
+                trim_bounds(image)
+            } else {
+                (0, 0, image.width(), image.height())
+            }
+        })
+        .collect();
+
+    // Largest first gives MaxRects its best results; ties fall back to the key so the
+    // layout is deterministic
+    let mut order: Vec<usize> = (0..sprites.len()).collect();
+    order.sort_by(|&a, &b| {
+        let (_, _, aw, ah) = trimmed[a];
+        let (_, _, bw, bh) = trimmed[b];
+        bw.max(bh)
+            .cmp(&aw.max(ah))
+            .then((bw as u64 * bh as u64).cmp(&(aw as u64 * ah as u64)))
+            .then(sprites[a].0.cmp(&sprites[b].0))
+    });
+
+    let mut pages: Vec<Page> = Vec::new();
+    let mut placements: Vec<Option<(usize, Rect, bool)>> = vec![None; sprites.len()];
+    for index in order {
+        let (_, _, w, h) = trimmed[index];
+        let (pw, ph) = (w + margin, h + margin);
+        let fits = |pw: u32, ph: u32| pw <= bin_width && ph <= bin_height;
+        if !(fits(pw, ph) || config.allow_rotation && fits(ph, pw)) {
+            return Err(AtlasError::SpriteTooLarge(
+                sprites[index].0.clone(),
+                w,
+                h,
+                max_width,
+                max_height,
+            ));
+        }
+
+        let mut placed = None;
+        for (page_index, page) in pages.iter().enumerate() {
+            if let Some(found) = page.find(pw, ph, config.allow_rotation, config.heuristic) {
+                placed = Some((page_index, found));
+                break;
+            }
+        }
+        if placed.is_none() {
+            if !pages.is_empty() && !config.multi_page {
+                return Err(AtlasError::PageOverflow(max_width, max_height));
+            }
+            let page = Page::new(bin_width, bin_height);
+            let found = page
+                .find(pw, ph, config.allow_rotation, config.heuristic)
+                .expect("sprite fits an empty page");
+            pages.push(page);
+            placed = Some((pages.len() - 1, found));
+        }
+
+        let (page_index, (rect, rotated)) = placed.unwrap();
+        pages[page_index].place(rect);
+        placements[index] = Some((page_index, rect, rotated));
+    }
+
+    // Page sizes from the placed sprites
+    let mut extents = vec![(0u32, 0u32); pages.len()];
+    for (page_index, rect, _) in placements.iter().flatten() {
+        let extent = &mut extents[*page_index];
+        extent.0 = extent.0.max(rect.right() - config.padding.min(rect.w));
+        extent.1 = extent.1.max(rect.bottom() - config.padding.min(rect.h));
+    }
+    let mut images: Vec<RgbaImage> = extents
+        .iter()
+        .map(|&(w, h)| {
+            let (mut w, mut h) = (w + border * 2, h + border * 2);
+            if config.power_of_two {
+                w = w.next_power_of_two();
+                h = h.next_power_of_two();
+            }
+            RgbaImage::new(w.max(1), h.max(1))
+        })
+        .collect();
+
+    let mut packed = Vec::with_capacity(sprites.len());
+    for (index, (key, image)) in sprites.iter().enumerate() {
+        let (page, rect, rotated) = placements[index].expect("every sprite is placed");
+        let (trim_x, trim_y, w, h) = trimmed[index];
+        let mut sprite = imageops::crop_imm(image, trim_x, trim_y, w, h).to_image();
+        if rotated {
+            sprite = imageops::rotate90(&sprite);
+        }
+
+        let x = rect.x + border + config.extrusion;
+        let y = rect.y + border + config.extrusion;
+        draw_sprite(&mut images[page], &sprite, x, y, config.extrusion);
+
+        packed.push(PackedSprite {
+            key: key.clone(),
+            page,
+            x,
+            y,
+            width: sprite.width(),
+            height: sprite.height(),
+            rotated,
+            trim_x,
+            trim_y,
+            source_width: image.width(),
+            source_height: image.height(),
+        });
+    }
+
+    Ok(PackedAtlas {
+        pages: images,
+        sprites: packed,
+    })
+}
+
+/// Largest power of two not above `n`, or 1 for 0
+fn prev_power_of_two(n: u32) -> u32 {
+    1 << n.max(1).ilog2()
+}
+
+/// Copy a sprite into the page and repeat its edge pixels `extrusion` times outwards.
+fn draw_sprite(page: &mut RgbaImage, sprite: &RgbaImage, x: u32, y: u32, extrusion: u32) {
+    let (w, h) = sprite.dimensions();
+    // Nothing to copy or extrude
+    if w == 0 || h == 0 {
+        return;
+    }
+    let e = extrusion as i64;
+    for dy in -e..h as i64 + e {
+        for dx in -e..w as i64 + e {
+            let (px, py) = (x as i64 + dx, y as i64 + dy);
+            if px < 0 || py < 0 || px >= page.width() as i64 || py >= page.height() as i64 {
+                continue;
+            }
+            let sx = dx.clamp(0, w as i64 - 1) as u32;
+            let sy = dy.clamp(0, h as i64 - 1) as u32;
+            let pixel: Rgba<u8> = *sprite.get_pixel(sx, sy);
+            page.put_pixel(px as u32, py as u32, pixel);
+        }
+    }
+}
+
+/// Pack sprites for an `OfficialAtlas` and write the layout back into it.
+///
+/// Sprites are matched to resources by id. `ax/ay/aw/ah` receive the packed rectangle
+/// and, when trimming, the trimmed-away offset is added to `x/y`; strips with `cols/rows`
+/// are trimmed by the border all of their frames share. Page `n` is the
+/// `n`-th resource flagged as an atlas image; extra atlas resources are created by
+/// bumping the number at the end of the last one's id and path. Returns the page images.
+///
+/// Resources have no way to mark a sprite as rotated, so `allow_rotation` is refused.
+pub fn pack_official_atlas(
+    atlas: &mut OfficialAtlas,
+    sprites: &[(String, RgbaImage)],
+    config: &PackerConfig,
+) -> Result<Vec<RgbaImage>> {
+    if config.allow_rotation {
+        return Err(AtlasError::Generic(format!(
+            "{} cannot hold rotated sprites; pack it without rotation",
+            atlas.id
+        )));
+    }
+
+    // Animation strips must lose the same border on every frame, so trimming is done
+    // here with each resource's frame grid rather than by the packer
+    let mut offsets = Vec::with_capacity(sprites.len());
+    let packed = if config.trim {
+        let trimmed: Vec<(String, RgbaImage)> = sprites
+            .iter()
+            .map(|(key, image)| {
+                let in_sprite = |e: AtlasError| AtlasError::Generic(format!("{}: {}", key, e));
+                let grid = atlas
+                    .resources
+                    .iter()
+                    .find(|r| r.id == *key)
+                    .map(|r| frame_grid(r.cols, r.rows))
+                    .transpose()
+                    .map_err(in_sprite)?
+                    .flatten()
+                    .unwrap_or((1, 1));
+                let (x, y, image) = trim_frames(image, grid.0, grid.1).map_err(in_sprite)?;
+                offsets.push((x, y));
+                Ok((key.clone(), image))
+            })
+            .collect::<Result<_>>()?;
+        let config = PackerConfig {
+            trim: false,
+            ..config.clone()
+        };
+        pack_sprites(&trimmed, &config)?
+    } else {
+        offsets.resize(sprites.len(), (0, 0));
+        pack_sprites(sprites, config)?
+    };
+
+    let mut atlas_ids: Vec<String> = atlas
+        .resources
+        .iter()
+        .filter(|r| r.atlas == Some(true))
+        .map(|r| r.id.clone())
+        .collect();
+    while atlas_ids.len() < packed.pages.len() {
+        let template = atlas
+            .resources
+            .iter()
+            .rev()
+            .find(|r| r.atlas == Some(true))
+            .cloned()
+            .ok_or_else(|| {
+                AtlasError::Generic(format!(
+                    "{} needs {} pages but has no atlas image resource to extend",
+                    atlas.id,
+                    packed.pages.len()
+                ))
+            })?;
+        let page = Resource {
+            id: next_page_name(&template.id),
+            path: template.path.as_ref().map(|path| match path {
+                PathOrPaths::Single(p) => PathOrPaths::Single(next_page_name(p)),
+                PathOrPaths::Multiple(parts) => {
+                    let mut parts = parts.clone();
+                    if let Some(last) = parts.last_mut() {
+                        *last = next_page_name(last);
+                    }
+                    PathOrPaths::Multiple(parts)
+                }
+            }),
+            ..template
+        };
+        // Keep atlas images ahead of the sprites that reference them
+        let insert_at = atlas
+            .resources
+            .iter()
+            .rposition(|r| r.atlas == Some(true))
+            .map_or(0, |i| i + 1);
+        atlas_ids.push(page.id.clone());
+        atlas.resources.insert(insert_at, page);
+    }
+
+    for (page_index, image) in packed.pages.iter().enumerate() {
+        if let Some(res) = atlas
+            .resources
+            .iter_mut()
+            .find(|r| r.id == atlas_ids[page_index])
+        {
+            res.width = Some(image.width());
+            res.height = Some(image.height());
+        }
+    }
+
+    for (sprite, &(trim_x, trim_y)) in packed.sprites.iter().zip(&offsets) {
+        let Some(res) = atlas.resources.iter_mut().find(|r| r.id == sprite.key) else {
+            continue;
+        };
+        res.ax = Some(sprite.x);
+        res.ay = Some(sprite.y);
+        res.aw = Some(sprite.width);
+        res.ah = Some(sprite.height);
+        if trim_x != 0 {
+            res.x = Some(res.x.unwrap_or(0) + trim_x as i32);
+        }
+        if trim_y != 0 {
+            res.y = Some(res.y.unwrap_or(0) + trim_y as i32);
+        }
+        if res.parent.is_some() || atlas_ids.len() > 1 {
+            res.parent = Some(atlas_ids[sprite.page].clone());
+        }
+    }
+
+    Ok(packed.pages)
+}
+
+/// `ATLASIMAGE_X_00` -> `ATLASIMAGE_X_01`; names without a trailing number get `_01`.
+fn next_page_name(name: &str) -> String {
+    let digits = name.len() - name.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return format!("{}_01", name);
+    }
+    let (stem, number) = name.split_at(name.len() - digits);
+    let next = number.parse::<u64>().unwrap_or(0) + 1;
+    format!("{}{:0width$}", stem, next, width = digits)
+}
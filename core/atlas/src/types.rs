@@ -13,7 +13,7 @@ pub struct OfficialAtlas {
     pub resources: Vec<Resource>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Resource {
     pub id: String,
     #[serde(rename = "type")]
@@ -38,9 +38,18 @@ pub struct Resource {
     pub rows: Option<u32>,
 
     pub atlas: Option<bool>,
+
+    // Atlas image a sprite lives in
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub runtime: Option<bool>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// A resource path, either joined or split into its segments
+/// (`["ATLASES", "ZOMBIES_1536_00"]`). Sprites spread over several atlas pages
+/// point at theirs through `parent`, not through the path.
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum PathOrPaths {
     Single(String),
@@ -1,11 +1,25 @@
 use crate::error::{NewtonError, Result};
 use crate::types::{
-    MResourceGroup, MSubgroupWrapper, ResourceType, ShellSubgroupData, SubgroupWrapper,
+    MResourceGroup, MSubgroupWrapper, PathDef, ResourceType, ShellSubgroupData, SubgroupWrapper,
 };
 use byteorder::{LE, ReadBytesExt};
-use std::io::Read;
+use std::io::{Cursor, Read};
 
-pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
+/// Size of the fixed property block that follows each resource's type byte: slot,
+/// width, height, x, y, ax, ay, aw, ah, cols, rows, the atlas flag and two reserved bytes.
+pub(crate) const PROPERTY_BLOCK_SIZE: usize = 11 * 4 + 3;
+
+/// Decode a Newton manifest, keeping resource types added by newer game builds.
+pub fn decode_newton(reader: impl Read) -> Result<MResourceGroup> {
+    decode(reader, false)
+}
+
+/// Decode a Newton manifest, failing on resource types this crate does not know.
+pub fn decode_newton_strict(reader: impl Read) -> Result<MResourceGroup> {
+    decode(reader, true)
+}
+
+fn decode(mut reader: impl Read, strict: bool) -> Result<MResourceGroup> {
     let slot_count = reader.read_u32::<LE>()?;
     let groups_count = reader.read_u32::<LE>()?;
     let mut groups = Vec::with_capacity(groups_count as usize);
@@ -84,30 +98,28 @@ pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
             let mut res_list = Vec::with_capacity(resources_count as usize);
             for _ in 0..resources_count {
                 let res_type_byte = reader.read_u8()?;
-                let res_type_enum = ResourceType::from_u8(res_type_byte)?;
-                let res_type_str = match res_type_enum {
-                    ResourceType::Image => "Image",
-                    ResourceType::PopAnim => "PopAnim",
-                    ResourceType::SoundBank => "SoundBank",
-                    ResourceType::File => "File",
-                    ResourceType::PrimeFont => "PrimeFont",
-                    ResourceType::RenderEffect => "RenderEffect",
-                    ResourceType::DecodedSoundBank => "DecodedSoundBank",
-                }
-                .to_string();
-
-                let slot = reader.read_u32::<LE>()?;
-                let width = reader.read_u32::<LE>()?;
-                let height = reader.read_u32::<LE>()?;
-                let x = reader.read_i32::<LE>()?;
-                let y = reader.read_i32::<LE>()?;
-                let ax = reader.read_u32::<LE>()?;
-                let ay = reader.read_u32::<LE>()?;
-                let aw = reader.read_u32::<LE>()?;
-                let ah = reader.read_u32::<LE>()?;
-                let cols = reader.read_u32::<LE>()?;
-                let rows = reader.read_u32::<LE>()?;
-                let is_atlas = reader.read_u8()? != 0;
+                let res_type_enum = if strict {
+                    ResourceType::from_u8(res_type_byte)?
+                } else {
+                    ResourceType::from_code(res_type_byte)
+                };
+                let res_type_str = res_type_enum.name();
+
+                let mut properties = [0u8; PROPERTY_BLOCK_SIZE];
+                reader.read_exact(&mut properties)?;
+                let mut block = Cursor::new(&properties[..]);
+                let slot = block.read_u32::<LE>()?;
+                let width = block.read_u32::<LE>()?;
+                let height = block.read_u32::<LE>()?;
+                let x = block.read_i32::<LE>()?;
+                let y = block.read_i32::<LE>()?;
+                let ax = block.read_u32::<LE>()?;
+                let ay = block.read_u32::<LE>()?;
+                let aw = block.read_u32::<LE>()?;
+                let ah = block.read_u32::<LE>()?;
+                let cols = block.read_u32::<LE>()?;
+                let rows = block.read_u32::<LE>()?;
+                let is_atlas = block.read_u8()? != 0;
 
                 // Logic from C# "is_sprite"
                 let is_sprite = aw != 0 && ah != 0;
@@ -135,8 +147,6 @@ pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
                 let r_wrapper_cols = if cols != 1 { Some(cols) } else { None };
                 let r_wrapper_rows = if rows != 1 { Some(rows) } else { None };
 
-                reader.read_u8()?; // skip
-                reader.read_u8()?; // skip
                 let resource_has_parent = reader.read_u8()?;
 
                 let id = read_string(&mut reader)?;
@@ -149,10 +159,10 @@ pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
                 };
 
                 let mut resource_x = MSubgroupWrapper {
-                    res_type: res_type_str,
-                    slot: r_wrapper_slot,
+                    r#type: res_type_str,
+                    slot: Some(r_wrapper_slot),
                     id,
-                    path,
+                    path: PathDef::String(path),
                     width: r_wrapper_width,
                     height: r_wrapper_height,
                     x: r_wrapper_x,
@@ -168,6 +178,7 @@ pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
                     parent,
                     srcpath: None,
                     force_original_vector_symbol_size: None,
+                    newton_properties: None,
                 };
 
                 match res_type_enum {
@@ -175,10 +186,15 @@ pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
                         resource_x.force_original_vector_symbol_size = Some(true);
                     }
                     ResourceType::RenderEffect => {
-                        let path_str = resource_x.path.clone();
-                        resource_x.srcpath = Some(format!("res\\common\\{}", path_str));
+                        let path_str = resource_x.path.to_joined();
+                        resource_x.srcpath =
+                            Some(PathDef::String(format!("res\\common\\{}", path_str)));
                         // C# logic
                     }
+                    // Field meanings may differ for newer types, so keep the block as read
+                    ResourceType::Unknown(_) => {
+                        resource_x.newton_properties = Some(to_hex(&properties));
+                    }
                     _ => {
                         if is_atlas {
                             resource_x.atlas = Some(true);
@@ -196,15 +212,41 @@ pub fn decode_newton(mut reader: impl Read) -> Result<MResourceGroup> {
 
         groups.push(ShellSubgroupData {
             id,
+            r#type: group_type,
             res,
-            group_type,
             parent,
             subgroups,
             resources,
         });
     }
 
-    Ok(MResourceGroup { slot_count, groups })
+    Ok(MResourceGroup {
+        version: None,
+        content_version: None,
+        slot_count,
+        groups,
+    })
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(NewtonError::DeserializationError(format!(
+            "Invalid hex string: {}",
+            hex
+        )));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| {
+                NewtonError::DeserializationError(format!("Invalid hex string: {}", hex))
+            })
+        })
+        .collect()
 }
 
 pub(crate) fn read_string(reader: &mut impl Read) -> Result<String> {
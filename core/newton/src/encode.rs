@@ -1,5 +1,6 @@
+use crate::decode::{PROPERTY_BLOCK_SIZE, from_hex};
 use crate::error::{NewtonError, Result};
-use crate::types::MResourceGroup;
+use crate::types::{MResourceGroup, ResourceType};
 use byteorder::{LE, WriteBytesExt};
 use std::io::Write;
 
@@ -8,13 +9,13 @@ pub fn encode_newton(resource: &MResourceGroup, mut writer: impl Write) -> Resul
     writer.write_u32::<LE>(resource.groups.len() as u32)?;
 
     for group in &resource.groups {
-        match group.group_type.as_str() {
+        match group.r#type.as_str() {
             "composite" => writer.write_u8(1)?,
             "simple" => writer.write_u8(2)?,
             _ => {
                 return Err(NewtonError::DeserializationError(format!(
                     "Unknown group type: {}",
-                    group.group_type
+                    group.r#type
                 )));
             }
         }
@@ -44,7 +45,7 @@ pub fn encode_newton(resource: &MResourceGroup, mut writer: impl Write) -> Resul
             write_string(&mut writer, p)?;
         }
 
-        if group.group_type == "composite"
+        if group.r#type == "composite"
             && let Some(subs) = &group.subgroups
         {
             for sub in subs {
@@ -58,64 +59,71 @@ pub fn encode_newton(resource: &MResourceGroup, mut writer: impl Write) -> Resul
             }
         }
 
-        if group.group_type == "simple"
+        if group.r#type == "simple"
             && let Some(res_list) = &group.resources
         {
             for res in res_list {
-                let type_byte = match res.res_type.as_str() {
-                    "Image" => 1,
-                    "PopAnim" => 2,
-                    "SoundBank" => 3,
-                    "File" => 4,
-                    "PrimeFont" => 5,
-                    "RenderEffect" => 6,
-                    "DecodedSoundBank" => 7,
-                    _ => {
+                let res_type = ResourceType::from_str(&res.r#type)?;
+                writer.write_u8(res_type.to_u8())?;
+                let slot = res.slot.ok_or_else(|| {
+                    NewtonError::DeserializationError(format!(
+                        "Resource {} has no slot; renumber the slots first",
+                        res.id
+                    ))
+                })?;
+                writer.write_u32::<LE>(slot)?;
+
+                // Unknown types write their property block back as decoded; only the
+                // slot is taken from the model so renumbering still applies
+                if let (ResourceType::Unknown(_), Some(hex)) = (res_type, &res.newton_properties) {
+                    let properties = from_hex(hex)?;
+                    if properties.len() != PROPERTY_BLOCK_SIZE {
                         return Err(NewtonError::DeserializationError(format!(
-                            "Unknown resource type: {}",
-                            res.res_type
+                            "Property block of {} must be {} bytes, found {}",
+                            res.id,
+                            PROPERTY_BLOCK_SIZE,
+                            properties.len()
                         )));
                     }
-                };
-                writer.write_u8(type_byte)?;
-                writer.write_u32::<LE>(res.slot)?;
-
-                writer.write_u32::<LE>(res.width.unwrap_or(0))?;
-                writer.write_u32::<LE>(res.height.unwrap_or(0))?;
-
-                if let Some(x) = res.x {
-                    writer.write_i32::<LE>(x)?;
-                } else if res.aw.unwrap_or(0) != 0 && res.ah.unwrap_or(0) != 0 {
-                    writer.write_i32::<LE>(0)?;
+                    writer.write_all(&properties[4..])?;
                 } else {
-                    writer.write_i32::<LE>(0x7FFFFFFF)?;
-                }
+                    writer.write_u32::<LE>(res.width.unwrap_or(0))?;
+                    writer.write_u32::<LE>(res.height.unwrap_or(0))?;
+
+                    if let Some(x) = res.x {
+                        writer.write_i32::<LE>(x)?;
+                    } else if res.aw.unwrap_or(0) != 0 && res.ah.unwrap_or(0) != 0 {
+                        writer.write_i32::<LE>(0)?;
+                    } else {
+                        writer.write_i32::<LE>(0x7FFFFFFF)?;
+                    }
 
-                if let Some(y) = res.y {
-                    writer.write_i32::<LE>(y)?;
-                } else if res.aw.unwrap_or(0) != 0 && res.ah.unwrap_or(0) != 0 {
-                    writer.write_i32::<LE>(0)?;
-                } else {
-                    writer.write_i32::<LE>(0x7FFFFFFF)?;
-                }
+                    if let Some(y) = res.y {
+                        writer.write_i32::<LE>(y)?;
+                    } else if res.aw.unwrap_or(0) != 0 && res.ah.unwrap_or(0) != 0 {
+                        writer.write_i32::<LE>(0)?;
+                    } else {
+                        writer.write_i32::<LE>(0x7FFFFFFF)?;
+                    }
+
+                    writer.write_u32::<LE>(res.ax.unwrap_or(0))?;
+                    writer.write_u32::<LE>(res.ay.unwrap_or(0))?;
+                    writer.write_u32::<LE>(res.aw.unwrap_or(0))?;
+                    writer.write_u32::<LE>(res.ah.unwrap_or(0))?;
 
-                writer.write_u32::<LE>(res.ax.unwrap_or(0))?;
-                writer.write_u32::<LE>(res.ay.unwrap_or(0))?;
-                writer.write_u32::<LE>(res.aw.unwrap_or(0))?;
-                writer.write_u32::<LE>(res.ah.unwrap_or(0))?;
+                    writer.write_u32::<LE>(res.cols.unwrap_or(1))?;
+                    writer.write_u32::<LE>(res.rows.unwrap_or(1))?;
 
-                writer.write_u32::<LE>(res.cols.unwrap_or(1))?;
-                writer.write_u32::<LE>(res.rows.unwrap_or(1))?;
+                    if res.atlas.unwrap_or(false) {
+                        writer.write_u8(1)?;
+                    } else {
+                        writer.write_u8(0)?;
+                    }
 
-                if res.atlas.unwrap_or(false) {
                     writer.write_u8(1)?;
-                } else {
-                    writer.write_u8(0)?;
+                    writer.write_u8(1)?;
                 }
 
-                writer.write_u8(1)?;
-                writer.write_u8(1)?;
-
                 if res.parent.is_some() {
                     writer.write_u8(1)?;
                 } else {
@@ -123,7 +131,7 @@ pub fn encode_newton(resource: &MResourceGroup, mut writer: impl Write) -> Resul
                 }
 
                 write_string(&mut writer, &res.id)?;
-                write_string(&mut writer, &res.path)?;
+                write_string(&mut writer, &res.path.to_joined())?;
 
                 if let Some(p) = &res.parent {
                     write_string(&mut writer, p)?;
@@ -3,7 +3,116 @@ pub mod encode;
 pub mod error;
 pub mod types;
 
-pub use decode::decode_newton;
+pub use decode::{decode_newton, decode_newton_strict};
 pub use encode::encode_newton;
 pub use error::{NewtonError, Result};
 pub use types::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::{LE, WriteBytesExt};
+    use std::io::Cursor;
+
+    fn write_string(out: &mut Vec<u8>, s: &str) {
+        out.write_u32::<LE>(s.len() as u32).unwrap();
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    /// One simple group holding a File and a resource of type 9, which no known build
+    /// uses, with a property block no known type would write
+    fn unknown_type_newton() -> Vec<u8> {
+        let mut out = Vec::new();
+        out.write_u32::<LE>(2).unwrap(); // slot_count
+        out.write_u32::<LE>(1).unwrap(); // groups
+        out.write_u8(2).unwrap(); // simple
+        out.write_u32::<LE>(0).unwrap(); // res
+        out.write_u32::<LE>(0).unwrap(); // subgroups
+        out.write_u32::<LE>(2).unwrap(); // resources
+        out.write_u8(1).unwrap(); // version
+        out.write_u8(0).unwrap(); // no parent
+        write_string(&mut out, "Global");
+
+        out.write_u8(ResourceType::File.to_u8()).unwrap();
+        for value in [0, 0, 0, 0x7FFF_FFFF, 0x7FFF_FFFF, 0, 0, 0, 0, 1, 1] {
+            out.write_u32::<LE>(value).unwrap();
+        }
+        out.extend_from_slice(&[0, 1, 1]);
+        out.write_u8(0).unwrap();
+        write_string(&mut out, "FILE_A");
+        write_string(&mut out, "data\\a.rton");
+
+        out.write_u8(9).unwrap();
+        out.write_u32::<LE>(1).unwrap(); // slot
+        out.extend((0..43).map(|i| (i * 37 + 11) as u8));
+        out.write_u8(1).unwrap();
+        write_string(&mut out, "NEW_B");
+        write_string(&mut out, "data\\b");
+        write_string(&mut out, "FILE_A");
+        out
+    }
+
+    #[test]
+    fn test_unknown_type_round_trip() {
+        let data = unknown_type_newton();
+        let group = decode_newton(Cursor::new(&data)).unwrap();
+        let resource = &group.groups[0].resources.as_ref().unwrap()[1];
+        assert_eq!(resource.r#type, "Unknown_9");
+        assert_eq!(resource.slot, Some(1));
+        assert!(resource.newton_properties.is_some());
+
+        let mut out = Vec::new();
+        encode_newton(&group, &mut out).unwrap();
+        assert_eq!(out, data);
+
+        // Through JSON as written by `newton decode`, too
+        let json: MResourceGroup = serde_json::from_value(to_json(&group).unwrap()).unwrap();
+        let mut out = Vec::new();
+        encode_newton(&json, &mut out).unwrap();
+        assert_eq!(out, data);
+    }
+
+    #[test]
+    fn test_type_names_round_trip() {
+        for code in 1..=u8::MAX {
+            let resource_type = ResourceType::from_code(code);
+            let parsed = ResourceType::from_str(&resource_type.name()).unwrap();
+            assert_eq!(parsed, resource_type);
+            assert_eq!(parsed.to_u8(), code);
+        }
+
+        // Known codes spelled as unknown would lose their property block
+        assert!(ResourceType::from_str("Unknown_3").is_err());
+        assert!(ResourceType::from_str("Unknown_300").is_err());
+        assert!(ResourceType::from_str("Sound").is_err());
+    }
+
+    #[test]
+    fn test_strict_rejects_unknown_type() {
+        let data = unknown_type_newton();
+        assert!(decode_newton_strict(Cursor::new(&data)).is_err());
+    }
+
+    #[test]
+    fn test_json_keeps_newton_key() {
+        let group: MResourceGroup = serde_json::from_str(
+            r#"{"slot_count":1,"groups":[{"id":"G","type":"simple","resources":[
+                {"type":"PopAnim","slot":0,"id":"POPANIM_A","path":"pam\\a",
+                 "force_original_vector_symbol_size":true}]}]}"#,
+        )
+        .unwrap();
+        let resource = &group.groups[0].resources.as_ref().unwrap()[0];
+        assert_eq!(resource.force_original_vector_symbol_size, Some(true));
+
+        let json = to_json(&group).unwrap();
+        let resource = &json["groups"][0]["resources"][0];
+        assert_eq!(resource[FORCE_ORIGINAL_VECTOR_SYMBOL_SIZE_KEY], true);
+        assert!(resource.get("forceOriginalVectorSymbolSize").is_none());
+
+        let back: MResourceGroup = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            back.groups[0].resources.as_ref().unwrap()[0].force_original_vector_symbol_size,
+            Some(true)
+        );
+    }
+}
@@ -1,104 +1,99 @@
 use crate::error::{NewtonError, Result};
-use serde::{Deserialize, Serialize};
+pub use resources::{MSubgroupWrapper, PathDef, ResourceGroup, ShellSubgroupData, SubgroupWrapper};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MResourceGroup {
-    pub slot_count: u32,
-    pub groups: Vec<ShellSubgroupData>,
-}
+/// Newton manifests share the resource group model with `resources.json` and
+/// `resources.rton`; Newton itself does not store the versions.
+pub type MResourceGroup = ResourceGroup;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ShellSubgroupData {
-    pub id: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub res: Option<String>,
-    #[serde(rename = "type")]
-    pub group_type: String, // "composite" or "simple"
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub parent: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub subgroups: Option<Vec<SubgroupWrapper>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub resources: Option<Vec<MSubgroupWrapper>>,
-}
+/// Key `newton decode` writes for `force_original_vector_symbol_size`, which
+/// `resources.json` and RTON spell `forceOriginalVectorSymbolSize`. Both are accepted
+/// when reading.
+pub const FORCE_ORIGINAL_VECTOR_SYMBOL_SIZE_KEY: &str = "force_original_vector_symbol_size";
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SubgroupWrapper {
-    pub id: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub res: Option<String>,
+/// The JSON `newton decode` writes for a manifest
+pub fn to_json(group: &MResourceGroup) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(group)?;
+    let groups = value.get_mut("groups").and_then(|g| g.as_array_mut());
+    for group in groups.into_iter().flatten() {
+        let resources = group.get_mut("resources").and_then(|r| r.as_array_mut());
+        for resource in resources.into_iter().flatten() {
+            if let Some(resource) = resource.as_object_mut()
+                && let Some(force) = resource.remove("forceOriginalVectorSymbolSize")
+            {
+                resource.insert(FORCE_ORIGINAL_VECTOR_SYMBOL_SIZE_KEY.to_string(), force);
+            }
+        }
+    }
+    Ok(value)
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MSubgroupWrapper {
-    #[serde(rename = "type")]
-    pub res_type: String,
-    pub slot: u32,
-    pub id: String,
-    pub path: String,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub width: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub height: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub x: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub y: Option<i32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ax: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ay: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub aw: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub ah: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub cols: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub rows: Option<u32>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub atlas: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub runtime: Option<bool>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub parent: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub srcpath: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub force_original_vector_symbol_size: Option<bool>,
-}
+/// Prefix of the type name given to resource type codes this crate does not know,
+/// e.g. `Unknown_9`.
+pub const UNKNOWN_TYPE_PREFIX: &str = "Unknown_";
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ResourceType {
-    Image = 1,
-    PopAnim = 2,
-    SoundBank = 3,
-    File = 4,
-    PrimeFont = 5,
-    RenderEffect = 6,
-    DecodedSoundBank = 7,
+    Image,
+    PopAnim,
+    SoundBank,
+    File,
+    PrimeFont,
+    RenderEffect,
+    DecodedSoundBank,
+    /// A type code added by a newer game build
+    Unknown(u8),
 }
 
 impl ResourceType {
+    /// Strict lookup that rejects type codes outside 1-7.
     pub fn from_u8(v: u8) -> Result<Self> {
-        match v {
-            1 => Ok(ResourceType::Image),
-            2 => Ok(ResourceType::PopAnim),
-            3 => Ok(ResourceType::SoundBank),
-            4 => Ok(ResourceType::File),
-            5 => Ok(ResourceType::PrimeFont),
-            6 => Ok(ResourceType::RenderEffect),
-            7 => Ok(ResourceType::DecodedSoundBank),
-            _ => Err(NewtonError::DeserializationError(format!(
+        match Self::from_code(v) {
+            ResourceType::Unknown(_) => Err(NewtonError::DeserializationError(format!(
                 "Unknown resource type: {}",
                 v
             ))),
+            known => Ok(known),
+        }
+    }
+
+    /// Lenient lookup that keeps unrecognised codes as [`ResourceType::Unknown`].
+    pub fn from_code(v: u8) -> Self {
+        match v {
+            1 => ResourceType::Image,
+            2 => ResourceType::PopAnim,
+            3 => ResourceType::SoundBank,
+            4 => ResourceType::File,
+            5 => ResourceType::PrimeFont,
+            6 => ResourceType::RenderEffect,
+            7 => ResourceType::DecodedSoundBank,
+            n => ResourceType::Unknown(n),
         }
     }
 
     pub fn to_u8(self) -> u8 {
-        self as u8
+        match self {
+            ResourceType::Image => 1,
+            ResourceType::PopAnim => 2,
+            ResourceType::SoundBank => 3,
+            ResourceType::File => 4,
+            ResourceType::PrimeFont => 5,
+            ResourceType::RenderEffect => 6,
+            ResourceType::DecodedSoundBank => 7,
+            ResourceType::Unknown(n) => n,
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            ResourceType::Image => "Image".into(),
+            ResourceType::PopAnim => "PopAnim".into(),
+            ResourceType::SoundBank => "SoundBank".into(),
+            ResourceType::File => "File".into(),
+            ResourceType::PrimeFont => "PrimeFont".into(),
+            ResourceType::RenderEffect => "RenderEffect".into(),
+            ResourceType::DecodedSoundBank => "DecodedSoundBank".into(),
+            ResourceType::Unknown(n) => format!("{}{}", UNKNOWN_TYPE_PREFIX, n),
+        }
     }
 
     pub fn from_str(s: &str) -> Result<Self> {
@@ -110,10 +105,26 @@ impl ResourceType {
             "PrimeFont" => Ok(ResourceType::PrimeFont),
             "RenderEffect" => Ok(ResourceType::RenderEffect),
             "DecodedSoundBank" => Ok(ResourceType::DecodedSoundBank),
-            _ => Err(NewtonError::DeserializationError(format!(
-                "Unknown resource type string: {}",
-                s
-            ))),
+            _ => {
+                let code = s
+                    .strip_prefix(UNKNOWN_TYPE_PREFIX)
+                    .and_then(|code| code.parse::<u8>().ok())
+                    .ok_or_else(|| {
+                        NewtonError::DeserializationError(format!(
+                            "Unknown resource type string: {}",
+                            s
+                        ))
+                    })?;
+                // A named type would write its own property block, not the carried one
+                match Self::from_code(code) {
+                    unknown @ ResourceType::Unknown(_) => Ok(unknown),
+                    known => Err(NewtonError::DeserializationError(format!(
+                        "{} is the code of {}; write that type name instead",
+                        s,
+                        known.name()
+                    ))),
+                }
+            }
         }
     }
 }
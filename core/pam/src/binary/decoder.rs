@@ -1,17 +1,24 @@
 use crate::types::*;
-use anyhow::{Result, bail};
+use anyhow::{Context, Result, bail};
 use byteorder::{LE, ReadBytesExt};
 use std::io::Read;
 
 pub fn decode_pam(reader: &mut impl Read) -> Result<PamInfo> {
     let magic = reader.read_u32::<LE>()?;
     if magic != PAM_MAGIC {
-        bail!("Invalid PAM magic: {:#X}", magic);
+        bail!(
+            "Invalid PAM magic {:#X} at offset 0, expected {:#X}",
+            magic,
+            PAM_MAGIC
+        );
     }
 
     let version = reader.read_i32::<LE>()?;
     if !(1..=6).contains(&version) {
-        bail!("PAM version out of range: {}", version);
+        bail!(
+            "PAM version {} at offset 4 is not supported (expected 1 to 6)",
+            version
+        );
     }
 
     let frame_rate = reader.read_u8()? as i32;
@@ -28,14 +35,18 @@ pub fn decode_pam(reader: &mut impl Read) -> Result<PamInfo> {
 
     let images_count = reader.read_u16::<LE>()? as usize;
     let mut image = Vec::with_capacity(images_count);
-    for _ in 0..images_count {
-        image.push(read_image_info(reader, version)?);
+    for i in 0..images_count {
+        image.push(
+            read_image_info(reader, version)
+                .with_context(|| format!("Failed to read image {}", i))?,
+        );
     }
 
     let sprites_count = reader.read_u16::<LE>()? as usize;
     let mut sprite = Vec::with_capacity(sprites_count);
-    for _ in 0..sprites_count {
-        let mut s = read_sprite_info(reader, version)?;
+    for i in 0..sprites_count {
+        let mut s = read_sprite_info(reader, version)
+            .with_context(|| format!("Failed to read sprite {}", i))?;
         if version < 4 {
             s.frame_rate = frame_rate as f64;
         }
@@ -50,7 +61,7 @@ pub fn decode_pam(reader: &mut impl Read) -> Result<PamInfo> {
     };
 
     if has_main_sprite {
-        main_sprite = read_sprite_info(reader, version)?;
+        main_sprite = read_sprite_info(reader, version).context("Failed to read main sprite")?;
         if version < 4 {
             main_sprite.frame_rate = frame_rate as f64;
         }
@@ -143,8 +154,11 @@ fn read_sprite_info<R: Read>(reader: &mut R, version: i32) -> Result<SpriteInfo>
     work_area[1] = frames_count as i32;
 
     let mut frame = Vec::with_capacity(frames_count);
-    for _ in 0..frames_count {
-        frame.push(read_frame_info(reader, version)?);
+    for i in 0..frames_count {
+        frame.push(
+            read_frame_info(reader, version)
+                .with_context(|| format!("Failed to read frame {}", i))?,
+        );
     }
 
     Ok(SpriteInfo {
@@ -1,9 +1,20 @@
 use crate::types::*;
-use anyhow::Result;
+use crate::validate::Severity;
+use anyhow::{Result, bail};
 use byteorder::{LE, WriteBytesExt};
 use std::io::Write;
 
 pub fn encode_pam<W: Write>(pam: &PamInfo, writer: &mut W) -> Result<()> {
+    let errors: Vec<String> = pam
+        .validate()
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .map(|issue| issue.to_string())
+        .collect();
+    if !errors.is_empty() {
+        bail!("Invalid PAM:\n  {}", errors.join("\n  "));
+    }
+
     writer.write_u32::<LE>(PAM_MAGIC)?;
     writer.write_i32::<LE>(pam.version)?;
     writer.write_u8(pam.frame_rate as u8)?;
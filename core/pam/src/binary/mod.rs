@@ -1,5 +1,7 @@
 pub mod decoder;
 pub mod encoder;
+pub mod rename;
 
 pub use decoder::decode_pam;
 pub use encoder::encode_pam;
+pub use rename::rename_image_id;
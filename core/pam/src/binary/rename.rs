@@ -0,0 +1,72 @@
+use crate::types::*;
+use anyhow::{Result, bail};
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read};
+
+/// Offset of the image count: magic, version, frame rate, position and size
+const IMAGES_OFFSET: u64 = 17;
+
+/// Rename the resource id of every image named `old_id` by splicing the image table,
+/// leaving every other byte of the PAM as it was. Returns the new file and the number
+/// of images renamed.
+pub fn rename_image_id(data: &[u8], old_id: &str, new_id: &str) -> Result<(Vec<u8>, usize)> {
+    let mut reader = Cursor::new(data);
+    let magic = reader.read_u32::<LE>()?;
+    if magic != PAM_MAGIC {
+        bail!(
+            "Invalid PAM magic {:#X} at offset 0, expected {:#X}",
+            magic,
+            PAM_MAGIC
+        );
+    }
+    let version = reader.read_i32::<LE>()?;
+    if !(1..=6).contains(&version) {
+        bail!(
+            "PAM version {} at offset 4 is not supported (expected 1 to 6)",
+            version
+        );
+    }
+
+    reader.set_position(IMAGES_OFFSET);
+    let images_count = reader.read_u16::<LE>()?;
+    // Size and transform following each name
+    let record_len = match version {
+        1 => 6,
+        2 | 3 => 20,
+        _ => 24,
+    };
+
+    let mut out = Vec::with_capacity(data.len());
+    let mut copied = 0;
+    let mut renamed = 0;
+    for _ in 0..images_count {
+        let start = reader.position() as usize;
+        let len = reader.read_u16::<LE>()? as usize;
+        let mut name = vec![0u8; len];
+        reader.read_exact(&mut name)?;
+        reader.set_position(reader.position() + record_len);
+
+        let mut image = ImageInfo {
+            name: String::from_utf8_lossy(&name).to_string(),
+            size: [-1; 2],
+            transform: Vec::new(),
+        };
+        if image.resource_id() != old_id {
+            continue;
+        }
+        image.set_resource_id(new_id);
+        if image.name.len() > u16::MAX as usize {
+            bail!("Image name {} is too long", image.name);
+        }
+        out.extend_from_slice(&data[copied..start]);
+        out.write_u16::<LE>(image.name.len() as u16)?;
+        out.extend_from_slice(image.name.as_bytes());
+        copied = start + 2 + len;
+        renamed += 1;
+    }
+    if reader.position() as usize > data.len() {
+        bail!("PAM image table runs past the end of the file");
+    }
+    out.extend_from_slice(&data[copied..]);
+    Ok((out, renamed))
+}
@@ -0,0 +1,507 @@
+use crate::types::{AddsInfo, FrameInfo, ImageInfo, MovesInfo, PamInfo, RemovesInfo, SpriteInfo};
+use anyhow::{Context, Result, bail};
+use std::collections::{HashMap, HashSet};
+
+/// A sprite of a PAM: the main sprite or an entry of `PamInfo::sprite`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpriteRef {
+    Main,
+    Sprite(usize),
+}
+
+impl PamInfo {
+    /// Label `frame` of the main sprite, replacing the label it had
+    pub fn insert_label(&mut self, frame: usize, label: &str) -> Result<()> {
+        let frame_count = self.main_sprite.frame.len();
+        if frame >= frame_count {
+            bail!("Frame {} is out of range ({} frames)", frame, frame_count);
+        }
+        if let Some(existing) = self.label_frame(label)
+            && existing != frame
+        {
+            bail!("Label '{}' is already on frame {}", label, existing);
+        }
+        self.main_sprite.frame[frame].label = Some(label.to_string());
+        Ok(())
+    }
+
+    /// Remove a label from the main sprite. Returns the frame it was on.
+    pub fn remove_label(&mut self, label: &str) -> Option<usize> {
+        let frame = self.label_frame(label)?;
+        self.main_sprite.frame[frame].label = None;
+        Some(frame)
+    }
+
+    pub fn rename_label(&mut self, label: &str, new_label: &str) -> Result<()> {
+        let frame = self
+            .label_frame(label)
+            .with_context(|| format!("Label '{}' not found", label))?;
+        self.insert_label(frame, new_label)
+    }
+
+    /// Frame of the main sprite carrying `label`
+    pub fn label_frame(&self, label: &str) -> Option<usize> {
+        self.main_sprite
+            .frame
+            .iter()
+            .position(|f| f.label.as_deref() == Some(label))
+    }
+
+    /// Change the animation frame rate and resample every sprite to keep its timing.
+    /// Each new frame shows the source frame playing at that moment; labels and commands
+    /// move to the first new frame of their source frame, stops to the last one.
+    pub fn set_frame_rate(&mut self, frame_rate: i32) -> Result<()> {
+        if frame_rate <= 0 {
+            bail!("Frame rate must be positive, got {}", frame_rate);
+        }
+        if self.frame_rate <= 0 {
+            bail!("Current frame rate {} can't be resampled", self.frame_rate);
+        }
+        let ratio = frame_rate as f64 / self.frame_rate as f64;
+        // Resample copies so a sprite that can't be resampled leaves the PAM unchanged
+        let mut sprites = self.sprite.clone();
+        let mut main_sprite = self.main_sprite.clone();
+        for sprite in sprites.iter_mut().chain(Some(&mut main_sprite)) {
+            resample_sprite(sprite, ratio)?;
+        }
+        self.sprite = sprites;
+        self.main_sprite = main_sprite;
+        self.frame_rate = frame_rate;
+        Ok(())
+    }
+
+    /// Copy sprites of `other` into this PAM, together with the nested sprites and
+    /// images they use. Images with the same name, size and transform are shared.
+    /// Returns the new `PamInfo::sprite` index of each requested sprite.
+    pub fn merge_sprites(&mut self, other: &PamInfo, sprites: &[SpriteRef]) -> Result<Vec<usize>> {
+        // Nested sprites first, so every copy can be remapped when it is added
+        let mut order = Vec::new();
+        let mut seen = HashSet::new();
+        for sprite in sprites {
+            if let SpriteRef::Sprite(index) = *sprite {
+                collect_sprites(other, index, &mut seen, &mut order)?;
+            } else {
+                for child in nested_sprites(&other.main_sprite) {
+                    collect_sprites(other, child, &mut seen, &mut order)?;
+                }
+            }
+        }
+
+        let mut image_map = HashMap::new();
+        let mut sprite_map = HashMap::new();
+        for index in order {
+            let new_index =
+                self.copy_sprite(other, &other.sprite[index], &sprite_map, &mut image_map)?;
+            sprite_map.insert(index, new_index);
+        }
+        let mut result = Vec::with_capacity(sprites.len());
+        for sprite in sprites {
+            result.push(match *sprite {
+                SpriteRef::Sprite(index) => sprite_map[&index],
+                SpriteRef::Main => {
+                    self.copy_sprite(other, &other.main_sprite, &sprite_map, &mut image_map)?
+                }
+            });
+        }
+        Ok(result)
+    }
+
+    /// Append a copy of `source`, a sprite of `other`, remapping its resources
+    fn copy_sprite(
+        &mut self,
+        other: &PamInfo,
+        source: &SpriteInfo,
+        sprite_map: &HashMap<usize, usize>,
+        image_map: &mut HashMap<usize, usize>,
+    ) -> Result<usize> {
+        let mut sprite = source.clone();
+        for append in sprite.frame.iter_mut().flat_map(|f| f.append.iter_mut()) {
+            let resource = append.resource as usize;
+            append.resource = if append.sprite {
+                *sprite_map
+                    .get(&resource)
+                    .with_context(|| format!("Sprite {} is out of range", resource))?
+            } else {
+                let image = other
+                    .image
+                    .get(resource)
+                    .with_context(|| format!("Image {} is out of range", resource))?;
+                match image_map.get(&resource) {
+                    Some(index) => *index,
+                    None => {
+                        let index = self.find_or_add_image(image);
+                        image_map.insert(resource, index);
+                        index
+                    }
+                }
+            } as i32;
+        }
+        self.sprite.push(sprite);
+        Ok(self.sprite.len() - 1)
+    }
+
+    fn find_or_add_image(&mut self, image: &ImageInfo) -> usize {
+        let existing = self.image.iter().position(|i| {
+            i.name == image.name && i.size == image.size && i.transform == image.transform
+        });
+        existing.unwrap_or_else(|| {
+            self.image.push(image.clone());
+            self.image.len() - 1
+        })
+    }
+
+    /// Delete a sprite along with every layer showing it, renumbering references to
+    /// the sprites after it
+    pub fn remove_sprite(&mut self, index: usize) -> Result<()> {
+        if index >= self.sprite.len() {
+            bail!(
+                "Sprite {} is out of range ({} sprites)",
+                index,
+                self.sprite.len()
+            );
+        }
+        self.sprite.remove(index);
+        for sprite in self.sprite.iter_mut().chain(Some(&mut self.main_sprite)) {
+            drop_layers(sprite, |append| {
+                append.sprite && append.resource as usize == index
+            });
+            for append in sprite.frame.iter_mut().flat_map(|f| f.append.iter_mut()) {
+                if append.sprite && append.resource as usize > index {
+                    append.resource -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete an image along with every layer showing it, renumbering references to
+    /// the images after it
+    pub fn remove_image(&mut self, index: usize) -> Result<()> {
+        if index >= self.image.len() {
+            bail!(
+                "Image {} is out of range ({} images)",
+                index,
+                self.image.len()
+            );
+        }
+        self.image.remove(index);
+        for sprite in self.sprite.iter_mut().chain(Some(&mut self.main_sprite)) {
+            drop_layers(sprite, |append| {
+                !append.sprite && append.resource as usize == index
+            });
+            for append in sprite.frame.iter_mut().flat_map(|f| f.append.iter_mut()) {
+                if !append.sprite && append.resource as usize > index {
+                    append.resource -= 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Delete sprites the main sprite never shows, directly or nested. Returns how many
+    /// were removed.
+    pub fn remove_unused_sprites(&mut self) -> usize {
+        let mut used = HashSet::new();
+        let mut pending: Vec<usize> = nested_sprites(&self.main_sprite).collect();
+        while let Some(index) = pending.pop() {
+            if index < self.sprite.len() && used.insert(index) {
+                pending.extend(nested_sprites(&self.sprite[index]));
+            }
+        }
+
+        let remap = compact(self.sprite.len(), |i| used.contains(&i));
+        let mut index = 0;
+        self.sprite.retain(|_| {
+            index += 1;
+            used.contains(&(index - 1))
+        });
+        for sprite in self.sprite.iter_mut().chain(Some(&mut self.main_sprite)) {
+            for append in sprite.frame.iter_mut().flat_map(|f| f.append.iter_mut()) {
+                if append.sprite
+                    && let Some(Some(new_index)) = remap.get(append.resource as usize)
+                {
+                    append.resource = *new_index as i32;
+                }
+            }
+        }
+        remap.iter().filter(|i| i.is_none()).count()
+    }
+
+    /// Delete images no sprite shows. Returns how many were removed.
+    pub fn remove_unused_images(&mut self) -> usize {
+        let used: HashSet<usize> = self
+            .sprite
+            .iter()
+            .chain(Some(&self.main_sprite))
+            .flat_map(|s| s.frame.iter().flat_map(|f| f.append.iter()))
+            .filter(|append| !append.sprite)
+            .map(|append| append.resource as usize)
+            .collect();
+
+        let remap = compact(self.image.len(), |i| used.contains(&i));
+        let mut index = 0;
+        self.image.retain(|_| {
+            index += 1;
+            used.contains(&(index - 1))
+        });
+        for sprite in self.sprite.iter_mut().chain(Some(&mut self.main_sprite)) {
+            for append in sprite.frame.iter_mut().flat_map(|f| f.append.iter_mut()) {
+                if !append.sprite
+                    && let Some(Some(new_index)) = remap.get(append.resource as usize)
+                {
+                    append.resource = *new_index as i32;
+                }
+            }
+        }
+        remap.iter().filter(|i| i.is_none()).count()
+    }
+}
+
+/// New index of each of `len` entries once those failing `keep` are dropped
+fn compact(len: usize, keep: impl Fn(usize) -> bool) -> Vec<Option<usize>> {
+    let mut next = 0;
+    (0..len)
+        .map(|i| {
+            keep(i).then(|| {
+                next += 1;
+                next - 1
+            })
+        })
+        .collect()
+}
+
+fn nested_sprites(sprite: &SpriteInfo) -> impl Iterator<Item = usize> + '_ {
+    sprite
+        .frame
+        .iter()
+        .flat_map(|f| f.append.iter())
+        .filter(|append| append.sprite)
+        .map(|append| append.resource as usize)
+}
+
+/// Post-order walk of the sprites `index` depends on, itself last
+fn collect_sprites(
+    pam: &PamInfo,
+    index: usize,
+    seen: &mut HashSet<usize>,
+    order: &mut Vec<usize>,
+) -> Result<()> {
+    if !seen.insert(index) {
+        return Ok(());
+    }
+    let sprite = pam
+        .sprite
+        .get(index)
+        .with_context(|| format!("Sprite {} is out of range", index))?;
+    for child in nested_sprites(sprite) {
+        collect_sprites(pam, child, seen, order)?;
+    }
+    order.push(index);
+    Ok(())
+}
+
+/// Remove every layer appended with an element matching `matches`, with its changes
+/// and removal. Like in `replay`, an append over a displayed layer replaces it, so a
+/// dropped append still removes the layer it was appended over.
+fn drop_layers(sprite: &mut SpriteInfo, matches: impl Fn(&AddsInfo) -> bool) {
+    let mut displayed = HashSet::new();
+    let mut dropped = HashSet::new();
+    for frame in &mut sprite.frame {
+        frame.remove.retain(|remove| {
+            displayed.remove(&remove.index);
+            !dropped.remove(&remove.index)
+        });
+        let mut replaced = Vec::new();
+        frame.append.retain(|append| {
+            if matches(append) {
+                if displayed.remove(&append.index) {
+                    replaced.push(RemovesInfo {
+                        index: append.index,
+                    });
+                }
+                dropped.insert(append.index);
+                false
+            } else {
+                dropped.remove(&append.index);
+                displayed.insert(append.index);
+                true
+            }
+        });
+        frame.remove.extend(replaced);
+        frame
+            .change
+            .retain(|change| !dropped.contains(&change.index));
+    }
+}
+
+/// Full state of one layer, for replaying and re-diffing a sprite's frames
+#[derive(Clone)]
+struct LayerState {
+    /// Distinguishes re-appends of the same layer index
+    instance: usize,
+    append: AddsInfo,
+    transform: Vec<f64>,
+    color: Option<[f64; 4]>,
+    source_rectangle: Option<[i32; 4]>,
+    /// Nested sprite frame of the last clock reset, and how many resets happened
+    sprite_frame_number: i32,
+    resets: usize,
+}
+
+fn replay(sprite: &SpriteInfo) -> Vec<Vec<LayerState>> {
+    let mut layers: Vec<LayerState> = Vec::new();
+    let mut instances = 0;
+    let mut states = Vec::with_capacity(sprite.frame.len());
+    for frame in &sprite.frame {
+        for remove in &frame.remove {
+            layers.retain(|l| l.append.index != remove.index);
+        }
+        for append in &frame.append {
+            layers.retain(|l| l.append.index != append.index);
+            instances += 1;
+            layers.push(LayerState {
+                instance: instances,
+                append: append.clone(),
+                transform: Vec::new(),
+                color: None,
+                source_rectangle: None,
+                sprite_frame_number: 0,
+                resets: 0,
+            });
+        }
+        for change in &frame.change {
+            let Some(layer) = layers.iter_mut().find(|l| l.append.index == change.index) else {
+                continue;
+            };
+            layer.transform = change.transform.clone();
+            if change.color.is_some() {
+                layer.color = change.color;
+            }
+            if change.source_rectangle.is_some() {
+                layer.source_rectangle = change.source_rectangle;
+            }
+            if change.sprite_frame_number != 0 {
+                layer.sprite_frame_number = change.sprite_frame_number;
+                layer.resets += 1;
+            }
+        }
+        layers.sort_by_key(|l| l.append.index);
+        states.push(layers.clone());
+    }
+    states
+}
+
+/// Frame edits turning the `previous` layers into `current`
+fn diff_layers(previous: &[LayerState], current: &[LayerState], ratio: f64) -> FrameInfo {
+    let scale_frame = |frame: i32| (frame as f64 * ratio).round() as i32;
+    let mut frame = FrameInfo::default();
+    for old in previous {
+        if !current.iter().any(|l| l.instance == old.instance) {
+            frame.remove.push(RemovesInfo {
+                index: old.append.index,
+            });
+        }
+    }
+    for layer in current {
+        let old = previous.iter().find(|l| l.instance == layer.instance);
+        let reset = layer.resets != old.map_or(0, |o| o.resets);
+        match old {
+            None => {
+                let mut append = layer.append.clone();
+                append.preload_frame = scale_frame(append.preload_frame);
+                frame.append.push(append);
+            }
+            Some(old)
+                if old.transform == layer.transform
+                    && old.color == layer.color
+                    && old.source_rectangle == layer.source_rectangle
+                    && !reset =>
+            {
+                continue;
+            }
+            Some(_) => {}
+        }
+        if layer.transform.is_empty() {
+            // Appended but never moved in the source either
+            continue;
+        }
+        frame.change.push(MovesInfo {
+            index: layer.append.index,
+            transform: layer.transform.clone(),
+            color: if old.is_none_or(|o| o.color != layer.color) {
+                layer.color
+            } else {
+                None
+            },
+            source_rectangle: if old.is_none_or(|o| o.source_rectangle != layer.source_rectangle) {
+                layer.source_rectangle
+            } else {
+                None
+            },
+            sprite_frame_number: if reset {
+                scale_frame(layer.sprite_frame_number)
+            } else {
+                0
+            },
+        });
+    }
+    frame
+}
+
+fn resample_sprite(sprite: &mut SpriteInfo, ratio: f64) -> Result<()> {
+    let old_count = sprite.frame.len();
+    sprite.frame_rate *= ratio;
+    if old_count == 0 {
+        return Ok(());
+    }
+    let new_count = ((old_count as f64 * ratio).round() as usize).max(1);
+    // Source frame playing during new frame `j`, and the new frames of source frame `i`
+    let source = |j: usize| (((j as f64 + 1e-9) / ratio) as usize).min(old_count - 1);
+    let first_of =
+        |i: usize| ((i as f64 * ratio - 1e-9).ceil().max(0.0) as usize).min(new_count - 1);
+    let last_of = |i: usize| {
+        (((i + 1) as f64 * ratio - 1e-9).ceil() as usize)
+            .saturating_sub(1)
+            .clamp(first_of(i), new_count - 1)
+    };
+
+    let states = replay(sprite);
+    let mut frames: Vec<FrameInfo> = Vec::with_capacity(new_count);
+    let empty = Vec::new();
+    for j in 0..new_count {
+        let previous = if j == 0 {
+            &empty
+        } else {
+            &states[source(j - 1)]
+        };
+        frames.push(diff_layers(previous, &states[source(j)], ratio));
+    }
+
+    for (i, old) in sprite.frame.iter().enumerate() {
+        if let Some(label) = &old.label {
+            let target = &mut frames[first_of(i)];
+            if let Some(existing) = &target.label {
+                bail!(
+                    "Labels '{}' and '{}' would share frame {} at the new frame rate",
+                    existing,
+                    label,
+                    first_of(i)
+                );
+            }
+            target.label = Some(label.clone());
+        }
+        frames[first_of(i)]
+            .command
+            .extend(old.command.iter().cloned());
+        if old.stop {
+            frames[last_of(i)].stop = true;
+        }
+    }
+
+    sprite.work_area = [
+        (sprite.work_area[0] as f64 * ratio).round() as i32,
+        (sprite.work_area[1] as f64 * ratio).round() as i32,
+    ];
+    sprite.frame = frames;
+    Ok(())
+}
@@ -1,21 +1,20 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, bail};
 use quick_xml::Reader;
 use quick_xml::events::Event;
 use regex::Regex;
 use serde::Deserialize;
 use std::collections::HashMap;
-use std::fs;
-use std::io::Read;
+use std::io::Cursor;
 use std::path::Path;
-use zip::result::ZipError;
-use zip::ZipArchive;
 
+use super::source::XflSource;
+use super::timeline::{Element, ElementItem, Timeline, bake_element, parse_timelines};
+use crate::render::Matrix;
 use crate::types::*;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct PamSidecar {
-    schema: Option<String>,
     version: Option<i32>,
     frame_rate: Option<i32>,
     position: Option<[f64; 2]>,
@@ -23,590 +22,677 @@ struct PamSidecar {
     image_names: Option<Vec<String>>,
 }
 
-pub fn convert_from_fla(input_path: &Path, _resolution: i32) -> Result<PamInfo> {
-    let file = fs::File::open(input_path)
-        .with_context(|| format!("Failed to open {:?}", input_path))?;
-    let mut archive = ZipArchive::new(file).context("Failed to read FLA archive")?;
+/// `DOMDocument` attributes and its bitmap library
+#[derive(Default)]
+struct DocumentInfo {
+    width: Option<f64>,
+    height: Option<f64>,
+    frame_rate: Option<f64>,
+    /// `DOMBitmapItem`s by library name: their file in `LIBRARY/` and pixel size
+    bitmaps: HashMap<String, (Option<String>, Option<[i32; 2]>)>,
+}
 
+/// Build a `PamInfo` from a `.fla` archive or an XFL folder. Images and sprites are
+/// inferred from the library symbols, so documents saved again by Animate (renamed
+/// symbols, nested symbols, tweens) still convert; `PAM.sidecar.json` only restores
+/// what XFL has no place for, like the PAM version, position and image ids.
+pub fn convert_from_fla(input_path: &Path, resolution: i32) -> Result<PamInfo> {
+    let mut source = XflSource::open(input_path)?;
+
+    let doc_str = source.read_string("DOMDocument.xml")?;
+    let document = parse_document_info(&doc_str)?;
+    let root = parse_timelines(&doc_str)
+        .context("Failed to parse DOMDocument.xml")?
+        .into_iter()
+        .reduce(|first, timeline| {
+            if first.name != "animation" && timeline.name == "animation" {
+                timeline
+            } else {
+                first
+            }
+        })
+        .context("DOMDocument.xml has no timeline")?;
+    let sidecar = read_pam_sidecar(&mut source)?;
+
+    let frame_rate = document
+        .frame_rate
+        .map(|fr| fr.round() as i32)
+        .or(sidecar.as_ref().and_then(|sc| sc.frame_rate))
+        .unwrap_or(30);
     let mut pam_info = PamInfo {
         version: 5,
-        frame_rate: 30,
+        frame_rate,
         position: [0.0, 0.0],
-        size: [0.0, 0.0],
+        size: [
+            document.width.unwrap_or(0.0),
+            document.height.unwrap_or(0.0),
+        ],
         image: vec![],
         sprite: vec![],
-        main_sprite: SpriteInfo {
-            name: Some("main_sprite".to_string()),
-            description: None,
-            frame_rate: 30.0,
-            work_area: [0, 0],
-            frame: vec![],
-        },
+        main_sprite: SpriteInfo::default(),
     };
+    if let Some(sc) = &sidecar {
+        if let Some(v) = sc.version {
+            pam_info.version = v;
+        }
+        if let Some(pos) = sc.position {
+            pam_info.position = pos;
+        }
+        if let (None, None, Some(size)) = (document.width, document.height, sc.size) {
+            pam_info.size = size;
+        }
+    }
 
-    let doc_str = read_zip_entry(&mut archive, "DOMDocument.xml")?;
+    let library = read_library(&mut source)?;
+    let mut resources = Resources::new(&library, &document, &mut source, resolution);
+
+    // The root timeline usually just shows the main symbol; otherwise it is the
+    // animation itself
+    let main_symbol = single_element(&root)
+        .filter(|element| element.matrix == Matrix::IDENTITY.0)
+        .and_then(|element| match &element.item {
+            ElementItem::Symbol(name) => resources.sprite_order.contains(name).then_some(name),
+            ElementItem::Bitmap(_) => None,
+        })
+        .cloned();
+    if let Some(name) = &main_symbol {
+        resources.sprite_order.retain(|n| n != name);
+    }
+    for (index, name) in resources.sprite_order.iter().enumerate() {
+        resources.sprite_symbols.insert(name.clone(), index);
+    }
 
-    let mut reader = Reader::from_str(&doc_str);
-    reader.config_mut().trim_text(true);
+    let image_names = sidecar.as_ref().and_then(|sc| sc.image_names.as_ref());
+    resources.build_images(image_names);
 
-    let mut inside_animation = false;
-    let mut current_layer_name = String::new();
-    let mut current_frame_index = 0;
+    let sprite_order = resources.sprite_order.clone();
+    for name in &sprite_order {
+        let mut sprite = build_sprite(&library[name], frame_rate as f64, &mut resources);
+        sprite.name = symbol_sprite_name(name);
+        pam_info.sprite.push(sprite);
+    }
 
-    let stop_regex = Regex::new(r"stop\(\)").unwrap();
-    let fs_regex = Regex::new(r#"fscommand\("([^"]+)"(?:,\s*"([^"]*)")?\)"#).unwrap();
+    let mut main_sprite = match &main_symbol {
+        Some(name) => {
+            // The symbol draws, the root timeline keeps its labels, stops and commands
+            let mut content = build_sprite(&library[name], frame_rate as f64, &mut resources);
+            let frames_len = content.frame.len().max(root.frame_count());
+            content.frame.resize_with(frames_len, Default::default);
+            content.work_area = [0, frames_len as i32];
+            annotate_frames(&root, &mut content.frame);
+            content
+        }
+        None => build_sprite(&root, frame_rate as f64, &mut resources),
+    };
+    main_sprite.name = Some("main_sprite".to_string());
+    pam_info.main_sprite = main_sprite;
+    pam_info.image = resources.images;
+
+    Ok(pam_info)
+}
 
+fn parse_document_info(xml: &str) -> Result<DocumentInfo> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut info = DocumentInfo::default();
     loop {
         match reader.read_event() {
             Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().as_ref() {
                 b"DOMDocument" => {
                     for attr in e.attributes() {
                         let attr = attr?;
-                        if attr.key.as_ref() == b"width" {
-                            pam_info.size[0] =
-                                String::from_utf8_lossy(&attr.value).parse().unwrap_or(0.0);
-                        } else if attr.key.as_ref() == b"height" {
-                            pam_info.size[1] =
-                                String::from_utf8_lossy(&attr.value).parse().unwrap_or(0.0);
-                        } else if attr.key.as_ref() == b"frameRate" {
-                            pam_info.frame_rate =
-                                String::from_utf8_lossy(&attr.value).parse().unwrap_or(30);
+                        let value = String::from_utf8_lossy(&attr.value).parse().ok();
+                        match attr.key.as_ref() {
+                            b"width" => info.width = value,
+                            b"height" => info.height = value,
+                            b"frameRate" => info.frame_rate = value,
+                            _ => {}
                         }
                     }
                 }
-                b"DOMTimeline" => {
+                b"DOMBitmapItem" => {
+                    let mut name = None;
+                    let mut href = None;
+                    let mut right = None;
+                    let mut bottom = None;
                     for attr in e.attributes() {
                         let attr = attr?;
-                        if attr.key.as_ref() == b"name"
-                            && String::from_utf8_lossy(&attr.value) == "animation"
-                        {
-                            inside_animation = true;
+                        let value = String::from_utf8_lossy(&attr.value).into_owned();
+                        match attr.key.as_ref() {
+                            b"name" => name = Some(value),
+                            b"href" => href = Some(value),
+                            b"frameRight" => right = value.parse::<i32>().ok(),
+                            b"frameBottom" => bottom = value.parse::<i32>().ok(),
+                            _ => {}
                         }
                     }
-                }
-                b"DOMLayer" if inside_animation => {
-                    for attr in e.attributes() {
-                        let attr = attr?;
-                        if attr.key.as_ref() == b"name" {
-                            current_layer_name = String::from_utf8_lossy(&attr.value).to_string();
-                        }
-                    }
-                }
-                b"DOMFrame" if inside_animation => {
-                    if current_layer_name == "flow" || current_layer_name == "command" {
-                        for attr in e.attributes() {
-                            let attr = attr?;
-                            if attr.key.as_ref() == b"index" {
-                                current_frame_index =
-                                    String::from_utf8_lossy(&attr.value).parse().unwrap_or(0);
-                                if current_frame_index >= pam_info.main_sprite.frame.len() {
-                                    pam_info
-                                        .main_sprite
-                                        .frame
-                                        .resize_with(current_frame_index + 1, Default::default);
-                                }
-                            } else if attr.key.as_ref() == b"name" && current_layer_name == "flow" {
-                                pam_info.main_sprite.frame[current_frame_index].label =
-                                    Some(String::from_utf8_lossy(&attr.value).to_string());
-                            }
-                        }
+                    // Frame bounds are in twips
+                    let size = right.zip(bottom).map(|(r, b)| [r / 20, b / 20]);
+                    if let Some(name) = name {
+                        info.bitmaps.insert(name, (href, size));
                     }
                 }
                 _ => {}
             },
-            Ok(Event::End(ref e)) => {
-                if e.name().as_ref() == b"DOMTimeline" {
-                    inside_animation = false;
-                }
-            }
-            Ok(Event::Text(e)) if inside_animation => {
-                let text = String::from_utf8_lossy(&e.into_inner()).into_owned();
-                if current_layer_name == "flow" && stop_regex.is_match(&text) {
-                    pam_info.main_sprite.frame[current_frame_index].stop = true;
-                } else if current_layer_name == "command" {
-                    for caps in fs_regex.captures_iter(&text) {
-                        let cmd = caps.get(1).unwrap().as_str().to_string();
-                        let arg = caps.get(2).map_or("", |m| m.as_str()).to_string();
-                        pam_info.main_sprite.frame[current_frame_index]
-                            .command
-                            .push([cmd, arg]);
-                    }
-                }
-            }
             Ok(Event::Eof) => break,
-            Err(e) => anyhow::bail!("Error parsing DOMDocument XML: {:?}", e),
+            Err(e) => bail!("Error parsing DOMDocument XML: {:?}", e),
             _ => {}
         }
     }
+    Ok(info)
+}
 
-    let sidecar = read_pam_sidecar(&mut archive)?;
-    if let Some(sc) = &sidecar {
-        let _ = &sc.schema;
-        if let Some(v) = sc.version {
-            pam_info.version = v;
-        }
-        if let Some(fr) = sc.frame_rate {
-            pam_info.frame_rate = fr;
-        }
-        if let Some(pos) = sc.position {
-            pam_info.position = pos;
-        }
-        if let Some(size) = sc.size {
-            pam_info.size = size;
+fn read_pam_sidecar(source: &mut XflSource) -> Result<Option<PamSidecar>> {
+    for name in [
+        "PAM.sidecar.json",
+        "pam.sidecar.json",
+        "PAM.sidecar",
+        "pam.sidecar",
+    ] {
+        if let Some(data) = source.read_optional(name)?
+            && let Ok(sidecar) = serde_json::from_slice::<PamSidecar>(&data)
+        {
+            return Ok(Some(sidecar));
         }
     }
+    Ok(None)
+}
+
+/// Timelines of every library symbol, by item name
+fn read_library(source: &mut XflSource) -> Result<HashMap<String, Timeline>> {
+    let mut library = HashMap::new();
+    let mut entries: Vec<String> = source
+        .entries()?
+        .into_iter()
+        .filter(|name| name.starts_with("LIBRARY/") && name.ends_with(".xml"))
+        .collect();
+    entries.sort();
+    for entry in entries {
+        let xml = source.read_string(&entry)?;
+        let Some(name) = symbol_item_name(&xml) else {
+            continue;
+        };
+        let timeline = parse_timelines(&xml)
+            .with_context(|| format!("Failed to parse {}", entry))?
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        library.insert(name, timeline);
+    }
+    Ok(library)
+}
 
-    let mut id_to_name: HashMap<i32, String> = HashMap::new();
-    let mut id_to_size: HashMap<i32, [i32; 2]> = HashMap::new();
-    let dim_regex = Regex::new(r"_(\d+)x(\d+)(_\d+)?$").unwrap();
-
-    let source_entries: Vec<(i32, String)> = {
-        let mut entries = Vec::new();
-        for i in 0..archive.len() {
-            if let Ok(entry) = archive.by_index(i) {
-                let name = entry.name().to_string();
-                if let Some(rest) = name.strip_prefix("LIBRARY/source/source_") {
-                    if let Some(stem) = rest.strip_suffix(".xml") {
-                        if let Ok(idx) = stem.parse::<i32>() {
-                            entries.push((idx, name));
-                        }
-                    }
+/// `name` of the `DOMSymbolItem` a library file describes
+fn symbol_item_name(xml: &str) -> Option<String> {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
+                if e.name().as_ref() != b"DOMSymbolItem" {
+                    return None;
                 }
+                return e
+                    .attributes()
+                    .flatten()
+                    .find(|a| a.key.as_ref() == b"name")
+                    .map(|a| String::from_utf8_lossy(&a.value).into_owned());
             }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
         }
-        entries.sort_by_key(|k| k.0);
-        entries
-    };
+    }
+}
 
-    for (idx, entry_name) in &source_entries {
-        let xml_str = read_zip_entry(&mut archive, entry_name)?;
-        let mut rd = Reader::from_str(&xml_str);
-        rd.config_mut().trim_text(true);
-        loop {
-            match rd.read_event() {
-                Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
-                    if e.name().as_ref() == b"DOMBitmapInstance" {
-                        let mut lib_name: Option<String> = None;
-                        for attr in e.attributes() {
-                            let attr = attr?;
-                            if attr.key.as_ref() == b"libraryItemName" {
-                                lib_name = Some(String::from_utf8_lossy(&attr.value).replace("media/", ""));
-                            }
-                        }
-                        if let Some(name) = lib_name {
-                            let dim_source = name.split('|').next().unwrap_or(&name);
-                            let mut size = [0, 0];
-                            if let Some(caps) = dim_regex.captures(dim_source) {
-                                size[0] = caps.get(1).unwrap().as_str().parse().unwrap_or(0);
-                                size[1] = caps.get(2).unwrap().as_str().parse().unwrap_or(0);
-                            }
-                            id_to_name.insert(*idx, name);
-                            id_to_size.insert(*idx, size);
-                        }
-                    }
-                }
-                Ok(Event::Eof) => break,
-                _ => {}
+/// The only element a timeline ever shows
+fn single_element(timeline: &Timeline) -> Option<&Element> {
+    let mut elements = timeline.elements();
+    let first = elements.next()?;
+    elements.next().is_none().then_some(first)
+}
+
+/// Position of a library symbol written by `convert_to_fla` (`image/image_3` is 3)
+fn generated_index(name: &str, folder: &str) -> Option<usize> {
+    name.strip_prefix(folder)?.parse().ok()
+}
+
+/// PAM name of a sprite symbol: generated names carry none, renamed ones keep theirs
+fn symbol_sprite_name(name: &str) -> Option<String> {
+    if generated_index(name, "sprite/sprite_").is_some() {
+        return None;
+    }
+    Some(name.rsplit('/').next().unwrap_or(name).to_string())
+}
+
+/// PAM resources inferred from the library: bitmap symbols are image sources,
+/// symbols showing only a source are images and everything else is a sprite
+struct Resources<'a> {
+    document: &'a DocumentInfo,
+    source: &'a mut XflSource,
+    /// Bitmap and matrix of each source symbol
+    sources: HashMap<String, (String, [f64; 6])>,
+    /// Image symbols in PAM order, with the source they show and its placement
+    image_order: Vec<(String, String, [f64; 6])>,
+    image_symbols: HashMap<String, usize>,
+    sprite_order: Vec<String>,
+    sprite_symbols: HashMap<String, usize>,
+    /// Images made for bitmaps and sources placed straight on a timeline
+    bitmap_images: HashMap<String, usize>,
+    images: Vec<ImageInfo>,
+    bitmap_scale: f64,
+}
+
+impl<'a> Resources<'a> {
+    fn new(
+        library: &HashMap<String, Timeline>,
+        document: &'a DocumentInfo,
+        source: &'a mut XflSource,
+        resolution: i32,
+    ) -> Self {
+        let mut sources = HashMap::new();
+        for (name, timeline) in library {
+            if let Some(element) = single_element(timeline)
+                && let ElementItem::Bitmap(bitmap) = &element.item
+            {
+                sources.insert(name.clone(), (bitmap.clone(), element.matrix));
             }
         }
-    }
 
-    if let Some(names) = sidecar
-        .as_ref()
-        .and_then(|sc| sc.image_names.as_ref())
-    {
-        for (i, full_name) in names.iter().enumerate() {
-            if full_name.is_empty() {
+        let mut image_order = Vec::new();
+        let mut sprite_order = Vec::new();
+        for (name, timeline) in library {
+            if sources.contains_key(name) {
                 continue;
             }
-            let idx = (i + 1) as i32;
-            if id_to_name.contains_key(&idx) {
-                id_to_name.insert(idx, full_name.clone());
+            match single_element(timeline) {
+                Some(Element {
+                    item: ElementItem::Symbol(shown),
+                    matrix,
+                    ..
+                }) if sources.contains_key(shown) => {
+                    image_order.push((name.clone(), shown.clone(), *matrix));
+                }
+                _ => sprite_order.push(name.clone()),
             }
         }
+        // Generated symbols keep their PAM order, the others follow by name
+        let order_key = |name: &String, folder: &str| {
+            (
+                generated_index(name, folder).unwrap_or(usize::MAX),
+                name.clone(),
+            )
+        };
+        image_order.sort_by_key(|(name, _, _)| order_key(name, "image/image_"));
+        sprite_order.sort_by_key(|name| order_key(name, "sprite/sprite_"));
+
+        Self {
+            document,
+            source,
+            sources,
+            image_symbols: HashMap::new(),
+            image_order,
+            sprite_order,
+            sprite_symbols: HashMap::new(),
+            bitmap_images: HashMap::new(),
+            images: Vec::new(),
+            bitmap_scale: 1200.0 / resolution.max(1) as f64,
+        }
     }
 
-    let mut image_keys: Vec<i32> = id_to_name.keys().copied().collect();
-    image_keys.sort();
-
-    for &idx in &image_keys {
-        let name = id_to_name[&idx].clone();
-        let size = id_to_size[&idx];
-        let mut transform = vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
-
-        let img_name = format!("LIBRARY/image/image_{}.xml", idx);
-        if let Ok(xml_str) = read_zip_entry(&mut archive, &img_name) {
-            let mut rd = Reader::from_str(&xml_str);
-            rd.config_mut().trim_text(true);
-            loop {
-                match rd.read_event() {
-                    Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => {
-                        if e.name().as_ref() == b"Matrix" {
-                            for attr in e.attributes() {
-                                let attr = attr.unwrap();
-                                let v = String::from_utf8_lossy(&attr.value).parse().unwrap_or(0.0);
-                                match attr.key.as_ref() {
-                                    b"a" => transform[0] = v,
-                                    b"b" => transform[1] = v,
-                                    b"c" => transform[2] = v,
-                                    b"d" => transform[3] = v,
-                                    b"tx" => transform[4] = v,
-                                    b"ty" => transform[5] = v,
-                                    _ => {}
-                                }
-                            }
-                        }
-                    }
-                    Ok(Event::Eof) => break,
-                    _ => {}
-                }
+    /// Create the images of the image symbols. A sidecar name replaces the bitmap
+    /// name only while it still refers to the same bitmap.
+    fn build_images(&mut self, sidecar_names: Option<&Vec<String>>) {
+        let image_order = std::mem::take(&mut self.image_order);
+        for (name, source, matrix) in &image_order {
+            let bitmap = self.sources[source].0.clone();
+            let mut info = self.bitmap_image(&bitmap, matrix.to_vec());
+            let sidecar_name = generated_index(name, "image/image_")
+                .and_then(|i| sidecar_names?.get(i.checked_sub(1)?))
+                .filter(|n| n.split('|').next() == Some(info.name.as_str()));
+            if let Some(sidecar_name) = sidecar_name {
+                info.name = sidecar_name.clone();
             }
+            self.image_symbols.insert(name.clone(), self.images.len());
+            self.images.push(info);
         }
+        self.image_order = image_order;
+    }
 
-        pam_info.image.push(ImageInfo {
+    fn bitmap_image(&mut self, bitmap: &str, transform: Vec<f64>) -> ImageInfo {
+        let name = bitmap.strip_prefix("media/").unwrap_or(bitmap).to_string();
+        let size = self.bitmap_size(bitmap, &name);
+        ImageInfo {
             name,
             size,
             transform,
-        });
-    }
-
-    let sprite_entries: Vec<(i32, String)> = {
-        let mut entries = Vec::new();
-        for i in 0..archive.len() {
-            if let Ok(entry) = archive.by_index(i) {
-                let name = entry.name().to_string();
-                if let Some(rest) = name.strip_prefix("LIBRARY/sprite/sprite_") {
-                    if let Some(stem) = rest.strip_suffix(".xml") {
-                        if let Ok(idx) = stem.parse::<i32>() {
-                            entries.push((idx, name));
-                        }
-                    }
-                }
-            }
         }
-        entries.sort_by_key(|k| k.0);
-        entries
-    };
-
-    for (_, entry_name) in &sprite_entries {
-        let xml_str = read_zip_entry(&mut archive, entry_name)?;
-        let sp = parse_sprite_xml(&xml_str)?;
-        pam_info.sprite.push(sp);
     }
 
-    if let Ok(main_xml) = read_zip_entry(&mut archive, "LIBRARY/main.xml") {
-        let mut main_sp = parse_sprite_xml(&main_xml)?;
-        main_sp.name = Some("main_sprite".to_string());
-
-        let frames_len = pam_info.main_sprite.frame.len().max(main_sp.frame.len());
-        pam_info
-            .main_sprite
-            .frame
-            .resize_with(frames_len, Default::default);
-        main_sp.frame.resize_with(frames_len, Default::default);
-
-        for i in 0..frames_len {
-            pam_info.main_sprite.frame[i].remove = std::mem::take(&mut main_sp.frame[i].remove);
-            pam_info.main_sprite.frame[i].append = std::mem::take(&mut main_sp.frame[i].append);
-            pam_info.main_sprite.frame[i].change = std::mem::take(&mut main_sp.frame[i].change);
+    /// Pixel size of a bitmap: from its library item, its PNG, or a `_<w>x<h>` name
+    fn bitmap_size(&mut self, bitmap: &str, name: &str) -> [i32; 2] {
+        let (href, size) = self
+            .document
+            .bitmaps
+            .get(bitmap)
+            .cloned()
+            .unwrap_or_default();
+        if let Some(size) = size {
+            return size;
+        }
+        let file = href.unwrap_or_else(|| format!("{}.png", bitmap));
+        if let Ok(Some(data)) = self.source.read_optional(&format!("LIBRARY/{}", file))
+            && let Ok(reader) = image::ImageReader::new(Cursor::new(data)).with_guessed_format()
+            && let Ok((width, height)) = reader.into_dimensions()
+        {
+            return [width as i32, height as i32];
         }
+        let dim_regex = Regex::new(r"_(\d+)x(\d+)(_\d+)?$").unwrap();
+        dim_regex
+            .captures(name)
+            .map(|caps| [caps[1].parse().unwrap_or(0), caps[2].parse().unwrap_or(0)])
+            .unwrap_or([0, 0])
     }
 
-    Ok(pam_info)
-}
-
-fn read_zip_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<String> {
-    let mut entry = archive
-        .by_name(name)
-        .with_context(|| format!("{} not found in FLA", name))?;
-    let mut s = String::new();
-    entry.read_to_string(&mut s)?;
-    Ok(s)
-}
-
-fn read_zip_entry_optional(
-    archive: &mut ZipArchive<fs::File>,
-    name: &str,
-) -> Result<Option<String>> {
-    match archive.by_name(name) {
-        Ok(mut entry) => {
-            let mut s = String::new();
-            entry.read_to_string(&mut s)?;
-            Ok(Some(s))
-        }
-        Err(ZipError::FileNotFound) => Ok(None),
-        Err(e) => Err(e.into()),
+    /// PAM resource shown by a timeline element, whether it is a sprite, and the
+    /// matrix to apply after the element's own
+    fn resolve(&mut self, item: &ElementItem) -> Option<(i32, bool, Matrix)> {
+        let unscale = Matrix::scale(1.0 / self.bitmap_scale, 1.0 / self.bitmap_scale);
+        let (bitmap, placement) = match item {
+            ElementItem::Symbol(name) => {
+                if let Some(&index) = self.image_symbols.get(name) {
+                    return Some((index as i32, false, Matrix::IDENTITY));
+                }
+                if let Some(&index) = self.sprite_symbols.get(name) {
+                    return Some((index as i32, true, Matrix::IDENTITY));
+                }
+                let Some((bitmap, matrix)) = self.sources.get(name).cloned() else {
+                    eprintln!("Warning: library item {} not found", name);
+                    return None;
+                };
+                (bitmap, Matrix(matrix))
+            }
+            ElementItem::Bitmap(bitmap) => (bitmap.clone(), Matrix::IDENTITY),
+        };
+        let index = match self.bitmap_images.get(&bitmap) {
+            Some(&index) => index,
+            None => {
+                let info = self.bitmap_image(&bitmap, Matrix::IDENTITY.0.to_vec());
+                self.images.push(info);
+                self.bitmap_images.insert(bitmap, self.images.len() - 1);
+                self.images.len() - 1
+            }
+        };
+        Some((index as i32, false, placement.then(&unscale)))
     }
 }
 
-fn read_pam_sidecar(archive: &mut ZipArchive<fs::File>) -> Result<Option<PamSidecar>> {
-    for name in [
-        "PAM.sidecar.json",
-        "pam.sidecar.json",
-        "PAM.sidecar",
-        "pam.sidecar",
-    ] {
-        if let Some(text) = read_zip_entry_optional(archive, name)? {
-            if let Ok(sidecar) = serde_json::from_str::<PamSidecar>(&text) {
-                return Ok(Some(sidecar));
-            }
+/// Labels, `stop()` and `fscommand` calls of a timeline's keyframes
+fn annotate_frames(timeline: &Timeline, frames: &mut [FrameInfo]) {
+    let stop_regex = Regex::new(r"stop\(\)").unwrap();
+    let fs_regex = Regex::new(r#"fscommand\("([^"]+)"(?:,\s*"([^"]*)")?\)"#).unwrap();
+    for keyframe in timeline.layers.iter().flat_map(|l| l.frames.iter()) {
+        let Some(frame) = frames.get_mut(keyframe.index) else {
+            continue;
+        };
+        if let Some(label) = &keyframe.label
+            && keyframe.label_type.as_deref().is_none_or(|t| t == "name")
+            && frame.label.is_none()
+        {
+            frame.label = Some(label.clone());
+        }
+        if stop_regex.is_match(&keyframe.script) {
+            frame.stop = true;
+        }
+        for caps in fs_regex.captures_iter(&keyframe.script) {
+            let cmd = caps[1].to_string();
+            let arg = caps.get(2).map_or("", |m| m.as_str()).to_string();
+            frame.command.push([cmd, arg]);
         }
     }
-    Ok(None)
 }
 
-fn parse_sprite_xml(xml_str: &str) -> Result<SpriteInfo> {
-    let mut reader = Reader::from_str(&xml_str);
-    reader.config_mut().trim_text(true);
+/// State of one PAM layer on one frame
+#[derive(Clone, PartialEq)]
+struct LayerState {
+    resource: i32,
+    sprite: bool,
+    additive: bool,
+    transform: [f64; 6],
+    color: [f64; 4],
+    /// Nested sprite frame to restart from, on the first frame of a keyframe
+    restart: Option<i32>,
+}
 
-    let mut sp = SpriteInfo {
+/// Turn a timeline into PAM frames. Every element slot of every layer becomes one
+/// PAM layer, numbered from the bottom; tweens are baked into per-frame changes.
+fn build_sprite(timeline: &Timeline, frame_rate: f64, resources: &mut Resources) -> SpriteInfo {
+    let frame_count = timeline.frame_count();
+    let mut sprite = SpriteInfo {
         name: None,
         description: None,
-        frame_rate: 30.0,
-        work_area: [0, 0],
+        frame_rate,
+        work_area: [0, frame_count as i32],
         frame: Vec::new(),
     };
+    sprite.frame.resize_with(frame_count, Default::default);
 
-    let mut total_frames = 0;
-
-    #[derive(Clone, PartialEq, Debug)]
-    struct ElementState {
-        resource: i32,
-        transform: Vec<f64>,
-        color: Vec<f64>,
-        first_frame: Option<i32>,
-    }
-
-    let mut state_map: HashMap<i32, Vec<Option<ElementState>>> = HashMap::new();
+    annotate_frames(timeline, &mut sprite.frame);
 
-    let mut current_z_index: Option<i32> = None;
-    let mut current_start_idx = 0;
-    let mut current_duration = 1;
-    let mut current_state: Option<ElementState> = None;
+    let mut tracks: Vec<Vec<Option<LayerState>>> = Vec::new();
+    for layer in timeline.layers.iter().rev() {
+        if !layer.has_content() {
+            continue;
+        }
 
-    loop {
-        match reader.read_event() {
-            Ok(Event::Start(ref e)) | Ok(Event::Empty(ref e)) => match e.name().as_ref() {
-                b"DOMLayer" => {
-                    for attr in e.attributes() {
-                        let attr = attr?;
-                        if attr.key.as_ref() == b"name" {
-                            let name_str = String::from_utf8_lossy(&attr.value);
-                            current_z_index = name_str.parse().ok();
-                        }
-                    }
-                }
-                b"DOMFrame" => {
-                    for attr in e.attributes() {
-                        let attr = attr?;
-                        if attr.key.as_ref() == b"index" {
-                            current_start_idx =
-                                String::from_utf8_lossy(&attr.value).parse().unwrap_or(0);
-                        } else if attr.key.as_ref() == b"duration" {
-                            current_duration =
-                                String::from_utf8_lossy(&attr.value).parse().unwrap_or(1);
-                        }
-                    }
-                    total_frames = total_frames.max(current_start_idx + current_duration);
-                }
-                b"DOMSymbolInstance" => {
-                    let mut resource_id = -1;
-                    let mut first_frame = None;
-                    for attr in e.attributes() {
-                        let attr = attr?;
-                        if attr.key.as_ref() == b"libraryItemName" {
-                            let lib_item = String::from_utf8_lossy(&attr.value);
-                            if let Some(stripped) = lib_item.strip_prefix("image/image_") {
-                                resource_id = stripped.parse::<i32>().unwrap_or(1) - 1;
-                            } else if let Some(stripped) = lib_item.strip_prefix("sprite/sprite_") {
-                                resource_id = stripped.parse::<i32>().unwrap_or(1) - 1 + 10000;
-                            }
-                        } else if attr.key.as_ref() == b"firstFrame" {
-                            first_frame =
-                                Some(String::from_utf8_lossy(&attr.value).parse().unwrap_or(0));
-                        }
-                    }
-                    if resource_id >= 0 {
-                        current_state = Some(ElementState {
-                            resource: resource_id,
-                            transform: vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
-                            color: vec![1.0, 1.0, 1.0, 1.0],
-                            first_frame,
-                        });
-                    }
-                }
-                b"Matrix" => {
-                    if let Some(state) = &mut current_state {
-                        for attr in e.attributes() {
-                            let attr = attr?;
-                            let val = String::from_utf8_lossy(&attr.value).parse().unwrap_or(0.0);
-                            match attr.key.as_ref() {
-                                b"a" => state.transform[0] = val,
-                                b"b" => state.transform[1] = val,
-                                b"c" => state.transform[2] = val,
-                                b"d" => state.transform[3] = val,
-                                b"tx" => state.transform[4] = val,
-                                b"ty" => state.transform[5] = val,
-                                _ => {}
-                            }
-                        }
-                    }
-                }
-                b"Color" => {
-                    if let Some(state) = &mut current_state {
-                        let mut rgba_mult = [1.0, 1.0, 1.0, 1.0];
-                        let mut rgba_offset = vec![0.0, 0.0, 0.0, 0.0];
-                        let mut has_offset = false;
-                        for attr in e.attributes() {
-                            let attr = attr?;
-                            let val = String::from_utf8_lossy(&attr.value).parse().unwrap_or(0.0);
-                            match attr.key.as_ref() {
-                                b"redMultiplier" => rgba_mult[0] = val,
-                                b"greenMultiplier" => rgba_mult[1] = val,
-                                b"blueMultiplier" => rgba_mult[2] = val,
-                                b"alphaMultiplier" => rgba_mult[3] = val,
-                                b"redOffset" => {
-                                    rgba_offset[0] = val;
-                                    has_offset = true;
-                                }
-                                b"greenOffset" => {
-                                    rgba_offset[1] = val;
-                                    has_offset = true;
-                                }
-                                b"blueOffset" => {
-                                    rgba_offset[2] = val;
-                                    has_offset = true;
-                                }
-                                b"alphaOffset" => {
-                                    rgba_offset[3] = val;
-                                    has_offset = true;
-                                }
-                                _ => {}
-                            }
-                        }
-                        state.color = vec![rgba_mult[0], rgba_mult[1], rgba_mult[2], rgba_mult[3]];
-                        if has_offset {
-                            state.color.extend(rgba_offset);
-                        }
-                    }
-                }
-                _ => {}
-            },
-            Ok(Event::End(ref e)) => match e.name().as_ref() {
-                b"DOMFrame" => {
-                    if let Some(z) = current_z_index {
-                        let tl = state_map.entry(z).or_default();
-                        let end_idx = current_start_idx + current_duration;
-                        if tl.len() < end_idx {
-                            tl.resize(end_idx, None);
-                        }
-                        if let Some(state) = &current_state {
-                            for item in tl.iter_mut().take(end_idx).skip(current_start_idx) {
-                                *item = Some(state.clone());
-                            }
-                        }
-                    }
-                    current_state = None;
-                }
-                b"DOMLayer" => {
-                    current_z_index = None;
+        let slots = layer
+            .frames
+            .iter()
+            .map(|f| f.elements.len())
+            .max()
+            .unwrap_or(0);
+        for slot in 0..slots {
+            let mut track = vec![None; frame_count];
+            for (k, keyframe) in layer.frames.iter().enumerate() {
+                let Some(element) = keyframe.elements.get(slot) else {
+                    continue;
+                };
+                let Some((resource, is_sprite, placement)) = resources.resolve(&element.item)
+                else {
+                    continue;
+                };
+                let next = layer.frames.get(k + 1);
+                for offset in 0..keyframe.duration {
+                    let Some(state) = track.get_mut(keyframe.index + offset) else {
+                        break;
+                    };
+                    let Some((matrix, color)) =
+                        bake_element(keyframe, slot, next, offset, frame_rate)
+                    else {
+                        continue;
+                    };
+                    *state = Some(LayerState {
+                        resource,
+                        sprite: is_sprite,
+                        additive: element.additive,
+                        transform: Matrix(matrix).then(&placement).0,
+                        color,
+                        restart: element
+                            .first_frame
+                            .filter(|&f| f > 0 && offset == 0 && is_sprite),
+                    });
                 }
-                _ => {}
-            },
-            Ok(Event::Eof) => break,
-            Err(_) => break, // Ignore errors, handle cleanly instead of panicking on bad XML nodes
-            _ => {}
+            }
+            tracks.push(track);
         }
     }
 
-    sp.frame.resize_with(total_frames, Default::default);
-
-    let mut z_keys: Vec<i32> = state_map.keys().copied().collect();
-    z_keys.sort();
-
-    for &z in &z_keys {
-        let tl = &state_map[&z];
-        let mut prev_state: Option<ElementState> = None;
-        let mut virtual_prev: Option<ElementState> = None;
-
-        for t in 0..total_frames {
-            let curr = if t < tl.len() { tl[t].clone() } else { None };
-
-            match (&prev_state, &curr) {
+    let white = [1.0; 4];
+    for (index, track) in tracks.iter().enumerate() {
+        let index = index as i32;
+        let mut previous: Option<&LayerState> = None;
+        for (frame, state) in sprite.frame.iter_mut().zip(track) {
+            let same_element = |p: &LayerState, c: &LayerState| {
+                p.resource == c.resource && p.sprite == c.sprite && p.additive == c.additive
+            };
+            let (moved_from, recolored_from) = match (previous, state) {
                 (Some(_), None) => {
-                    sp.frame[t].remove.push(RemovesInfo { index: z });
-                    virtual_prev = None;
+                    frame.remove.push(RemovesInfo { index });
+                    (None, None)
                 }
-                (None, Some(c)) => {
-                    sp.frame[t].append.push(AddsInfo {
-                        index: z,
-                        resource: c.resource % 10000,
+                (Some(p), Some(c)) if same_element(p, c) => (Some(p.transform), Some(p.color)),
+                (p, Some(c)) => {
+                    if p.is_some() {
+                        frame.remove.push(RemovesInfo { index });
+                    }
+                    frame.append.push(AddsInfo {
+                        index,
                         name: None,
-                        sprite: c.resource >= 10000,
-                        additive: false,
+                        resource: c.resource,
+                        sprite: c.sprite,
+                        additive: c.additive,
                         preload_frame: 0,
                         time_scale: 1.0,
                     });
-                    virtual_prev = Some(ElementState {
-                        resource: c.resource,
-                        transform: vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
-                        color: vec![1.0, 1.0, 1.0, 1.0],
-                        first_frame: None,
-                    });
+                    (Some(Matrix::IDENTITY.0), Some(white))
                 }
-                (Some(p), Some(c)) => {
-                    if p.resource != c.resource {
-                        sp.frame[t].remove.push(RemovesInfo { index: z });
-                        sp.frame[t].append.push(AddsInfo {
-                            index: z,
-                            resource: c.resource % 10000,
-                            name: None,
-                            sprite: c.resource >= 10000,
-                            additive: false,
-                            preload_frame: 0,
-                            time_scale: 1.0,
-                        });
-                        virtual_prev = Some(ElementState {
-                            resource: c.resource,
-                            transform: vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0],
-                            color: vec![1.0, 1.0, 1.0, 1.0],
-                            first_frame: None,
-                        });
-                    }
-                }
-                _ => {}
+                (None, None) => (None, None),
+            };
+            if let (Some(c), Some(from), Some(from_color)) = (state, moved_from, recolored_from)
+                && (c.transform != from || c.color != from_color || c.restart.is_some())
+            {
+                frame.change.push(MovesInfo {
+                    index,
+                    transform: c.transform.to_vec(),
+                    color: (c.color != from_color).then_some(c.color),
+                    source_rectangle: None,
+                    sprite_frame_number: c.restart.unwrap_or(0),
+                });
             }
+            previous = state.as_ref();
+        }
+    }
 
-            if let Some(c) = &curr {
-                let vp = virtual_prev.unwrap();
-                let transform_change = vp.transform != c.transform;
-                let color_change = vp.color != c.color;
-                let frame_change = vp.first_frame != c.first_frame;
-
-                if transform_change || color_change || frame_change {
-                    let mut color_arr = None;
-                    if c.color != vec![1.0, 1.0, 1.0, 1.0] {
-                        let r = *c.color.first().unwrap_or(&1.0);
-                        let g = *c.color.get(1).unwrap_or(&1.0);
-                        let b = *c.color.get(2).unwrap_or(&1.0);
-                        let a = *c.color.get(3).unwrap_or(&1.0);
-                        color_arr = Some([r, g, b, a]);
-                    }
-                    sp.frame[t].change.push(MovesInfo {
-                        index: z,
-                        transform: c.transform.clone(),
-                        color: color_arr,
-                        source_rectangle: None,
-                        sprite_frame_number: c.first_frame.unwrap_or(0),
-                    });
-                }
+    sprite
+}
 
-                virtual_prev = Some(c.clone());
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Severity;
+    use std::fs;
+    use std::io::Write;
+
+    /// A document with no symbols and no sidecar: bitmaps sit straight on the
+    /// root timeline, one of them tweened
+    const DOCUMENT: &str = r#"<DOMDocument width="390" height="390" frameRate="30">
+  <media>
+    <DOMBitmapItem name="media/head" href="head.png" frameRight="1200" frameBottom="800"/>
+    <DOMBitmapItem name="media/arm" href="arm.png" frameRight="400" frameBottom="400"/>
+  </media>
+  <timelines>
+    <DOMTimeline name="animation">
+      <layers>
+        <DOMLayer name="arm">
+          <frames>
+            <DOMFrame index="0" duration="2">
+              <elements>
+                <DOMBitmapInstance libraryItemName="media/arm">
+                  <matrix><Matrix tx="30" ty="20"/></matrix>
+                </DOMBitmapInstance>
+              </elements>
+            </DOMFrame>
+          </frames>
+        </DOMLayer>
+        <DOMLayer name="head">
+          <frames>
+            <DOMFrame index="0" duration="2" name="idle" labelType="name" tweenType="motion">
+              <actionscript><script><![CDATA[stop();]]></script></actionscript>
+              <elements>
+                <DOMBitmapInstance libraryItemName="media/head"/>
+              </elements>
+            </DOMFrame>
+            <DOMFrame index="2">
+              <elements>
+                <DOMBitmapInstance libraryItemName="media/head">
+                  <matrix><Matrix tx="20"/></matrix>
+                </DOMBitmapInstance>
+              </elements>
+            </DOMFrame>
+          </frames>
+        </DOMLayer>
+      </layers>
+    </DOMTimeline>
+  </timelines>
+</DOMDocument>"#;
+
+    fn assert_library_less(pam: &PamInfo) {
+        assert_eq!(pam.frame_rate, 30);
+        assert_eq!(pam.size, [390.0, 390.0]);
+        assert!(pam.sprite.is_empty());
+        let images: Vec<_> = pam
+            .image
+            .iter()
+            .map(|i| (i.name.as_str(), i.size))
+            .collect();
+        assert_eq!(images, [("head", [60, 40]), ("arm", [20, 20])]);
+
+        let frames = &pam.main_sprite.frame;
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[0].label.as_deref(), Some("idle"));
+        assert!(frames[0].stop);
+        let appends: Vec<_> = frames[0]
+            .append
+            .iter()
+            .map(|a| (a.index, a.resource))
+            .collect();
+        assert_eq!(appends, [(0, 0), (1, 1)]);
+
+        // The bottom layer's classic tween is baked into a move per frame
+        let head_x: Vec<_> = frames
+            .iter()
+            .map(|f| {
+                f.change
+                    .iter()
+                    .find(|c| c.index == 0)
+                    .map(|c| c.transform[4])
+            })
+            .collect();
+        assert_eq!(head_x, [None, Some(10.0), Some(20.0)]);
+        assert_eq!(frames[2].remove.len(), 1);
+        assert_eq!(frames[2].remove[0].index, 1);
+        assert!(
+            pam.validate()
+                .iter()
+                .all(|i| i.severity == Severity::Warning)
+        );
+    }
 
-            prev_state = curr;
-        }
+    #[test]
+    fn test_convert_library_less_xfl_folder() {
+        let dir = std::env::temp_dir().join(format!("xfl_folder_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("DOMDocument.xml"), DOCUMENT).unwrap();
+        fs::write(dir.join("animation.xfl"), "PROXY-CS5").unwrap();
+
+        let from_folder = convert_from_fla(&dir, 1200);
+        let from_xfl = convert_from_fla(&dir.join("animation.xfl"), 1200);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_library_less(&from_folder.unwrap());
+        assert_library_less(&from_xfl.unwrap());
     }
 
-    Ok(sp)
+    #[test]
+    fn test_convert_library_less_fla() {
+        let path = std::env::temp_dir().join(format!("library_less_{}.fla", std::process::id()));
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        zip.start_file("DOMDocument.xml", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        zip.write_all(DOCUMENT.as_bytes()).unwrap();
+        zip.finish().unwrap();
+
+        let pam = convert_from_fla(&path, 1200);
+        fs::remove_file(&path).unwrap();
+
+        assert_library_less(&pam.unwrap());
+    }
 }
@@ -1,11 +1,13 @@
 use super::xml_writer::XmlWriter;
 use crate::PamInfo;
+use crate::media::{MediaSource, media_name};
 use crate::types::{ImageInfo, SpriteInfo};
 use anyhow::{Context, Result};
+use image::{ImageFormat, RgbaImage};
 use serde::Serialize;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::io::Write;
+use std::io::{Cursor, Write};
 use std::path::Path;
 use zip::ZipWriter;
 use zip::write::SimpleFileOptions;
@@ -24,7 +26,19 @@ struct PamSidecar {
     image_names: Vec<String>,
 }
 
-pub fn convert_to_fla(pam: &PamInfo, output_path: &Path, resolution: i32) -> Result<()> {
+/// Write `pam` as a zipped XFL document. With a `media` source, the image bitmaps are
+/// embedded under `LIBRARY/media/`; otherwise they are only referenced by name.
+pub fn convert_to_fla(
+    pam: &PamInfo,
+    output_path: &Path,
+    resolution: i32,
+    media: Option<&MediaSource>,
+) -> Result<()> {
+    let bitmaps = match media {
+        Some(media) => media.load(pam),
+        None => vec![None; pam.image.len()],
+    };
+
     if let Some(parent) = output_path.parent() {
         fs::create_dir_all(parent)?;
     }
@@ -40,6 +54,24 @@ pub fn convert_to_fla(pam: &PamInfo, output_path: &Path, resolution: i32) -> Res
     zip.add_directory("LIBRARY/image/", options)?;
     zip.add_directory("LIBRARY/sprite/", options)?;
 
+    // Embed bitmaps, once per media file
+    let mut embedded = HashSet::new();
+    for (image, bitmap) in pam.image.iter().zip(&bitmaps) {
+        let Some(bitmap) = bitmap else {
+            continue;
+        };
+        let name = media_name(image);
+        if !embedded.insert(name) {
+            continue;
+        }
+        let mut png = Vec::new();
+        bitmap
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .with_context(|| format!("Failed to encode bitmap {}", name))?;
+        zip.start_file(format!("LIBRARY/media/{}.png", name), options)?;
+        zip.write_all(&png)?;
+    }
+
     // Write source and image documents
     for (i, image) in pam.image.iter().enumerate() {
         let source_data = write_source_document(i, image, resolution)?;
@@ -64,7 +96,7 @@ pub fn convert_to_fla(pam: &PamInfo, output_path: &Path, resolution: i32) -> Res
     zip.write_all(&main_data)?;
 
     // DOMDocument.xml
-    let dom_data = write_dom_document(pam).context("DOMDocument generation failed")?;
+    let dom_data = write_dom_document(pam, &bitmaps).context("DOMDocument generation failed")?;
     zip.start_file("DOMDocument.xml", options)?;
     zip.write_all(&dom_data)?;
 
@@ -89,10 +121,7 @@ fn write_source_document(
     let mut w = XmlWriter::new(Vec::new());
 
     let name = format!("source/source_{}", index + 1);
-    let media_name = format!(
-        "media/{}",
-        image.name.split('|').next().unwrap_or(&image.name)
-    );
+    let media_name = format!("media/{}", media_name(image));
 
     w.start_element(
         "DOMSymbolItem",
@@ -451,7 +480,7 @@ fn decode_frame_node_list(
     frame_node_list
 }
 
-fn write_dom_document(pam: &PamInfo) -> Result<Vec<u8>> {
+fn write_dom_document(pam: &PamInfo, bitmaps: &[Option<RgbaImage>]) -> Result<Vec<u8>> {
     let mut w = XmlWriter::new(Vec::new());
 
     w.start_element(
@@ -495,17 +524,47 @@ fn write_dom_document(pam: &PamInfo) -> Result<Vec<u8>> {
     w.end_element("folders")?;
 
     w.start_element("media", &[])?;
-    for image in &pam.image {
-        let name = image.name.split('|').next().unwrap_or(&image.name);
-        w.write_element(
-            "DOMBitmapItem",
-            &[
-                ("name", &format!("media/{}", name)),
-                ("href", &format!("media/{}.png", name)),
-                ("bitmapDataHRef", &format!("media/{}.png", name)),
-            ],
-            None,
-        )?;
+    let mut written = HashSet::new();
+    for (image, bitmap) in pam.image.iter().zip(bitmaps) {
+        let name = media_name(image);
+        if !written.insert(name) {
+            continue;
+        }
+        let item_name = format!("media/{}", name);
+        let href = format!("media/{}.png", name);
+        match bitmap {
+            Some(bitmap) => {
+                // Embedded PNG: Animate reads it from LIBRARY/media, sizes are in twips
+                let external = format!("./LIBRARY/{}", href);
+                let right = (bitmap.width() * 20).to_string();
+                let bottom = (bitmap.height() * 20).to_string();
+                w.write_element(
+                    "DOMBitmapItem",
+                    &[
+                        ("name", &item_name),
+                        ("href", &href),
+                        ("sourceExternalFilepath", &external),
+                        ("allowSmoothing", "true"),
+                        ("useImportedJPEGData", "false"),
+                        ("compressionType", "lossless"),
+                        ("frameRight", &right),
+                        ("frameBottom", &bottom),
+                    ],
+                    None,
+                )?;
+            }
+            None => {
+                w.write_element(
+                    "DOMBitmapItem",
+                    &[
+                        ("name", &item_name),
+                        ("href", &href),
+                        ("bitmapDataHRef", &href),
+                    ],
+                    None,
+                )?;
+            }
+        }
     }
     w.end_element("media")?;
 
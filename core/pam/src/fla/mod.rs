@@ -1,5 +1,7 @@
 pub mod decoder;
 pub mod encoder;
+pub mod source;
+pub mod timeline;
 pub mod xml_writer;
 
 pub use decoder::convert_from_fla;
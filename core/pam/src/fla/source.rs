@@ -0,0 +1,90 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+/// Files of an XFL document: a zipped `.fla` or an uncompressed folder holding
+/// `DOMDocument.xml`. Entry names are relative to the document root and use `/`.
+pub enum XflSource {
+    Zip(ZipArchive<fs::File>),
+    Directory(PathBuf),
+}
+
+impl XflSource {
+    /// Open a `.fla` archive, an XFL folder, or the `.xfl` file inside such a folder
+    pub fn open(path: &Path) -> Result<Self> {
+        if path.is_dir() {
+            return Ok(XflSource::Directory(path.to_path_buf()));
+        }
+        let is_xfl = path
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("xfl"));
+        if is_xfl {
+            let dir = path.parent().unwrap_or(Path::new("."));
+            return Ok(XflSource::Directory(dir.to_path_buf()));
+        }
+        let file = fs::File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+        let archive = ZipArchive::new(file).context("Failed to read FLA archive")?;
+        Ok(XflSource::Zip(archive))
+    }
+
+    pub fn read_string(&mut self, name: &str) -> Result<String> {
+        let bytes = self
+            .read_optional(name)?
+            .with_context(|| format!("{} not found in FLA", name))?;
+        Ok(String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    pub fn read_optional(&mut self, name: &str) -> Result<Option<Vec<u8>>> {
+        match self {
+            XflSource::Zip(archive) => match archive.by_name(name) {
+                Ok(mut entry) => {
+                    let mut data = Vec::new();
+                    entry.read_to_end(&mut data)?;
+                    Ok(Some(data))
+                }
+                Err(ZipError::FileNotFound) => Ok(None),
+                Err(e) => Err(e.into()),
+            },
+            XflSource::Directory(root) => {
+                let path = root.join(name);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                let data = fs::read(&path).with_context(|| format!("Failed to read {:?}", path))?;
+                Ok(Some(data))
+            }
+        }
+    }
+
+    /// Names of every file in the document
+    pub fn entries(&mut self) -> Result<Vec<String>> {
+        match self {
+            XflSource::Zip(archive) => Ok(archive
+                .file_names()
+                .filter(|name| !name.ends_with('/'))
+                .map(str::to_string)
+                .collect()),
+            XflSource::Directory(root) => {
+                let mut names = Vec::new();
+                collect_files(root, "", &mut names)?;
+                Ok(names)
+            }
+        }
+    }
+}
+
+fn collect_files(dir: &Path, prefix: &str, names: &mut Vec<String>) -> Result<()> {
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &format!("{}/", name), names)?;
+        } else {
+            names.push(name);
+        }
+    }
+    Ok(())
+}
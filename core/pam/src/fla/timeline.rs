@@ -0,0 +1,671 @@
+use anyhow::{Result, bail};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+const IDENTITY: [f64; 6] = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// A `DOMTimeline` of the document or of a library symbol
+#[derive(Debug, Default)]
+pub struct Timeline {
+    pub name: String,
+    /// In document order, top layer first
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Debug, Default)]
+pub struct Layer {
+    pub name: String,
+    pub layer_type: Option<String>,
+    pub frames: Vec<KeyFrame>,
+}
+
+#[derive(Debug)]
+pub struct KeyFrame {
+    pub index: usize,
+    pub duration: usize,
+    pub label: Option<String>,
+    pub label_type: Option<String>,
+    pub script: String,
+    pub tween: Tween,
+    /// Bottom element first
+    pub elements: Vec<Element>,
+}
+
+#[derive(Debug)]
+pub enum Tween {
+    None,
+    /// Classic tween towards the next keyframe of the layer, eased by `acceleration`
+    /// (-100 to 100) and turning as `rotate` asks
+    Classic {
+        acceleration: f64,
+        rotate: Rotate,
+    },
+    /// Motion object tween: property curves relative to the keyframe's element
+    Motion(MotionCurves),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Rotate {
+    Auto,
+    Clockwise(u32),
+    CounterClockwise(u32),
+}
+
+/// `AnimationCore` curves of a motion object tween, keyed by property id
+#[derive(Debug, Default)]
+pub struct MotionCurves {
+    /// Ticks per second of the key times
+    pub time_scale: f64,
+    pub properties: HashMap<String, Vec<(f64, f64)>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Element {
+    pub item: ElementItem,
+    pub matrix: [f64; 6],
+    /// Pivot of tween rotation and scaling, in the element's own coordinates
+    pub transformation_point: [f64; 2],
+    pub color: [f64; 4],
+    pub first_frame: Option<i32>,
+    pub additive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ElementItem {
+    Symbol(String),
+    Bitmap(String),
+}
+
+impl Timeline {
+    pub fn frame_count(&self) -> usize {
+        self.layers
+            .iter()
+            .flat_map(|l| l.frames.iter())
+            .map(|f| f.index + f.duration)
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.layers
+            .iter()
+            .filter(|l| l.has_content())
+            .flat_map(|l| l.frames.iter())
+            .flat_map(|f| f.elements.iter())
+    }
+}
+
+impl Layer {
+    /// Guide and folder layers are never drawn; mask shapes have no PAM equivalent
+    pub fn has_content(&self) -> bool {
+        !matches!(
+            self.layer_type.as_deref(),
+            Some("guide") | Some("folder") | Some("mask")
+        )
+    }
+}
+
+/// Parse every `DOMTimeline` of an XFL document or symbol file
+pub fn parse_timelines(xml: &str) -> Result<Vec<Timeline>> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+
+    let mut timelines: Vec<Timeline> = Vec::new();
+    let mut stack: Vec<Vec<u8>> = Vec::new();
+    let mut frame: Option<KeyFrame> = None;
+    let mut element: Option<Element> = None;
+    let mut property: Option<String> = None;
+
+    loop {
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => bail!(
+                "Error parsing XFL at position {}: {:?}",
+                reader.buffer_position(),
+                e
+            ),
+        };
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                let name = e.name().as_ref().to_vec();
+                let parent = stack.last().map(Vec::as_slice);
+                match name.as_slice() {
+                    b"DOMTimeline" => timelines.push(Timeline {
+                        name: attr(e, b"name").unwrap_or_default(),
+                        layers: Vec::new(),
+                    }),
+                    b"DOMLayer" => {
+                        if let Some(timeline) = timelines.last_mut() {
+                            timeline.layers.push(Layer {
+                                name: attr(e, b"name").unwrap_or_default(),
+                                layer_type: attr(e, b"layerType"),
+                                frames: Vec::new(),
+                            });
+                        }
+                    }
+                    b"DOMFrame" => frame = Some(parse_frame(e)),
+                    b"DOMSymbolInstance" | b"DOMBitmapInstance"
+                        if matches!(parent, Some(b"elements") | Some(b"members")) =>
+                    {
+                        let library_name = attr(e, b"libraryItemName").unwrap_or_default();
+                        element = Some(Element {
+                            item: if name == b"DOMSymbolInstance" {
+                                ElementItem::Symbol(library_name)
+                            } else {
+                                ElementItem::Bitmap(library_name)
+                            },
+                            matrix: IDENTITY,
+                            transformation_point: [0.0; 2],
+                            color: [1.0; 4],
+                            first_frame: attr(e, b"firstFrame").and_then(|v| v.parse().ok()),
+                            additive: attr(e, b"blendMode").as_deref() == Some("add"),
+                        });
+                    }
+                    b"Matrix" if parent == Some(b"matrix") && in_element(&stack) => {
+                        if let Some(element) = &mut element {
+                            element.matrix = [
+                                attr_f64(e, b"a").unwrap_or(1.0),
+                                attr_f64(e, b"b").unwrap_or(0.0),
+                                attr_f64(e, b"c").unwrap_or(0.0),
+                                attr_f64(e, b"d").unwrap_or(1.0),
+                                attr_f64(e, b"tx").unwrap_or(0.0),
+                                attr_f64(e, b"ty").unwrap_or(0.0),
+                            ];
+                        }
+                    }
+                    b"Point" if parent == Some(b"transformationPoint") && in_element(&stack) => {
+                        if let Some(element) = &mut element {
+                            element.transformation_point = [
+                                attr_f64(e, b"x").unwrap_or(0.0),
+                                attr_f64(e, b"y").unwrap_or(0.0),
+                            ];
+                        }
+                    }
+                    b"Color" if parent == Some(b"color") && in_element(&stack) => {
+                        if let Some(element) = &mut element {
+                            let keys: [&[u8]; 4] = [
+                                b"redMultiplier",
+                                b"greenMultiplier",
+                                b"blueMultiplier",
+                                b"alphaMultiplier",
+                            ];
+                            for (value, key) in element.color.iter_mut().zip(keys) {
+                                *value = attr_f64(e, key).unwrap_or(1.0);
+                            }
+                        }
+                    }
+                    b"AnimationCore" => {
+                        if let Some(frame) = &mut frame {
+                            frame.tween = Tween::Motion(MotionCurves {
+                                time_scale: attr_f64(e, b"TimeScale").unwrap_or(1000.0),
+                                properties: HashMap::new(),
+                            });
+                        }
+                    }
+                    b"Property" => property = attr(e, b"id"),
+                    b"Keyframe" => {
+                        if let (Some(frame), Some(property)) = (&mut frame, &property)
+                            && let Tween::Motion(curves) = &mut frame.tween
+                        {
+                            // anchor is "<time>,<value>"; key times come from timevalue
+                            let value = attr(e, b"anchor")
+                                .and_then(|a| a.split(',').nth(1).and_then(|v| v.parse().ok()));
+                            let time = attr_f64(e, b"timevalue");
+                            if let (Some(time), Some(value)) = (time, value) {
+                                curves
+                                    .properties
+                                    .entry(property.clone())
+                                    .or_default()
+                                    .push((time, value));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if is_empty {
+                    end_element(
+                        &name,
+                        &mut timelines,
+                        &mut frame,
+                        &mut element,
+                        &mut property,
+                    );
+                } else {
+                    stack.push(name);
+                }
+            }
+            Event::End(ref e) => {
+                stack.pop();
+                end_element(
+                    e.name().as_ref(),
+                    &mut timelines,
+                    &mut frame,
+                    &mut element,
+                    &mut property,
+                );
+            }
+            Event::Text(ref e) if stack.last().map(Vec::as_slice) == Some(b"script") => {
+                if let Some(frame) = &mut frame {
+                    frame.script.push_str(&String::from_utf8_lossy(e.as_ref()));
+                }
+            }
+            Event::CData(ref e) if stack.last().map(Vec::as_slice) == Some(b"script") => {
+                if let Some(frame) = &mut frame {
+                    frame.script.push_str(&String::from_utf8_lossy(e.as_ref()));
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(timelines)
+}
+
+fn end_element(
+    name: &[u8],
+    timelines: &mut [Timeline],
+    frame: &mut Option<KeyFrame>,
+    element: &mut Option<Element>,
+    property: &mut Option<String>,
+) {
+    match name {
+        b"DOMSymbolInstance" | b"DOMBitmapInstance" => {
+            if let (Some(frame), Some(element)) = (frame.as_mut(), element.take()) {
+                frame.elements.push(element);
+            }
+        }
+        b"DOMFrame" => {
+            let layer = timelines.last_mut().and_then(|t| t.layers.last_mut());
+            if let (Some(layer), Some(frame)) = (layer, frame.take()) {
+                layer.frames.push(frame);
+            }
+        }
+        b"Property" => *property = None,
+        _ => {}
+    }
+}
+
+fn in_element(stack: &[Vec<u8>]) -> bool {
+    stack.len() >= 2
+        && matches!(
+            stack[stack.len() - 2].as_slice(),
+            b"DOMSymbolInstance" | b"DOMBitmapInstance"
+        )
+}
+
+fn parse_frame(e: &BytesStart) -> KeyFrame {
+    let tween = match attr(e, b"tweenType").as_deref() {
+        Some("motion") => {
+            let times = attr(e, b"motionTweenRotateTimes")
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(0);
+            Tween::Classic {
+                acceleration: attr_f64(e, b"acceleration").unwrap_or(0.0),
+                rotate: match attr(e, b"motionTweenRotate").as_deref() {
+                    Some("clockwise") => Rotate::Clockwise(times),
+                    Some("counter-clockwise") => Rotate::CounterClockwise(times),
+                    _ => Rotate::Auto,
+                },
+            }
+        }
+        // The curves are filled in from the frame's AnimationCore
+        Some("motion object") => Tween::Motion(MotionCurves::default()),
+        _ => Tween::None,
+    };
+    KeyFrame {
+        index: attr(e, b"index").and_then(|v| v.parse().ok()).unwrap_or(0),
+        duration: attr(e, b"duration")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(1)
+            .max(1),
+        label: attr(e, b"name"),
+        label_type: attr(e, b"labelType"),
+        script: String::new(),
+        tween,
+        elements: Vec::new(),
+    }
+}
+
+fn attr(e: &BytesStart, key: &[u8]) -> Option<String> {
+    e.attributes()
+        .flatten()
+        .find(|a| a.key.as_ref() == key)
+        .map(|a| String::from_utf8_lossy(&a.value).into_owned())
+}
+
+fn attr_f64(e: &BytesStart, key: &[u8]) -> Option<f64> {
+    attr(e, key).and_then(|v| v.parse().ok())
+}
+
+/// Matrix and color of a keyframe's element `frame` frames after the keyframe starts,
+/// with its tween applied. `next` is the layer's following keyframe.
+pub fn bake_element(
+    keyframe: &KeyFrame,
+    slot: usize,
+    next: Option<&KeyFrame>,
+    frame: usize,
+    frame_rate: f64,
+) -> Option<([f64; 6], [f64; 4])> {
+    let element = keyframe.elements.get(slot)?;
+    let baked = match &keyframe.tween {
+        Tween::Classic {
+            acceleration,
+            rotate,
+        } => {
+            let target = next
+                .filter(|next| next.index == keyframe.index + keyframe.duration)
+                .and_then(|next| next.elements.get(slot))
+                .filter(|target| target.item == element.item);
+            match target {
+                Some(target) => {
+                    let t = frame as f64 / keyframe.duration as f64;
+                    let e = (acceleration / 100.0).clamp(-1.0, 1.0);
+                    let t = t + e * t * (1.0 - t);
+                    (
+                        tween_matrix(element, target, t, *rotate),
+                        lerp4(&element.color, &target.color, t),
+                    )
+                }
+                None => (element.matrix, element.color),
+            }
+        }
+        Tween::Motion(curves) => (
+            motion_matrix(element, curves, frame, frame_rate),
+            element.color,
+        ),
+        Tween::None => (element.matrix, element.color),
+    };
+    Some(baked)
+}
+
+/// Flash's decomposition of a matrix: scales and skew angles
+struct Decomposed {
+    scale_x: f64,
+    scale_y: f64,
+    skew_x: f64,
+    skew_y: f64,
+}
+
+fn decompose(m: &[f64; 6]) -> Decomposed {
+    let [a, b, c, d, _, _] = *m;
+    Decomposed {
+        scale_x: a.hypot(b),
+        scale_y: c.hypot(d),
+        skew_x: (-c).atan2(d),
+        skew_y: b.atan2(a),
+    }
+}
+
+/// Matrix with the decomposed linear part that maps `pivot` to `position`
+fn compose(d: &Decomposed, pivot: [f64; 2], position: [f64; 2]) -> [f64; 6] {
+    let a = d.scale_x * d.skew_y.cos();
+    let b = d.scale_x * d.skew_y.sin();
+    let c = -d.scale_y * d.skew_x.sin();
+    let dd = d.scale_y * d.skew_x.cos();
+    [
+        a,
+        b,
+        c,
+        dd,
+        position[0] - (a * pivot[0] + c * pivot[1]),
+        position[1] - (b * pivot[0] + dd * pivot[1]),
+    ]
+}
+
+fn apply(m: &[f64; 6], p: [f64; 2]) -> [f64; 2] {
+    [
+        m[0] * p[0] + m[2] * p[1] + m[4],
+        m[1] * p[0] + m[3] * p[1] + m[5],
+    ]
+}
+
+/// Angle difference in (-PI, PI]
+fn wrap_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped <= -PI {
+        wrapped + 2.0 * PI
+    } else {
+        wrapped
+    }
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+fn lerp4(a: &[f64; 4], b: &[f64; 4], t: f64) -> [f64; 4] {
+    [
+        lerp(a[0], b[0], t),
+        lerp(a[1], b[1], t),
+        lerp(a[2], b[2], t),
+        lerp(a[3], b[3], t),
+    ]
+}
+
+/// Classic tween: scales and skews are interpolated separately and the
+/// transformation point moves in a straight line
+fn tween_matrix(from: &Element, to: &Element, t: f64, rotate: Rotate) -> [f64; 6] {
+    let (a, b) = (decompose(&from.matrix), decompose(&to.matrix));
+    let shortest = wrap_angle(b.skew_y - a.skew_y);
+    let turn = match rotate {
+        Rotate::Auto => shortest,
+        Rotate::Clockwise(times) => {
+            let delta = if shortest < 0.0 {
+                shortest + 2.0 * PI
+            } else {
+                shortest
+            };
+            delta + 2.0 * PI * times as f64
+        }
+        Rotate::CounterClockwise(times) => {
+            let delta = if shortest > 0.0 {
+                shortest - 2.0 * PI
+            } else {
+                shortest
+            };
+            delta - 2.0 * PI * times as f64
+        }
+    };
+    let skew_x_delta = wrap_angle(b.skew_x - a.skew_x) + (turn - shortest);
+    let decomposed = Decomposed {
+        scale_x: lerp(a.scale_x, b.scale_x, t),
+        scale_y: lerp(a.scale_y, b.scale_y, t),
+        skew_x: a.skew_x + skew_x_delta * t,
+        skew_y: a.skew_y + turn * t,
+    };
+
+    let from_position = apply(&from.matrix, from.transformation_point);
+    let to_position = apply(&to.matrix, to.transformation_point);
+    let pivot = [
+        lerp(from.transformation_point[0], to.transformation_point[0], t),
+        lerp(from.transformation_point[1], to.transformation_point[1], t),
+    ];
+    let position = [
+        lerp(from_position[0], to_position[0], t),
+        lerp(from_position[1], to_position[1], t),
+    ];
+    compose(&decomposed, pivot, position)
+}
+
+/// Motion object tween: the property curves offset the element's position,
+/// rotation, skew and scale
+fn motion_matrix(
+    element: &Element,
+    curves: &MotionCurves,
+    frame: usize,
+    frame_rate: f64,
+) -> [f64; 6] {
+    let time = frame as f64 * curves.time_scale / frame_rate;
+    let value = |id: &str, default: f64| {
+        curves
+            .properties
+            .get(id)
+            .map_or(default, |keys| sample_curve(keys, time))
+    };
+    let start = decompose(&element.matrix);
+    let rotation = value("Rotation_Z", 0.0).to_radians();
+    let decomposed = Decomposed {
+        scale_x: start.scale_x * value("Scale_X", 100.0) / 100.0,
+        scale_y: start.scale_y * value("Scale_Y", 100.0) / 100.0,
+        skew_x: start.skew_x + rotation + value("Skew_X", 0.0).to_radians(),
+        skew_y: start.skew_y + rotation + value("Skew_Y", 0.0).to_radians(),
+    };
+    let pivot = element.transformation_point;
+    let start_position = apply(&element.matrix, pivot);
+    let position = [
+        start_position[0] + value("Motion_X", 0.0),
+        start_position[1] + value("Motion_Y", 0.0),
+    ];
+    compose(&decomposed, pivot, position)
+}
+
+/// Linear interpolation between curve keys, holding the first and last values
+fn sample_curve(keys: &[(f64, f64)], time: f64) -> f64 {
+    let Some(&(first_time, first_value)) = keys.first() else {
+        return 0.0;
+    };
+    if time <= first_time {
+        return first_value;
+    }
+    for pair in keys.windows(2) {
+        let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+        if time <= t1 {
+            return if t1 > t0 {
+                lerp(v0, v1, (time - t0) / (t1 - t0))
+            } else {
+                v1
+            };
+        }
+    }
+    keys[keys.len() - 1].1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_matrix(actual: [f64; 6], expected: [f64; 6]) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    /// Timeline of a symbol whose only layer holds `frames`
+    fn symbol(frames: &str) -> Timeline {
+        let xml = format!(
+            r#"<DOMSymbolItem name="sprite/walk">
+  <timeline>
+    <DOMTimeline name="walk">
+      <layers>
+        <DOMLayer name="Layer 1">
+          <frames>{}</frames>
+        </DOMLayer>
+      </layers>
+    </DOMTimeline>
+  </timeline>
+</DOMSymbolItem>"#,
+            frames
+        );
+        parse_timelines(&xml).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_classic_tween() {
+        let timeline = symbol(
+            r#"
+            <DOMFrame index="0" duration="4" tweenType="motion" acceleration="0">
+              <elements>
+                <DOMSymbolInstance libraryItemName="image/head">
+                  <matrix><Matrix tx="0" ty="10"/></matrix>
+                  <color><Color alphaMultiplier="0"/></color>
+                </DOMSymbolInstance>
+              </elements>
+            </DOMFrame>
+            <DOMFrame index="4" duration="2">
+              <elements>
+                <DOMSymbolInstance libraryItemName="image/head">
+                  <matrix><Matrix a="2" d="2" tx="40" ty="10"/></matrix>
+                </DOMSymbolInstance>
+              </elements>
+            </DOMFrame>"#,
+        );
+        assert_eq!(timeline.frame_count(), 6);
+        let frames = &timeline.layers[0].frames;
+        assert!(matches!(frames[0].tween, Tween::Classic { .. }));
+
+        let (matrix, color) = bake_element(&frames[0], 0, frames.get(1), 2, 30.0).unwrap();
+        assert_matrix(matrix, [1.5, 0.0, 0.0, 1.5, 20.0, 10.0]);
+        assert_eq!(color, [1.0, 1.0, 1.0, 0.5]);
+
+        // The last keyframe has nothing to tween towards
+        let (matrix, _) = bake_element(&frames[1], 0, None, 1, 30.0).unwrap();
+        assert_matrix(matrix, [2.0, 0.0, 0.0, 2.0, 40.0, 10.0]);
+    }
+
+    #[test]
+    fn test_classic_tween_rotation() {
+        let timeline = symbol(
+            r#"
+            <DOMFrame index="0" duration="4" tweenType="motion" motionTweenRotate="clockwise" motionTweenRotateTimes="1">
+              <elements>
+                <DOMSymbolInstance libraryItemName="image/head"/>
+              </elements>
+            </DOMFrame>
+            <DOMFrame index="4">
+              <elements>
+                <DOMSymbolInstance libraryItemName="image/head"/>
+              </elements>
+            </DOMFrame>"#,
+        );
+        let frames = &timeline.layers[0].frames;
+
+        // One full clockwise turn: a quarter of the way is 90 degrees
+        let (matrix, _) = bake_element(&frames[0], 0, frames.get(1), 1, 30.0).unwrap();
+        assert_matrix(matrix, [0.0, 1.0, -1.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_motion_tween() {
+        let timeline = symbol(
+            r#"
+            <DOMFrame index="0" duration="10" tweenType="motion object">
+              <motionObjectXML>
+                <AnimationCore TimeScale="1000" Version="1" duration="1000">
+                  <PropertyContainer id="headContainer">
+                    <PropertyContainer id="Basic_Motion">
+                      <Property id="Motion_X">
+                        <Keyframe anchor="0,0" timevalue="0"/>
+                        <Keyframe anchor="0,100" timevalue="1000"/>
+                      </Property>
+                      <Property id="Rotation_Z">
+                        <Keyframe anchor="0,0" timevalue="0"/>
+                        <Keyframe anchor="0,90" timevalue="500"/>
+                      </Property>
+                    </PropertyContainer>
+                  </PropertyContainer>
+                </AnimationCore>
+              </motionObjectXML>
+              <elements>
+                <DOMSymbolInstance libraryItemName="image/head">
+                  <matrix><Matrix tx="5" ty="7"/></matrix>
+                </DOMSymbolInstance>
+              </elements>
+            </DOMFrame>"#,
+        );
+        let frames = &timeline.layers[0].frames;
+        let Tween::Motion(curves) = &frames[0].tween else {
+            panic!("expected a motion tween, got {:?}", frames[0].tween);
+        };
+        assert_eq!(curves.properties.len(), 2);
+
+        // At 10 fps frame 5 is half a second in: halfway along X, rotation done
+        let (matrix, _) = bake_element(&frames[0], 0, None, 5, 10.0).unwrap();
+        assert_matrix(matrix, [0.0, 1.0, -1.0, 0.0, 55.0, 7.0]);
+        // Past the last key the curves hold their final values
+        let (matrix, _) = bake_element(&frames[0], 0, None, 9, 5.0).unwrap();
+        assert_matrix(matrix, [0.0, 1.0, -1.0, 0.0, 105.0, 7.0]);
+    }
+}
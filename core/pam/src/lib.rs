@@ -1,7 +1,597 @@
 pub mod binary;
 pub mod types;
+pub mod edit;
 pub mod fla;
+pub mod media;
+pub mod preview;
+pub mod render;
+pub mod sen;
+pub mod spine;
+pub mod validate;
 
-pub use binary::{decode_pam, encode_pam};
+pub use binary::{decode_pam, encode_pam, rename_image_id};
 pub use types::PamInfo;
+pub use edit::SpriteRef;
 pub use fla::{convert_from_fla, convert_to_fla};
+pub use media::MediaSource;
+pub use preview::{PreviewFormat, write_preview};
+pub use render::{Matrix, PamRenderer, RenderOptions};
+pub use sen::{SenAnimation, convert_from_sen, convert_to_sen};
+pub use spine::{SpineSkeleton, convert_from_spine, convert_to_spine};
+pub use validate::{PamIssue, Severity};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::image_matrix;
+    use crate::types::{AddsInfo, FrameInfo, ImageInfo, MovesInfo, RemovesInfo, SpriteInfo};
+
+    fn image(name: &str, width: i32, height: i32) -> ImageInfo {
+        ImageInfo {
+            name: name.to_string(),
+            size: [width, height],
+            transform: vec![0.78125, 0.0, 0.0, 0.78125, -2.5, -5.0],
+        }
+    }
+
+    fn append(index: i32, resource: i32, sprite: bool) -> AddsInfo {
+        AddsInfo {
+            index,
+            name: None,
+            resource,
+            sprite,
+            additive: false,
+            preload_frame: 0,
+            time_scale: 1.0,
+        }
+    }
+
+    fn moved(index: i32, x: f64, y: f64) -> MovesInfo {
+        MovesInfo {
+            index,
+            transform: vec![1.0, 0.0, 0.0, 1.0, x, y],
+            color: None,
+            source_rectangle: None,
+            sprite_frame_number: 0,
+        }
+    }
+
+    /// Two labelled animations of the main sprite showing images only
+    fn flat_pam() -> PamInfo {
+        let frames = vec![
+            FrameInfo {
+                label: Some("idle".to_string()),
+                append: vec![append(0, 0, false), append(1, 1, false)],
+                change: vec![moved(0, 10.0, 20.0), moved(1, 30.0, 20.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                change: vec![moved(0, 12.0, 20.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                stop: true,
+                remove: vec![RemovesInfo { index: 1 }],
+                change: vec![moved(0, 14.0, 22.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                label: Some("walk".to_string()),
+                append: vec![append(1, 1, false)],
+                change: vec![moved(1, 40.0, 20.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                stop: true,
+                change: vec![moved(1, 44.0, 20.0)],
+                ..Default::default()
+            },
+        ];
+        PamInfo {
+            version: 6,
+            frame_rate: 30,
+            position: [0.0, 0.0],
+            size: [100.0, 80.0],
+            image: vec![
+                image("head|IMAGE_HEAD", 32, 32),
+                image("arm|IMAGE_ARM", 16, 8),
+            ],
+            sprite: Vec::new(),
+            main_sprite: SpriteInfo {
+                frame_rate: 30.0,
+                work_area: [0, frames.len() as i32],
+                frame: frames,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// [`flat_pam`] with a nested sprite showing the head, blinking in and out
+    fn nested_pam() -> PamInfo {
+        let mut pam = flat_pam();
+        pam.sprite.push(SpriteInfo {
+            name: Some("blink".to_string()),
+            frame_rate: 30.0,
+            work_area: [0, 3],
+            frame: vec![
+                FrameInfo {
+                    append: vec![append(0, 0, false)],
+                    change: vec![moved(0, 1.0, 1.0)],
+                    ..Default::default()
+                },
+                FrameInfo {
+                    change: vec![moved(0, 2.0, 1.0)],
+                    ..Default::default()
+                },
+                FrameInfo {
+                    remove: vec![RemovesInfo { index: 0 }],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        });
+        let main = &mut pam.main_sprite.frame;
+        main[0].append.push(append(2, 0, true));
+        main[0].change.push(moved(2, 50.0, 50.0));
+        main[3].remove.push(RemovesInfo { index: 2 });
+        pam
+    }
+
+    fn errors(pam: &PamInfo) -> Vec<String> {
+        pam.validate()
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| issue.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_edits_keep_pam_valid() {
+        let base = nested_pam();
+        assert_eq!(errors(&base), Vec::<String>::new());
+
+        type Edit = fn(&mut PamInfo);
+        let edits: [(&str, Edit); 7] = [
+            ("set_frame_rate 24", |pam| pam.set_frame_rate(24).unwrap()),
+            ("set_frame_rate 60", |pam| pam.set_frame_rate(60).unwrap()),
+            ("remove_image 0", |pam| pam.remove_image(0).unwrap()),
+            ("remove_image 1", |pam| pam.remove_image(1).unwrap()),
+            ("remove_sprite 0", |pam| pam.remove_sprite(0).unwrap()),
+            ("merge_sprites main", |pam| {
+                pam.merge_sprites(&nested_pam(), &[SpriteRef::Main])
+                    .unwrap();
+            }),
+            ("merge_sprites 0", |pam| {
+                pam.merge_sprites(&nested_pam(), &[SpriteRef::Sprite(0)])
+                    .unwrap();
+            }),
+        ];
+        for (name, edit) in edits {
+            let mut pam = nested_pam();
+            edit(&mut pam);
+            assert_eq!(errors(&pam), Vec::<String>::new(), "{}", name);
+        }
+
+        let mut pam = nested_pam();
+        pam.remove_image(0).unwrap();
+        assert_eq!(pam.image.len(), 1);
+        assert!(pam.sprite[0].frame.iter().all(|f| f.append.is_empty()));
+        assert_eq!(pam.main_sprite.frame[0].append.len(), 2);
+    }
+
+    #[test]
+    fn test_drop_layer_appended_over_another() {
+        // Layer 0 shows the head, then the arm is appended over it without a remove
+        let mut pam = flat_pam();
+        pam.main_sprite.frame = vec![
+            FrameInfo {
+                append: vec![append(0, 0, false)],
+                change: vec![moved(0, 10.0, 20.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                append: vec![append(0, 1, false)],
+                change: vec![moved(0, 12.0, 20.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                change: vec![moved(0, 14.0, 20.0)],
+                ..Default::default()
+            },
+            FrameInfo {
+                remove: vec![RemovesInfo { index: 0 }],
+                ..Default::default()
+            },
+        ];
+        pam.main_sprite.work_area = [0, 4];
+
+        // Dropping the arm must not bring the replaced head back
+        let mut without_arm = pam.clone();
+        without_arm.remove_image(1).unwrap();
+        assert_eq!(errors(&without_arm), Vec::<String>::new());
+        let shown: Vec<usize> = placements(&without_arm).iter().map(Vec::len).collect();
+        assert_eq!(shown, [1, 0, 0, 0]);
+
+        // Dropping the head leaves the arm as it was
+        let mut without_head = pam.clone();
+        without_head.remove_image(0).unwrap();
+        assert_eq!(errors(&without_head), Vec::<String>::new());
+        let shown: Vec<usize> = placements(&without_head).iter().map(Vec::len).collect();
+        assert_eq!(shown, [0, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_validate_issue_kinds() {
+        type Break = fn(&mut PamInfo);
+        let cases: [(Break, Severity, &str); 12] = [
+            (|pam| pam.version = 7, Severity::Error, "version 7"),
+            (|pam| pam.frame_rate = 0, Severity::Error, "frame rate 0"),
+            (|pam| pam.size[0] = 4000.0, Severity::Warning, "size"),
+            (
+                |pam| pam.image[0].transform.pop().map(drop).unwrap(),
+                Severity::Error,
+                "image 0 (head|IMAGE_HEAD) has a transform of 5 values",
+            ),
+            (
+                |pam| pam.sprite[0].work_area = [0, 9],
+                Severity::Warning,
+                "work area [0, 9]",
+            ),
+            (
+                |pam| {
+                    pam.main_sprite.frame[1]
+                        .remove
+                        .push(RemovesInfo { index: 5 })
+                },
+                Severity::Error,
+                "removes layer 5",
+            ),
+            (
+                |pam| pam.main_sprite.frame[0].append[0].resource = 9,
+                Severity::Error,
+                "layer 0 shows image 9 but there are 2 images",
+            ),
+            (
+                |pam| pam.main_sprite.frame[1].append.push(append(0, 1, false)),
+                Severity::Warning,
+                "appends layer 0 which is already displayed",
+            ),
+            (
+                |pam| pam.main_sprite.frame[1].change.push(moved(7, 0.0, 0.0)),
+                Severity::Warning,
+                "moves layer 7 which is not displayed",
+            ),
+            (
+                |pam| pam.main_sprite.frame[1].change[0].transform.truncate(4),
+                Severity::Error,
+                "layer 0 has a transform of 4 values",
+            ),
+            (
+                |pam| pam.main_sprite.frame[3].label = Some("idle".to_string()),
+                Severity::Error,
+                "label 'idle' is already used on frame 0",
+            ),
+            (
+                |pam| pam.sprite[0].frame[0].append.push(append(1, 0, true)),
+                Severity::Error,
+                "contains itself",
+            ),
+        ];
+        for (break_pam, severity, message) in cases {
+            let mut pam = nested_pam();
+            break_pam(&mut pam);
+            let issues = pam.validate();
+            assert!(
+                issues
+                    .iter()
+                    .any(|i| i.severity == severity && i.to_string().contains(message)),
+                "{:?} '{}' not in {:?}",
+                severity,
+                message,
+                issues
+            );
+
+            // Only errors stop the PAM from being written
+            let encoded = encode_pam(&pam, &mut Vec::new());
+            assert_eq!(
+                encoded.is_ok(),
+                severity == Severity::Warning,
+                "{}",
+                message
+            );
+        }
+    }
+
+    #[test]
+    fn test_set_frame_rate_rejects_without_changes() {
+        // The main sprite's labels on frames 0 and 3 would share a frame at 5 fps,
+        // after the nested sprite was already resampled
+        let mut pam = nested_pam();
+        let before = serde_json::to_value(&pam).unwrap();
+        let err = pam.set_frame_rate(5).unwrap_err();
+        assert!(err.to_string().contains("walk"), "{}", err);
+        assert_eq!(serde_json::to_value(&pam).unwrap(), before);
+    }
+
+    #[test]
+    fn test_render_frame_pixels() {
+        let mut pam = flat_pam();
+        for image in &mut pam.image {
+            image.transform = vec![1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        }
+        let red = image::RgbaImage::from_pixel(32, 32, image::Rgba([255, 0, 0, 255]));
+        let green = image::RgbaImage::from_pixel(16, 8, image::Rgba([0, 255, 0, 255]));
+        let renderer = PamRenderer::new(&pam, vec![Some(red), Some(green)]);
+        let options = RenderOptions::default();
+
+        // Head at (10, 20) with the arm on top of it at (30, 20)
+        let frame = renderer.render_frame(0, &options).unwrap();
+        assert_eq!(frame.dimensions(), (100, 80));
+        assert_eq!(frame.get_pixel(11, 21).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(40, 50).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(35, 22).0, [0, 255, 0, 255]);
+        assert_eq!(frame.get_pixel(44, 26).0, [0, 255, 0, 255]);
+        assert_eq!(frame.get_pixel(5, 5).0, [0, 0, 0, 0]);
+        assert_eq!(frame.get_pixel(44, 40).0, [0, 0, 0, 0]);
+
+        // The head moved to (14, 22) and the arm is gone
+        let frame = renderer.render_frame(2, &options).unwrap();
+        assert_eq!(frame.get_pixel(35, 23).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(12, 21).0, [0, 0, 0, 0]);
+
+        let options = RenderOptions {
+            scale: 2.0,
+            background: [0, 0, 255, 255],
+            ..Default::default()
+        };
+        let frame = renderer.render_frame(0, &options).unwrap();
+        assert_eq!(frame.dimensions(), (200, 160));
+        assert_eq!(frame.get_pixel(22, 42).0, [255, 0, 0, 255]);
+        assert_eq!(frame.get_pixel(10, 10).0, [0, 0, 255, 255]);
+
+        let options = RenderOptions {
+            scale: 1e6,
+            ..Default::default()
+        };
+        assert!(renderer.render_frame(0, &options).is_err());
+    }
+
+    #[test]
+    fn test_preview_decodes() {
+        use image::AnimationDecoder;
+        use image::codecs::png::PngDecoder;
+        use image::codecs::webp::WebPDecoder;
+        use std::io::Cursor;
+
+        let shades = [0, 100, 200];
+        let frames: Vec<_> = shades
+            .iter()
+            .map(|&shade| image::RgbaImage::from_pixel(5, 3, image::Rgba([shade, 50, 0, 255])))
+            .collect();
+
+        for format in [PreviewFormat::Apng, PreviewFormat::Webp] {
+            let mut data = Vec::new();
+            write_preview(&frames, 30.0, format, &mut data).unwrap();
+            let decoded = match format {
+                PreviewFormat::Apng => PngDecoder::new(Cursor::new(&data))
+                    .unwrap()
+                    .apng()
+                    .unwrap()
+                    .into_frames(),
+                PreviewFormat::Webp => WebPDecoder::new(Cursor::new(&data)).unwrap().into_frames(),
+                PreviewFormat::Gif => unreachable!(),
+            };
+            let decoded = decoded.collect_frames().unwrap();
+
+            assert_eq!(decoded.len(), 3, "{:?}", format);
+            let delays: Vec<u32> = decoded
+                .iter()
+                .map(|frame| {
+                    let (numer, denom) = frame.delay().numer_denom_ms();
+                    (numer as f64 / denom as f64).round() as u32
+                })
+                .collect();
+            // 1000 / 30 ms, rounded so three frames last exactly 100 ms
+            assert_eq!(delays, [33, 34, 33], "{:?}", format);
+            for (frame, shade) in decoded.iter().zip(shades) {
+                assert_eq!(frame.buffer().dimensions(), (5, 3), "{:?}", format);
+                assert_eq!(frame.buffer().get_pixel(4, 2).0, [shade, 50, 0, 255]);
+            }
+        }
+    }
+
+    fn solid(width: u32, height: u32, rgba: [u8; 4]) -> image::RgbaImage {
+        image::RgbaImage::from_pixel(width, height, image::Rgba(rgba))
+    }
+
+    #[test]
+    fn test_media_sources() {
+        let mut pam = flat_pam();
+        pam.image.push(image("leg|IMAGE_LEG", 8, 8));
+
+        // Bitmaps are found by file name first, then by resource id
+        let dir = std::env::temp_dir().join(format!("pam_media_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        solid(4, 4, [255, 0, 0, 255])
+            .save(dir.join("head.png"))
+            .unwrap();
+        solid(2, 2, [0, 255, 0, 255])
+            .save(dir.join("IMAGE_ARM.png"))
+            .unwrap();
+        let bitmaps = MediaSource::Directory(&dir).load(&pam);
+        assert_eq!(bitmaps[0], Some(solid(4, 4, [255, 0, 0, 255])));
+        assert_eq!(bitmaps[1], Some(solid(2, 2, [0, 255, 0, 255])));
+        assert_eq!(bitmaps[2], None);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // Sprites are cropped from the page their parent names, or the first one
+        let atlas: atlas::OfficialAtlas = serde_json::from_value(serde_json::json!({
+            "id": "GROUP",
+            "resources": [
+                { "id": "ATLAS_00", "atlas": true },
+                { "id": "ATLAS_01", "atlas": true },
+                { "id": "image_head", "ax": 1, "ay": 2, "aw": 3, "ah": 2 },
+                { "id": "IMAGE_ARM", "parent": "ATLAS_01", "ax": 4, "ay": 0, "aw": 2, "ah": 5 },
+                { "id": "IMAGE_LEG", "ax": 6, "ay": 6, "aw": 4, "ah": 4 }
+            ]
+        }))
+        .unwrap();
+        let pages: Vec<_> = (0..2u8)
+            .map(|page| {
+                image::RgbaImage::from_fn(8, 8, |x, y| image::Rgba([page, x as u8, y as u8, 255]))
+            })
+            .collect();
+        let bitmaps = MediaSource::Atlas {
+            atlas: &atlas,
+            pages: &pages,
+        }
+        .load(&pam);
+        let crop = |page: &image::RgbaImage, x, y, w, h| {
+            Some(image::imageops::crop_imm(page, x, y, w, h).to_image())
+        };
+        assert_eq!(bitmaps[0], crop(&pages[0], 1, 2, 3, 2));
+        assert_eq!(bitmaps[1], crop(&pages[1], 4, 0, 2, 5));
+        // Out of the page bounds
+        assert_eq!(bitmaps[2], None);
+    }
+
+    #[test]
+    fn test_fla_embeds_media() {
+        use std::io::Read;
+
+        let pam = flat_pam();
+        let dir = std::env::temp_dir().join(format!("pam_fla_media_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let head = solid(4, 3, [255, 0, 0, 255]);
+        head.save(dir.join("head.png")).unwrap();
+
+        let path = dir.join("out.fla");
+        convert_to_fla(&pam, &path, 1200, Some(&MediaSource::Directory(&dir))).unwrap();
+        let mut zip = zip::ZipArchive::new(std::fs::File::open(&path).unwrap()).unwrap();
+
+        let mut png = Vec::new();
+        zip.by_name("LIBRARY/media/head.png")
+            .unwrap()
+            .read_to_end(&mut png)
+            .unwrap();
+        assert_eq!(image::load_from_memory(&png).unwrap().to_rgba8(), head);
+        // Only bitmaps that were found are embedded
+        assert!(zip.by_name("LIBRARY/media/arm.png").is_err());
+
+        let mut dom = String::new();
+        zip.by_name("DOMDocument.xml")
+            .unwrap()
+            .read_to_string(&mut dom)
+            .unwrap();
+        assert!(dom.contains(
+            r#"name="media/head" href="media/head.png" sourceExternalFilepath="./LIBRARY/media/head.png""#
+        ));
+        assert!(dom.contains(r#"frameRight="80" frameBottom="60""#));
+        assert!(
+            dom.contains(r#"name="media/arm" href="media/arm.png" bitmapDataHRef="media/arm.png""#)
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Size and placement of every image shown on each frame, in drawing order
+    fn placements(pam: &PamInfo) -> Vec<Vec<([i32; 2], [i64; 6])>> {
+        let renderer = PamRenderer::new(pam, Vec::new());
+        (0..renderer.frame_count())
+            .map(|frame| {
+                renderer
+                    .display_list(frame)
+                    .iter()
+                    .map(|node| {
+                        let image = &pam.image[node.path.last().unwrap().resource as usize];
+                        let world = node.transform.then(&image_matrix(image));
+                        (image.size, world.0.map(|v| (v * 100.0).round() as i64))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_spine_round_trip() {
+        let pam = flat_pam();
+        let spine = convert_to_spine(&pam, 1200).unwrap();
+        let json = serde_json::to_string(&spine).unwrap();
+        let spine: SpineSkeleton = serde_json::from_str(&json).unwrap();
+        let back = convert_from_spine(&spine, 1200).unwrap();
+
+        assert_eq!(back.frame_rate, pam.frame_rate);
+        assert_eq!(back.label_frame("idle"), Some(0));
+        assert_eq!(back.label_frame("walk"), Some(3));
+        let expected = placements(&pam);
+        assert_eq!(
+            expected.iter().map(Vec::len).collect::<Vec<_>>(),
+            [2, 2, 1, 2, 2]
+        );
+        assert_eq!(placements(&back), expected);
+        assert!(back.is_valid());
+    }
+
+    #[test]
+    fn test_sen_round_trip() {
+        let mut pam = nested_pam();
+        let blink = &mut pam.sprite[0].frame;
+        blink[1].change[0].transform = vec![0.5, 2.0, 1.0];
+        blink[1].change[0].color = Some([1.0, 0.5, 0.5, 0.75]);
+        let main = &mut pam.main_sprite.frame;
+        main[1].change[0].transform = vec![1.0, 0.5, 0.0, 1.0, 12.0, 20.0];
+        main[1]
+            .command
+            .push(["fscommand".to_string(), "shake".to_string()]);
+        main[3].append[0].additive = true;
+
+        let json = serde_json::to_string(&convert_to_sen(&pam)).unwrap();
+        let sen: SenAnimation = serde_json::from_str(&json).unwrap();
+        let back = convert_from_sen(&sen);
+
+        // Changes come back in their shortest form
+        assert_eq!(back.main_sprite.frame[0].change[0].transform, [10.0, 20.0]);
+        assert_eq!(back.sprite[0].frame[1].change[0].transform, [0.5, 2.0, 1.0]);
+        assert_eq!(
+            back.main_sprite.frame[1].change[0].transform,
+            [1.0, 0.5, 0.0, 1.0, 12.0, 20.0]
+        );
+        assert_eq!(placements(&back), placements(&pam));
+
+        // Everything else is unchanged
+        let without_transforms = |pam: &PamInfo| {
+            let mut pam = pam.clone();
+            for sprite in pam.sprite.iter_mut().chain(Some(&mut pam.main_sprite)) {
+                for change in sprite.frame.iter_mut().flat_map(|f| f.change.iter_mut()) {
+                    change.transform.clear();
+                }
+            }
+            serde_json::to_value(pam).unwrap()
+        };
+        assert_eq!(without_transforms(&back), without_transforms(&pam));
+        assert!(back.is_valid());
+    }
+
+    #[test]
+    fn test_spine_rejects_unknown_timelines() {
+        let json = serde_json::json!({
+            "skeleton": { "spine": "4.1.00" },
+            "bones": [{ "name": "root" }],
+            "animations": {
+                "idle": {
+                    "bones": {
+                        "root": { "translatex": [{ "time": 0.5, "value": 10 }] }
+                    }
+                }
+            }
+        });
+        let err = serde_json::from_value::<SpineSkeleton>(json).unwrap_err();
+        assert!(err.to_string().contains("translatex"));
+
+        let json = serde_json::json!({
+            "animations": {
+                "idle": { "slots": { "head": { "alpha": [{ "value": 0.5 }] } } }
+            }
+        });
+        assert!(serde_json::from_value::<SpineSkeleton>(json).is_err());
+    }
+}
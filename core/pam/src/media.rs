@@ -0,0 +1,80 @@
+use crate::types::{ImageInfo, PamInfo};
+use atlas::OfficialAtlas;
+use image::RgbaImage;
+use std::path::Path;
+
+/// Where the bitmaps of a PAM's images come from
+pub enum MediaSource<'a> {
+    /// Folder of split sprites, named `<file>.png` or `<RESOURCE_ID>.png`
+    Directory(&'a Path),
+    /// Atlas page images (first page first) and their `OfficialAtlas` description;
+    /// images are cropped out by resource id
+    Atlas {
+        atlas: &'a OfficialAtlas,
+        pages: &'a [RgbaImage],
+    },
+}
+
+impl MediaSource<'_> {
+    /// Bitmap of every `pam.image` entry, in order. Missing or unreadable bitmaps are
+    /// `None` and reported on stderr.
+    pub fn load(&self, pam: &PamInfo) -> Vec<Option<RgbaImage>> {
+        pam.image
+            .iter()
+            .map(|info| {
+                let bitmap = self.load_image(info);
+                if bitmap.is_none() {
+                    eprintln!("Warning: no bitmap found for image {}", info.name);
+                }
+                bitmap
+            })
+            .collect()
+    }
+
+    fn load_image(&self, info: &ImageInfo) -> Option<RgbaImage> {
+        match self {
+            MediaSource::Directory(dir) => {
+                let candidates = [
+                    dir.join(format!("{}.png", media_name(info))),
+                    dir.join(format!("{}.png", info.resource_id())),
+                ];
+                let path = candidates.iter().find(|p| p.is_file())?;
+                match image::open(path) {
+                    Ok(img) => Some(img.to_rgba8()),
+                    Err(e) => {
+                        eprintln!("Warning: failed to load {:?}: {}", path, e);
+                        None
+                    }
+                }
+            }
+            MediaSource::Atlas { atlas, pages } => {
+                let id = info.resource_id();
+                let res = atlas
+                    .resources
+                    .iter()
+                    .find(|r| r.id.eq_ignore_ascii_case(id))?;
+                let (ax, ay, aw, ah) = (res.ax?, res.ay?, res.aw?, res.ah?);
+                // Sprites without a parent live on the first page
+                let page = match &res.parent {
+                    Some(parent) => atlas
+                        .resources
+                        .iter()
+                        .filter(|r| r.atlas == Some(true))
+                        .position(|r| &r.id == parent)?,
+                    None => 0,
+                };
+                let page = pages.get(page)?;
+                if ax + aw > page.width() || ay + ah > page.height() {
+                    eprintln!("Warning: image {} is out of the atlas bounds", id);
+                    return None;
+                }
+                Some(image::imageops::crop_imm(page, ax, ay, aw, ah).to_image())
+            }
+        }
+    }
+}
+
+/// Library name of an image's bitmap: the file part of `name`
+pub fn media_name(info: &ImageInfo) -> &str {
+    info.name.split('|').next().unwrap_or(&info.name)
+}
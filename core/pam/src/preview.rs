@@ -0,0 +1,133 @@
+use anyhow::{Context, Result, bail};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::io::Write;
+
+/// Container for an animated preview
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewFormat {
+    Apng,
+    Gif,
+    Webp,
+}
+
+impl PreviewFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PreviewFormat::Apng => "png",
+            PreviewFormat::Gif => "gif",
+            PreviewFormat::Webp => "webp",
+        }
+    }
+}
+
+/// Write `frames` as one looping animation playing at `frame_rate` frames per second.
+/// All frames must share the size of the first one.
+pub fn write_preview<W: Write>(
+    frames: &[RgbaImage],
+    frame_rate: f64,
+    format: PreviewFormat,
+    writer: W,
+) -> Result<()> {
+    let Some(first) = frames.first() else {
+        bail!("No frames to write");
+    };
+    if frames.iter().any(|f| f.dimensions() != first.dimensions()) {
+        bail!("Preview frames must all have the same size");
+    }
+    let frame_rate = if frame_rate > 0.0 { frame_rate } else { 30.0 };
+    match format {
+        PreviewFormat::Apng => write_apng(frames, frame_rate, writer),
+        PreviewFormat::Gif => write_gif(frames, frame_rate, writer),
+        PreviewFormat::Webp => write_webp(frames, frame_rate, writer),
+    }
+}
+
+/// Millisecond delay of each frame, rounded so the total length stays exact
+fn frame_delays_ms(count: usize, frame_rate: f64) -> Vec<u32> {
+    let at = |i: usize| (i as f64 * 1000.0 / frame_rate).round() as u32;
+    (0..count).map(|i| (at(i + 1) - at(i)).max(1)).collect()
+}
+
+fn write_apng<W: Write>(frames: &[RgbaImage], frame_rate: f64, writer: W) -> Result<()> {
+    let (width, height) = frames[0].dimensions();
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    let mut png = encoder.write_header()?;
+    for (frame, delay) in frames.iter().zip(frame_delays_ms(frames.len(), frame_rate)) {
+        png.set_frame_delay(delay.min(u16::MAX as u32) as u16, 1000)?;
+        png.write_image_data(frame.as_raw())
+            .context("Failed to write APNG frame")?;
+    }
+    png.finish()?;
+    Ok(())
+}
+
+fn write_gif<W: Write>(frames: &[RgbaImage], frame_rate: f64, writer: W) -> Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(writer, 10);
+    encoder.set_repeat(Repeat::Infinite)?;
+    let delays = frame_delays_ms(frames.len(), frame_rate);
+    encoder
+        .encode_frames(frames.iter().zip(delays).map(|(frame, delay)| {
+            Frame::from_parts(frame.clone(), 0, 0, Delay::from_numer_denom_ms(delay, 1))
+        }))
+        .context("Failed to write GIF")?;
+    Ok(())
+}
+
+/// Animated WebP: every frame is encoded lossless and wrapped in an `ANMF` chunk
+fn write_webp<W: Write>(frames: &[RgbaImage], frame_rate: f64, mut writer: W) -> Result<()> {
+    let (width, height) = frames[0].dimensions();
+    if width > 1 << 24 || height > 1 << 24 {
+        bail!("Preview is too large for WebP");
+    }
+
+    let mut body = Vec::new();
+    let mut vp8x = vec![0x10 | 0x02, 0, 0, 0];
+    vp8x.extend_from_slice(&u24(width - 1));
+    vp8x.extend_from_slice(&u24(height - 1));
+    write_riff_chunk(&mut body, b"VP8X", &vp8x);
+    // Transparent background, loop forever
+    write_riff_chunk(&mut body, b"ANIM", &[0, 0, 0, 0, 0, 0]);
+
+    for (frame, delay) in frames.iter().zip(frame_delays_ms(frames.len(), frame_rate)) {
+        let mut still = Vec::new();
+        image_webp::WebPEncoder::new(&mut still)
+            .encode(frame.as_raw(), width, height, image_webp::ColorType::Rgba8)
+            .context("Failed to encode WebP frame")?;
+
+        let mut anmf = Vec::new();
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(0));
+        anmf.extend_from_slice(&u24(width - 1));
+        anmf.extend_from_slice(&u24(height - 1));
+        anmf.extend_from_slice(&u24(delay.min(0xFF_FFFF)));
+        // Replace the canvas instead of blending over the previous frame
+        anmf.push(0x02);
+        // The still file is RIFF header + VP8L chunk; keep only the chunk
+        anmf.extend_from_slice(still.get(12..).context("Invalid WebP frame")?);
+        write_riff_chunk(&mut body, b"ANMF", &anmf);
+    }
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(body.len() as u32 + 4).to_le_bytes())?;
+    writer.write_all(b"WEBP")?;
+    writer.write_all(&body)?;
+    Ok(())
+}
+
+fn write_riff_chunk(out: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+fn u24(value: u32) -> [u8; 3] {
+    let bytes = value.to_le_bytes();
+    [bytes[0], bytes[1], bytes[2]]
+}
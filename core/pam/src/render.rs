@@ -0,0 +1,311 @@
+use crate::types::{ImageInfo, PamInfo, SpriteInfo};
+use anyhow::Result;
+use image::RgbaImage;
+use raster::Canvas;
+pub use raster::Matrix;
+use std::ops::Range;
+
+/// Nested sprites deeper than this are assumed to be cyclic and skipped
+const MAX_SPRITE_DEPTH: usize = 32;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Output pixels per animation unit
+    pub scale: f64,
+    /// Texture resolution the bitmaps were exported at; they are drawn at
+    /// `1200 / resolution` like in the FLA export
+    pub resolution: i32,
+    /// Canvas fill behind the animation
+    pub background: [u8; 4],
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            resolution: 1200,
+            background: [0, 0, 0, 0],
+        }
+    }
+}
+
+/// An element on a sprite's display list at one frame
+#[derive(Debug, Clone)]
+struct Layer {
+    index: i32,
+    resource: i32,
+    sprite: bool,
+    additive: bool,
+    transform: Matrix,
+    color: [f64; 4],
+    source_rectangle: Option<[i32; 4]>,
+    /// Frame of the parent at which the nested sprite's clock was last set
+    anchor_frame: usize,
+    /// Nested sprite frame at `anchor_frame`
+    anchor_child_frame: f64,
+    time_scale: f64,
+}
+
+/// Display list of every frame of one sprite, ordered by layer index
+struct Timeline {
+    frames: Vec<Vec<Layer>>,
+}
+
+impl Timeline {
+    fn build(sprite: &SpriteInfo) -> Self {
+        let mut layers: Vec<Layer> = Vec::new();
+        let mut frames = Vec::with_capacity(sprite.frame.len());
+        for (frame_index, frame) in sprite.frame.iter().enumerate() {
+            for remove in &frame.remove {
+                layers.retain(|l| l.index != remove.index);
+            }
+            for append in &frame.append {
+                layers.retain(|l| l.index != append.index);
+                layers.push(Layer {
+                    index: append.index,
+                    resource: append.resource,
+                    sprite: append.sprite,
+                    additive: append.additive,
+                    transform: Matrix::IDENTITY,
+                    color: [1.0; 4],
+                    source_rectangle: None,
+                    anchor_frame: frame_index,
+                    anchor_child_frame: append.preload_frame as f64,
+                    time_scale: append.time_scale as f64,
+                });
+            }
+            for change in &frame.change {
+                let Some(layer) = layers.iter_mut().find(|l| l.index == change.index) else {
+                    continue;
+                };
+                layer.transform = transform_matrix(&change.transform);
+                if let Some(color) = change.color {
+                    layer.color = color;
+                }
+                if change.source_rectangle.is_some() {
+                    layer.source_rectangle = change.source_rectangle;
+                }
+                if change.sprite_frame_number != 0 {
+                    layer.anchor_frame = frame_index;
+                    layer.anchor_child_frame = change.sprite_frame_number as f64;
+                }
+            }
+            layers.sort_by_key(|l| l.index);
+            frames.push(layers.clone());
+        }
+        Self { frames }
+    }
+}
+
+/// Element of a display tree: `sprite` tells whether `resource` indexes
+/// `PamInfo::sprite` or `PamInfo::image`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeKey {
+    pub index: i32,
+    pub resource: i32,
+    pub sprite: bool,
+}
+
+/// One element shown at a frame
+#[derive(Debug, Clone)]
+pub struct DisplayNode {
+    /// Keys of this element and every sprite element above it, from the main sprite down
+    pub path: Vec<NodeKey>,
+    /// Transform relative to the parent element
+    pub transform: Matrix,
+    /// Colour multiplier relative to the parent element
+    pub color: [f64; 4],
+    pub additive: bool,
+    pub source_rectangle: Option<[i32; 4]>,
+}
+
+/// Headless rasteriser for PAM animations
+pub struct PamRenderer<'a> {
+    pam: &'a PamInfo,
+    images: Vec<Option<RgbaImage>>,
+    sprites: Vec<Timeline>,
+    main: Timeline,
+}
+
+impl<'a> PamRenderer<'a> {
+    /// `images` holds the bitmap of each `pam.image` entry, in order; missing bitmaps
+    /// are skipped when drawing.
+    pub fn new(pam: &'a PamInfo, images: Vec<Option<RgbaImage>>) -> Self {
+        Self {
+            pam,
+            images,
+            sprites: pam.sprite.iter().map(Timeline::build).collect(),
+            main: Timeline::build(&pam.main_sprite),
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.main.frames.len()
+    }
+
+    /// Labelled frame ranges of the main sprite, each running to the next label or
+    /// the first `stop` after it
+    pub fn labels(&self) -> Vec<(String, Range<usize>)> {
+        let frames = &self.pam.main_sprite.frame;
+        let starts: Vec<(usize, &str)> = frames
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| f.label.as_deref().map(|label| (i, label)))
+            .collect();
+        starts
+            .iter()
+            .enumerate()
+            .map(|(n, &(start, label))| {
+                let next = starts.get(n + 1).map_or(frames.len(), |&(i, _)| i);
+                let end = (start..next)
+                    .find(|&i| frames[i].stop)
+                    .map_or(next, |i| i + 1);
+                (label.to_string(), start..end)
+            })
+            .collect()
+    }
+
+    pub fn label_range(&self, label: &str) -> Option<Range<usize>> {
+        self.labels()
+            .into_iter()
+            .find(|(name, _)| name == label)
+            .map(|(_, range)| range)
+    }
+
+    /// Output size for `pam.size` at the option's scale
+    pub fn canvas_size(&self, options: &RenderOptions) -> (u32, u32) {
+        let width = (self.pam.size[0] * options.scale).ceil().max(1.0);
+        let height = (self.pam.size[1] * options.scale).ceil().max(1.0);
+        (width as u32, height as u32)
+    }
+
+    /// Rasterise one frame of the main sprite. The canvas covers `pam.position` to
+    /// `pam.position + pam.size`; fails when that is too large to allocate.
+    pub fn render_frame(&self, frame: usize, options: &RenderOptions) -> Result<RgbaImage> {
+        let (width, height) = self.canvas_size(options);
+        let mut canvas = Canvas::new(width, height, options.background)?;
+        let view = Matrix::scale(options.scale, options.scale).then(&Matrix([
+            1.0,
+            0.0,
+            0.0,
+            1.0,
+            -self.pam.position[0],
+            -self.pam.position[1],
+        ]));
+        let bitmap_scale = 1200.0 / options.resolution.max(1) as f64;
+        let root = (view, [1.0; 4]);
+        self.walk(
+            &self.main,
+            frame as f64,
+            &root,
+            0,
+            &mut |(matrix, color): &(Matrix, [f64; 4]), layer: &Layer| {
+                let matrix = matrix.then(&layer.transform);
+                let color = multiply_color(color, &layer.color);
+                if color[3] <= 0.0 {
+                    return None;
+                }
+                if !layer.sprite
+                    && let (Some(info), Some(Some(bitmap))) = (
+                        self.pam.image.get(layer.resource as usize),
+                        self.images.get(layer.resource as usize),
+                    )
+                {
+                    let matrix = matrix
+                        .then(&image_matrix(info))
+                        .then(&Matrix::scale(bitmap_scale, bitmap_scale));
+                    canvas.draw(
+                        bitmap,
+                        layer.source_rectangle,
+                        &matrix,
+                        color,
+                        layer.additive,
+                    );
+                }
+                Some((matrix, color))
+            },
+        );
+        Ok(canvas.into_image())
+    }
+
+    /// Every element shown at `frame` of the main sprite, in drawing order with
+    /// sprite elements before their children
+    pub fn display_list(&self, frame: usize) -> Vec<DisplayNode> {
+        let mut nodes = Vec::new();
+        self.walk(
+            &self.main,
+            frame as f64,
+            &Vec::new(),
+            0,
+            &mut |path: &Vec<NodeKey>, layer: &Layer| {
+                let mut path = path.clone();
+                path.push(NodeKey {
+                    index: layer.index,
+                    resource: layer.resource,
+                    sprite: layer.sprite,
+                });
+                nodes.push(DisplayNode {
+                    path: path.clone(),
+                    transform: layer.transform,
+                    color: layer.color,
+                    additive: layer.additive,
+                    source_rectangle: layer.source_rectangle,
+                });
+                Some(path)
+            },
+        );
+        nodes
+    }
+
+    /// Visit the layers of `timeline` at `frame` depth first. `visit` derives each
+    /// layer's state from its parent's; returning `None` skips a sprite's children.
+    fn walk<S>(
+        &self,
+        timeline: &Timeline,
+        frame: f64,
+        parent: &S,
+        depth: usize,
+        visit: &mut impl FnMut(&S, &Layer) -> Option<S>,
+    ) {
+        if timeline.frames.is_empty() || depth > MAX_SPRITE_DEPTH {
+            return;
+        }
+        let frame_index = (frame.max(0.0) as usize) % timeline.frames.len();
+        for layer in &timeline.frames[frame_index] {
+            let Some(state) = visit(parent, layer) else {
+                continue;
+            };
+            if !layer.sprite {
+                continue;
+            }
+            let Some(child) = self.sprites.get(layer.resource as usize) else {
+                continue;
+            };
+            let elapsed = frame_index.saturating_sub(layer.anchor_frame) as f64;
+            let child_frame = layer.anchor_child_frame + elapsed * layer.time_scale;
+            self.walk(child, child_frame, &state, depth + 1, visit);
+        }
+    }
+}
+
+fn multiply_color(a: &[f64; 4], b: &[f64; 4]) -> [f64; 4] {
+    [a[0] * b[0], a[1] * b[1], a[2] * b[2], a[3] * b[3]]
+}
+
+/// Read a PAM transform: `[a, b, c, d, tx, ty]`, `[angle, tx, ty]` or `[tx, ty]`
+pub fn transform_matrix(transform: &[f64]) -> Matrix {
+    match *transform {
+        [a, b, c, d, tx, ty] => Matrix([a, b, c, d, tx, ty]),
+        [angle, tx, ty] => {
+            let (sin, cos) = angle.sin_cos();
+            Matrix([cos, sin, -sin, cos, tx, ty])
+        }
+        [tx, ty] => Matrix([1.0, 0.0, 0.0, 1.0, tx, ty]),
+        _ => Matrix::IDENTITY,
+    }
+}
+
+/// Placement of an image's bitmap inside the image symbol
+pub fn image_matrix(info: &ImageInfo) -> Matrix {
+    transform_matrix(&info.transform)
+}
@@ -0,0 +1,342 @@
+use crate::render::transform_matrix;
+use crate::types::{AddsInfo, FrameInfo, ImageInfo, MovesInfo, PamInfo, RemovesInfo, SpriteInfo};
+use serde::{Deserialize, Serialize};
+
+// Sen's `.pam.json` layout: named fields instead of positional arrays, change
+// transforms as full matrices and the image name split into its path and id.
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenAnimation {
+    pub version: i32,
+    pub frame_rate: i32,
+    pub position: SenPoint,
+    pub size: SenSize,
+    #[serde(default)]
+    pub image: Vec<SenImage>,
+    #[serde(default)]
+    pub sprite: Vec<SenSprite>,
+    #[serde(default)]
+    pub main_sprite: SenSprite,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SenPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SenSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenImage {
+    pub path: String,
+    #[serde(default)]
+    pub id: String,
+    pub dimension: SenDimension,
+    pub transform: [f64; 6],
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SenDimension {
+    pub width: i32,
+    pub height: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SenSprite {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub frame_rate: f64,
+    #[serde(default)]
+    pub work_area: SenWorkArea,
+    #[serde(default)]
+    pub frame: Vec<SenFrame>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct SenWorkArea {
+    pub start: i32,
+    pub duration: i32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SenFrame {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub stop: bool,
+    #[serde(default)]
+    pub command: Vec<SenCommand>,
+    #[serde(default)]
+    pub remove: Vec<SenRemove>,
+    #[serde(default)]
+    pub append: Vec<SenAppend>,
+    #[serde(default)]
+    pub change: Vec<SenChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenCommand {
+    pub command: String,
+    #[serde(default)]
+    pub parameter: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenRemove {
+    pub index: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenAppend {
+    pub index: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub resource: i32,
+    #[serde(default)]
+    pub sprite: bool,
+    #[serde(default)]
+    pub additive: bool,
+    #[serde(default)]
+    pub preload_frame: i32,
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f32,
+}
+
+fn default_time_scale() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenChange {
+    pub index: i32,
+    pub transform: Vec<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<[f64; 4]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_rectangle: Option<[i32; 4]>,
+    #[serde(default)]
+    pub sprite_frame_number: i32,
+}
+
+pub fn convert_to_sen(pam: &PamInfo) -> SenAnimation {
+    SenAnimation {
+        version: pam.version,
+        frame_rate: pam.frame_rate,
+        position: SenPoint {
+            x: pam.position[0],
+            y: pam.position[1],
+        },
+        size: SenSize {
+            width: pam.size[0],
+            height: pam.size[1],
+        },
+        image: pam.image.iter().map(image_to_sen).collect(),
+        sprite: pam.sprite.iter().map(sprite_to_sen).collect(),
+        main_sprite: sprite_to_sen(&pam.main_sprite),
+    }
+}
+
+pub fn convert_from_sen(sen: &SenAnimation) -> PamInfo {
+    PamInfo {
+        version: sen.version,
+        frame_rate: sen.frame_rate,
+        position: [sen.position.x, sen.position.y],
+        size: [sen.size.width, sen.size.height],
+        image: sen.image.iter().map(image_from_sen).collect(),
+        sprite: sen.sprite.iter().map(sprite_from_sen).collect(),
+        main_sprite: sprite_from_sen(&sen.main_sprite),
+    }
+}
+
+fn image_to_sen(image: &ImageInfo) -> SenImage {
+    let (path, id) = image.name.split_once('|').unwrap_or((&image.name, ""));
+    SenImage {
+        path: path.to_string(),
+        id: id.to_string(),
+        dimension: SenDimension {
+            width: image.size[0],
+            height: image.size[1],
+        },
+        transform: transform_matrix(&image.transform).0,
+    }
+}
+
+fn image_from_sen(image: &SenImage) -> ImageInfo {
+    ImageInfo {
+        name: if image.id.is_empty() {
+            image.path.clone()
+        } else {
+            format!("{}|{}", image.path, image.id)
+        },
+        size: [image.dimension.width, image.dimension.height],
+        transform: image.transform.to_vec(),
+    }
+}
+
+fn sprite_to_sen(sprite: &SpriteInfo) -> SenSprite {
+    SenSprite {
+        name: sprite.name.clone().unwrap_or_default(),
+        description: sprite.description.clone().unwrap_or_default(),
+        frame_rate: sprite.frame_rate,
+        work_area: SenWorkArea {
+            start: sprite.work_area[0],
+            duration: sprite.work_area[1],
+        },
+        frame: sprite.frame.iter().map(frame_to_sen).collect(),
+    }
+}
+
+fn sprite_from_sen(sprite: &SenSprite) -> SpriteInfo {
+    let non_empty = |s: &String| (!s.is_empty()).then(|| s.clone());
+    SpriteInfo {
+        name: non_empty(&sprite.name),
+        description: non_empty(&sprite.description),
+        frame_rate: sprite.frame_rate,
+        work_area: [sprite.work_area.start, sprite.work_area.duration],
+        frame: sprite.frame.iter().map(frame_from_sen).collect(),
+    }
+}
+
+fn frame_to_sen(frame: &FrameInfo) -> SenFrame {
+    SenFrame {
+        label: frame.label.clone(),
+        stop: frame.stop,
+        command: frame
+            .command
+            .iter()
+            .map(|[command, parameter]| SenCommand {
+                command: command.clone(),
+                parameter: parameter.clone(),
+            })
+            .collect(),
+        remove: frame
+            .remove
+            .iter()
+            .map(|r| SenRemove { index: r.index })
+            .collect(),
+        append: frame
+            .append
+            .iter()
+            .map(|a| SenAppend {
+                index: a.index,
+                name: a.name.clone(),
+                resource: a.resource,
+                sprite: a.sprite,
+                additive: a.additive,
+                preload_frame: a.preload_frame,
+                time_scale: a.time_scale,
+            })
+            .collect(),
+        change: frame
+            .change
+            .iter()
+            .map(|c| SenChange {
+                index: c.index,
+                transform: transform_matrix(&c.transform).0.to_vec(),
+                color: c.color,
+                source_rectangle: c.source_rectangle,
+                sprite_frame_number: c.sprite_frame_number,
+            })
+            .collect(),
+    }
+}
+
+fn frame_from_sen(frame: &SenFrame) -> FrameInfo {
+    FrameInfo {
+        label: frame.label.clone(),
+        stop: frame.stop,
+        command: frame
+            .command
+            .iter()
+            .map(|c| [c.command.clone(), c.parameter.clone()])
+            .collect(),
+        remove: frame
+            .remove
+            .iter()
+            .map(|r| RemovesInfo { index: r.index })
+            .collect(),
+        append: frame
+            .append
+            .iter()
+            .map(|a| AddsInfo {
+                index: a.index,
+                name: a.name.clone(),
+                resource: a.resource,
+                sprite: a.sprite,
+                additive: a.additive,
+                preload_frame: a.preload_frame,
+                time_scale: a.time_scale,
+            })
+            .collect(),
+        change: frame
+            .change
+            .iter()
+            .map(|c| MovesInfo {
+                index: c.index,
+                transform: compact_transform(&c.transform),
+                color: c.color,
+                source_rectangle: c.source_rectangle,
+                sprite_frame_number: c.sprite_frame_number,
+            })
+            .collect(),
+    }
+}
+
+/// Shortest PAM form of a change transform: `[tx, ty]` for translations,
+/// `[angle, tx, ty]` for rotations the binary angle (thousandths of a radian) holds
+/// exactly, the full matrix otherwise
+fn compact_transform(transform: &[f64]) -> Vec<f64> {
+    const EPSILON: f64 = 1e-6;
+    let &[a, b, c, d, tx, ty] = transform else {
+        return transform.to_vec();
+    };
+    let near = |x: f64, y: f64| (x - y).abs() < EPSILON;
+    let angle = b.atan2(a);
+    let rounded = (angle * 1000.0).round() / 1000.0;
+    if near(a, 1.0) && near(b, 0.0) && near(c, 0.0) && near(d, 1.0) {
+        vec![tx, ty]
+    } else if near(a, d) && near(b, -c) && near(a * a + b * b, 1.0) && near(angle, rounded) {
+        vec![rounded, tx, ty]
+    } else {
+        transform.to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compact_transform() {
+        // Translation only
+        assert_eq!(
+            compact_transform(&[1.0, 0.0, 0.0, 1.0, 3.0, -4.0]),
+            [3.0, -4.0]
+        );
+        // Rotation by an angle the binary format stores exactly
+        let (sin, cos) = 1.234f64.sin_cos();
+        assert_eq!(
+            compact_transform(&[cos, sin, -sin, cos, 3.0, -4.0]),
+            [1.234, 3.0, -4.0]
+        );
+        // Rotations between thousandths of a radian, scales and skews keep the matrix
+        let (sin, cos) = 1.2345f64.sin_cos();
+        for matrix in [
+            [cos, sin, -sin, cos, 3.0, -4.0],
+            [2.0, 0.0, 0.0, 2.0, 3.0, -4.0],
+            [1.0, 0.5, 0.0, 1.0, 3.0, -4.0],
+        ] {
+            assert_eq!(compact_transform(&matrix), matrix);
+        }
+        // Anything but a full matrix is left alone
+        assert_eq!(compact_transform(&[0.5, 1.0, 2.0]), [0.5, 1.0, 2.0]);
+    }
+}
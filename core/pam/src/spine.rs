@@ -0,0 +1,921 @@
+use crate::media::media_name;
+use crate::render::{DisplayNode, Matrix, NodeKey, PamRenderer, image_matrix};
+use crate::types::{AddsInfo, FrameInfo, ImageInfo, MovesInfo, PamInfo, RemovesInfo, SpriteInfo};
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+const SPINE_VERSION: &str = "4.1.00";
+/// Animation name used when the main sprite has no labels
+const DEFAULT_ANIMATION: &str = "animation";
+const ROOT_BONE: &str = "root";
+
+/// Spine skeleton JSON, limited to what maps onto PAM: bones, slots, region
+/// attachments and bone/slot timelines
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpineSkeleton {
+    pub skeleton: SpineInfo,
+    #[serde(default)]
+    pub bones: Vec<SpineBone>,
+    #[serde(default)]
+    pub slots: Vec<SpineSlot>,
+    #[serde(default)]
+    pub skins: Vec<SpineSkin>,
+    #[serde(default)]
+    pub animations: BTreeMap<String, SpineAnimation>,
+    /// Constraints, events and other sections without a PAM equivalent
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpineInfo {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash: Option<String>,
+    #[serde(default)]
+    pub spine: String,
+    #[serde(default)]
+    pub x: f64,
+    #[serde(default)]
+    pub y: f64,
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub images: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpineBone {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub x: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rotation: f64,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub scale_x: f64,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub scale_y: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shear_x: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub shear_y: f64,
+    /// Inheritance mode; only `normal` can be imported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transform: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpineSlot {
+    pub name: String,
+    pub bone: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blend: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SpineSkin {
+    pub name: String,
+    /// Slot name -> attachment name -> attachment
+    #[serde(default)]
+    pub attachments: BTreeMap<String, BTreeMap<String, SpineAttachment>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpineAttachment {
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+    /// Image name when it differs from the attachment name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub x: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub y: f64,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub rotation: f64,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub scale_x: f64,
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub scale_y: f64,
+    #[serde(default)]
+    pub width: f64,
+    #[serde(default)]
+    pub height: f64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpineAnimation {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub slots: BTreeMap<String, SpineSlotTimeline>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub bones: BTreeMap<String, SpineBoneTimeline>,
+    /// Draw order, deform, constraint and event timelines
+    #[serde(flatten)]
+    pub other: BTreeMap<String, Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpineSlotTimeline {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachment: Vec<SpineAttachmentKey>,
+    /// `color` in Spine 3.x
+    #[serde(default, alias = "color", skip_serializing_if = "Vec::is_empty")]
+    pub rgba: Vec<SpineColorKey>,
+}
+
+/// Unknown timelines, such as the split `translatex` of Spine 4.1, fail to parse
+/// rather than being dropped
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpineBoneTimeline {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rotate: Vec<SpineKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub translate: Vec<SpineKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scale: Vec<SpineKey>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub shear: Vec<SpineKey>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpineKey {
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub time: f64,
+    /// Rotation in degrees; `angle` in Spine 3.x
+    #[serde(default, alias = "angle", skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub curve: Option<Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpineAttachmentKey {
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub time: f64,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SpineColorKey {
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub time: f64,
+    pub color: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub curve: Option<Value>,
+}
+
+fn is_zero(v: &f64) -> bool {
+    *v == 0.0
+}
+
+fn is_one(v: &f64) -> bool {
+    *v == 1.0
+}
+
+fn one() -> f64 {
+    1.0
+}
+
+/// Local bone transform in Spine terms
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Pose {
+    x: f64,
+    y: f64,
+    rotation: f64,
+    scale_x: f64,
+    scale_y: f64,
+    shear_x: f64,
+    shear_y: f64,
+}
+
+impl Pose {
+    /// Split an affine matrix into rotation, scale and Y shear
+    fn from_matrix(m: &Matrix) -> Self {
+        let [a, b, c, d, tx, ty] = m.0;
+        let rotation = b.atan2(a).to_degrees();
+        let mut shear_y = d.atan2(c).to_degrees() - rotation - 90.0;
+        while shear_y > 180.0 {
+            shear_y -= 360.0;
+        }
+        while shear_y <= -180.0 {
+            shear_y += 360.0;
+        }
+        Pose {
+            x: round(tx),
+            y: round(ty),
+            rotation: round(rotation),
+            scale_x: round(a.hypot(b)),
+            scale_y: round(c.hypot(d)),
+            shear_x: 0.0,
+            shear_y: round(shear_y),
+        }
+    }
+
+    fn to_matrix(self) -> Matrix {
+        let (sin_x, cos_x) = (self.rotation + self.shear_x).to_radians().sin_cos();
+        let (sin_y, cos_y) = (self.rotation + 90.0 + self.shear_y).to_radians().sin_cos();
+        Matrix([
+            cos_x * self.scale_x,
+            sin_x * self.scale_x,
+            cos_y * self.scale_y,
+            sin_y * self.scale_y,
+            self.x,
+            self.y,
+        ])
+    }
+}
+
+/// Round away float noise so unchanged values compare equal and print short
+fn round(v: f64) -> f64 {
+    let r = (v * 1e6).round() / 1e6;
+    if r == 0.0 { 0.0 } else { r }
+}
+
+/// Convert between PAM's y-down and Spine's y-up space
+fn flip(m: &Matrix) -> Matrix {
+    let [a, b, c, d, tx, ty] = m.0;
+    Matrix([a, -b, -c, d, tx, -ty])
+}
+
+fn stepped() -> Option<Value> {
+    Some(Value::String("stepped".to_string()))
+}
+
+fn color_hex(color: &[f64; 4]) -> String {
+    color
+        .iter()
+        .map(|c| format!("{:02x}", (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        .collect()
+}
+
+fn parse_color(hex: &str) -> Result<[f64; 4]> {
+    let channel = |i: usize| -> Result<f64> {
+        let byte = hex
+            .get(i * 2..i * 2 + 2)
+            .map_or(Ok(255), |s| u8::from_str_radix(s, 16))
+            .with_context(|| format!("Invalid colour {}", hex))?;
+        Ok(byte as f64 / 255.0)
+    };
+    Ok([channel(0)?, channel(1)?, channel(2)?, channel(3)?])
+}
+
+/// Bake `pam` into a Spine skeleton. Every element of the display tree becomes a bone
+/// under its sprite's bone, every image element also a slot with a region attachment,
+/// and every label of the main sprite an animation keyed frame by frame with stepped
+/// curves. Bitmaps are assumed to be exported at `resolution`, like for FLA.
+pub fn convert_to_spine(pam: &PamInfo, resolution: i32) -> Result<SpineSkeleton> {
+    let renderer = PamRenderer::new(pam, Vec::new());
+    let frame_rate = if pam.frame_rate > 0 {
+        pam.frame_rate as f64
+    } else {
+        30.0
+    };
+    let bitmap_scale = 1200.0 / resolution.max(1) as f64;
+
+    let mut ranges = renderer.labels();
+    if ranges.is_empty() {
+        ranges.push((DEFAULT_ANIMATION.to_string(), 0..renderer.frame_count()));
+    }
+
+    let frames: Vec<HashMap<Vec<NodeKey>, DisplayNode>> = (0..renderer.frame_count())
+        .map(|frame| {
+            renderer
+                .display_list(frame)
+                .into_iter()
+                .map(|node| (node.path.clone(), node))
+                .collect()
+        })
+        .collect();
+
+    // Sorted paths put parents before children and follow the drawing order
+    let paths: BTreeSet<Vec<NodeKey>> = ranges
+        .iter()
+        .flat_map(|(_, range)| range.clone())
+        .flat_map(|frame| frames[frame].keys().cloned())
+        .collect();
+    let mut names: HashMap<Vec<NodeKey>, String> = HashMap::new();
+    for path in &paths {
+        let name = node_name(pam, path.last().unwrap());
+        let name = match names.get(&path[..path.len() - 1]) {
+            Some(parent) => format!("{}/{}", parent, name),
+            None => name,
+        };
+        names.insert(path.clone(), name);
+    }
+
+    let mut bones = vec![SpineBone::named(ROOT_BONE, None)];
+    let mut slots = Vec::new();
+    let mut attachments = BTreeMap::new();
+    for path in &paths {
+        let name = &names[path];
+        let parent = names
+            .get(&path[..path.len() - 1])
+            .map_or(ROOT_BONE, String::as_str);
+        bones.push(SpineBone::named(name, Some(parent)));
+
+        let key = path.last().unwrap();
+        if key.sprite {
+            continue;
+        }
+        let image = image_of(pam, key)?;
+        let [width, height] = image.size;
+        if width <= 0 || height <= 0 {
+            bail!(
+                "Image {} has no size, which Spine attachments need",
+                image.name
+            );
+        }
+        let additive = ranges
+            .iter()
+            .flat_map(|(_, range)| range.clone())
+            .any(|frame| frames[frame].get(path).is_some_and(|n| n.additive));
+        slots.push(SpineSlot {
+            name: name.clone(),
+            bone: name.clone(),
+            color: None,
+            attachment: None,
+            blend: additive.then(|| "additive".to_string()),
+        });
+        // The bitmap's top left corner sits at the bone origin
+        let attachment_name = image.resource_id().to_string();
+        let file = media_name(image);
+        let attachment = SpineAttachment {
+            type_: None,
+            path: (file != attachment_name).then(|| file.to_string()),
+            x: round(width as f64 * bitmap_scale / 2.0),
+            y: round(-(height as f64) * bitmap_scale / 2.0),
+            rotation: 0.0,
+            scale_x: round(bitmap_scale),
+            scale_y: round(bitmap_scale),
+            width: width as f64,
+            height: height as f64,
+        };
+        attachments.insert(
+            name.clone(),
+            BTreeMap::from([(attachment_name, attachment)]),
+        );
+    }
+
+    let mut animations = BTreeMap::new();
+    for (label, range) in &ranges {
+        let time = |frame: usize| round((frame - range.start) as f64 / frame_rate);
+        let last = range.end.saturating_sub(1);
+        let mut animation = SpineAnimation::default();
+
+        for path in &paths {
+            let name = &names[path];
+            let key = path.last().unwrap();
+            let mut timeline = SpineBoneTimeline::default();
+            let mut previous: Option<Pose> = None;
+            for frame in range.clone() {
+                let Some(node) = frames[frame].get(path) else {
+                    continue;
+                };
+                let local = if key.sprite {
+                    node.transform
+                } else {
+                    node.transform.then(&image_matrix(image_of(pam, key)?))
+                };
+                let pose = Pose::from_matrix(&flip(&local));
+                let changed = |f: fn(&Pose) -> [f64; 2]| previous.is_none_or(|p| f(&p) != f(&pose));
+                if changed(|p| [p.rotation, 0.0]) {
+                    timeline.rotate.push(SpineKey {
+                        time: time(frame),
+                        value: Some(pose.rotation),
+                        curve: stepped(),
+                        ..Default::default()
+                    });
+                }
+                if changed(|p| [p.x, p.y]) {
+                    timeline
+                        .translate
+                        .push(SpineKey::xy(time(frame), pose.x, pose.y));
+                }
+                if changed(|p| [p.scale_x, p.scale_y]) {
+                    timeline
+                        .scale
+                        .push(SpineKey::xy(time(frame), pose.scale_x, pose.scale_y));
+                }
+                if changed(|p| [p.shear_x, p.shear_y]) {
+                    timeline
+                        .shear
+                        .push(SpineKey::xy(time(frame), pose.shear_x, pose.shear_y));
+                }
+                previous = Some(pose);
+            }
+            if !timeline.rotate.is_empty() {
+                animation.bones.insert(name.clone(), timeline);
+            }
+
+            if key.sprite {
+                continue;
+            }
+            let attachment_name = image_of(pam, key)?.resource_id();
+            let mut timeline = SpineSlotTimeline::default();
+            let mut shown: Option<Option<&str>> = None;
+            let mut tint: Option<[f64; 4]> = None;
+            for frame in range.clone() {
+                let visible = frames[frame].contains_key(path);
+                let current = visible.then_some(attachment_name);
+                // Keying the last frame keeps the animation's length
+                if shown != Some(current) || frame == last {
+                    timeline.attachment.push(SpineAttachmentKey {
+                        time: time(frame),
+                        name: current.map(str::to_string),
+                    });
+                    shown = Some(current);
+                }
+                if !visible {
+                    continue;
+                }
+                // Spine slots don't inherit colour, so bake in the sprites' tints
+                let color = (1..=path.len()).fold([1.0; 4], |acc, len| {
+                    let c = frames[frame][&path[..len]].color;
+                    [acc[0] * c[0], acc[1] * c[1], acc[2] * c[2], acc[3] * c[3]]
+                });
+                let color = color.map(round);
+                if tint != Some(color) {
+                    timeline.rgba.push(SpineColorKey {
+                        time: time(frame),
+                        color: color_hex(&color),
+                        curve: stepped(),
+                    });
+                    tint = Some(color);
+                }
+            }
+            animation.slots.insert(name.clone(), timeline);
+        }
+
+        let mut name = label.clone();
+        let mut n = 2;
+        while animations.contains_key(&name) {
+            name = format!("{}_{}", label, n);
+            n += 1;
+        }
+        animations.insert(name, animation);
+    }
+
+    Ok(SpineSkeleton {
+        skeleton: SpineInfo {
+            hash: None,
+            spine: SPINE_VERSION.to_string(),
+            x: pam.position[0],
+            y: -(pam.position[1] + pam.size[1]),
+            width: pam.size[0],
+            height: pam.size[1],
+            fps: Some(frame_rate),
+            images: Some("./images/".to_string()),
+        },
+        bones,
+        slots,
+        skins: vec![SpineSkin {
+            name: "default".to_string(),
+            attachments,
+        }],
+        animations,
+        other: BTreeMap::new(),
+    })
+}
+
+fn image_of<'a>(pam: &'a PamInfo, key: &NodeKey) -> Result<&'a ImageInfo> {
+    pam.image
+        .get(key.resource as usize)
+        .with_context(|| format!("Image {} is out of range", key.resource))
+}
+
+fn node_name(pam: &PamInfo, key: &NodeKey) -> String {
+    let resource = if key.sprite {
+        pam.sprite
+            .get(key.resource as usize)
+            .and_then(|s| s.name.as_deref())
+            .filter(|name| !name.is_empty())
+            .map_or_else(|| format!("sprite_{}", key.resource), str::to_string)
+    } else {
+        pam.image.get(key.resource as usize).map_or_else(
+            || format!("image_{}", key.resource),
+            |i| i.resource_id().to_string(),
+        )
+    };
+    format!("{}_{}", key.index, resource)
+}
+
+impl SpineBone {
+    fn named(name: &str, parent: Option<&str>) -> Self {
+        SpineBone {
+            name: name.to_string(),
+            parent: parent.map(str::to_string),
+            x: 0.0,
+            y: 0.0,
+            rotation: 0.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            shear_x: 0.0,
+            shear_y: 0.0,
+            transform: None,
+        }
+    }
+
+    fn pose(&self) -> Pose {
+        Pose {
+            x: self.x,
+            y: self.y,
+            rotation: self.rotation,
+            scale_x: self.scale_x,
+            scale_y: self.scale_y,
+            shear_x: self.shear_x,
+            shear_y: self.shear_y,
+        }
+    }
+}
+
+impl SpineKey {
+    fn xy(time: f64, x: f64, y: f64) -> Self {
+        SpineKey {
+            time,
+            x: Some(x),
+            y: Some(y),
+            curve: stepped(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Value of a keyed timeline at `time`: stepped keys hold, all others interpolate
+/// linearly (Bezier curves are approximated as linear). Key times are compared with
+/// some slack since exported times are rounded.
+fn sample_keys<T, V>(
+    keys: &[T],
+    time: f64,
+    key_time: impl Fn(&T) -> f64,
+    value: impl Fn(&T) -> V,
+    curve: impl Fn(&T) -> Option<&Value>,
+    lerp: impl Fn(&V, &V, f64) -> V,
+) -> Option<V> {
+    let next = keys.iter().position(|k| key_time(k) > time + 1e-4);
+    let current = match next {
+        Some(0) => return None,
+        Some(i) => i - 1,
+        None => keys.len().checked_sub(1)?,
+    };
+    let from = value(&keys[current]);
+    let Some(next) = next else {
+        return Some(from);
+    };
+    let is_stepped = matches!(curve(&keys[current]), Some(Value::String(s)) if s == "stepped");
+    if is_stepped {
+        return Some(from);
+    }
+    let (t0, t1) = (key_time(&keys[current]), key_time(&keys[next]));
+    let t = if t1 > t0 {
+        (time - t0) / (t1 - t0)
+    } else {
+        0.0
+    };
+    Some(lerp(&from, &value(&keys[next]), t))
+}
+
+fn sample_pair(keys: &[SpineKey], time: f64, default: f64) -> Option<[f64; 2]> {
+    sample_keys(
+        keys,
+        time,
+        |k| k.time,
+        |k| [k.x.unwrap_or(default), k.y.unwrap_or(default)],
+        |k| k.curve.as_ref(),
+        |a, b, t| [a[0] + (b[0] - a[0]) * t, a[1] + (b[1] - a[1]) * t],
+    )
+}
+
+/// A slot's attachment turned into a PAM layer
+struct LayerDef {
+    slot: usize,
+    attachment: String,
+    image: usize,
+    additive: bool,
+}
+
+/// Convert a Spine skeleton back to PAM. Slot attachments become layers of a single
+/// main sprite whose transforms are the baked world transforms of their bones, and each
+/// animation becomes a label ending in a stop frame. Features without a PAM equivalent
+/// (meshes, constraints, deform and draw order timelines, non-default bone inheritance)
+/// are rejected rather than approximated.
+pub fn convert_from_spine(spine: &SpineSkeleton, resolution: i32) -> Result<PamInfo> {
+    for (section, value) in &spine.other {
+        if is_used(value) && section != "events" {
+            bail!("Spine section '{}' has no PAM equivalent", section);
+        }
+    }
+    let frame_rate = spine.skeleton.fps.filter(|fps| *fps > 0.0).unwrap_or(30.0);
+    let bitmap_scale = 1200.0 / resolution.max(1) as f64;
+
+    let mut bone_index: HashMap<&str, usize> = HashMap::new();
+    let mut parents = Vec::new();
+    for (i, bone) in spine.bones.iter().enumerate() {
+        if bone.transform.as_deref().is_some_and(|t| t != "normal") {
+            bail!(
+                "Bone {} uses transform mode {:?}",
+                bone.name,
+                bone.transform
+            );
+        }
+        let parent =
+            match &bone.parent {
+                Some(parent) => Some(*bone_index.get(parent.as_str()).with_context(|| {
+                    format!("Bone {} has unknown parent {}", bone.name, parent)
+                })?),
+                None => None,
+            };
+        parents.push(parent);
+        bone_index.insert(&bone.name, i);
+    }
+    let mut slot_bones = Vec::new();
+    for slot in &spine.slots {
+        slot_bones.push(
+            *bone_index
+                .get(slot.bone.as_str())
+                .with_context(|| format!("Slot {} has unknown bone {}", slot.name, slot.bone))?,
+        );
+    }
+
+    // Images and layers from the default skin, in slot order
+    let skin = spine
+        .skins
+        .iter()
+        .find(|s| s.name == "default")
+        .or_else(|| spine.skins.first());
+    let mut images: Vec<ImageInfo> = Vec::new();
+    let mut layers: Vec<LayerDef> = Vec::new();
+    for (slot_index, slot) in spine.slots.iter().enumerate() {
+        let Some(slot_attachments) = skin.and_then(|s| s.attachments.get(&slot.name)) else {
+            continue;
+        };
+        for (name, attachment) in slot_attachments {
+            if attachment.type_.as_deref().is_some_and(|t| t != "region") {
+                bail!(
+                    "Attachment {} of slot {} is a {:?}, only regions map to PAM images",
+                    name,
+                    slot.name,
+                    attachment.type_
+                );
+            }
+            let image = attachment_image(name, attachment, bitmap_scale);
+            let index = match images.iter().position(|i| {
+                i.name == image.name && i.size == image.size && i.transform == image.transform
+            }) {
+                Some(index) => index,
+                None => {
+                    images.push(image);
+                    images.len() - 1
+                }
+            };
+            layers.push(LayerDef {
+                slot: slot_index,
+                attachment: name.clone(),
+                image: index,
+                additive: slot.blend.as_deref() == Some("additive"),
+            });
+        }
+    }
+
+    let setup_colors = spine
+        .slots
+        .iter()
+        .map(|slot| slot.color.as_deref().map_or(Ok([1.0; 4]), parse_color))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut frames: Vec<FrameInfo> = Vec::new();
+    let mut previous: Vec<Option<(Matrix, [f64; 4])>> = vec![None; layers.len()];
+    for (label, animation) in &spine.animations {
+        for (section, value) in &animation.other {
+            if is_used(value) && section != "events" {
+                bail!(
+                    "Animation {} uses {} timelines, which have no PAM equivalent",
+                    label,
+                    section
+                );
+            }
+        }
+        let duration = animation_duration(animation);
+        let frame_count = (duration * frame_rate).round() as usize + 1;
+        let first_frame = frames.len();
+
+        for i in 0..frame_count {
+            let time = i as f64 / frame_rate;
+
+            // World transforms in Spine space; parents come before children
+            let mut world: Vec<Matrix> = Vec::with_capacity(spine.bones.len());
+            for (b, bone) in spine.bones.iter().enumerate() {
+                let mut pose = bone.pose();
+                if let Some(timeline) = animation.bones.get(&bone.name) {
+                    if let Some(angle) = sample_keys(
+                        &timeline.rotate,
+                        time,
+                        |k| k.time,
+                        |k| k.value.unwrap_or(0.0),
+                        |k| k.curve.as_ref(),
+                        |a, b, t| a + (b - a) * t,
+                    ) {
+                        pose.rotation += angle;
+                    }
+                    if let Some([x, y]) = sample_pair(&timeline.translate, time, 0.0) {
+                        pose.x += x;
+                        pose.y += y;
+                    }
+                    if let Some([x, y]) = sample_pair(&timeline.scale, time, 1.0) {
+                        pose.scale_x *= x;
+                        pose.scale_y *= y;
+                    }
+                    if let Some([x, y]) = sample_pair(&timeline.shear, time, 0.0) {
+                        pose.shear_x += x;
+                        pose.shear_y += y;
+                    }
+                }
+                let local = pose.to_matrix();
+                world.push(match parents[b] {
+                    Some(parent) => world[parent].then(&local),
+                    None => local,
+                });
+            }
+
+            let mut frame = FrameInfo::default();
+            if i == 0 {
+                frame.label = Some(label.clone());
+            }
+            frame.stop = i + 1 == frame_count;
+            for (l, layer) in layers.iter().enumerate() {
+                let slot = &spine.slots[layer.slot];
+                let timeline = animation.slots.get(&slot.name);
+                let attachment = timeline
+                    .and_then(|t| {
+                        sample_keys(
+                            &t.attachment,
+                            time,
+                            |k| k.time,
+                            |k| k.name.clone(),
+                            |_| None,
+                            |a, _, _| a.clone(),
+                        )
+                    })
+                    .unwrap_or_else(|| slot.attachment.clone());
+                let state = if attachment.as_deref() == Some(layer.attachment.as_str()) {
+                    let mut color = setup_colors[layer.slot];
+                    if let Some(keys) = timeline.map(|t| &t.rgba) {
+                        let sampled = sample_keys(
+                            keys,
+                            time,
+                            |k| k.time,
+                            |k| parse_color(&k.color).unwrap_or([1.0; 4]),
+                            |k| k.curve.as_ref(),
+                            |a, b, t| std::array::from_fn(|c| a[c] + (b[c] - a[c]) * t),
+                        );
+                        if let Some(sampled) = sampled {
+                            color = std::array::from_fn(|c| color[c] * sampled[c]);
+                        }
+                    }
+                    let transform = flip(&world[slot_bones[layer.slot]]);
+                    Some((Matrix(transform.0.map(round)), color.map(round)))
+                } else {
+                    None
+                };
+
+                match (&previous[l], &state) {
+                    (Some(_), None) => frame.remove.push(RemovesInfo { index: l as i32 }),
+                    (None, Some((transform, color))) => {
+                        frame.append.push(AddsInfo {
+                            index: l as i32,
+                            name: None,
+                            resource: layer.image as i32,
+                            sprite: false,
+                            additive: layer.additive,
+                            preload_frame: 0,
+                            time_scale: 1.0,
+                        });
+                        frame.change.push(MovesInfo {
+                            index: l as i32,
+                            transform: transform.0.to_vec(),
+                            color: (*color != [1.0; 4]).then_some(*color),
+                            source_rectangle: None,
+                            sprite_frame_number: 0,
+                        });
+                    }
+                    (Some((old_transform, old_color)), Some((transform, color)))
+                        if old_transform != transform || old_color != color =>
+                    {
+                        frame.change.push(MovesInfo {
+                            index: l as i32,
+                            transform: transform.0.to_vec(),
+                            color: (old_color != color).then_some(*color),
+                            source_rectangle: None,
+                            sprite_frame_number: 0,
+                        });
+                    }
+                    _ => {}
+                }
+                previous[l] = state;
+            }
+            frames.push(frame);
+        }
+        if frames.len() == first_frame {
+            bail!("Animation {} has no frames", label);
+        }
+    }
+
+    let frame_total = frames.len() as i32;
+    Ok(PamInfo {
+        version: 6,
+        frame_rate: frame_rate.round() as i32,
+        position: [
+            spine.skeleton.x,
+            -(spine.skeleton.y + spine.skeleton.height),
+        ],
+        size: [spine.skeleton.width, spine.skeleton.height],
+        image: images,
+        sprite: Vec::new(),
+        main_sprite: SpriteInfo {
+            name: None,
+            description: None,
+            frame_rate,
+            work_area: [0, frame_total],
+            frame: frames,
+        },
+    })
+}
+
+/// PAM image for a region attachment: the attachment's placement without the bitmap scale
+fn attachment_image(name: &str, attachment: &SpineAttachment, bitmap_scale: f64) -> ImageInfo {
+    let (width, height) = (attachment.width, attachment.height);
+    let placement = Pose {
+        x: attachment.x,
+        y: attachment.y,
+        rotation: attachment.rotation,
+        scale_x: attachment.scale_x,
+        scale_y: attachment.scale_y,
+        shear_x: 0.0,
+        shear_y: 0.0,
+    }
+    .to_matrix()
+    // Bitmap pixels (y down, from the top left) to the region's centred y-up space
+    .then(&Matrix([1.0, 0.0, 0.0, -1.0, -width / 2.0, height / 2.0]));
+    // Back to y down; the layer transform takes care of the bone's side of the flip
+    let transform = Matrix::scale(1.0, -1.0)
+        .then(&placement)
+        .then(&Matrix::scale(1.0 / bitmap_scale, 1.0 / bitmap_scale));
+    let file = attachment.path.as_deref().unwrap_or(name);
+    ImageInfo {
+        name: if file == name {
+            name.to_string()
+        } else {
+            format!("{}|{}", file, name)
+        },
+        size: [width.round() as i32, height.round() as i32],
+        transform: transform.0.map(round).to_vec(),
+    }
+}
+
+fn animation_duration(animation: &SpineAnimation) -> f64 {
+    let bones = animation.bones.values().flat_map(|t| {
+        t.rotate
+            .iter()
+            .chain(&t.translate)
+            .chain(&t.scale)
+            .chain(&t.shear)
+            .map(|k| k.time)
+    });
+    let slots = animation.slots.values().flat_map(|t| {
+        t.attachment
+            .iter()
+            .map(|k| k.time)
+            .chain(t.rgba.iter().map(|k| k.time))
+    });
+    bones.chain(slots).fold(0.0, f64::max)
+}
+
+/// Whether an unsupported section actually holds anything
+fn is_used(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        _ => true,
+    }
+}
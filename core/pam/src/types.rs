@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 pub const PAM_MAGIC: u32 = 0xBAF01954;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PamInfo {
     pub version: i32,
     pub frame_rate: i32,
@@ -13,14 +13,31 @@ pub struct PamInfo {
     pub main_sprite: SpriteInfo,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageInfo {
     pub name: String,
     pub size: [i32; 2],
     pub transform: Vec<f64>, // Using Vec because length varies
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+impl ImageInfo {
+    /// Resource id of the image: the part of `name` after `|`, or the whole name
+    pub fn resource_id(&self) -> &str {
+        self.name
+            .split_once('|')
+            .map_or(self.name.as_str(), |(_, id)| id)
+    }
+
+    /// Replace the resource id, keeping the image file part of `name`
+    pub fn set_resource_id(&mut self, id: &str) {
+        self.name = match self.name.split_once('|') {
+            Some((file, _)) => format!("{}|{}", file, id),
+            None => id.to_string(),
+        };
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SpriteInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
@@ -31,7 +48,7 @@ pub struct SpriteInfo {
     pub frame: Vec<FrameInfo>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FrameInfo {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub label: Option<String>,
@@ -47,12 +64,12 @@ pub struct FrameInfo {
     pub change: Vec<MovesInfo>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemovesInfo {
     pub index: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AddsInfo {
     pub index: i32,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -64,7 +81,7 @@ pub struct AddsInfo {
     pub time_scale: f32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MovesInfo {
     pub index: i32,
     pub transform: Vec<f64>,
@@ -0,0 +1,272 @@
+use crate::edit::SpriteRef;
+use crate::types::{PamInfo, SpriteInfo};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The PAM can't be played back as written
+    Error,
+    /// The PAM is playable but a value will be lost or clamped when encoded
+    Warning,
+}
+
+/// Problem found by `PamInfo::validate`, with where it happens
+#[derive(Debug, Clone, PartialEq)]
+pub struct PamIssue {
+    pub severity: Severity,
+    /// Sprite the issue is in, `None` for the header and the image table
+    pub sprite: Option<SpriteRef>,
+    pub sprite_name: Option<String>,
+    pub frame: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for PamIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: ")?,
+            Severity::Warning => write!(f, "warning: ")?,
+        }
+        match self.sprite {
+            Some(SpriteRef::Main) => write!(f, "main sprite")?,
+            Some(SpriteRef::Sprite(index)) => write!(f, "sprite {}", index)?,
+            None => {}
+        }
+        if let Some(name) = self.sprite_name.as_deref().filter(|n| !n.is_empty()) {
+            write!(f, " '{}'", name)?;
+        }
+        if let Some(frame) = self.frame {
+            write!(f, ", frame {}", frame)?;
+        }
+        if self.sprite.is_some() {
+            write!(f, ": ")?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Largest value a u16 in twentieths of a pixel holds
+const MAX_POSITION: f64 = u16::MAX as f64 / 20.0;
+
+impl PamInfo {
+    /// Check the PAM by replaying the display list of every sprite frame by frame.
+    /// Reports dangling resource and layer indices, label collisions, nesting cycles and
+    /// values the binary format can't hold as errors. Appends over a displayed layer and
+    /// moves of a hidden one play back fine but are likely mistakes, so they only warn.
+    pub fn validate(&self) -> Vec<PamIssue> {
+        let mut issues = Vec::new();
+        let mut header = |severity, message: String| {
+            issues.push(PamIssue {
+                severity,
+                sprite: None,
+                sprite_name: None,
+                frame: None,
+                message,
+            })
+        };
+
+        if !(1..=6).contains(&self.version) {
+            header(
+                Severity::Error,
+                format!(
+                    "version {} is not supported (expected 1 to 6)",
+                    self.version
+                ),
+            );
+        }
+        if !(1..=255).contains(&self.frame_rate) {
+            header(
+                Severity::Error,
+                format!("frame rate {} is out of range (1 to 255)", self.frame_rate),
+            );
+        }
+        for (what, values) in [("position", self.position), ("size", self.size)] {
+            if values.iter().any(|v| !(0.0..=MAX_POSITION).contains(v)) {
+                header(
+                    Severity::Warning,
+                    format!(
+                        "{} {:?} is clamped to 0 to {} when encoded",
+                        what, values, MAX_POSITION
+                    ),
+                );
+            }
+        }
+        for (index, image) in self.image.iter().enumerate() {
+            if image.transform.len() != 6 {
+                header(
+                    Severity::Error,
+                    format!(
+                        "image {} ({}) has a transform of {} values, expected 6",
+                        index,
+                        image.name,
+                        image.transform.len()
+                    ),
+                );
+            }
+        }
+
+        let sprites = self
+            .sprite
+            .iter()
+            .enumerate()
+            .map(|(i, s)| (SpriteRef::Sprite(i), s))
+            .chain(Some((SpriteRef::Main, &self.main_sprite)));
+        for (sprite_ref, sprite) in sprites {
+            self.validate_sprite(sprite_ref, sprite, &mut issues);
+        }
+        self.validate_nesting(&mut issues);
+        issues
+    }
+
+    /// True if `validate` finds no errors; warnings are allowed
+    pub fn is_valid(&self) -> bool {
+        self.validate()
+            .iter()
+            .all(|issue| issue.severity == Severity::Warning)
+    }
+
+    fn validate_sprite(
+        &self,
+        sprite_ref: SpriteRef,
+        sprite: &SpriteInfo,
+        issues: &mut Vec<PamIssue>,
+    ) {
+        let mut report = |severity, frame: Option<usize>, message: String| {
+            issues.push(PamIssue {
+                severity,
+                sprite: Some(sprite_ref),
+                sprite_name: sprite.name.clone(),
+                frame,
+                message,
+            })
+        };
+
+        let frame_count = sprite.frame.len() as i32;
+        let [start, end] = sprite.work_area;
+        if start < 0 || start > end || end > frame_count {
+            report(
+                Severity::Warning,
+                None,
+                format!(
+                    "work area [{}, {}] is outside the {} frames",
+                    start, end, frame_count
+                ),
+            );
+        }
+
+        let mut displayed = HashSet::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        for (index, frame) in sprite.frame.iter().enumerate() {
+            let at = Some(index);
+            for remove in &frame.remove {
+                if !displayed.remove(&remove.index) {
+                    report(
+                        Severity::Error,
+                        at,
+                        format!("removes layer {} which is not displayed", remove.index),
+                    );
+                }
+            }
+            for append in &frame.append {
+                let (kind, count) = if append.sprite {
+                    ("sprite", self.sprite.len())
+                } else {
+                    ("image", self.image.len())
+                };
+                if append.resource < 0 || append.resource as usize >= count {
+                    report(
+                        Severity::Error,
+                        at,
+                        format!(
+                            "layer {} shows {} {} but there are {} {}s",
+                            append.index, kind, append.resource, count, kind
+                        ),
+                    );
+                }
+                // Playback replaces the displayed layer, like a remove before the append
+                if !displayed.insert(append.index) {
+                    report(
+                        Severity::Warning,
+                        at,
+                        format!(
+                            "appends layer {} which is already displayed, replacing it",
+                            append.index
+                        ),
+                    );
+                }
+            }
+            for change in &frame.change {
+                if !displayed.contains(&change.index) {
+                    report(
+                        Severity::Warning,
+                        at,
+                        format!(
+                            "moves layer {} which is not displayed; the move is ignored",
+                            change.index
+                        ),
+                    );
+                }
+                if ![2, 3, 6].contains(&change.transform.len()) {
+                    report(
+                        Severity::Error,
+                        at,
+                        format!(
+                            "layer {} has a transform of {} values, expected 2, 3 or 6",
+                            change.index,
+                            change.transform.len()
+                        ),
+                    );
+                }
+            }
+            if let Some(label) = &frame.label
+                && let Some(first) = labels.insert(label, index)
+            {
+                report(
+                    Severity::Error,
+                    at,
+                    format!("label '{}' is already used on frame {}", label, first),
+                );
+            }
+        }
+    }
+
+    /// Sprites that show themselves, directly or through other sprites, never finish
+    /// drawing
+    fn validate_nesting(&self, issues: &mut Vec<PamIssue>) {
+        let children: Vec<HashSet<usize>> = self
+            .sprite
+            .iter()
+            .map(|sprite| {
+                sprite
+                    .frame
+                    .iter()
+                    .flat_map(|f| f.append.iter())
+                    .filter(|append| append.sprite && append.resource >= 0)
+                    .map(|append| append.resource as usize)
+                    .filter(|&resource| resource < self.sprite.len())
+                    .collect()
+            })
+            .collect();
+
+        for (index, sprite) in self.sprite.iter().enumerate() {
+            let mut seen = HashSet::new();
+            let mut pending: Vec<usize> = children[index].iter().copied().collect();
+            while let Some(child) = pending.pop() {
+                if child == index {
+                    issues.push(PamIssue {
+                        severity: Severity::Error,
+                        sprite: Some(SpriteRef::Sprite(index)),
+                        sprite_name: sprite.name.clone(),
+                        frame: None,
+                        message: "contains itself through its nested sprites".to_string(),
+                    });
+                    break;
+                }
+                if seen.insert(child) {
+                    pending.extend(children[child].iter().copied());
+                }
+            }
+        }
+    }
+}
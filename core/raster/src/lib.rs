@@ -0,0 +1,292 @@
+//! Software rasterizing shared by the animation renderers: affine matrices and a
+//! premultiplied canvas that draws bitmaps through them
+
+use image::{Rgba, RgbaImage};
+use std::fmt;
+
+/// Largest canvas, in pixels, the renderers will allocate (16384 x 16384)
+pub const MAX_CANVAS_PIXELS: usize = 1 << 28;
+
+/// A canvas with more than `MAX_CANVAS_PIXELS` pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanvasTooLarge {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for CanvasTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{} canvas is larger than {} pixels",
+            self.width, self.height, MAX_CANVAS_PIXELS
+        )
+    }
+}
+
+impl std::error::Error for CanvasTooLarge {}
+
+/// 2D affine matrix `[a, b, c, d, tx, ty]`, mapping `(x, y)` to
+/// `(a * x + c * y + tx, b * x + d * y + ty)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix(pub [f64; 6]);
+
+impl Matrix {
+    pub const IDENTITY: Matrix = Matrix([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    pub fn scale(sx: f64, sy: f64) -> Self {
+        Matrix([sx, 0.0, 0.0, sy, 0.0, 0.0])
+    }
+
+    /// `self` applied after `inner`
+    pub fn then(&self, inner: &Matrix) -> Matrix {
+        let [a, b, c, d, tx, ty] = self.0;
+        let [ia, ib, ic, id, itx, ity] = inner.0;
+        Matrix([
+            a * ia + c * ib,
+            b * ia + d * ib,
+            a * ic + c * id,
+            b * ic + d * id,
+            a * itx + c * ity + tx,
+            b * itx + d * ity + ty,
+        ])
+    }
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [a, b, c, d, tx, ty] = self.0;
+        (a * x + c * y + tx, b * x + d * y + ty)
+    }
+
+    pub fn invert(&self) -> Option<Matrix> {
+        let [a, b, c, d, tx, ty] = self.0;
+        let det = a * d - b * c;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let (ia, ib, ic, id) = (d / det, -b / det, -c / det, a / det);
+        Some(Matrix([
+            ia,
+            ib,
+            ic,
+            id,
+            -(ia * tx + ic * ty),
+            -(ib * tx + id * ty),
+        ]))
+    }
+}
+
+/// Premultiplied floating point canvas
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    /// Pixel count of a `width` x `height` canvas, checked against `MAX_CANVAS_PIXELS`
+    pub fn pixel_count(width: u32, height: u32) -> Result<usize, CanvasTooLarge> {
+        (width as usize)
+            .checked_mul(height as usize)
+            .filter(|&count| count <= MAX_CANVAS_PIXELS)
+            .ok_or(CanvasTooLarge { width, height })
+    }
+
+    pub fn new(width: u32, height: u32, background: [u8; 4]) -> Result<Self, CanvasTooLarge> {
+        let count = Self::pixel_count(width, height)?;
+        let alpha = background[3] as f32 / 255.0;
+        let fill = [
+            background[0] as f32 / 255.0 * alpha,
+            background[1] as f32 / 255.0 * alpha,
+            background[2] as f32 / 255.0 * alpha,
+            alpha,
+        ];
+        Ok(Self {
+            width,
+            height,
+            pixels: vec![fill; count],
+        })
+    }
+
+    /// Draw `bitmap` (or its `source` rectangle) through `matrix`, which maps bitmap
+    /// pixels to canvas pixels, tinted by `color`
+    pub fn draw(
+        &mut self,
+        bitmap: &RgbaImage,
+        source: Option<[i32; 4]>,
+        matrix: &Matrix,
+        color: [f64; 4],
+        additive: bool,
+    ) {
+        let (sx, sy, sw, sh) = match source {
+            Some([x, y, w, h]) if w > 0 && h > 0 => {
+                (x.max(0) as u32, y.max(0) as u32, w as u32, h as u32)
+            }
+            _ => (0, 0, bitmap.width(), bitmap.height()),
+        };
+        let sw = sw.min(bitmap.width().saturating_sub(sx));
+        let sh = sh.min(bitmap.height().saturating_sub(sy));
+        if sw == 0 || sh == 0 {
+            return;
+        }
+        let Some(inverse) = matrix.invert() else {
+            return;
+        };
+
+        // Bounding box of the transformed rectangle, clipped to the canvas
+        let corners = [
+            matrix.apply(0.0, 0.0),
+            matrix.apply(sw as f64, 0.0),
+            matrix.apply(0.0, sh as f64),
+            matrix.apply(sw as f64, sh as f64),
+        ];
+        let min_x = corners.iter().map(|c| c.0).fold(f64::MAX, f64::min);
+        let max_x = corners.iter().map(|c| c.0).fold(f64::MIN, f64::max);
+        let min_y = corners.iter().map(|c| c.1).fold(f64::MAX, f64::min);
+        let max_y = corners.iter().map(|c| c.1).fold(f64::MIN, f64::max);
+        let x0 = min_x.floor().max(0.0) as u32;
+        let y0 = min_y.floor().max(0.0) as u32;
+        let x1 = (max_x.ceil().max(0.0) as u32).min(self.width);
+        let y1 = (max_y.ceil().max(0.0) as u32).min(self.height);
+
+        let tint = color.map(|c| c.clamp(0.0, 1.0) as f32);
+        for py in y0..y1 {
+            for px in x0..x1 {
+                let (u, v) = inverse.apply(px as f64 + 0.5, py as f64 + 0.5);
+                if u < 0.0 || v < 0.0 || u >= sw as f64 || v >= sh as f64 {
+                    continue;
+                }
+                let [r, g, b, a] = sample(bitmap, sx, sy, sw, sh, u, v);
+                let a = a * tint[3];
+                if a <= 0.0 {
+                    continue;
+                }
+                // `sample` is premultiplied, so tint the colour and scale by the tint alpha
+                let src = [
+                    r * tint[0] * tint[3],
+                    g * tint[1] * tint[3],
+                    b * tint[2] * tint[3],
+                    a,
+                ];
+                let dst = &mut self.pixels[(py * self.width + px) as usize];
+                if additive {
+                    for channel in 0..3 {
+                        dst[channel] = (dst[channel] + src[channel]).min(1.0);
+                    }
+                    dst[3] = (dst[3] + src[3]).min(1.0);
+                } else {
+                    let keep = 1.0 - src[3];
+                    for channel in 0..4 {
+                        dst[channel] = src[channel] + dst[channel] * keep;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn into_image(self) -> RgbaImage {
+        let mut image = RgbaImage::new(self.width, self.height);
+        for (pixel, value) in image.pixels_mut().zip(self.pixels) {
+            let a = value[3];
+            let unmultiply = |c: f32| {
+                if a > 0.0 {
+                    ((c / a).clamp(0.0, 1.0) * 255.0).round() as u8
+                } else {
+                    0
+                }
+            };
+            *pixel = Rgba([
+                unmultiply(value[0]),
+                unmultiply(value[1]),
+                unmultiply(value[2]),
+                (a.clamp(0.0, 1.0) * 255.0).round() as u8,
+            ]);
+        }
+        image
+    }
+}
+
+/// Bilinear, premultiplied sample of the `(sx, sy, sw, sh)` region at `(u, v)`
+fn sample(bitmap: &RgbaImage, sx: u32, sy: u32, sw: u32, sh: u32, u: f64, v: f64) -> [f32; 4] {
+    let fx = (u - 0.5).max(0.0);
+    let fy = (v - 0.5).max(0.0);
+    let x0 = (fx.floor() as u32).min(sw - 1);
+    let y0 = (fy.floor() as u32).min(sh - 1);
+    let x1 = (x0 + 1).min(sw - 1);
+    let y1 = (y0 + 1).min(sh - 1);
+    let tx = (fx - x0 as f64).clamp(0.0, 1.0) as f32;
+    let ty = (fy - y0 as f64).clamp(0.0, 1.0) as f32;
+
+    let texel = |x: u32, y: u32| {
+        let p = bitmap.get_pixel(sx + x, sy + y).0;
+        let a = p[3] as f32 / 255.0;
+        [
+            p[0] as f32 / 255.0 * a,
+            p[1] as f32 / 255.0 * a,
+            p[2] as f32 / 255.0 * a,
+            a,
+        ]
+    };
+    let (p00, p10, p01, p11) = (texel(x0, y0), texel(x1, y0), texel(x0, y1), texel(x1, y1));
+    let mut out = [0.0; 4];
+    for channel in 0..4 {
+        let top = p00[channel] + (p10[channel] - p00[channel]) * tx;
+        let bottom = p01[channel] + (p11[channel] - p01[channel]) * tx;
+        out[channel] = top + (bottom - top) * ty;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix_invert() {
+        let matrix = Matrix::scale(2.0, 4.0).then(&Matrix([0.0, 1.0, -1.0, 0.0, 3.0, 5.0]));
+        assert_eq!(matrix.apply(1.0, 0.0), (6.0, 24.0));
+        let inverse = matrix.invert().unwrap();
+        assert_eq!(inverse.apply(6.0, 24.0), (1.0, 0.0));
+        assert_eq!(inverse.then(&matrix), Matrix::IDENTITY);
+        assert!(Matrix::scale(0.0, 1.0).invert().is_none());
+    }
+
+    #[test]
+    fn test_canvas_draw() {
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let mut canvas = Canvas::new(4, 4, [0, 0, 255, 255]).unwrap();
+        canvas.draw(
+            &red,
+            None,
+            &Matrix([1.0, 0.0, 0.0, 1.0, 1.0, 1.0]),
+            [1.0; 4],
+            false,
+        );
+        canvas.draw(
+            &red,
+            Some([0, 0, 1, 1]),
+            &Matrix::IDENTITY,
+            [1.0, 1.0, 1.0, 0.5],
+            true,
+        );
+        let image = canvas.into_image();
+
+        assert_eq!(image.get_pixel(0, 0).0, [128, 0, 255, 255]);
+        assert_eq!(image.get_pixel(2, 2).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(3, 3).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_canvas_too_large() {
+        // 70000 * 70000 wraps to a small canvas in u32 arithmetic
+        let err = Canvas::new(70000, 70000, [0; 4]).err().unwrap();
+        assert_eq!(
+            err,
+            CanvasTooLarge {
+                width: 70000,
+                height: 70000
+            }
+        );
+        assert!(Canvas::new(u32::MAX, u32::MAX, [0; 4]).is_err());
+        assert_eq!(Canvas::pixel_count(16384, 16384), Ok(MAX_CANVAS_PIXELS));
+    }
+}
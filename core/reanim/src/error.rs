@@ -17,4 +17,13 @@ pub enum ReanimError {
 
     #[error("String decode error")]
     StringDecodeError,
+
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+
+    #[error("Invalid <{0}> value: {1:?}")]
+    InvalidValue(String, String),
+
+    #[error(transparent)]
+    Canvas(#[from] raster::CanvasTooLarge),
 }
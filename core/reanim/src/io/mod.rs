@@ -1,5 +1,5 @@
 pub mod reader;
 pub mod writer;
 
-pub use reader::{decode, decode_pc, decode_phone32, decode_phone64};
+pub use reader::{decode, decode_pc, decode_phone32, decode_phone64, decode_versioned};
 pub use writer::encode;
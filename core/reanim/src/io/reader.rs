@@ -1,5 +1,5 @@
 use crate::error::ReanimError;
-use crate::types::{Reanim, ReanimTrack, ReanimTransform};
+use crate::types::{Reanim, ReanimTrack, ReanimTransform, ReanimVersion};
 use byteorder::{LE, ReadBytesExt};
 use flate2::read::ZlibDecoder;
 use std::io::{Cursor, Read};
@@ -330,16 +330,22 @@ pub fn decode_phone64(data: &[u8]) -> Result<Reanim, ReanimError> {
     Ok(reanim)
 }
 
-pub fn decode(data: &[u8]) -> Result<crate::types::Reanim, ReanimError> {
+pub fn decode(data: &[u8]) -> Result<Reanim, ReanimError> {
+    decode_versioned(data).map(|(reanim, _)| reanim)
+}
+
+/// Like [`decode`], also returning the variant that matched so the file can be
+/// written back the same way.
+pub fn decode_versioned(data: &[u8]) -> Result<(Reanim, ReanimVersion), ReanimError> {
     // Try each in sequence like the C# code does
     if let Ok(r) = decode_pc(data) {
-        return Ok(r);
+        return Ok((r, ReanimVersion::PC));
     }
     if let Ok(r) = decode_phone32(data) {
-        return Ok(r);
+        return Ok((r, ReanimVersion::Phone32));
     }
     if let Ok(r) = decode_phone64(data) {
-        return Ok(r);
+        return Ok((r, ReanimVersion::Phone64));
     }
     Err(ReanimError::InvalidVariant)
 }
@@ -1,12 +1,16 @@
 pub mod error;
 pub mod io;
+pub mod render;
 pub mod types;
 pub mod xfl;
+pub mod xml;
 
 pub use error::ReanimError;
-pub use io::{decode, decode_pc, decode_phone32, decode_phone64, encode};
+pub use io::{decode, decode_pc, decode_phone32, decode_phone64, decode_versioned, encode};
+pub use render::{ReanimRenderer, RenderOptions, load_images};
 pub use types::{Reanim, ReanimTrack, ReanimTransform, ReanimVersion};
 pub use xfl::{decode_xfl, encode_xfl};
+pub use xml::{decode_xml, encode_xml};
 
 #[cfg(test)]
 mod tests {
@@ -98,4 +102,131 @@ mod tests {
             decoded.tracks[0].transforms[1].i
         );
     }
+
+    #[test]
+    fn test_decode_versioned() {
+        let original = create_test_reanim();
+        for version in [
+            ReanimVersion::PC,
+            ReanimVersion::Phone32,
+            ReanimVersion::Phone64,
+        ] {
+            let encoded = encode(&original, version).unwrap();
+            let (decoded, detected) = decode_versioned(&encoded).unwrap();
+            assert_eq!(detected, version);
+            assert_eq!(original.tracks[0].name, decoded.tracks[0].name);
+        }
+    }
+
+    #[test]
+    fn test_reanim_xml_parse() {
+        let text = "<fps>12</fps>\n<track>\n<name>anim_idle</name>\n\
+                    <t><f>-1</f></t>\n\
+                    <t><x>1.5</x><y>-2</y><f>0</f><i>IMAGE_REANIM_HEAD</i></t>\n\
+                    <t><sx>0.5</sx></t>\n<t/>\n\
+                    <t><text>a &amp; b</text></t>\n</track>\n";
+        let reanim = decode_xml(text).unwrap();
+
+        assert_eq!(reanim.fps, 12.0);
+        assert_eq!(reanim.tracks.len(), 1);
+        let track = &reanim.tracks[0];
+        assert_eq!(track.name, "anim_idle");
+        assert_eq!(track.transforms.len(), 5);
+        assert_eq!(track.transforms[2].x, None);
+        assert_eq!(track.transforms[4].text.as_deref(), Some("a & b"));
+
+        let resolved = track.resolved_transforms();
+        assert_eq!(resolved[0].f, Some(-1.0));
+        assert_eq!(resolved[0].sx, Some(1.0));
+        assert_eq!(resolved[2].x, Some(1.5));
+        assert_eq!(resolved[2].y, Some(-2.0));
+        assert_eq!(resolved[2].sx, Some(0.5));
+        assert_eq!(resolved[3].i.as_deref(), Some("IMAGE_REANIM_HEAD"));
+        assert_eq!(resolved[4].sx, Some(0.5));
+    }
+
+    #[test]
+    fn test_reanim_xml_roundtrip() {
+        let mut original = create_test_reanim();
+        original.do_scale = Some(0);
+        original.tracks[0].transforms[0].text = Some("<3 & \"quotes\"".to_string());
+        let text = encode_xml(&original);
+        let decoded = decode_xml(&text).unwrap();
+        assert_eq!(original, decoded);
+    }
+
+    #[test]
+    fn test_reanim_xml_whitespace() {
+        let text = "<fps> 12 </fps>\n<track>\n  <name> anim_idle </name>\n  <t>\n    \
+                    <x> 1.5 </x>\n    <font>\tDwarvenTodcraft18 </font>\n    \
+                    <text>  two  spaces\n</text>\n  </t>\n  <t><text> </text></t>\n</track>\n";
+        let reanim = decode_xml(text).unwrap();
+
+        assert_eq!(reanim.fps, 12.0);
+        let track = &reanim.tracks[0];
+        assert_eq!(track.name, "anim_idle");
+        assert_eq!(track.transforms[0].x, Some(1.5));
+        assert_eq!(
+            track.transforms[0].font.as_deref(),
+            Some("DwarvenTodcraft18")
+        );
+        assert_eq!(track.transforms[0].text.as_deref(), Some("  two  spaces\n"));
+        assert_eq!(track.transforms[1].text.as_deref(), Some(" "));
+
+        let decoded = decode_xml(&encode_xml(&reanim)).unwrap();
+        assert_eq!(reanim, decoded);
+    }
+
+    #[test]
+    fn test_reanim_xml_invalid_number() {
+        let text = "<fps>12</fps><track><name>a</name><t><x>left</x></t></track>";
+        assert!(matches!(
+            decode_xml(text),
+            Err(ReanimError::InvalidValue(tag, _)) if tag == "x"
+        ));
+    }
+
+    #[test]
+    fn test_reanim_render() {
+        let text = "<fps>12</fps>\n\
+                    <track><name>anim_idle</name><t><f>-1</f></t><t><f>0</f></t></track>\n\
+                    <track><name>body</name><t><x>10</x><y>5</y><i>IMAGE_BODY</i></t>\n\
+                    <t><f>-1</f></t></track>\n\
+                    <track><name>arm</name><t><x>12</x><y>5</y><sx>2</sx><i>IMAGE_BODY</i></t>\n\
+                    <t><a>0.5</a></t></track>\n";
+        let reanim = decode_xml(text).unwrap();
+        let mut images = std::collections::HashMap::new();
+        images.insert(
+            "IMAGE_BODY".to_string(),
+            image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 0, 0, 255])),
+        );
+        let renderer = ReanimRenderer::new(&reanim, images);
+        assert_eq!(renderer.frame_count(), 2);
+        assert_eq!(renderer.anim_range("anim_idle"), Some(1..2));
+
+        let options = RenderOptions::default();
+        let bounds = renderer.bounds(0..2, &options);
+        assert_eq!(bounds, [10.0, 5.0, 6.0, 2.0]);
+
+        let first = renderer.render_frame(0, bounds, &options).unwrap();
+        assert_eq!(first.dimensions(), (6, 2));
+        assert_eq!(first.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(first.get_pixel(5, 1).0, [255, 0, 0, 255]);
+
+        // The body is hidden and the arm keeps its position at half alpha
+        let second = renderer.render_frame(1, bounds, &options).unwrap();
+        assert_eq!(second.get_pixel(0, 0).0[3], 0);
+        assert_eq!(second.get_pixel(3, 0).0, [255, 0, 0, 128]);
+        assert!(
+            renderer
+                .render_frame(0, [0.0, 0.0, 70000.0, 70000.0], &options)
+                .is_err()
+        );
+
+        let hidden = RenderOptions {
+            hide: vec!["a*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(renderer.bounds(0..2, &hidden), [10.0, 5.0, 2.0, 2.0]);
+    }
 }
@@ -0,0 +1,251 @@
+use crate::error::ReanimError;
+use crate::types::{Reanim, ReanimTransform};
+use crate::xfl::encoder::get_name_by_id;
+use image::RgbaImage;
+use raster::{Canvas, Matrix};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// Output pixels per animation unit
+    pub scale: f64,
+    /// Canvas fill behind the animation
+    pub background: [u8; 4],
+    /// Tracks to draw, by name with `*` matching any run of characters; empty draws
+    /// every track
+    pub show: Vec<String>,
+    /// Tracks never drawn, matched like `show`
+    pub hide: Vec<String>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            background: [0, 0, 0, 0],
+            show: Vec::new(),
+            hide: Vec::new(),
+        }
+    }
+}
+
+impl RenderOptions {
+    pub fn is_visible(&self, track: &str) -> bool {
+        (self.show.is_empty() || self.show.iter().any(|p| wildcard_match(p, track)))
+            && !self.hide.iter().any(|p| wildcard_match(p, track))
+    }
+}
+
+/// Case-insensitive match of `name` against `pattern`, where `*` matches any run of
+/// characters
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let (pattern, name) = (pattern.to_lowercase(), name.to_lowercase());
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = name.strip_prefix(parts.next().unwrap_or("")) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Matrix of a resolved transform: `kx` and `ky` are skew angles in degrees, the
+/// scales apply before them
+pub fn transform_matrix(ts: &ReanimTransform) -> Matrix {
+    let skew_x = (ts.kx.unwrap_or(0.0) as f64).to_radians();
+    let skew_y = (ts.ky.unwrap_or(0.0) as f64).to_radians();
+    let sx = ts.sx.unwrap_or(1.0) as f64;
+    let sy = ts.sy.unwrap_or(1.0) as f64;
+    Matrix([
+        skew_x.cos() * sx,
+        skew_x.sin() * sx,
+        -skew_y.sin() * sy,
+        skew_y.cos() * sy,
+        ts.x.unwrap_or(0.0) as f64,
+        ts.y.unwrap_or(0.0) as f64,
+    ])
+}
+
+/// Load the bitmap of every image a reanim shows from `dir`. Files match an image id
+/// by the id without `IMAGE_REANIM_` or by the whole id, ignoring case; ids without a
+/// readable file are left out.
+pub fn load_images(reanim: &Reanim, dir: &Path) -> Result<HashMap<String, RgbaImage>, ReanimError> {
+    let mut files = HashMap::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(stem) = path.file_stem() {
+            files.insert(stem.to_string_lossy().to_lowercase(), path);
+        }
+    }
+
+    let mut images = HashMap::new();
+    for track in &reanim.tracks {
+        for id in track.transforms.iter().filter_map(|ts| ts.i.as_ref()) {
+            if images.contains_key(id) {
+                continue;
+            }
+            let path = files
+                .get(&get_name_by_id(id, &track.name, 0))
+                .or_else(|| files.get(&id.to_lowercase()));
+            if let Some(Ok(image)) = path.map(image::open) {
+                images.insert(id.clone(), image.to_rgba8());
+            }
+        }
+    }
+    Ok(images)
+}
+
+/// Headless rasteriser for reanims. Tracks are drawn in order, the first one at the
+/// bottom; a track shows its image unless its frame `f` is -1. Text tracks are not
+/// drawn.
+pub struct ReanimRenderer<'a> {
+    reanim: &'a Reanim,
+    tracks: Vec<Vec<ReanimTransform>>,
+    images: HashMap<String, RgbaImage>,
+}
+
+impl<'a> ReanimRenderer<'a> {
+    /// `images` holds the bitmaps by image id; images without one are skipped
+    pub fn new(reanim: &'a Reanim, images: HashMap<String, RgbaImage>) -> Self {
+        Self {
+            reanim,
+            tracks: reanim
+                .tracks
+                .iter()
+                .map(|track| track.resolved_transforms())
+                .collect(),
+            images,
+        }
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.tracks.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    /// Image ids shown by the reanim that have no bitmap
+    pub fn missing_images(&self) -> Vec<&str> {
+        let mut missing: Vec<&str> = self
+            .tracks
+            .iter()
+            .flatten()
+            .filter_map(|ts| ts.i.as_deref())
+            .filter(|id| !self.images.contains_key(*id))
+            .collect();
+        missing.sort_unstable();
+        missing.dedup();
+        missing
+    }
+
+    /// Frame ranges of the `anim_*` tracks, which mark the animations of a reanim by
+    /// being shown on their frames
+    pub fn anims(&self) -> Vec<(String, Range<usize>)> {
+        self.reanim
+            .tracks
+            .iter()
+            .filter(|track| track.name.starts_with("anim_"))
+            .filter_map(|track| Some((track.name.clone(), self.anim_range(&track.name)?)))
+            .collect()
+    }
+
+    /// First to last frame on which the track `name` is shown
+    pub fn anim_range(&self, name: &str) -> Option<Range<usize>> {
+        let index = self.reanim.tracks.iter().position(|t| t.name == name)?;
+        let shown = |ts: &ReanimTransform| !is_hidden(ts);
+        let transforms = &self.tracks[index];
+        let start = transforms.iter().position(shown)?;
+        let end = transforms.iter().rposition(shown)? + 1;
+        Some(start..end)
+    }
+
+    /// Area `[x, y, width, height]` covering every image drawn on `frames`, in whole
+    /// animation units, so all frames can share one canvas
+    pub fn bounds(&self, frames: Range<usize>, options: &RenderOptions) -> [f64; 4] {
+        let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+        let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+        for frame in frames {
+            for (matrix, bitmap, _) in self.drawables(frame, options) {
+                let (w, h) = (bitmap.width() as f64, bitmap.height() as f64);
+                for (x, y) in [(0.0, 0.0), (w, 0.0), (0.0, h), (w, h)] {
+                    let (x, y) = matrix.apply(x, y);
+                    min_x = min_x.min(x);
+                    min_y = min_y.min(y);
+                    max_x = max_x.max(x);
+                    max_y = max_y.max(y);
+                }
+            }
+        }
+        if min_x > max_x {
+            return [0.0, 0.0, 1.0, 1.0];
+        }
+        let (x, y) = (min_x.floor(), min_y.floor());
+        [
+            x,
+            y,
+            (max_x.ceil() - x).max(1.0),
+            (max_y.ceil() - y).max(1.0),
+        ]
+    }
+
+    /// Rasterise one frame onto a canvas showing `bounds` (as returned by `bounds`);
+    /// fails when the canvas is too large to allocate
+    pub fn render_frame(
+        &self,
+        frame: usize,
+        bounds: [f64; 4],
+        options: &RenderOptions,
+    ) -> Result<RgbaImage, ReanimError> {
+        let [x, y, width, height] = bounds;
+        let canvas_width = (width * options.scale).ceil().max(1.0) as u32;
+        let canvas_height = (height * options.scale).ceil().max(1.0) as u32;
+        let mut canvas = Canvas::new(canvas_width, canvas_height, options.background)?;
+        let view =
+            Matrix::scale(options.scale, options.scale).then(&Matrix([1.0, 0.0, 0.0, 1.0, -x, -y]));
+        for (matrix, bitmap, alpha) in self.drawables(frame, options) {
+            canvas.draw(
+                bitmap,
+                None,
+                &view.then(&matrix),
+                [1.0, 1.0, 1.0, alpha],
+                false,
+            );
+        }
+        Ok(canvas.into_image())
+    }
+
+    /// Matrix, bitmap and alpha of every image shown at `frame`, bottom first
+    fn drawables<'s>(
+        &'s self,
+        frame: usize,
+        options: &'s RenderOptions,
+    ) -> impl Iterator<Item = (Matrix, &'s RgbaImage, f64)> + 's {
+        self.reanim
+            .tracks
+            .iter()
+            .zip(&self.tracks)
+            .filter(|(track, _)| options.is_visible(&track.name))
+            .filter_map(move |(_, transforms)| {
+                let ts = transforms.get(frame)?;
+                let alpha = ts.a.unwrap_or(1.0) as f64;
+                if is_hidden(ts) || alpha <= 0.0 {
+                    return None;
+                }
+                let bitmap = self.images.get(ts.i.as_ref()?)?;
+                Some((transform_matrix(ts), bitmap, alpha))
+            })
+    }
+}
+
+fn is_hidden(ts: &ReanimTransform) -> bool {
+    ts.f.unwrap_or(0.0) == -1.0
+}
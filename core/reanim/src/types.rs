@@ -34,6 +34,57 @@ pub struct ReanimTransform {
     pub text: Option<String>,
 }
 
+impl ReanimTrack {
+    /// Transforms as the game plays them: every omitted field is taken from the
+    /// previous frame, and the first frame falls back to x, y, kx, ky and f of 0,
+    /// sx, sy and a of 1
+    pub fn resolved_transforms(&self) -> Vec<ReanimTransform> {
+        let mut current = ReanimTransform {
+            x: Some(0.0),
+            y: Some(0.0),
+            kx: Some(0.0),
+            ky: Some(0.0),
+            sx: Some(1.0),
+            sy: Some(1.0),
+            f: Some(0.0),
+            a: Some(1.0),
+            ..Default::default()
+        };
+        self.transforms
+            .iter()
+            .map(|ts| {
+                current.inherit(ts);
+                current.clone()
+            })
+            .collect()
+    }
+}
+
+impl ReanimTransform {
+    /// Overwrite the fields `next` sets
+    fn inherit(&mut self, next: &ReanimTransform) {
+        fn set<T: Clone>(field: &mut Option<T>, value: &Option<T>) {
+            if value.is_some() {
+                field.clone_from(value);
+            }
+        }
+        set(&mut self.x, &next.x);
+        set(&mut self.y, &next.y);
+        set(&mut self.kx, &next.kx);
+        set(&mut self.ky, &next.ky);
+        set(&mut self.sx, &next.sx);
+        set(&mut self.sy, &next.sy);
+        set(&mut self.f, &next.f);
+        set(&mut self.a, &next.a);
+        set(&mut self.i, &next.i);
+        set(&mut self.resource, &next.resource);
+        set(&mut self.i2, &next.i2);
+        set(&mut self.resource2, &next.resource2);
+        set(&mut self.font, &next.font);
+        set(&mut self.text, &next.text);
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ReanimVersion {
     PC = 1,
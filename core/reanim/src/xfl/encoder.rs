@@ -154,7 +154,7 @@ pub fn encode_xfl(reanim: &Reanim, output_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-fn get_name_by_id(id: &str, _label_name: &str, _label_index: usize) -> String {
+pub(crate) fn get_name_by_id(id: &str, _label_name: &str, _label_index: usize) -> String {
     let mut name = id.to_string();
     if name.starts_with("IMAGE_REANIM_") {
         name = name[13..].to_string();
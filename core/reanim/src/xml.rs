@@ -0,0 +1,185 @@
+use crate::error::ReanimError;
+use crate::types::{Reanim, ReanimTrack, ReanimTransform};
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::Event;
+
+// The PvZ1-style `.reanim` text: a bare list of `<fps>`, `<doScale>` and `<track>`
+// elements without a root. Every `<t>` is one frame and only lists the fields that
+// change, so omitted fields stay `None` here just like the -10000 placeholders of the
+// binary formats; `ReanimTrack::resolved_transforms` applies the inheritance.
+
+/// Parse `.reanim` text
+pub fn decode_xml(text: &str) -> Result<Reanim, ReanimError> {
+    // Whitespace is kept so `<text>` values survive; indentation between elements
+    // is dropped when the next element starts and names and numbers are trimmed
+    let mut reader = Reader::from_str(text);
+
+    let mut reanim = Reanim::default();
+    let mut path: Vec<String> = Vec::new();
+    let mut value = String::new();
+    let mut track: Option<ReanimTrack> = None;
+    let mut transform: Option<ReanimTransform> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                match (name.as_str(), path.last().map(String::as_str)) {
+                    ("track", None) => track = Some(ReanimTrack::default()),
+                    ("t", Some("track")) => transform = Some(ReanimTransform::default()),
+                    _ => {}
+                }
+                path.push(name);
+                value.clear();
+            }
+            Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.name().as_ref()).into_owned();
+                match (name.as_str(), path.last().map(String::as_str)) {
+                    ("t", Some("track")) => {
+                        if let Some(track) = &mut track {
+                            track.transforms.push(ReanimTransform::default());
+                        }
+                    }
+                    (_, parent) => {
+                        close_element(&name, parent, "", &mut reanim, &mut track, &mut transform)?
+                    }
+                }
+            }
+            Event::Text(e) => value.push_str(&e.unescape()?),
+            Event::CData(e) => value.push_str(&String::from_utf8_lossy(&e)),
+            Event::End(_) => {
+                let Some(name) = path.pop() else {
+                    continue;
+                };
+                let parent = path.last().map(String::as_str);
+                close_element(
+                    &name,
+                    parent,
+                    &value,
+                    &mut reanim,
+                    &mut track,
+                    &mut transform,
+                )?;
+                value.clear();
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(reanim)
+}
+
+fn close_element(
+    name: &str,
+    parent: Option<&str>,
+    value: &str,
+    reanim: &mut Reanim,
+    track: &mut Option<ReanimTrack>,
+    transform: &mut Option<ReanimTransform>,
+) -> Result<(), ReanimError> {
+    match (parent, name) {
+        (None, "fps") => reanim.fps = parse_number(name, value)?.unwrap_or(0.0),
+        (None, "doScale") => reanim.do_scale = parse_number(name, value)?,
+        (None, "track") => reanim.tracks.extend(track.take()),
+        (Some("track"), "name") => {
+            if let Some(track) = track {
+                track.name = value.trim().to_string();
+            }
+        }
+        (Some("track"), "t") => {
+            if let (Some(track), Some(transform)) = (track.as_mut(), transform.take()) {
+                track.transforms.push(transform);
+            }
+        }
+        (Some("t"), _) => {
+            if let Some(ts) = transform {
+                set_field(ts, name, value)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn set_field(ts: &mut ReanimTransform, name: &str, value: &str) -> Result<(), ReanimError> {
+    let text = Some(value.trim().to_string());
+    match name {
+        "x" => ts.x = parse_number(name, value)?,
+        "y" => ts.y = parse_number(name, value)?,
+        "kx" => ts.kx = parse_number(name, value)?,
+        "ky" => ts.ky = parse_number(name, value)?,
+        "sx" => ts.sx = parse_number(name, value)?,
+        "sy" => ts.sy = parse_number(name, value)?,
+        "f" => ts.f = parse_number(name, value)?,
+        "a" => ts.a = parse_number(name, value)?,
+        "i" => ts.i = text,
+        "resource" => ts.resource = text,
+        "i2" => ts.i2 = text,
+        "resource2" => ts.resource2 = text,
+        "font" => ts.font = text,
+        "text" => ts.text = Some(value.to_string()),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// An empty element counts as omitted
+fn parse_number<T: std::str::FromStr>(tag: &str, value: &str) -> Result<Option<T>, ReanimError> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok(None);
+    }
+    value
+        .parse()
+        .map(Some)
+        .map_err(|_| ReanimError::InvalidValue(tag.to_string(), value.to_string()))
+}
+
+/// Write `.reanim` text, one `<t>` per line with only the fields that are set
+pub fn encode_xml(reanim: &Reanim) -> String {
+    let mut out = format!("<fps>{}</fps>\n", reanim.fps);
+    if let Some(do_scale) = reanim.do_scale {
+        out.push_str(&format!("<doScale>{}</doScale>\n", do_scale));
+    }
+
+    for track in &reanim.tracks {
+        out.push_str("<track>\n");
+        out.push_str(&format!("<name>{}</name>\n", escape(&track.name)));
+        for ts in &track.transforms {
+            out.push_str("<t>");
+            let numbers = [
+                ("x", ts.x),
+                ("y", ts.y),
+                ("kx", ts.kx),
+                ("ky", ts.ky),
+                ("sx", ts.sx),
+                ("sy", ts.sy),
+                ("f", ts.f),
+                ("a", ts.a),
+            ];
+            for (tag, value) in numbers {
+                if let Some(value) = value {
+                    out.push_str(&format!("<{tag}>{value}</{tag}>"));
+                }
+            }
+            let texts = [
+                ("i", &ts.i),
+                ("resource", &ts.resource),
+                ("i2", &ts.i2),
+                ("resource2", &ts.resource2),
+                ("font", &ts.font),
+                ("text", &ts.text),
+            ];
+            for (tag, value) in texts {
+                if let Some(value) = value {
+                    out.push_str(&format!("<{tag}>{}</{tag}>", escape(value)));
+                }
+            }
+            out.push_str("</t>\n");
+        }
+        out.push_str("</track>\n");
+    }
+    out
+}
@@ -8,6 +8,10 @@ pub enum ResourcesError {
     Json(#[from] serde_json::Error),
     #[error("Group `{0}` has missing nested members")]
     MissingGroupMembers(String),
+    #[error("`{0}` redefines `{1}`")]
+    MergeConflict(String, String),
+    #[error("`{1}` and `{2}` would both get the id `{0}`")]
+    IdCollision(String, String, String),
 }
 
 pub type Result<T> = std::result::Result<T, ResourcesError>;
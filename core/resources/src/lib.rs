@@ -1,9 +1,439 @@
 pub mod error;
+pub mod merge;
 pub mod reader;
+pub mod rename;
+pub mod scaffold;
+pub mod slots;
 pub mod types;
+pub mod validate;
 pub mod writer;
 
 pub use error::{ResourcesError, Result};
+pub use merge::*;
 pub use reader::*;
+pub use rename::*;
+pub use scaffold::*;
+pub use slots::*;
 pub use types::*;
+pub use validate::*;
 pub use writer::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Grouped manifest in the order and with the slots a res.json reads back as
+    fn sample_group() -> ResourceGroup {
+        serde_json::from_value(json!({
+            "version": 1,
+            "content_version": 1,
+            "slot_count": 5,
+            "groups": [
+                {
+                    "id": "Global",
+                    "type": "simple",
+                    "resources": [
+                        { "type": "File", "slot": 0, "id": "FILE_A", "path": ["data", "a.rton"] },
+                        {
+                            "type": "PopAnim",
+                            "slot": 1,
+                            "id": "POPANIM_B",
+                            "path": ["pam", "b"],
+                            "forceOriginalVectorSymbolSize": true,
+                            "srcpath": ["src", "b"]
+                        }
+                    ]
+                },
+                {
+                    "id": "UI",
+                    "type": "composite",
+                    "subgroups": [{ "id": "UI_1536", "res": "1536" }]
+                },
+                {
+                    "id": "UI_1536",
+                    "type": "simple",
+                    "res": "1536",
+                    "parent": "UI",
+                    "resources": [
+                        {
+                            "type": "Image",
+                            "slot": 2,
+                            "id": "ATLAS_UI_1536_00",
+                            "path": ["atlases", "UI_1536_00"],
+                            "atlas": true,
+                            "runtime": true,
+                            "width": 512,
+                            "height": 256
+                        },
+                        {
+                            "type": "Image",
+                            "slot": 3,
+                            "id": "IMAGE_UI_BUTTON",
+                            "path": ["images", "button"],
+                            "parent": "ATLAS_UI_1536_00",
+                            "x": -4,
+                            "ax": 0, "ay": 0, "aw": 64, "ah": 32
+                        },
+                        {
+                            "type": "Image",
+                            "slot": 4,
+                            "id": "IMAGE_UI_ICON",
+                            "path": ["images", "icon"],
+                            "parent": "ATLAS_UI_1536_00",
+                            "cols": 2,
+                            "ax": 64, "ay": 0, "aw": 32, "ah": 32
+                        }
+                    ]
+                }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_res_info_round_trip() {
+        let group = sample_group();
+        assert!(res_info_losses(&group, "array").unwrap().is_empty());
+
+        let res_info = convert_resource_group_to_res_info(&group, "array").unwrap();
+        let back = convert_res_info_to_resource_group(&res_info).unwrap();
+        assert_eq!(
+            serde_json::to_value(&back).unwrap(),
+            serde_json::to_value(&group).unwrap()
+        );
+
+        let again = convert_resource_group_to_res_info(&back, "array").unwrap();
+        assert_eq!(
+            serde_json::to_value(&again).unwrap(),
+            serde_json::to_value(&res_info).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_res_info_losses() {
+        let mut group = sample_group();
+        let resources = group.groups[0].resources.as_mut().unwrap();
+        resources[0].runtime = Some(true);
+        resources[0].slot = Some(1);
+        resources[1].slot = Some(0);
+
+        let losses = res_info_losses(&group, "array").unwrap();
+        assert!(losses.contains(&"`runtime` of `FILE_A` in `Global`".to_string()));
+        assert!(losses.contains(&"`slot` of `FILE_A` in `Global`".to_string()));
+        assert!(losses.contains(&"`slot` of `POPANIM_B` in `Global`".to_string()));
+        assert_eq!(losses.len(), 3);
+
+        // Only the path shape changes
+        let losses = res_info_losses(&sample_group(), "string").unwrap();
+        assert!(losses.is_empty());
+    }
+
+    fn issue_kinds(group: &ResourceGroup, files: Option<&PackedFiles>) -> Vec<IssueKind> {
+        validate_resource_group(group, files)
+            .into_iter()
+            .map(|issue| issue.kind)
+            .collect()
+    }
+
+    fn resource_mut<'a>(group: &'a mut ResourceGroup, id: &str) -> &'a mut MSubgroupWrapper {
+        group
+            .groups
+            .iter_mut()
+            .filter_map(|g| g.resources.as_mut())
+            .flatten()
+            .find(|res| res.id == id)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_validate_clean() {
+        assert!(issue_kinds(&sample_group(), None).is_empty());
+
+        let mut files = PackedFiles::new();
+        files.insert("DATA/A.RTON", None);
+        files.insert("PAM/B", None);
+        files.insert(
+            "ATLASES/UI_1536_00.PTX",
+            Some(Dimension {
+                width: 512,
+                height: 256,
+            }),
+        );
+        assert!(issue_kinds(&sample_group(), Some(&files)).is_empty());
+
+        // Only the atlas is missing; sprites are not files of their own
+        let mut files = PackedFiles::new();
+        files.insert("data\\a.rton", None);
+        files.insert("pam\\b", None);
+        assert_eq!(
+            issue_kinds(&sample_group(), Some(&files)),
+            [IssueKind::MissingPath]
+        );
+    }
+
+    #[test]
+    fn test_validate_duplicate_id() {
+        let mut group = sample_group();
+        let copy = group.groups[0].clone();
+        group.groups.push(copy);
+        assert_eq!(issue_kinds(&group, None), [IssueKind::DuplicateId]);
+
+        let mut group = sample_group();
+        let resources = group.groups[0].resources.as_mut().unwrap();
+        let copy = resources[0].clone();
+        resources.push(copy);
+        assert_eq!(issue_kinds(&group, None), [IssueKind::DuplicateId]);
+
+        // The same id in another group must keep its slot
+        let mut group = sample_group();
+        let mut copy = group.groups[0].resources.as_ref().unwrap()[0].clone();
+        copy.slot = Some(4);
+        group.groups[2].resources.as_mut().unwrap().push(copy);
+        assert_eq!(
+            issue_kinds(&group, None),
+            [IssueKind::DuplicateId, IssueKind::SlotCollision]
+        );
+    }
+
+    #[test]
+    fn test_validate_bad_slot() {
+        let mut group = sample_group();
+        resource_mut(&mut group, "POPANIM_B").slot = Some(0);
+        assert_eq!(issue_kinds(&group, None), [IssueKind::SlotCollision]);
+
+        let mut group = sample_group();
+        resource_mut(&mut group, "IMAGE_UI_ICON").slot = Some(5);
+        assert_eq!(issue_kinds(&group, None), [IssueKind::SlotOutOfRange]);
+
+        let mut group = sample_group();
+        resource_mut(&mut group, "IMAGE_UI_ICON").slot = None;
+        assert_eq!(issue_kinds(&group, None), [IssueKind::MissingSlot]);
+    }
+
+    #[test]
+    fn test_validate_missing_parent() {
+        let mut group = sample_group();
+        resource_mut(&mut group, "IMAGE_UI_ICON").parent = Some("ATLAS_MISSING".into());
+        assert_eq!(issue_kinds(&group, None), [IssueKind::UnknownParent]);
+
+        // A parent must be an atlas, not just any resource
+        let mut group = sample_group();
+        resource_mut(&mut group, "IMAGE_UI_ICON").parent = Some("FILE_A".into());
+        assert_eq!(issue_kinds(&group, None), [IssueKind::UnknownParent]);
+
+        let mut group = sample_group();
+        group.groups[1].subgroups.as_mut().unwrap()[0].id = "UI_768".into();
+        assert_eq!(issue_kinds(&group, None), [IssueKind::UnknownSubgroup]);
+    }
+
+    #[test]
+    fn test_validate_atlas_bounds() {
+        let mut group = sample_group();
+        resource_mut(&mut group, "IMAGE_UI_ICON").ax = Some(500);
+        assert_eq!(issue_kinds(&group, None), [IssueKind::AtlasOutOfBounds]);
+
+        // The real texture size wins over the declared one
+        let mut files = PackedFiles::new();
+        files.insert("DATA/A.RTON", None);
+        files.insert("PAM/B", None);
+        files.insert(
+            "ATLASES/UI_1536_00.PTX",
+            Some(Dimension {
+                width: 64,
+                height: 64,
+            }),
+        );
+        assert_eq!(
+            issue_kinds(&sample_group(), Some(&files)),
+            [IssueKind::AtlasOutOfBounds]
+        );
+    }
+
+    fn slots(group: &ResourceGroup) -> Vec<(String, Option<u32>)> {
+        group
+            .groups
+            .iter()
+            .filter_map(|g| g.resources.as_ref())
+            .flatten()
+            .map(|res| (res.id.clone(), res.slot))
+            .collect()
+    }
+
+    fn new_resource(id: &str) -> MSubgroupWrapper {
+        serde_json::from_value(json!({ "type": "File", "id": id, "path": ["data", id] })).unwrap()
+    }
+
+    #[test]
+    fn test_renumber_compact() {
+        let mut group = sample_group();
+        let expected = slots(&group);
+        for (i, res) in group.groups[2]
+            .resources
+            .as_mut()
+            .unwrap()
+            .iter_mut()
+            .enumerate()
+        {
+            res.slot = Some(10 + i as u32);
+        }
+        group.groups[0].resources.as_mut().unwrap()[0].slot = None;
+
+        assert_eq!(renumber_slots(&mut group, SlotMode::Compact), 4);
+        assert_eq!(slots(&group), expected);
+        assert_eq!(group.slot_count, 5);
+
+        // Another resolution of the same resource shares its slot
+        let copy = group.groups[0].resources.as_ref().unwrap()[1].clone();
+        group.groups[2].resources.as_mut().unwrap().insert(0, copy);
+        renumber_slots(&mut group, SlotMode::Compact);
+        assert_eq!(slots(&group)[2], ("POPANIM_B".to_string(), Some(1)));
+        assert_eq!(group.slot_count, 5);
+    }
+
+    #[test]
+    fn test_renumber_append() {
+        // A new entry ahead of the slot 0 holder must not take its slot
+        let mut group = sample_group();
+        let resources = group.groups[0].resources.as_mut().unwrap();
+        resources.insert(0, new_resource("FILE_NEW"));
+        let mut expected = slots(&group);
+        expected[0].1 = Some(5);
+
+        assert_eq!(renumber_slots(&mut group, SlotMode::Append), 1);
+        assert_eq!(slots(&group), expected);
+        assert_eq!(group.slot_count, 6);
+        assert!(validate_resource_group(&group, None).is_empty());
+
+        // Of two ids holding one slot, the later one moves
+        let mut group = sample_group();
+        group.groups[2].resources.as_mut().unwrap()[2].slot = Some(1);
+        assert_eq!(renumber_slots(&mut group, SlotMode::Append), 1);
+        assert_eq!(slots(&group)[4], ("IMAGE_UI_ICON".to_string(), Some(4)));
+        assert_eq!(slots(&group)[1], ("POPANIM_B".to_string(), Some(1)));
+
+        // An id without a slot takes the one it holds in another group
+        let mut group = sample_group();
+        let mut copy = group.groups[0].resources.as_ref().unwrap()[1].clone();
+        copy.slot = None;
+        group.groups[2].resources.as_mut().unwrap().insert(0, copy);
+        assert_eq!(renumber_slots(&mut group, SlotMode::Append), 1);
+        assert_eq!(slots(&group)[2], ("POPANIM_B".to_string(), Some(1)));
+        assert_eq!(group.slot_count, 5);
+    }
+
+    #[test]
+    fn test_merge_keeps_base_slots() {
+        let mut base = sample_group();
+        let before = slots(&base);
+
+        // The layer numbers its own entries from 0, colliding with the base
+        let layer: ResourceGroup = serde_json::from_value(json!({
+            "slot_count": 4,
+            "groups": [
+                {
+                    "id": "Global",
+                    "type": "simple",
+                    "resources": [
+                        { "type": "File", "slot": 3, "id": "FILE_C", "path": ["data", "c"] },
+                        { "type": "File", "slot": 3, "id": "FILE_A", "path": ["data", "a2"] }
+                    ]
+                },
+                {
+                    "id": "Mod",
+                    "type": "simple",
+                    "resources": [
+                        { "type": "File", "slot": 1, "id": "FILE_MOD", "path": ["data", "mod"] },
+                        { "type": "PopAnim", "slot": 2, "id": "POPANIM_B", "path": ["pam", "b"] }
+                    ]
+                }
+            ]
+        }))
+        .unwrap();
+
+        let report = merge_resource_groups(
+            &mut base,
+            &[("mod".into(), layer)],
+            ConflictPolicy::Override,
+        )
+        .unwrap();
+        assert_eq!(report.overridden.len(), 1);
+        assert_eq!(report.added.len(), 1);
+        assert_eq!(report.added_groups.len(), 1);
+
+        let after = slots(&base);
+        for (id, slot) in &before {
+            assert!(after.contains(&(id.clone(), *slot)), "{} moved", id);
+        }
+        assert!(after.contains(&("FILE_C".to_string(), Some(5))));
+        assert!(after.contains(&("FILE_MOD".to_string(), Some(6))));
+        assert_eq!(after.iter().filter(|(id, _)| id == "POPANIM_B").count(), 2);
+        assert!(!after.contains(&("POPANIM_B".to_string(), Some(2))));
+        assert_eq!(base.slot_count, 7);
+        assert!(validate_resource_group(&base, None).is_empty());
+    }
+
+    fn scaffold_sprite(name: &str) -> ScaffoldSprite {
+        ScaffoldSprite {
+            name: name.to_string(),
+            width: 8,
+            height: 4,
+        }
+    }
+
+    #[test]
+    fn test_scaffold_id() {
+        assert_eq!(
+            scaffold_id("IMAGE_", "plants/Peashooter Head"),
+            "IMAGE_PLANTS_PEASHOOTER_HEAD"
+        );
+        assert_eq!(scaffold_id("IMAGE_", "image_icon"), "IMAGE_ICON");
+    }
+
+    #[test]
+    fn test_scaffold_leaves_slots_unassigned() {
+        let options = ScaffoldOptions {
+            group: "Mod_1536".to_string(),
+            res: Some("1536".to_string()),
+            parent: Some("Mod".to_string()),
+            atlas: true,
+            ..ScaffoldOptions::default()
+        };
+        let layer =
+            scaffold_resources(&[scaffold_sprite("a/b"), scaffold_sprite("c")], &options).unwrap();
+        assert_eq!(
+            slots(&layer),
+            vec![
+                ("ATLASIMAGE_ATLAS_MOD_1536_00".to_string(), None),
+                ("IMAGE_A_B".to_string(), None),
+                ("IMAGE_C".to_string(), None),
+            ]
+        );
+
+        let mut base = sample_group();
+        merge_resource_groups(&mut base, &[("mod".into(), layer)], ConflictPolicy::Error).unwrap();
+        let after = slots(&base);
+        assert!(after.contains(&("ATLASIMAGE_ATLAS_MOD_1536_00".to_string(), Some(5))));
+        assert!(after.contains(&("IMAGE_A_B".to_string(), Some(6))));
+        assert!(after.contains(&("IMAGE_C".to_string(), Some(7))));
+        assert!(validate_resource_group(&base, None).is_empty());
+    }
+
+    #[test]
+    fn test_scaffold_id_collision() {
+        let options = ScaffoldOptions {
+            group: "Mod".to_string(),
+            ..ScaffoldOptions::default()
+        };
+        let sprites = [scaffold_sprite("a/b"), scaffold_sprite("a_b")];
+        match scaffold_resources(&sprites, &options) {
+            Err(ResourcesError::IdCollision(id, first, second)) => {
+                assert_eq!(
+                    (id.as_str(), first.as_str(), second.as_str()),
+                    ("IMAGE_A_B", "a/b", "a_b")
+                );
+            }
+            other => panic!("expected a collision, got {:?}", other),
+        }
+    }
+}
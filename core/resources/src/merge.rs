@@ -0,0 +1,176 @@
+use crate::error::{ResourcesError, Result};
+use crate::slots::{renumber_slots, SlotMode};
+use crate::types::*;
+use serde::{Deserialize, Serialize};
+
+/// What to do when a layer redefines a group or resource id that already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The later layer replaces the existing entry
+    Override,
+    /// The existing entry is kept and the layer's entry is dropped
+    Keep,
+    /// Stop with [`ResourcesError::MergeConflict`]
+    Error,
+}
+
+/// One id touched by a merge, and the layer it came from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeEntry {
+    pub source: String,
+    pub group: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MergeReport {
+    /// Groups that did not exist before their layer
+    pub added_groups: Vec<MergeEntry>,
+    /// Resources and subgroups appended to existing groups
+    pub added: Vec<MergeEntry>,
+    /// Existing groups or resources replaced by a later layer
+    pub overridden: Vec<MergeEntry>,
+    /// Conflicting entries from a layer that were dropped
+    pub kept: Vec<MergeEntry>,
+    /// Resources whose slot changed when slots were reassigned after the merge
+    pub reassigned_slots: usize,
+}
+
+/// Merge mod layers onto a base manifest, in order, by group and resource id.
+///
+/// Overridden resources keep the slot of the entry they replace. Resources a layer adds
+/// drop the slots they had in the layer, then slots are reassigned in
+/// [`SlotMode::Append`]: base slots stay stable, an id the base already has shares its
+/// slot and new resources are numbered after the base ones.
+pub fn merge_resource_groups(
+    base: &mut ResourceGroup,
+    layers: &[(String, ResourceGroup)],
+    policy: ConflictPolicy,
+) -> Result<MergeReport> {
+    let mut report = MergeReport::default();
+    for (source, layer) in layers {
+        merge_layer(base, layer, source, policy, &mut report)?;
+    }
+    report.reassigned_slots = renumber_slots(base, SlotMode::Append);
+    Ok(report)
+}
+
+fn merge_layer(
+    base: &mut ResourceGroup,
+    layer: &ResourceGroup,
+    source: &str,
+    policy: ConflictPolicy,
+    report: &mut MergeReport,
+) -> Result<()> {
+    if base.version.is_none() {
+        base.version = layer.version;
+    }
+    if base.content_version.is_none() {
+        base.content_version = layer.content_version;
+    }
+
+    let entry = |group: &str, id: Option<&str>| MergeEntry {
+        source: source.to_string(),
+        group: group.to_string(),
+        id: id.map(str::to_string),
+    };
+
+    for group in &layer.groups {
+        let Some(existing) = base.groups.iter_mut().find(|g| g.id == group.id) else {
+            base.groups.push(without_slots(group, None));
+            report.added_groups.push(entry(&group.id, None));
+            continue;
+        };
+
+        // A group that changes between composite and simple cannot be merged member-wise
+        if existing.r#type != group.r#type {
+            match policy {
+                ConflictPolicy::Override => {
+                    *existing = without_slots(group, Some(existing));
+                    report.overridden.push(entry(&group.id, None));
+                }
+                ConflictPolicy::Keep => report.kept.push(entry(&group.id, None)),
+                ConflictPolicy::Error => {
+                    return Err(ResourcesError::MergeConflict(
+                        source.to_string(),
+                        group.id.clone(),
+                    ))
+                }
+            }
+            continue;
+        }
+
+        if policy == ConflictPolicy::Override {
+            if group.res.is_some() {
+                existing.res = group.res.clone();
+            }
+            if group.parent.is_some() {
+                existing.parent = group.parent.clone();
+            }
+        }
+
+        for sub in group.subgroups.iter().flatten() {
+            let subgroups = existing.subgroups.get_or_insert_with(Vec::new);
+            match subgroups.iter_mut().find(|s| s.id == sub.id) {
+                Some(current) => {
+                    if policy == ConflictPolicy::Override && sub.res.is_some() {
+                        current.res = sub.res.clone();
+                    }
+                }
+                None => {
+                    subgroups.push(sub.clone());
+                    report.added.push(entry(&group.id, Some(&sub.id)));
+                }
+            }
+        }
+
+        for res in group.resources.iter().flatten() {
+            let resources = existing.resources.get_or_insert_with(Vec::new);
+            let Some(current) = resources.iter_mut().find(|r| r.id == res.id) else {
+                resources.push(MSubgroupWrapper {
+                    slot: None,
+                    ..res.clone()
+                });
+                report.added.push(entry(&group.id, Some(&res.id)));
+                continue;
+            };
+            match policy {
+                ConflictPolicy::Override => {
+                    let slot = current.slot;
+                    *current = res.clone();
+                    current.slot = slot;
+                    report.overridden.push(entry(&group.id, Some(&res.id)));
+                }
+                ConflictPolicy::Keep => report.kept.push(entry(&group.id, Some(&res.id))),
+                ConflictPolicy::Error => {
+                    return Err(ResourcesError::MergeConflict(
+                        source.to_string(),
+                        format!("{}/{}", group.id, res.id),
+                    ))
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy of a layer's group with the slots cleared, except for ids the `replaced` base
+/// group already numbered
+fn without_slots(
+    group: &ShellSubgroupData,
+    replaced: Option<&ShellSubgroupData>,
+) -> ShellSubgroupData {
+    let base_slot = |id: &str| {
+        replaced
+            .and_then(|g| g.resources.as_ref())
+            .and_then(|resources| resources.iter().find(|r| r.id == id))
+            .and_then(|r| r.slot)
+    };
+    let mut group = group.clone();
+    for res in group.resources.iter_mut().flatten() {
+        res.slot = base_slot(&res.id);
+    }
+    group
+}
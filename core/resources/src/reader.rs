@@ -76,7 +76,7 @@ pub fn convert_res_info_to_resource_group(res_info: &ResInfo) -> Result<Resource
         }
     }
 
-    crate::writer::rewrite_slot(&mut resource_group);
+    crate::slots::renumber_slots(&mut resource_group, crate::slots::SlotMode::Compact);
     Ok(resource_group)
 }
 
@@ -102,7 +102,7 @@ fn generate_image_info(
             let atlas: AtlasWrapper = serde_json::from_value(value.clone())?;
 
             let resource = MSubgroupWrapper {
-                slot: 0,
+                slot: None,
                 id: key.clone(),
                 path: atlas.path,
                 r#type: atlas.r#type,
@@ -121,6 +121,7 @@ fn generate_image_info(
                 ah: None,
                 force_original_vector_symbol_size: None,
                 srcpath: None,
+                newton_properties: None,
             };
 
             if let Some(resources) = composite_k.resources.as_mut() {
@@ -128,7 +129,7 @@ fn generate_image_info(
 
                 for (sub_key, sub_value) in atlas.data {
                     let sub_resource = MSubgroupWrapper {
-                        slot: 0,
+                        slot: None,
                         id: sub_key,
                         path: sub_value.path,
                         r#type: sub_value.r#type,
@@ -155,6 +156,7 @@ fn generate_image_info(
                         height: None,
                         force_original_vector_symbol_size: None,
                         srcpath: None,
+                        newton_properties: None,
                     };
                     resources.push(sub_resource);
                 }
@@ -186,7 +188,7 @@ fn generate_file_info(
             for (key, value) in data_map {
                 let data: CommonDataWrapper = serde_json::from_value(value.clone())?;
                 let resource = MSubgroupWrapper {
-                    slot: 0,
+                    slot: None,
                     id: key.clone(),
                     path: data.path,
                     r#type: data.r#type,
@@ -205,6 +207,7 @@ fn generate_file_info(
                     ay: None,
                     aw: None,
                     ah: None,
+                    newton_properties: None,
                 };
                 if let Some(resources) = composite_k.resources.as_mut() {
                     resources.push(resource);
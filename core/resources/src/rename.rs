@@ -0,0 +1,23 @@
+use crate::types::*;
+
+/// Rename a resource id everywhere in the manifest: its declarations in every group and
+/// the `parent` of sprites cut from it. Returns the number of fields changed.
+pub fn rename_resource_id(resource_group: &mut ResourceGroup, old_id: &str, new_id: &str) -> usize {
+    let mut renamed = 0;
+    for res in resource_group
+        .groups
+        .iter_mut()
+        .filter_map(|g| g.resources.as_mut())
+        .flatten()
+    {
+        if res.id == old_id {
+            res.id = new_id.to_string();
+            renamed += 1;
+        }
+        if res.parent.as_deref() == Some(old_id) {
+            res.parent = Some(new_id.to_string());
+            renamed += 1;
+        }
+    }
+    renamed
+}
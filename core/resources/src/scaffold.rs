@@ -0,0 +1,165 @@
+use crate::error::{ResourcesError, Result};
+use crate::types::*;
+use std::collections::HashMap;
+
+/// An image found when scanning a folder of new art
+#[derive(Debug, Clone)]
+pub struct ScaffoldSprite {
+    /// Path relative to the scanned folder, `/`-separated and without extension
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScaffoldOptions {
+    /// Id of the simple group that receives the resources
+    pub group: String,
+    /// Prepended to every generated resource id
+    pub id_prefix: String,
+    /// Segments prepended to every resource path
+    pub path_prefix: Vec<String>,
+    /// Resolution of the group, such as `1536`
+    pub res: Option<String>,
+    /// Composite group the new group is registered in
+    pub parent: Option<String>,
+    /// Add an atlas image for the group and point every sprite at it
+    pub atlas: bool,
+}
+
+impl Default for ScaffoldOptions {
+    fn default() -> Self {
+        Self {
+            group: String::new(),
+            id_prefix: "IMAGE_".to_string(),
+            path_prefix: vec!["images".to_string()],
+            res: None,
+            parent: None,
+            atlas: false,
+        }
+    }
+}
+
+/// `plants/Peashooter Head` -> `IMAGE_PLANTS_PEASHOOTER_HEAD`
+///
+/// Names that already carry the prefix are not prefixed twice.
+pub fn scaffold_id(prefix: &str, name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if id.starts_with(&prefix.to_ascii_uppercase()) {
+        id
+    } else {
+        format!("{}{}", prefix, id)
+    }
+}
+
+/// Build a manifest layer holding one group with an `Image` entry per sprite.
+///
+/// Slots are left unassigned; merge the layer into a manifest with
+/// [`merge_resource_groups`](crate::merge_resource_groups) to number them. With
+/// [`ScaffoldOptions::atlas`] the group also gets `ATLASIMAGE_ATLAS_<GROUP>_00` at
+/// `atlases/<group>_00`, still to be packed. Fails when two names map to the same
+/// id, such as `a/b` and `a_b`.
+pub fn scaffold_resources(
+    sprites: &[ScaffoldSprite],
+    options: &ScaffoldOptions,
+) -> Result<ResourceGroup> {
+    let atlas_id = format!("ATLASIMAGE_ATLAS_{}_00", options.group.to_uppercase());
+    let image = |id: String, path: Vec<String>, width: u32, height: u32| MSubgroupWrapper {
+        r#type: "Image".to_string(),
+        slot: None,
+        id,
+        path: PathDef::Array(path),
+        atlas: None,
+        runtime: None,
+        x: None,
+        y: None,
+        cols: None,
+        rows: None,
+        parent: None,
+        ax: None,
+        ay: None,
+        aw: None,
+        ah: None,
+        width: Some(width),
+        height: Some(height),
+        force_original_vector_symbol_size: None,
+        srcpath: None,
+        newton_properties: None,
+    };
+
+    let mut resources = Vec::with_capacity(sprites.len() + 1);
+    let mut names: HashMap<String, &str> = HashMap::new();
+    if options.atlas {
+        names.insert(atlas_id.clone(), "the atlas image");
+        let path = vec!["atlases".to_string(), format!("{}_00", options.group)];
+        resources.push(MSubgroupWrapper {
+            atlas: Some(true),
+            runtime: Some(true),
+            ..image(atlas_id.clone(), path, 0, 0)
+        });
+    }
+    for sprite in sprites {
+        let mut path = options.path_prefix.clone();
+        path.extend(
+            sprite
+                .name
+                .split(['/', '\\'])
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+        );
+        let id = scaffold_id(&options.id_prefix, &sprite.name);
+        if let Some(other) = names.insert(id.clone(), &sprite.name) {
+            return Err(ResourcesError::IdCollision(
+                id,
+                other.to_string(),
+                sprite.name.clone(),
+            ));
+        }
+        let mut res = image(id, path, sprite.width, sprite.height);
+        if options.atlas {
+            res.parent = Some(atlas_id.clone());
+            res.x = Some(0);
+            res.y = Some(0);
+        }
+        resources.push(res);
+    }
+
+    let mut groups = Vec::with_capacity(2);
+    if let Some(parent) = &options.parent {
+        groups.push(ShellSubgroupData {
+            id: parent.clone(),
+            r#type: "composite".to_string(),
+            res: None,
+            parent: None,
+            subgroups: Some(vec![SubgroupWrapper {
+                id: options.group.clone(),
+                res: options.res.clone(),
+            }]),
+            resources: None,
+        });
+    }
+    groups.push(ShellSubgroupData {
+        id: options.group.clone(),
+        r#type: "simple".to_string(),
+        res: options.res.clone(),
+        parent: options.parent.clone(),
+        subgroups: None,
+        resources: Some(resources),
+    });
+
+    Ok(ResourceGroup {
+        version: None,
+        content_version: None,
+        slot_count: 0,
+        groups,
+    })
+}
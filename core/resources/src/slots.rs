@@ -0,0 +1,57 @@
+use crate::types::*;
+use std::collections::{HashMap, HashSet};
+
+/// How [`renumber_slots`] assigns slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotMode {
+    /// Number every resource from 0 in manifest order
+    Compact,
+    /// Keep existing slots and give resources without a slot of their own one past the
+    /// current maximum, so slots already referenced by the game do not move
+    Append,
+}
+
+/// Reassign resource slots and update `slot_count`.
+///
+/// A resource id that appears in several groups (one per resolution) shares one slot.
+/// In [`SlotMode::Append`] every assigned slot is reserved before new ones are handed
+/// out, so a resource keeps its slot unless an earlier resource with a different id
+/// already holds it. Returns the number of resources whose slot changed.
+pub fn renumber_slots(resource_group: &mut ResourceGroup, mode: SlotMode) -> usize {
+    let mut id_map: HashMap<String, u32> = HashMap::new();
+    let mut next = 0;
+
+    if mode == SlotMode::Append {
+        let mut taken: HashSet<u32> = HashSet::new();
+        for group in &resource_group.groups {
+            for res in group.resources.iter().flatten() {
+                let Some(slot) = res.slot else {
+                    continue;
+                };
+                if !id_map.contains_key(&res.id) && taken.insert(slot) {
+                    id_map.insert(res.id.clone(), slot);
+                }
+            }
+        }
+        next = taken.iter().max().map_or(0, |max| max + 1);
+    }
+
+    let mut changed = 0;
+    for group in &mut resource_group.groups {
+        if let Some(resources) = &mut group.resources {
+            for res in resources {
+                let slot = *id_map.entry(res.id.clone()).or_insert_with(|| {
+                    next += 1;
+                    next - 1
+                });
+                if res.slot != Some(slot) {
+                    res.slot = Some(slot);
+                    changed += 1;
+                }
+            }
+        }
+    }
+
+    resource_group.slot_count = id_map.values().max().map_or(0, |max| max + 1);
+    changed
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 /// Path definition which can be either a string or an array of strings
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -9,6 +9,43 @@ pub enum PathDef {
     String(String),
 }
 
+impl PathDef {
+    /// The path as a single backslash-separated string, as stored by Newton and the game
+    pub fn to_joined(&self) -> String {
+        match self {
+            PathDef::Array(parts) => parts.join("\\"),
+            PathDef::String(path) => path.clone(),
+        }
+    }
+
+    /// Split into path segments, accepting either separator
+    pub fn segments(&self) -> Vec<String> {
+        match self {
+            PathDef::Array(parts) => parts.clone(),
+            PathDef::String(path) => path
+                .split(['\\', '/'])
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+        }
+    }
+
+    /// Re-shape the path for a ResInfo `expand_path` setting ("string" or "array")
+    pub fn expanded(&self, expand_path: &str) -> PathDef {
+        match expand_path {
+            "string" => PathDef::String(self.to_joined()),
+            "array" => PathDef::Array(self.segments()),
+            _ => self.clone(),
+        }
+    }
+}
+
+impl From<String> for PathDef {
+    fn from(path: String) -> Self {
+        PathDef::String(path)
+    }
+}
+
 /// A dimension property format used in composite atlases
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Dimension {
@@ -17,9 +54,14 @@ pub struct Dimension {
 }
 
 /// The official layout structure when bundled (resources.xml equivalent structure)
+///
+/// Shared by every encoding of the manifest: Newton binary, `resources.rton` and the
+/// grouped `resources.json`. Newton does not store the versions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResourceGroup {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content_version: Option<u32>,
     pub slot_count: u32,
     pub groups: Vec<ShellSubgroupData>,
@@ -49,7 +91,9 @@ pub struct SubgroupWrapper {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MSubgroupWrapper {
     pub r#type: String, // E.g., "Image", "Sound", "File"
-    pub slot: u32,
+    /// `None` for new entries until `renumber_slots` gives them one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<u32>,
     pub id: String,
     pub path: PathDef,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -79,28 +123,36 @@ pub struct MSubgroupWrapper {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub height: Option<u32>,
     #[serde(
+        default,
         skip_serializing_if = "Option::is_none",
-        rename = "forceOriginalVectorSymbolSize"
+        rename = "forceOriginalVectorSymbolSize",
+        alias = "force_original_vector_symbol_size"
     )]
     pub force_original_vector_symbol_size: Option<bool>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub srcpath: Option<PathDef>,
+    /// Hex dump of the Newton property block of a resource type Newton does not know,
+    /// kept so newer manifests round-trip
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub newton_properties: Option<String>,
 }
 
 // ---------------------------------------------------------
 // Flattened dictionary layout (res.json equivalent structure)
 // ---------------------------------------------------------
 
+/// Maps are sorted by id, so reading a res.json back gives the same group order and
+/// the same slots every time
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResInfo {
     pub expand_path: String, // "string" or "array"
-    pub groups: HashMap<String, GroupDictionary>,
+    pub groups: BTreeMap<String, GroupDictionary>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GroupDictionary {
     pub is_composite: bool,
-    pub subgroup: HashMap<String, MSubgroupData>,
+    pub subgroup: BTreeMap<String, MSubgroupData>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -117,7 +169,7 @@ pub struct AtlasWrapper {
     pub r#type: String,
     pub path: PathDef,
     pub dimension: Dimension,
-    pub data: HashMap<String, SpriteData>,
+    pub data: BTreeMap<String, SpriteData>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -144,7 +196,7 @@ pub struct DefaultProperty {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CommonWrapper {
     pub r#type: String,
-    pub data: HashMap<String, CommonDataWrapper>,
+    pub data: BTreeMap<String, CommonDataWrapper>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,291 @@
+use crate::types::*;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Kind of problem found by [`validate_resource_group`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IssueKind {
+    /// Resource path has no matching file in the unpacked RSB
+    MissingPath,
+    /// Sprite rectangle extends past its parent atlas image
+    AtlasOutOfBounds,
+    /// Group or resource id is declared more than once
+    DuplicateId,
+    /// `parent` does not name an atlas resource
+    UnknownParent,
+    /// Composite group lists a subgroup that is not defined
+    UnknownSubgroup,
+    /// Two different resources share a slot
+    SlotCollision,
+    /// Slot is not below `slot_count`
+    SlotOutOfRange,
+    /// Resource has not been given a slot yet
+    MissingSlot,
+}
+
+impl IssueKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IssueKind::MissingPath => "missing-path",
+            IssueKind::AtlasOutOfBounds => "atlas-out-of-bounds",
+            IssueKind::DuplicateId => "duplicate-id",
+            IssueKind::UnknownParent => "unknown-parent",
+            IssueKind::UnknownSubgroup => "unknown-subgroup",
+            IssueKind::SlotCollision => "slot-collision",
+            IssueKind::SlotOutOfRange => "slot-out-of-range",
+            IssueKind::MissingSlot => "missing-slot",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationIssue {
+    pub kind: IssueKind,
+    /// Id of the group the problem was found in
+    pub group: String,
+    /// Id of the offending resource or subgroup, empty for group-level issues
+    pub id: String,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.id.is_empty() {
+            write!(f, "[{}] {}: {}", self.kind.name(), self.group, self.message)
+        } else {
+            write!(
+                f,
+                "[{}] {}/{}: {}",
+                self.kind.name(),
+                self.group,
+                self.id,
+                self.message
+            )
+        }
+    }
+}
+
+/// Files available in an unpacked RSB, keyed case-insensitively by their packet path
+///
+/// Manifest paths usually omit the extension (`ATLASES\GLOBAL_1536_00` is stored as
+/// `ATLASES\GLOBAL_1536_00.PTX`), so each file is also reachable by its stem.
+#[derive(Debug, Clone, Default)]
+pub struct PackedFiles {
+    files: HashMap<String, Option<Dimension>>,
+    stems: HashMap<String, Option<Dimension>>,
+}
+
+impl PackedFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a file, with its image size when it is a texture
+    pub fn insert(&mut self, path: &str, dimension: Option<Dimension>) {
+        let key = normalize_path(path);
+        if let Some((stem, ext)) = key.rsplit_once('.') {
+            if !ext.contains('\\') {
+                self.stems
+                    .entry(stem.to_string())
+                    .or_insert_with(|| dimension.clone());
+            }
+        }
+        self.files.insert(key, dimension);
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Look up a manifest path; `None` when no file matches
+    pub fn get(&self, path: &PathDef) -> Option<Option<&Dimension>> {
+        let key = normalize_path(&path.to_joined());
+        self.files
+            .get(&key)
+            .or_else(|| self.stems.get(&key))
+            .map(Option::as_ref)
+    }
+}
+
+fn normalize_path(path: &str) -> String {
+    path.replace('/', "\\").trim_matches('\\').to_uppercase()
+}
+
+/// Check a manifest for internal consistency and, when `files` is given, against the
+/// contents of an unpacked RSB.
+pub fn validate_resource_group(
+    resource_group: &ResourceGroup,
+    files: Option<&PackedFiles>,
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |kind, group: &str, id: &str, message: String| {
+        issues.push(ValidationIssue {
+            kind,
+            group: group.to_string(),
+            id: id.to_string(),
+            message,
+        })
+    };
+
+    let mut group_ids = HashSet::new();
+    for group in &resource_group.groups {
+        if !group_ids.insert(group.id.as_str()) {
+            issue(
+                IssueKind::DuplicateId,
+                &group.id,
+                "",
+                "group id is declared more than once".into(),
+            );
+        }
+    }
+
+    // Atlases are looked up by id from every group, since sprites may reference
+    // an atlas declared elsewhere
+    let mut atlases: HashMap<&str, &MSubgroupWrapper> = HashMap::new();
+    for res in all_resources(resource_group) {
+        if res.atlas == Some(true) {
+            atlases.entry(res.id.as_str()).or_insert(res);
+        }
+    }
+
+    // The same id may appear in several resolution groups, but must keep its slot
+    let mut slot_by_id: HashMap<&str, u32> = HashMap::new();
+    let mut id_by_slot: HashMap<u32, &str> = HashMap::new();
+
+    for group in &resource_group.groups {
+        if let Some(subgroups) = &group.subgroups {
+            for sub in subgroups {
+                if !group_ids.contains(sub.id.as_str()) {
+                    issue(
+                        IssueKind::UnknownSubgroup,
+                        &group.id,
+                        &sub.id,
+                        "subgroup is not defined in the manifest".into(),
+                    );
+                }
+            }
+        }
+
+        let Some(resources) = &group.resources else {
+            continue;
+        };
+        let mut ids_in_group = HashSet::new();
+        for res in resources {
+            if !ids_in_group.insert(res.id.as_str()) {
+                issue(
+                    IssueKind::DuplicateId,
+                    &group.id,
+                    &res.id,
+                    "resource id is declared more than once in this group".into(),
+                );
+            }
+
+            if let Some(res_slot) = res.slot {
+                match slot_by_id.get(res.id.as_str()) {
+                    Some(&slot) if slot != res_slot => issue(
+                        IssueKind::DuplicateId,
+                        &group.id,
+                        &res.id,
+                        format!(
+                            "resource id is declared with slot {} and slot {}",
+                            slot, res_slot
+                        ),
+                    ),
+                    Some(_) => {}
+                    None => {
+                        slot_by_id.insert(&res.id, res_slot);
+                    }
+                }
+                match id_by_slot.get(&res_slot) {
+                    Some(&other) if other != res.id => issue(
+                        IssueKind::SlotCollision,
+                        &group.id,
+                        &res.id,
+                        format!("slot {} is already used by {}", res_slot, other),
+                    ),
+                    Some(_) => {}
+                    None => {
+                        id_by_slot.insert(res_slot, &res.id);
+                    }
+                }
+                if res_slot >= resource_group.slot_count {
+                    issue(
+                        IssueKind::SlotOutOfRange,
+                        &group.id,
+                        &res.id,
+                        format!(
+                            "slot {} is not below slot_count {}",
+                            res_slot, resource_group.slot_count
+                        ),
+                    );
+                }
+            } else {
+                issue(
+                    IssueKind::MissingSlot,
+                    &group.id,
+                    &res.id,
+                    "resource has no slot".into(),
+                );
+            }
+
+            let Some(parent_id) = &res.parent else {
+                if let Some(files) = files {
+                    if files.get(&res.path).is_none() {
+                        issue(
+                            IssueKind::MissingPath,
+                            &group.id,
+                            &res.id,
+                            format!("{} does not exist", res.path.to_joined()),
+                        );
+                    }
+                }
+                continue;
+            };
+
+            let Some(atlas) = atlases.get(parent_id.as_str()) else {
+                issue(
+                    IssueKind::UnknownParent,
+                    &group.id,
+                    &res.id,
+                    format!("parent {} is not an atlas", parent_id),
+                );
+                continue;
+            };
+
+            // Prefer the real texture size over the declared one
+            let size = files
+                .and_then(|files| files.get(&atlas.path).flatten())
+                .map(|d| (d.width, d.height))
+                .or_else(|| atlas.width.zip(atlas.height));
+            if let Some((width, height)) = size {
+                let (ax, ay) = (res.ax.unwrap_or(0), res.ay.unwrap_or(0));
+                let (aw, ah) = (res.aw.unwrap_or(0), res.ah.unwrap_or(0));
+                if ax as u64 + aw as u64 > width as u64 || ay as u64 + ah as u64 > height as u64 {
+                    issue(
+                        IssueKind::AtlasOutOfBounds,
+                        &group.id,
+                        &res.id,
+                        format!(
+                            "rectangle {}x{} at ({}, {}) exceeds {} size {}x{}",
+                            aw, ah, ax, ay, parent_id, width, height
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+fn all_resources(resource_group: &ResourceGroup) -> impl Iterator<Item = &MSubgroupWrapper> {
+    resource_group
+        .groups
+        .iter()
+        .filter_map(|g| g.resources.as_ref())
+        .flatten()
+}
@@ -1,6 +1,7 @@
 use crate::types::*;
 use crate::Result;
-use std::collections::HashMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
 
 /// Convert a hierarchical ResourceGroup back to a flattened ResInfo structure
 pub fn convert_resource_group_to_res_info(
@@ -9,20 +10,26 @@ pub fn convert_resource_group_to_res_info(
 ) -> Result<ResInfo> {
     let mut res_info = ResInfo {
         expand_path: expand_path_str.to_string(),
-        groups: HashMap::new(),
+        groups: BTreeMap::new(),
     };
 
     for group in &resource_group.groups {
         if let Some(subgroups) = &group.subgroups {
             // It's a composite
-            let mut subgroup_dict = HashMap::new();
+            let mut subgroup_dict = BTreeMap::new();
             for sub in subgroups {
                 let found_group = resource_group.groups.iter().find(|g| g.id == sub.id);
                 if let Some(found) = found_group {
                     if sub.res.is_some() && sub.res.as_deref() != Some("0") {
-                        subgroup_dict.insert(sub.id.clone(), convert_atlas_subgroup_data(found)?);
+                        subgroup_dict.insert(
+                            sub.id.clone(),
+                            convert_atlas_subgroup_data(found, expand_path_str)?,
+                        );
                     } else {
-                        subgroup_dict.insert(sub.id.clone(), convert_common_subgroup_data(found)?);
+                        subgroup_dict.insert(
+                            sub.id.clone(),
+                            convert_common_subgroup_data(found, expand_path_str)?,
+                        );
                     }
                 }
             }
@@ -36,8 +43,11 @@ pub fn convert_resource_group_to_res_info(
             );
         } else if group.parent.is_none() && group.resources.is_some() {
             // Independent subgroup
-            let mut subgroup_dict = HashMap::new();
-            subgroup_dict.insert(group.id.clone(), convert_common_subgroup_data(group)?);
+            let mut subgroup_dict = BTreeMap::new();
+            subgroup_dict.insert(
+                group.id.clone(),
+                convert_common_subgroup_data(group, expand_path_str)?,
+            );
 
             res_info.groups.insert(
                 group.id.clone(),
@@ -52,8 +62,11 @@ pub fn convert_resource_group_to_res_info(
     Ok(res_info)
 }
 
-fn convert_atlas_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgroupData> {
-    let mut packet = HashMap::new();
+fn convert_atlas_subgroup_data(
+    subgroup: &ShellSubgroupData,
+    expand_path: &str,
+) -> Result<MSubgroupData> {
+    let mut packet = BTreeMap::new();
     let mut children_by_parent: HashMap<String, Vec<&MSubgroupWrapper>> = HashMap::new();
 
     if let Some(resources) = &subgroup.resources {
@@ -72,12 +85,12 @@ fn convert_atlas_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgroup
             if res.atlas == Some(true) {
                 let mut atlas = AtlasWrapper {
                     r#type: res.r#type.clone(),
-                    path: res.path.clone(),
+                    path: res.path.expanded(expand_path),
                     dimension: Dimension {
                         width: res.width.unwrap_or(0),
                         height: res.height.unwrap_or(0),
                     },
-                    data: HashMap::new(),
+                    data: BTreeMap::new(),
                 };
 
                 if let Some(children) = children_by_parent.get(&res.id) {
@@ -86,7 +99,7 @@ fn convert_atlas_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgroup
                             child.id.clone(),
                             SpriteData {
                                 r#type: child.r#type.clone(),
-                                path: child.path.clone(),
+                                path: child.path.expanded(expand_path),
                                 r#default: DefaultProperty {
                                     ax: child.ax.unwrap_or(0),
                                     ay: child.ay.unwrap_or(0),
@@ -112,8 +125,11 @@ fn convert_atlas_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgroup
     })
 }
 
-fn convert_common_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgroupData> {
-    let mut data_map = HashMap::new();
+fn convert_common_subgroup_data(
+    subgroup: &ShellSubgroupData,
+    expand_path: &str,
+) -> Result<MSubgroupData> {
+    let mut data_map = BTreeMap::new();
 
     if let Some(resources) = &subgroup.resources {
         for res in resources {
@@ -121,9 +137,9 @@ fn convert_common_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgrou
                 res.id.clone(),
                 CommonDataWrapper {
                     r#type: res.r#type.clone(),
-                    path: res.path.clone(),
+                    path: res.path.expanded(expand_path),
                     force_original_vector_symbol_size: res.force_original_vector_symbol_size,
-                    srcpath: res.srcpath.clone(),
+                    srcpath: res.srcpath.as_ref().map(|p| p.expanded(expand_path)),
                 },
             );
         }
@@ -140,24 +156,79 @@ fn convert_common_subgroup_data(subgroup: &ShellSubgroupData) -> Result<MSubgrou
     })
 }
 
-/// Helper fn to reassign incremental slots across unique resource objects globally within the group
-pub(crate) fn rewrite_slot(resource_group: &mut ResourceGroup) {
-    let mut id_map: HashMap<String, u32> = HashMap::new();
-    let mut count = 0;
-
-    for group in &mut resource_group.groups {
-        if let Some(resources) = &mut group.resources {
-            for res in resources {
-                if let Some(&slot) = id_map.get(&res.id) {
-                    res.slot = slot;
-                } else {
-                    res.slot = count;
-                    id_map.insert(res.id.clone(), count);
-                    count += 1;
-                }
+/// Describe what converting to the flat ResInfo layout would drop or change, found by
+/// converting there and back. Paths only count when they differ beyond the
+/// `expand_path` shape. An empty list means the conversion is lossless.
+pub fn res_info_losses(resource_group: &ResourceGroup, expand_path: &str) -> Result<Vec<String>> {
+    let res_info = convert_resource_group_to_res_info(resource_group, expand_path)?;
+    let round_trip = crate::reader::convert_res_info_to_resource_group(&res_info)?;
+    group_losses(resource_group, &round_trip, Some(expand_path))
+}
+
+/// Describe what differs between `resource_group` and `round_trip`, the same manifest
+/// after being written in another format and read back. With `expand_path` set, paths
+/// only count when they differ beyond that shape.
+pub fn group_losses(
+    resource_group: &ResourceGroup,
+    round_trip: &ResourceGroup,
+    expand_path: Option<&str>,
+) -> Result<Vec<String>> {
+    let mut losses = Vec::new();
+    if (resource_group.version, resource_group.content_version)
+        != (round_trip.version, round_trip.content_version)
+    {
+        losses.push("manifest version".to_string());
+    }
+
+    for group in &resource_group.groups {
+        let Some(other) = round_trip.groups.iter().find(|g| g.id == group.id) else {
+            losses.push(format!("group `{}`", group.id));
+            continue;
+        };
+        if (&group.r#type, &group.res, &group.parent) != (&other.r#type, &other.res, &other.parent)
+        {
+            losses.push(format!("type of group `{}`", group.id));
+        }
+        let subgroup_ids = |g: &ShellSubgroupData| {
+            let mut ids: Vec<(String, Option<String>)> = g
+                .subgroups
+                .iter()
+                .flatten()
+                .map(|s| (s.id.clone(), s.res.clone()))
+                .collect();
+            ids.sort();
+            ids
+        };
+        if subgroup_ids(group) != subgroup_ids(other) {
+            losses.push(format!("subgroups of `{}`", group.id));
+        }
+
+        for res in group.resources.iter().flatten() {
+            let Some(after) = other.resources.iter().flatten().find(|r| r.id == res.id) else {
+                losses.push(format!("resource `{}` in `{}`", res.id, group.id));
+                continue;
+            };
+            let mut before = res.clone();
+            if let Some(expand_path) = expand_path {
+                before.path = before.path.expanded(expand_path);
+                before.srcpath = before.srcpath.map(|p| p.expanded(expand_path));
+            }
+            let (Value::Object(before), Value::Object(after)) =
+                (serde_json::to_value(before)?, serde_json::to_value(after)?)
+            else {
+                continue;
+            };
+            let mut fields: Vec<&String> = before
+                .keys()
+                .chain(after.keys())
+                .filter(|key| before.get(*key) != after.get(*key))
+                .collect();
+            fields.sort();
+            fields.dedup();
+            for field in fields {
+                losses.push(format!("`{}` of `{}` in `{}`", field, res.id, group.id));
             }
         }
     }
-
-    resource_group.slot_count = count;
+    Ok(losses)
 }
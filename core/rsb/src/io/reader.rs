@@ -261,6 +261,7 @@ impl<R: Read + Seek> Rsb<R> {
                 format,
                 alpha_size,
                 alpha_format,
+                mip_count: None,
             });
         }
         Ok(infos)
@@ -0,0 +1,478 @@
+use crate::error::{Result, RsbError};
+use crate::ptx::codec::PtxCodec;
+use crate::ptx::codec::etc1::{
+    decode_etc1, decode_etc1_a8, decode_palette_alpha, encode_etc1_block, encode_palette_alpha,
+};
+use crate::ptx::codec::pvrtc::{decode_pvrtc_4bpp, decode_pvrtc_4bpp_a8};
+use crate::ptx::color::Rgba32;
+use crate::ptx::dither::{PtxEncodeOptions, quantize};
+use crate::ptx::types::PtxFormat;
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+
+fn etc1_size(width: u32, height: u32) -> usize {
+    width.div_ceil(4) as usize * height.div_ceil(4) as usize * 8
+}
+
+fn pvrtc_size(width: u32, height: u32) -> usize {
+    width.max(8) as usize * height.max(8) as usize / 2
+}
+
+fn not_implemented(format: PtxFormat) -> RsbError {
+    RsbError::DeserializationError(format!("Encoding not implemented for {:?}", format))
+}
+
+/// ETC1 colour blocks, padding partial blocks with opaque black.
+fn encode_etc1_rgb(image: &DynamicImage) -> Vec<u8> {
+    let width = image.width();
+    let height = image.height();
+    let mut data = Vec::with_capacity(etc1_size(width, height));
+    let blocks_x = width.div_ceil(4);
+    let blocks_y = height.div_ceil(4);
+
+    for by in 0..blocks_y {
+        for bx in 0..blocks_x {
+            // Extract 4x4 block
+            let mut block_pixels = [Rgba32::default(); 16];
+            for y in 0..4 {
+                for x in 0..4 {
+                    let px = bx * 4 + x;
+                    let py = by * 4 + y;
+                    if px < width && py < height {
+                        block_pixels[(y * 4 + x) as usize] =
+                            Rgba32::from_pixel(image.get_pixel(px, py));
+                    } else {
+                        block_pixels[(y * 4 + x) as usize] = Rgba32::new(0, 0, 0, 255);
+                    }
+                }
+            }
+
+            let encoded_block = encode_etc1_block(&block_pixels);
+            data.extend_from_slice(&encoded_block.to_be_bytes()); // ETC1 is Big Endian
+        }
+    }
+    data
+}
+
+/// 8-bit RGBA, stored as BGRA on PowerVR.
+pub struct Rgba8888Codec {
+    pub bgra: bool,
+}
+
+impl PtxCodec for Rgba8888Codec {
+    fn name(&self) -> &str {
+        if self.bgra { "Bgra8888" } else { "Rgba8888" }
+    }
+
+    fn format(&self) -> PtxFormat {
+        PtxFormat::Rgba8888
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        let num_pixels = width as usize * height as usize;
+        if data.len() < num_pixels * 4 {
+            return Err(RsbError::DeserializationError(format!(
+                "Insufficient data for Rgba8888: expected {}, got {}",
+                num_pixels * 4,
+                data.len()
+            )));
+        }
+
+        let mut img_buf = ImageBuffer::new(width, height);
+        for (pixel, p) in img_buf.pixels_mut().zip(data.chunks_exact(4)) {
+            *pixel = if self.bgra {
+                Rgba([p[2], p[1], p[0], p[3]])
+            } else {
+                Rgba([p[0], p[1], p[2], p[3]])
+            };
+        }
+        Ok(DynamicImage::ImageRgba8(img_buf))
+    }
+
+    fn encode(&self, image: &DynamicImage, _options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(self.data_size(image.width(), image.height()));
+        for p in image.to_rgba8().pixels() {
+            if self.bgra {
+                data.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+            } else {
+                data.extend_from_slice(&p.0);
+            }
+        }
+        Ok(data)
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        width as usize * height as usize * 4
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        (1, 1)
+    }
+}
+
+/// 16-bit layouts, either linear or tiled in 32x32 blocks.
+pub struct Packed16Codec {
+    pub format: PtxFormat,
+    pub name: &'static str,
+    /// Channel bit depths in RGBA order
+    pub bits: [u8; 4],
+    pub pack: fn([u8; 4]) -> u16,
+    pub unpack: fn(u16) -> Rgba<u8>,
+    pub tiled: bool,
+}
+
+const TILE: u32 = 32;
+
+impl Packed16Codec {
+    pub fn rgba4444(tiled: bool) -> Self {
+        Self {
+            format: if tiled {
+                PtxFormat::Rgba4444Block
+            } else {
+                PtxFormat::Rgba4444
+            },
+            name: if tiled { "Rgba4444Block" } else { "Rgba4444" },
+            bits: [4, 4, 4, 4],
+            // RRRR GGGG BBBB AAAA
+            pack: |l| {
+                ((l[0] as u16) << 12) | ((l[1] as u16) << 8) | ((l[2] as u16) << 4) | l[3] as u16
+            },
+            unpack: |val| {
+                let r = ((val & 0xF000) >> 12) as u8;
+                let g = ((val & 0x0F00) >> 8) as u8;
+                let b = ((val & 0x00F0) >> 4) as u8;
+                let a = (val & 0x000F) as u8;
+                Rgba([(r << 4) | r, (g << 4) | g, (b << 4) | b, (a << 4) | a])
+            },
+            tiled,
+        }
+    }
+
+    pub fn rgb565(tiled: bool) -> Self {
+        Self {
+            format: if tiled {
+                PtxFormat::Rgb565Block
+            } else {
+                PtxFormat::Rgb565
+            },
+            name: if tiled { "Rgb565Block" } else { "Rgb565" },
+            bits: [5, 6, 5, 0],
+            // RRRRR GGGGGG BBBBB
+            pack: |l| ((l[0] as u16) << 11) | ((l[1] as u16) << 5) | l[2] as u16,
+            unpack: |val| {
+                let r = ((val & 0xF800) >> 11) as u8;
+                let g = ((val & 0x07E0) >> 5) as u8;
+                let b = (val & 0x001F) as u8;
+                Rgba([
+                    (r << 3) | (r >> 2),
+                    (g << 2) | (g >> 4),
+                    (b << 3) | (b >> 2),
+                    255,
+                ])
+            },
+            tiled,
+        }
+    }
+
+    pub fn rgba5551(tiled: bool) -> Self {
+        Self {
+            format: if tiled {
+                PtxFormat::Rgba5551Block
+            } else {
+                PtxFormat::Rgba5551
+            },
+            name: if tiled { "Rgba5551Block" } else { "Rgba5551" },
+            bits: [5, 5, 5, 1],
+            // RRRRR GGGGG BBBBB A
+            pack: |l| {
+                ((l[0] as u16) << 11) | ((l[1] as u16) << 6) | ((l[2] as u16) << 1) | l[3] as u16
+            },
+            unpack: |val| {
+                let r = ((val & 0xF800) >> 11) as u8;
+                let g = ((val & 0x07C0) >> 6) as u8;
+                let b = ((val & 0x003E) >> 1) as u8;
+                let a = if val & 0x0001 == 1 { 255 } else { 0 };
+                Rgba([
+                    (r << 3) | (r >> 2),
+                    (g << 3) | (g >> 2),
+                    (b << 3) | (b >> 2),
+                    a,
+                ])
+            },
+            tiled,
+        }
+    }
+
+    /// Number of 16-bit values stored, including tile padding.
+    fn stored_pixels(&self, width: u32, height: u32) -> usize {
+        self.data_size(width, height) / 2
+    }
+
+    /// Image coordinates of the `index`-th stored value; padding lies outside the image.
+    fn coords(&self, index: usize, width: u32) -> (u32, u32) {
+        let index = index as u32;
+        if !self.tiled {
+            return (index % width, index / width);
+        }
+        let tile = index / (TILE * TILE);
+        let within = index % (TILE * TILE);
+        let blocks_x = width.div_ceil(TILE);
+        (
+            (tile % blocks_x) * TILE + within % TILE,
+            (tile / blocks_x) * TILE + within / TILE,
+        )
+    }
+}
+
+impl PtxCodec for Packed16Codec {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn format(&self) -> PtxFormat {
+        self.format
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        let num_pixels = width as usize * height as usize;
+        // Tiled data may be cut short; missing pixels stay transparent
+        if !self.tiled && data.len() < num_pixels * 2 {
+            return Err(RsbError::DeserializationError(format!(
+                "Insufficient data for {}: expected {}, got {}",
+                self.name,
+                num_pixels * 2,
+                data.len()
+            )));
+        }
+
+        let mut img_buf = ImageBuffer::new(width, height);
+        for (i, chunk) in data
+            .chunks_exact(2)
+            .take(self.stored_pixels(width, height))
+            .enumerate()
+        {
+            let (x, y) = self.coords(i, width);
+            if x < width && y < height {
+                let val = u16::from_le_bytes([chunk[0], chunk[1]]);
+                img_buf.put_pixel(x, y, (self.unpack)(val));
+            }
+        }
+        Ok(DynamicImage::ImageRgba8(img_buf))
+    }
+
+    fn encode(&self, image: &DynamicImage, options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        let width = image.width();
+        let height = image.height();
+        let levels = quantize(&image.to_rgba8(), self.bits, options.dither);
+
+        let mut data = Vec::with_capacity(self.data_size(width, height));
+        for i in 0..self.stored_pixels(width, height) {
+            let (x, y) = self.coords(i, width);
+            if x < width && y < height {
+                let val = (self.pack)(levels[(y * width + x) as usize]);
+                data.extend_from_slice(&val.to_le_bytes());
+            } else {
+                // Padding
+                data.extend_from_slice(&[0, 0]);
+            }
+        }
+        Ok(data)
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        if self.tiled {
+            width.div_ceil(TILE) as usize
+                * height.div_ceil(TILE) as usize
+                * (TILE * TILE) as usize
+                * 2
+        } else {
+            width as usize * height as usize * 2
+        }
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        if self.tiled { (TILE, TILE) } else { (1, 1) }
+    }
+}
+
+/// Opaque PVRTC 4bpp.
+pub struct Pvrtc4BppCodec;
+
+impl PtxCodec for Pvrtc4BppCodec {
+    fn name(&self) -> &str {
+        "Pvrtc4BppRgba"
+    }
+
+    fn format(&self) -> PtxFormat {
+        PtxFormat::Pvrtc4BppRgba
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        decode_pvrtc_4bpp(data, width, height)
+    }
+
+    fn encode(&self, _image: &DynamicImage, _options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        Err(not_implemented(self.format()))
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        pvrtc_size(width, height)
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        (4, 4)
+    }
+}
+
+/// PVRTC 4bpp colour followed by 8-bit alpha.
+pub struct Pvrtc4BppA8Codec;
+
+impl PtxCodec for Pvrtc4BppA8Codec {
+    fn name(&self) -> &str {
+        "Pvrtc4BppRgbaA8"
+    }
+
+    fn format(&self) -> PtxFormat {
+        PtxFormat::Pvrtc4BppRgbaA8
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        let alpha_size = width as usize * height as usize;
+        if data.len() < alpha_size {
+            return Err(RsbError::DeserializationError(
+                "Data too small for PVRTC+A8".into(),
+            ));
+        }
+        let offset = data.len().saturating_sub(alpha_size);
+        let pvrtc_data = &data[..offset];
+        let alpha_data = &data[offset..];
+
+        decode_pvrtc_4bpp_a8(pvrtc_data, alpha_data, width, height)
+    }
+
+    fn encode(&self, _image: &DynamicImage, _options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        Err(not_implemented(self.format()))
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        pvrtc_size(width, height) + width as usize * height as usize
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        (4, 4)
+    }
+}
+
+/// Opaque ETC1.
+pub struct Etc1Codec;
+
+impl PtxCodec for Etc1Codec {
+    fn name(&self) -> &str {
+        "Etc1"
+    }
+
+    fn format(&self) -> PtxFormat {
+        PtxFormat::Etc1
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        decode_etc1(data, width, height)
+    }
+
+    fn encode(&self, image: &DynamicImage, _options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        Ok(encode_etc1_rgb(image))
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        etc1_size(width, height)
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        (4, 4)
+    }
+}
+
+/// ETC1 colour with either 8-bit alpha or a second ETC1 surface holding alpha.
+pub struct Etc1A8Codec;
+
+impl PtxCodec for Etc1A8Codec {
+    fn name(&self) -> &str {
+        "Etc1A8"
+    }
+
+    fn format(&self) -> PtxFormat {
+        PtxFormat::Etc1A8
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        let pixels = width as usize * height as usize;
+        let opaque_size = pixels / 2;
+        // Check for Uncompressed Alpha (3x size) first
+        if data.len() >= opaque_size * 3 {
+            // Uncompressed
+            let offset = data.len().saturating_sub(pixels);
+            decode_etc1_a8(&data[..offset], &data[offset..], width, height, false)
+        } else if data.len() >= opaque_size * 2 {
+            // Likely dual-ETC1 (RGB + Alpha encoded as ETC1)
+            let midpoint = data.len() / 2;
+            decode_etc1_a8(&data[..midpoint], &data[midpoint..], width, height, true)
+        } else {
+            let offset = data.len().saturating_sub(pixels);
+            decode_etc1_a8(&data[..offset], &data[offset..], width, height, false)
+        }
+    }
+
+    fn encode(&self, image: &DynamicImage, _options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        // ETC1 + Uncompressed Alpha (Legacy/Standard)
+        let mut data = encode_etc1_rgb(image);
+        data.extend(image.to_rgba8().pixels().map(|p| p[3]));
+        Ok(data)
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        etc1_size(width, height) + width as usize * height as usize
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        (4, 4)
+    }
+
+    fn accepts(&self, data_len: usize, width: u32, height: u32) -> bool {
+        // The ETC1-compressed alpha variant is only twice the colour data
+        data_len >= width as usize * height as usize / 2 * 2
+    }
+}
+
+/// ETC1 colour with palettised alpha, shares format id 30 with PVRTC.
+pub struct Etc1PaletteCodec;
+
+impl PtxCodec for Etc1PaletteCodec {
+    fn name(&self) -> &str {
+        "Etc1Palette"
+    }
+
+    fn format(&self) -> PtxFormat {
+        PtxFormat::Etc1Palette
+    }
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+        decode_palette_alpha(data, width, height)
+    }
+
+    fn encode(&self, image: &DynamicImage, _options: &PtxEncodeOptions) -> Result<Vec<u8>> {
+        encode_palette_alpha(image)
+    }
+
+    fn data_size(&self, width: u32, height: u32) -> usize {
+        // 16-entry palette with 4-bit indices, as written by `encode_palette_alpha`
+        etc1_size(width, height) + 1 + 16 + (width as usize * height as usize).div_ceil(2)
+    }
+
+    fn block_dims(&self) -> (u32, u32) {
+        (4, 4)
+    }
+
+    fn accepts(&self, data_len: usize, width: u32, height: u32) -> bool {
+        // Smaller palettes use fewer index bits, so anything clearly past the colour data
+        data_len > width as usize * height as usize / 2 + 16
+    }
+}
@@ -1,2 +1,36 @@
 pub mod etc1;
+pub mod formats;
 pub mod pvrtc;
+
+use crate::error::Result;
+use crate::ptx::dither::PtxEncodeOptions;
+use crate::ptx::types::PtxFormat;
+use image::DynamicImage;
+
+/// A single PTX pixel layout.
+///
+/// Implementations are registered in `PtxRegistry` under a format id and platform;
+/// `PtxDecoder`/`PtxEncoder` dispatch to whichever codec the registry resolves.
+pub trait PtxCodec: Send + Sync {
+    /// Display name, also used by the CLI format listing.
+    fn name(&self) -> &str;
+
+    /// The layout this codec produces; downstream formats use `PtxFormat::Unknown(id)`.
+    fn format(&self) -> PtxFormat;
+
+    fn decode(&self, data: &[u8], width: u32, height: u32) -> Result<DynamicImage>;
+
+    fn encode(&self, image: &DynamicImage, options: &PtxEncodeOptions) -> Result<Vec<u8>>;
+
+    /// Bytes needed for one `width` x `height` surface as produced by `encode`.
+    fn data_size(&self, width: u32, height: u32) -> usize;
+
+    /// Pixel dimensions of one storage block (1x1 for linear layouts).
+    fn block_dims(&self) -> (u32, u32);
+
+    /// Whether `data_len` bytes plausibly hold this layout. Used to pick between codecs
+    /// sharing a format id; the default accepts anything at least `data_size` long.
+    fn accepts(&self, data_len: usize, width: u32, height: u32) -> bool {
+        data_len >= self.data_size(width, height)
+    }
+}
@@ -0,0 +1,202 @@
+//! DirectDraw Surface with a legacy pixel format header.
+//!
+//! DDS has no official ETC1 or PVRTC codes; the `ETC1` and `PTC4` FourCCs used here
+//! are the ones understood by common texture tools.
+
+use crate::error::{Result, RsbError};
+use crate::ptx::container::{ContainerTexture, GpuFormat};
+use crate::ptx::mipmap::mip_dimensions;
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read};
+
+pub const MAGIC: &[u8] = b"DDS ";
+
+const HEADER_SIZE: u32 = 124;
+const PIXEL_FORMAT_SIZE: u32 = 32;
+
+const DDSD_CAPS: u32 = 0x1;
+const DDSD_HEIGHT: u32 = 0x2;
+const DDSD_WIDTH: u32 = 0x4;
+const DDSD_PITCH: u32 = 0x8;
+const DDSD_PIXELFORMAT: u32 = 0x1000;
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDSD_LINEARSIZE: u32 = 0x80000;
+
+const DDPF_ALPHAPIXELS: u32 = 0x1;
+const DDPF_FOURCC: u32 = 0x4;
+const DDPF_RGB: u32 = 0x40;
+const DDPF_LUMINANCE: u32 = 0x20000;
+
+const DDSCAPS_COMPLEX: u32 = 0x8;
+const DDSCAPS_TEXTURE: u32 = 0x1000;
+const DDSCAPS_MIPMAP: u32 = 0x40_0000;
+
+const FOURCC_ETC1: &[u8; 4] = b"ETC1";
+const FOURCC_PVRTC_4BPP: &[u8; 4] = b"PTC4";
+
+/// (flags, fourCC, bit count, [R, G, B, A] masks)
+fn pixel_format(format: GpuFormat) -> (u32, [u8; 4], u32, [u32; 4]) {
+    let rgba = DDPF_RGB | DDPF_ALPHAPIXELS;
+    match format {
+        GpuFormat::Rgba8 => (
+            rgba,
+            [0; 4],
+            32,
+            [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000],
+        ),
+        GpuFormat::Bgra8 => (
+            rgba,
+            [0; 4],
+            32,
+            [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000],
+        ),
+        GpuFormat::Rgba4444 => (rgba, [0; 4], 16, [0xF000, 0x0F00, 0x00F0, 0x000F]),
+        GpuFormat::Rgb565 => (DDPF_RGB, [0; 4], 16, [0xF800, 0x07E0, 0x001F, 0]),
+        GpuFormat::Rgba5551 => (rgba, [0; 4], 16, [0xF800, 0x07C0, 0x003E, 0x0001]),
+        GpuFormat::R8 => (DDPF_LUMINANCE, [0; 4], 8, [0xFF, 0, 0, 0]),
+        GpuFormat::Etc1 => (DDPF_FOURCC, *FOURCC_ETC1, 0, [0; 4]),
+        GpuFormat::Pvrtc4Bpp => (DDPF_FOURCC, *FOURCC_PVRTC_4BPP, 0, [0; 4]),
+    }
+}
+
+pub fn write(texture: &ContainerTexture) -> Result<Vec<u8>> {
+    let (pf_flags, fourcc, bit_count, masks) = pixel_format(texture.format);
+    let has_mips = texture.levels.len() > 1;
+
+    let mut flags = DDSD_CAPS | DDSD_HEIGHT | DDSD_WIDTH | DDSD_PIXELFORMAT;
+    let pitch_or_linear_size = if texture.format.is_compressed() {
+        flags |= DDSD_LINEARSIZE;
+        texture.levels.first().map_or(0, |l| l.len())
+    } else {
+        flags |= DDSD_PITCH;
+        texture.width as usize * texture.format.unit_size()
+    };
+    let mut caps = DDSCAPS_TEXTURE;
+    if has_mips {
+        flags |= DDSD_MIPMAPCOUNT;
+        caps |= DDSCAPS_COMPLEX | DDSCAPS_MIPMAP;
+    }
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    out.write_u32::<LE>(HEADER_SIZE)?;
+    out.write_u32::<LE>(flags)?;
+    out.write_u32::<LE>(texture.height)?;
+    out.write_u32::<LE>(texture.width)?;
+    out.write_u32::<LE>(pitch_or_linear_size as u32)?;
+    out.write_u32::<LE>(0)?; // depth
+    out.write_u32::<LE>(texture.levels.len() as u32)?;
+    out.resize(out.len() + 11 * 4, 0); // reserved1
+
+    out.write_u32::<LE>(PIXEL_FORMAT_SIZE)?;
+    out.write_u32::<LE>(pf_flags)?;
+    out.extend_from_slice(&fourcc);
+    out.write_u32::<LE>(bit_count)?;
+    for mask in masks {
+        out.write_u32::<LE>(mask)?;
+    }
+
+    out.write_u32::<LE>(caps)?;
+    out.resize(out.len() + 4 * 4, 0); // caps2, caps3, caps4, reserved2
+
+    for level in &texture.levels {
+        out.extend_from_slice(level);
+    }
+    Ok(out)
+}
+
+pub fn read(data: &[u8]) -> Result<ContainerTexture> {
+    let mut cursor = Cursor::new(data);
+    let mut magic = [0u8; 4];
+    cursor.read_exact(&mut magic)?;
+    if magic != MAGIC {
+        return Err(RsbError::InvalidMagic(
+            "DDS ".into(),
+            String::from_utf8_lossy(&magic).into_owned(),
+        ));
+    }
+
+    let header_size = cursor.read_u32::<LE>()?;
+    if header_size != HEADER_SIZE {
+        return Err(RsbError::DeserializationError(format!(
+            "Invalid DDS header size: {}",
+            header_size
+        )));
+    }
+    let _flags = cursor.read_u32::<LE>()?;
+    let height = cursor.read_u32::<LE>()?.max(1);
+    let width = cursor.read_u32::<LE>()?;
+    let _pitch_or_linear_size = cursor.read_u32::<LE>()?;
+    let _depth = cursor.read_u32::<LE>()?;
+    let level_count = cursor.read_u32::<LE>()?.max(1);
+    cursor.set_position(cursor.position() + 11 * 4);
+
+    let _pf_size = cursor.read_u32::<LE>()?;
+    let pf_flags = cursor.read_u32::<LE>()?;
+    let mut fourcc = [0u8; 4];
+    cursor.read_exact(&mut fourcc)?;
+    let bit_count = cursor.read_u32::<LE>()?;
+    let mut masks = [0u32; 4];
+    for mask in &mut masks {
+        *mask = cursor.read_u32::<LE>()?;
+    }
+    let _caps = cursor.read_u32::<LE>()?;
+    let caps2 = cursor.read_u32::<LE>()?;
+    if caps2 != 0 {
+        return Err(RsbError::DeserializationError(
+            "Cube map and volume DDS files are not supported".into(),
+        ));
+    }
+    cursor.set_position(4 + HEADER_SIZE as u64);
+
+    let format = [
+        GpuFormat::Rgba8,
+        GpuFormat::Bgra8,
+        GpuFormat::Rgba4444,
+        GpuFormat::Rgb565,
+        GpuFormat::Rgba5551,
+        GpuFormat::R8,
+        GpuFormat::Etc1,
+        GpuFormat::Pvrtc4Bpp,
+    ]
+    .into_iter()
+    .find(|&candidate| {
+        let (flags, expected_fourcc, expected_bits, expected_masks) = pixel_format(candidate);
+        if flags & DDPF_FOURCC != 0 {
+            pf_flags & DDPF_FOURCC != 0 && fourcc == expected_fourcc
+        } else {
+            pf_flags & DDPF_FOURCC == 0
+                && bit_count == expected_bits
+                && masks[..3] == expected_masks[..3]
+                && (flags & DDPF_ALPHAPIXELS == 0 || masks[3] == expected_masks[3])
+        }
+    })
+    .ok_or_else(|| {
+        RsbError::DeserializationError(format!(
+            "Unsupported DDS pixel format: flags 0x{:X}, fourCC {:?}, {} bits",
+            pf_flags,
+            String::from_utf8_lossy(&fourcc),
+            bit_count
+        ))
+    })?;
+
+    super::check_level_count("DDS", width, height, level_count)?;
+    let mut levels = Vec::with_capacity(level_count as usize);
+    let mut offset = cursor.position() as usize;
+    for level in 0..level_count {
+        let (w, h) = mip_dimensions(width, height, level);
+        let size = format.level_size(w, h);
+        let level_data = data.get(offset..offset + size).ok_or_else(|| {
+            RsbError::DeserializationError("DDS level extends past end of file".into())
+        })?;
+        levels.push(level_data.to_vec());
+        offset += size;
+    }
+
+    Ok(ContainerTexture {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
@@ -0,0 +1,185 @@
+//! KTX 1.1 (Khronos texture) with OpenGL format enums.
+
+use crate::error::{Result, RsbError};
+use crate::ptx::container::{ContainerTexture, GpuFormat};
+use crate::ptx::mipmap::mip_dimensions;
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read};
+
+pub const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x31, 0x31, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const ENDIANNESS: u32 = 0x0403_0201;
+
+const GL_UNSIGNED_BYTE: u32 = 0x1401;
+const GL_UNSIGNED_SHORT_4_4_4_4: u32 = 0x8033;
+const GL_UNSIGNED_SHORT_5_5_5_1: u32 = 0x8034;
+const GL_UNSIGNED_SHORT_5_6_5: u32 = 0x8363;
+
+const GL_RED: u32 = 0x1903;
+const GL_RGB: u32 = 0x1907;
+const GL_RGBA: u32 = 0x1908;
+const GL_BGRA: u32 = 0x80E1;
+
+const GL_RGBA4: u32 = 0x8056;
+const GL_RGB5_A1: u32 = 0x8057;
+const GL_RGBA8: u32 = 0x8058;
+const GL_R8: u32 = 0x8229;
+const GL_RGB565: u32 = 0x8D62;
+const GL_ETC1_RGB8_OES: u32 = 0x8D64;
+const GL_COMPRESSED_RGBA_PVRTC_4BPPV1_IMG: u32 = 0x8C02;
+
+/// (glType, glTypeSize, glFormat, glInternalFormat, glBaseInternalFormat)
+fn gl_format(format: GpuFormat) -> (u32, u32, u32, u32, u32) {
+    match format {
+        GpuFormat::Rgba8 => (GL_UNSIGNED_BYTE, 1, GL_RGBA, GL_RGBA8, GL_RGBA),
+        GpuFormat::Bgra8 => (GL_UNSIGNED_BYTE, 1, GL_BGRA, GL_RGBA8, GL_RGBA),
+        GpuFormat::Rgba4444 => (GL_UNSIGNED_SHORT_4_4_4_4, 2, GL_RGBA, GL_RGBA4, GL_RGBA),
+        GpuFormat::Rgb565 => (GL_UNSIGNED_SHORT_5_6_5, 2, GL_RGB, GL_RGB565, GL_RGB),
+        GpuFormat::Rgba5551 => (GL_UNSIGNED_SHORT_5_5_5_1, 2, GL_RGBA, GL_RGB5_A1, GL_RGBA),
+        GpuFormat::R8 => (GL_UNSIGNED_BYTE, 1, GL_RED, GL_R8, GL_RED),
+        GpuFormat::Etc1 => (0, 1, 0, GL_ETC1_RGB8_OES, GL_RGB),
+        GpuFormat::Pvrtc4Bpp => (0, 1, 0, GL_COMPRESSED_RGBA_PVRTC_4BPPV1_IMG, GL_RGBA),
+    }
+}
+
+fn from_gl(gl_type: u32, gl_format: u32, internal_format: u32) -> Result<GpuFormat> {
+    Ok(match (gl_type, gl_format, internal_format) {
+        (GL_UNSIGNED_BYTE, GL_RGBA, _) => GpuFormat::Rgba8,
+        (GL_UNSIGNED_BYTE, GL_BGRA, _) => GpuFormat::Bgra8,
+        (GL_UNSIGNED_BYTE, GL_RED, _) => GpuFormat::R8,
+        (GL_UNSIGNED_SHORT_4_4_4_4, GL_RGBA, _) => GpuFormat::Rgba4444,
+        (GL_UNSIGNED_SHORT_5_6_5, GL_RGB, _) => GpuFormat::Rgb565,
+        (GL_UNSIGNED_SHORT_5_5_5_1, GL_RGBA, _) => GpuFormat::Rgba5551,
+        (0, _, GL_ETC1_RGB8_OES) => GpuFormat::Etc1,
+        (0, _, GL_COMPRESSED_RGBA_PVRTC_4BPPV1_IMG) => GpuFormat::Pvrtc4Bpp,
+        _ => {
+            return Err(RsbError::DeserializationError(format!(
+                "Unsupported KTX format: glType 0x{:X}, glFormat 0x{:X}, glInternalFormat 0x{:X}",
+                gl_type, gl_format, internal_format
+            )));
+        }
+    })
+}
+
+/// Uncompressed rows are padded to 4 bytes (GL_UNPACK_ALIGNMENT).
+fn row_bytes(format: GpuFormat, width: u32) -> Option<(usize, usize)> {
+    if format.is_compressed() {
+        return None;
+    }
+    let packed = width as usize * format.unit_size();
+    Some((packed, packed.next_multiple_of(4)))
+}
+
+pub fn write(texture: &ContainerTexture) -> Result<Vec<u8>> {
+    let (gl_type, gl_type_size, gl_format, internal_format, base_format) =
+        gl_format(texture.format);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&IDENTIFIER);
+    out.write_u32::<LE>(ENDIANNESS)?;
+    out.write_u32::<LE>(gl_type)?;
+    out.write_u32::<LE>(gl_type_size)?;
+    out.write_u32::<LE>(gl_format)?;
+    out.write_u32::<LE>(internal_format)?;
+    out.write_u32::<LE>(base_format)?;
+    out.write_u32::<LE>(texture.width)?;
+    out.write_u32::<LE>(texture.height)?;
+    out.write_u32::<LE>(0)?; // pixelDepth
+    out.write_u32::<LE>(0)?; // numberOfArrayElements
+    out.write_u32::<LE>(1)?; // numberOfFaces
+    out.write_u32::<LE>(texture.levels.len() as u32)?;
+    out.write_u32::<LE>(0)?; // bytesOfKeyValueData
+
+    for (level, data) in texture.levels.iter().enumerate() {
+        let (w, _) = mip_dimensions(texture.width, texture.height, level as u32);
+        match row_bytes(texture.format, w) {
+            Some((packed, padded)) => {
+                out.write_u32::<LE>((data.len() / packed * padded) as u32)?;
+                for row in data.chunks(packed) {
+                    out.extend_from_slice(row);
+                    out.resize(out.len() + padded - packed, 0);
+                }
+            }
+            None => {
+                out.write_u32::<LE>(data.len() as u32)?;
+                out.extend_from_slice(data);
+            }
+        }
+        // mipPadding
+        out.resize(out.len().next_multiple_of(4), 0);
+    }
+
+    Ok(out)
+}
+
+pub fn read(data: &[u8]) -> Result<ContainerTexture> {
+    let mut cursor = Cursor::new(data);
+    let mut identifier = [0u8; 12];
+    cursor.read_exact(&mut identifier)?;
+    if identifier != IDENTIFIER {
+        return Err(RsbError::InvalidMagic(
+            "KTX 11".into(),
+            String::from_utf8_lossy(&identifier).into_owned(),
+        ));
+    }
+    if cursor.read_u32::<LE>()? != ENDIANNESS {
+        return Err(RsbError::DeserializationError(
+            "Big-endian KTX files are not supported".into(),
+        ));
+    }
+
+    let gl_type = cursor.read_u32::<LE>()?;
+    let _gl_type_size = cursor.read_u32::<LE>()?;
+    let gl_format = cursor.read_u32::<LE>()?;
+    let internal_format = cursor.read_u32::<LE>()?;
+    let _base_format = cursor.read_u32::<LE>()?;
+    let width = cursor.read_u32::<LE>()?;
+    let height = cursor.read_u32::<LE>()?.max(1);
+    let depth = cursor.read_u32::<LE>()?;
+    let array_elements = cursor.read_u32::<LE>()?;
+    let faces = cursor.read_u32::<LE>()?;
+    let level_count = cursor.read_u32::<LE>()?.max(1);
+    let kv_bytes = cursor.read_u32::<LE>()?;
+    if depth > 1 || array_elements > 0 || faces != 1 {
+        return Err(RsbError::DeserializationError(
+            "Only single 2D KTX textures are supported".into(),
+        ));
+    }
+    cursor.set_position(cursor.position() + kv_bytes as u64);
+
+    let format = from_gl(gl_type, gl_format, internal_format)?;
+    super::check_level_count("KTX", width, height, level_count)?;
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for level in 0..level_count {
+        let (w, h) = mip_dimensions(width, height, level);
+        let image_size = cursor.read_u32::<LE>()? as usize;
+        let remaining = data.len().saturating_sub(cursor.position() as usize);
+        if image_size > remaining {
+            return Err(RsbError::DeserializationError(
+                "KTX level extends past end of file".into(),
+            ));
+        }
+        let mut raw = vec![0u8; image_size];
+        cursor.read_exact(&mut raw)?;
+        cursor.set_position(cursor.position().next_multiple_of(4));
+
+        let level_data = match row_bytes(format, w) {
+            Some((packed, padded)) => raw
+                .chunks(padded)
+                .take(h as usize)
+                .flat_map(|row| &row[..packed.min(row.len())])
+                .copied()
+                .collect(),
+            None => raw,
+        };
+        levels.push(level_data);
+    }
+
+    Ok(ContainerTexture {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
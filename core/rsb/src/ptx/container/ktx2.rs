@@ -0,0 +1,251 @@
+//! KTX 2.0 with Vulkan format enums and a basic data format descriptor.
+
+use crate::error::{Result, RsbError};
+use crate::ptx::container::{ContainerTexture, GpuFormat};
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use std::io::{Cursor, Read};
+
+pub const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+const VK_FORMAT_R4G4B4A4_UNORM_PACK16: u32 = 2;
+const VK_FORMAT_R5G6B5_UNORM_PACK16: u32 = 4;
+const VK_FORMAT_R5G5B5A1_UNORM_PACK16: u32 = 6;
+const VK_FORMAT_R8_UNORM: u32 = 9;
+const VK_FORMAT_R8G8B8A8_UNORM: u32 = 37;
+const VK_FORMAT_B8G8R8A8_UNORM: u32 = 44;
+// ETC1 is a subset of ETC2 RGB
+const VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK: u32 = 147;
+const VK_FORMAT_PVRTC1_4BPP_UNORM_BLOCK_IMG: u32 = 1_000_054_001;
+
+const KHR_DF_MODEL_RGBSDA: u8 = 1;
+const KHR_DF_MODEL_ETC1: u8 = 160;
+const KHR_DF_MODEL_PVRTC: u8 = 164;
+const KHR_DF_PRIMARIES_BT709: u8 = 1;
+const KHR_DF_TRANSFER_LINEAR: u8 = 1;
+const KHR_DF_CHANNEL_R: u8 = 0;
+const KHR_DF_CHANNEL_G: u8 = 1;
+const KHR_DF_CHANNEL_B: u8 = 2;
+const KHR_DF_CHANNEL_A: u8 = 15;
+
+const HEADER_SIZE: usize = 80;
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+fn vk_format(format: GpuFormat) -> u32 {
+    match format {
+        GpuFormat::Rgba8 => VK_FORMAT_R8G8B8A8_UNORM,
+        GpuFormat::Bgra8 => VK_FORMAT_B8G8R8A8_UNORM,
+        GpuFormat::Rgba4444 => VK_FORMAT_R4G4B4A4_UNORM_PACK16,
+        GpuFormat::Rgb565 => VK_FORMAT_R5G6B5_UNORM_PACK16,
+        GpuFormat::Rgba5551 => VK_FORMAT_R5G5B5A1_UNORM_PACK16,
+        GpuFormat::R8 => VK_FORMAT_R8_UNORM,
+        GpuFormat::Etc1 => VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK,
+        GpuFormat::Pvrtc4Bpp => VK_FORMAT_PVRTC1_4BPP_UNORM_BLOCK_IMG,
+    }
+}
+
+fn from_vk(vk_format: u32) -> Result<GpuFormat> {
+    Ok(match vk_format {
+        VK_FORMAT_R8G8B8A8_UNORM => GpuFormat::Rgba8,
+        VK_FORMAT_B8G8R8A8_UNORM => GpuFormat::Bgra8,
+        VK_FORMAT_R4G4B4A4_UNORM_PACK16 => GpuFormat::Rgba4444,
+        VK_FORMAT_R5G6B5_UNORM_PACK16 => GpuFormat::Rgb565,
+        VK_FORMAT_R5G5B5A1_UNORM_PACK16 => GpuFormat::Rgba5551,
+        VK_FORMAT_R8_UNORM => GpuFormat::R8,
+        VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK => GpuFormat::Etc1,
+        VK_FORMAT_PVRTC1_4BPP_UNORM_BLOCK_IMG => GpuFormat::Pvrtc4Bpp,
+        n => {
+            return Err(RsbError::DeserializationError(format!(
+                "Unsupported KTX2 vkFormat: {}",
+                n
+            )));
+        }
+    })
+}
+
+/// Samples as (channel, bit offset, bit length), lowest offset first.
+fn samples(format: GpuFormat) -> Vec<(u8, u16, u8)> {
+    match format {
+        GpuFormat::Rgba8 => vec![
+            (KHR_DF_CHANNEL_R, 0, 8),
+            (KHR_DF_CHANNEL_G, 8, 8),
+            (KHR_DF_CHANNEL_B, 16, 8),
+            (KHR_DF_CHANNEL_A, 24, 8),
+        ],
+        GpuFormat::Bgra8 => vec![
+            (KHR_DF_CHANNEL_B, 0, 8),
+            (KHR_DF_CHANNEL_G, 8, 8),
+            (KHR_DF_CHANNEL_R, 16, 8),
+            (KHR_DF_CHANNEL_A, 24, 8),
+        ],
+        GpuFormat::Rgba4444 => vec![
+            (KHR_DF_CHANNEL_A, 0, 4),
+            (KHR_DF_CHANNEL_B, 4, 4),
+            (KHR_DF_CHANNEL_G, 8, 4),
+            (KHR_DF_CHANNEL_R, 12, 4),
+        ],
+        GpuFormat::Rgb565 => vec![
+            (KHR_DF_CHANNEL_B, 0, 5),
+            (KHR_DF_CHANNEL_G, 5, 6),
+            (KHR_DF_CHANNEL_R, 11, 5),
+        ],
+        GpuFormat::Rgba5551 => vec![
+            (KHR_DF_CHANNEL_A, 0, 1),
+            (KHR_DF_CHANNEL_B, 1, 5),
+            (KHR_DF_CHANNEL_G, 6, 5),
+            (KHR_DF_CHANNEL_R, 11, 5),
+        ],
+        GpuFormat::R8 => vec![(KHR_DF_CHANNEL_R, 0, 8)],
+        // Compressed formats describe the whole 64-bit block as one colour sample
+        GpuFormat::Etc1 | GpuFormat::Pvrtc4Bpp => vec![(0, 0, 64)],
+    }
+}
+
+/// Data format descriptor: total size followed by one basic descriptor block.
+fn data_format_descriptor(format: GpuFormat) -> Result<Vec<u8>> {
+    let samples = samples(format);
+    let block_size = 24 + 16 * samples.len();
+    let (model, block_dim) = match format {
+        GpuFormat::Etc1 => (KHR_DF_MODEL_ETC1, 3),
+        GpuFormat::Pvrtc4Bpp => (KHR_DF_MODEL_PVRTC, 3),
+        _ => (KHR_DF_MODEL_RGBSDA, 0),
+    };
+
+    let mut dfd = Vec::with_capacity(4 + block_size);
+    dfd.write_u32::<LE>((4 + block_size) as u32)?;
+    dfd.write_u32::<LE>(0)?; // vendorId = Khronos, descriptorType = basic
+    dfd.write_u16::<LE>(2)?; // versionNumber
+    dfd.write_u16::<LE>(block_size as u16)?;
+    dfd.extend_from_slice(&[model, KHR_DF_PRIMARIES_BT709, KHR_DF_TRANSFER_LINEAR, 0]);
+    dfd.extend_from_slice(&[block_dim, block_dim, 0, 0]);
+    dfd.extend_from_slice(&[format.unit_size() as u8, 0, 0, 0, 0, 0, 0, 0]);
+
+    for (channel, offset, length) in samples {
+        dfd.write_u16::<LE>(offset)?;
+        dfd.push(length - 1);
+        dfd.push(channel);
+        dfd.extend_from_slice(&[0, 0, 0, 0]); // samplePosition
+        dfd.write_u32::<LE>(0)?; // sampleLower
+        let upper = if length >= 32 {
+            u32::MAX
+        } else {
+            (1u32 << length) - 1
+        };
+        dfd.write_u32::<LE>(upper)?;
+    }
+
+    Ok(dfd)
+}
+
+pub fn write(texture: &ContainerTexture) -> Result<Vec<u8>> {
+    let level_count = texture.levels.len();
+    let dfd = data_format_descriptor(texture.format)?;
+    let dfd_offset = HEADER_SIZE + LEVEL_INDEX_ENTRY_SIZE * level_count;
+
+    // Level data is stored smallest first, each level aligned to lcm(texel block, 4)
+    let align = match texture.format.unit_size() {
+        1 | 2 | 4 => 4,
+        n => n,
+    };
+    let mut offsets = vec![0usize; level_count];
+    let mut end = dfd_offset + dfd.len();
+    for level in (0..level_count).rev() {
+        end = end.next_multiple_of(align);
+        offsets[level] = end;
+        end += texture.levels[level].len();
+    }
+
+    let mut out = Vec::with_capacity(end);
+    out.extend_from_slice(&IDENTIFIER);
+    out.write_u32::<LE>(vk_format(texture.format))?;
+    let type_size = match texture.format {
+        GpuFormat::Rgba4444 | GpuFormat::Rgb565 | GpuFormat::Rgba5551 => 2,
+        _ => 1,
+    };
+    out.write_u32::<LE>(type_size)?;
+    out.write_u32::<LE>(texture.width)?;
+    out.write_u32::<LE>(texture.height)?;
+    out.write_u32::<LE>(0)?; // pixelDepth
+    out.write_u32::<LE>(0)?; // layerCount
+    out.write_u32::<LE>(1)?; // faceCount
+    out.write_u32::<LE>(level_count as u32)?;
+    out.write_u32::<LE>(0)?; // supercompressionScheme
+    out.write_u32::<LE>(dfd_offset as u32)?;
+    out.write_u32::<LE>(dfd.len() as u32)?;
+    out.write_u32::<LE>(0)?; // kvdByteOffset
+    out.write_u32::<LE>(0)?; // kvdByteLength
+    out.write_u64::<LE>(0)?; // sgdByteOffset
+    out.write_u64::<LE>(0)?; // sgdByteLength
+
+    for (level, data) in texture.levels.iter().enumerate() {
+        out.write_u64::<LE>(offsets[level] as u64)?;
+        out.write_u64::<LE>(data.len() as u64)?;
+        out.write_u64::<LE>(data.len() as u64)?;
+    }
+    out.extend_from_slice(&dfd);
+
+    for level in (0..level_count).rev() {
+        out.resize(offsets[level], 0);
+        out.extend_from_slice(&texture.levels[level]);
+    }
+
+    Ok(out)
+}
+
+pub fn read(data: &[u8]) -> Result<ContainerTexture> {
+    let mut cursor = Cursor::new(data);
+    let mut identifier = [0u8; 12];
+    cursor.read_exact(&mut identifier)?;
+    if identifier != IDENTIFIER {
+        return Err(RsbError::InvalidMagic(
+            "KTX 20".into(),
+            String::from_utf8_lossy(&identifier).into_owned(),
+        ));
+    }
+
+    let format = from_vk(cursor.read_u32::<LE>()?)?;
+    let _type_size = cursor.read_u32::<LE>()?;
+    let width = cursor.read_u32::<LE>()?;
+    let height = cursor.read_u32::<LE>()?.max(1);
+    let depth = cursor.read_u32::<LE>()?;
+    let layers = cursor.read_u32::<LE>()?;
+    let faces = cursor.read_u32::<LE>()?;
+    let level_count = cursor.read_u32::<LE>()?.max(1);
+    let supercompression = cursor.read_u32::<LE>()?;
+    if depth > 1 || layers > 1 || faces != 1 {
+        return Err(RsbError::DeserializationError(
+            "Only single 2D KTX2 textures are supported".into(),
+        ));
+    }
+    if supercompression != 0 {
+        return Err(RsbError::DeserializationError(format!(
+            "Supercompressed KTX2 files are not supported (scheme {})",
+            supercompression
+        )));
+    }
+
+    super::check_level_count("KTX2", width, height, level_count)?;
+
+    cursor.set_position(HEADER_SIZE as u64);
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for _ in 0..level_count {
+        let offset = cursor.read_u64::<LE>()?;
+        let length = cursor.read_u64::<LE>()?;
+        let _uncompressed_length = cursor.read_u64::<LE>()?;
+        let level_data = offset
+            .checked_add(length)
+            .and_then(|end| data.get(usize::try_from(offset).ok()?..usize::try_from(end).ok()?))
+            .ok_or_else(|| {
+                RsbError::DeserializationError("KTX2 level extends past end of file".into())
+            })?;
+        levels.push(level_data.to_vec());
+    }
+
+    Ok(ContainerTexture {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
@@ -0,0 +1,372 @@
+pub mod dds;
+pub mod ktx;
+pub mod ktx2;
+pub mod pvr;
+
+use crate::error::{Result, RsbError};
+use crate::ptx::decoder::PtxDecoder;
+use crate::ptx::mipmap::{full_mip_count, mip_dimensions};
+use crate::ptx::registry::{PtxPlatform, PtxRegistry};
+use crate::ptx::types::PtxFormat;
+
+/// GPU texture container a PTX payload can be wrapped in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    Ktx,
+    Ktx2,
+    Dds,
+    Pvr,
+}
+
+impl ContainerKind {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ContainerKind::Ktx => "ktx",
+            ContainerKind::Ktx2 => "ktx2",
+            ContainerKind::Dds => "dds",
+            ContainerKind::Pvr => "pvr",
+        }
+    }
+
+    /// Identify a container from its leading magic bytes.
+    pub fn from_magic(data: &[u8]) -> Option<Self> {
+        if data.starts_with(&ktx::IDENTIFIER) {
+            Some(ContainerKind::Ktx)
+        } else if data.starts_with(&ktx2::IDENTIFIER) {
+            Some(ContainerKind::Ktx2)
+        } else if data.starts_with(dds::MAGIC) {
+            Some(ContainerKind::Dds)
+        } else if data.starts_with(&pvr::VERSION.to_le_bytes()) {
+            Some(ContainerKind::Pvr)
+        } else {
+            None
+        }
+    }
+}
+
+/// Pixel layouts that have a standard GPU format equivalent.
+///
+/// The 16-bit formats are packed little-endian with red in the high bits, matching
+/// both PTX and `GL_UNSIGNED_SHORT_4_4_4_4` and friends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuFormat {
+    Rgba8,
+    Bgra8,
+    Rgba4444,
+    Rgb565,
+    Rgba5551,
+    /// Single 8-bit channel, used for the separate alpha plane of A8 formats.
+    R8,
+    Etc1,
+    Pvrtc4Bpp,
+}
+
+impl GpuFormat {
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, GpuFormat::Etc1 | GpuFormat::Pvrtc4Bpp)
+    }
+
+    /// Bytes per pixel for uncompressed formats, bytes per 4x4 block otherwise.
+    pub fn unit_size(&self) -> usize {
+        match self {
+            GpuFormat::Rgba8 | GpuFormat::Bgra8 => 4,
+            GpuFormat::Rgba4444 | GpuFormat::Rgb565 | GpuFormat::Rgba5551 => 2,
+            GpuFormat::R8 => 1,
+            GpuFormat::Etc1 | GpuFormat::Pvrtc4Bpp => 8,
+        }
+    }
+
+    /// Tightly packed byte size of one `width` x `height` surface.
+    pub fn level_size(&self, width: u32, height: u32) -> usize {
+        match self {
+            GpuFormat::Etc1 => width.div_ceil(4) as usize * height.div_ceil(4) as usize * 8,
+            // PVRTC surfaces are at least 8x8 pixels
+            GpuFormat::Pvrtc4Bpp => width.max(8) as usize * height.max(8) as usize / 2,
+            _ => width as usize * height as usize * self.unit_size(),
+        }
+    }
+}
+
+/// A texture as stored in a container: tightly packed levels, base level first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContainerTexture {
+    pub format: GpuFormat,
+    pub width: u32,
+    pub height: u32,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl ContainerTexture {
+    fn check_levels(&self) -> Result<()> {
+        for (level, data) in self.levels.iter().enumerate() {
+            let (w, h) = mip_dimensions(self.width, self.height, level as u32);
+            let expected = self.format.level_size(w, h);
+            if data.len() != expected {
+                return Err(RsbError::DeserializationError(format!(
+                    "Mip level {} of {:?} {}x{} should be {} bytes, got {}",
+                    level,
+                    self.format,
+                    w,
+                    h,
+                    expected,
+                    data.len()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reject a level count read from a container header that the base level can't
+/// have, before it sizes any allocation or loop.
+fn check_level_count(container: &str, width: u32, height: u32, level_count: u32) -> Result<()> {
+    let max = full_mip_count(width, height);
+    if level_count > max {
+        return Err(RsbError::DeserializationError(format!(
+            "{} declares {} mip levels but a {}x{} texture has at most {}",
+            container, level_count, width, height, max
+        )));
+    }
+    Ok(())
+}
+
+/// Wrap `texture` in a `kind` container.
+pub fn write_container(kind: ContainerKind, texture: &ContainerTexture) -> Result<Vec<u8>> {
+    texture.check_levels()?;
+    match kind {
+        ContainerKind::Ktx => ktx::write(texture),
+        ContainerKind::Ktx2 => ktx2::write(texture),
+        ContainerKind::Dds => dds::write(texture),
+        ContainerKind::Pvr => pvr::write(texture),
+    }
+}
+
+/// Read any supported container, detected from its magic bytes.
+pub fn read_container(data: &[u8]) -> Result<ContainerTexture> {
+    let texture = match ContainerKind::from_magic(data) {
+        Some(ContainerKind::Ktx) => ktx::read(data)?,
+        Some(ContainerKind::Ktx2) => ktx2::read(data)?,
+        Some(ContainerKind::Dds) => dds::read(data)?,
+        Some(ContainerKind::Pvr) => pvr::read(data)?,
+        None => {
+            return Err(RsbError::DeserializationError(
+                "Unrecognised texture container".into(),
+            ));
+        }
+    };
+    texture.check_levels()?;
+    Ok(texture)
+}
+
+/// PTX data split into surfaces that map onto standard GPU formats.
+///
+/// Formats with a separate alpha plane (ETC1A8, PVRTC+A8) export it as a second
+/// texture, either R8 or ETC1 for the compressed-alpha variant.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PtxSurfaces {
+    pub color: ContainerTexture,
+    pub alpha: Option<ContainerTexture>,
+}
+
+/// A PTX payload rebuilt from container textures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedPtx {
+    pub data: Vec<u8>,
+    pub format: PtxFormat,
+    pub format_code: i32,
+    pub alpha_format: Option<i32>,
+    /// RGBA8888 data was BGRA, i.e. meant for a PowerVR RSB.
+    pub is_powervr: bool,
+    pub width: u32,
+    pub height: u32,
+    pub mip_count: u32,
+}
+
+/// Split raw PTX data into container surfaces without re-encoding it.
+pub fn split_ptx(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    format_code: i32,
+    alpha_format: Option<i32>,
+    is_powervr: bool,
+    mip_count: Option<u32>,
+) -> Result<PtxSurfaces> {
+    let sizes = PtxDecoder::mip_level_sizes(
+        data.len(),
+        width,
+        height,
+        format_code,
+        alpha_format,
+        is_powervr,
+        mip_count,
+    )?;
+    let registry = PtxRegistry::global();
+    let platform = PtxPlatform::from_powervr(is_powervr);
+
+    let mut color: Option<ContainerTexture> = None;
+    let mut alpha: Option<ContainerTexture> = None;
+    let mut offset = 0;
+
+    for (level, size) in sizes.into_iter().enumerate() {
+        let (w, h) = mip_dimensions(width, height, level as u32);
+        let level_data = &data[offset..offset + size];
+        offset += size;
+
+        let codec = registry.decoder_for(format_code, platform, level_data.len(), w, h)?;
+        let pixels = w as usize * h as usize;
+        let etc1_size = GpuFormat::Etc1.level_size(w, h);
+
+        // Alpha planes sit after the colour data; ETC1-compressed alpha is recognised by
+        // `alpha_format` or by being too short for an 8-bit plane
+        let (color_format, color_data, alpha_plane) = match codec.format() {
+            PtxFormat::Rgba8888 if is_powervr => (GpuFormat::Bgra8, level_data, None),
+            PtxFormat::Rgba8888 => (GpuFormat::Rgba8, level_data, None),
+            PtxFormat::Rgba4444 => (GpuFormat::Rgba4444, level_data, None),
+            PtxFormat::Rgb565 => (GpuFormat::Rgb565, level_data, None),
+            PtxFormat::Rgba5551 => (GpuFormat::Rgba5551, level_data, None),
+            PtxFormat::Etc1 => (GpuFormat::Etc1, level_data, None),
+            PtxFormat::Pvrtc4BppRgba => (GpuFormat::Pvrtc4Bpp, level_data, None),
+            PtxFormat::Etc1A8
+                if alpha_format == Some(100) || level_data.len() < etc1_size + pixels =>
+            {
+                let midpoint = level_data.len() / 2;
+                (
+                    GpuFormat::Etc1,
+                    &level_data[..midpoint],
+                    Some((GpuFormat::Etc1, &level_data[midpoint..])),
+                )
+            }
+            PtxFormat::Etc1A8 | PtxFormat::Pvrtc4BppRgbaA8 => {
+                let split = level_data.len().saturating_sub(pixels);
+                let color_format = if codec.format() == PtxFormat::Etc1A8 {
+                    GpuFormat::Etc1
+                } else {
+                    GpuFormat::Pvrtc4Bpp
+                };
+                (
+                    color_format,
+                    &level_data[..split],
+                    Some((GpuFormat::R8, &level_data[split..])),
+                )
+            }
+            other => {
+                return Err(RsbError::DeserializationError(format!(
+                    "{:?} has no standard container equivalent",
+                    other
+                )));
+            }
+        };
+
+        push_level(&mut color, color_format, width, height, w, h, color_data)?;
+        if let Some((alpha_format, alpha_data)) = alpha_plane {
+            push_level(&mut alpha, alpha_format, width, height, w, h, alpha_data)?;
+        }
+    }
+
+    let color = color.ok_or_else(|| RsbError::DeserializationError("Empty PTX data".into()))?;
+    if alpha
+        .as_ref()
+        .is_some_and(|a| a.levels.len() != color.levels.len())
+    {
+        return Err(RsbError::DeserializationError(
+            "Mip levels disagree on having an alpha plane".into(),
+        ));
+    }
+    Ok(PtxSurfaces { color, alpha })
+}
+
+/// Append one level, keeping only the tightly packed part of `data`.
+fn push_level(
+    texture: &mut Option<ContainerTexture>,
+    format: GpuFormat,
+    width: u32,
+    height: u32,
+    level_width: u32,
+    level_height: u32,
+    data: &[u8],
+) -> Result<()> {
+    let size = format.level_size(level_width, level_height);
+    if data.len() < size {
+        return Err(RsbError::DeserializationError(format!(
+            "Insufficient data for {:?} {}x{}: expected {}, got {}",
+            format,
+            level_width,
+            level_height,
+            size,
+            data.len()
+        )));
+    }
+
+    let texture = texture.get_or_insert_with(|| ContainerTexture {
+        format,
+        width,
+        height,
+        levels: Vec::new(),
+    });
+    if texture.format != format {
+        return Err(RsbError::DeserializationError(format!(
+            "Mip levels mix {:?} and {:?}",
+            texture.format, format
+        )));
+    }
+    texture.levels.push(data[..size].to_vec());
+    Ok(())
+}
+
+/// Rebuild PTX data from a colour texture and an optional alpha texture.
+pub fn join_ptx(color: &ContainerTexture, alpha: Option<&ContainerTexture>) -> Result<ImportedPtx> {
+    if let Some(alpha) = alpha
+        && (alpha.width != color.width
+            || alpha.height != color.height
+            || alpha.levels.len() != color.levels.len())
+    {
+        return Err(RsbError::DeserializationError(format!(
+            "Alpha texture {}x{} with {} levels does not match colour texture {}x{} with {} levels",
+            alpha.width,
+            alpha.height,
+            alpha.levels.len(),
+            color.width,
+            color.height,
+            color.levels.len()
+        )));
+    }
+
+    let alpha_kind = alpha.map(|a| a.format);
+    let (format, alpha_format) = match (color.format, alpha_kind) {
+        (GpuFormat::Rgba8 | GpuFormat::Bgra8, None) => (PtxFormat::Rgba8888, None),
+        (GpuFormat::Rgba4444, None) => (PtxFormat::Rgba4444, None),
+        (GpuFormat::Rgb565, None) => (PtxFormat::Rgb565, None),
+        (GpuFormat::Rgba5551, None) => (PtxFormat::Rgba5551, None),
+        (GpuFormat::Etc1, None) => (PtxFormat::Etc1, None),
+        (GpuFormat::Etc1, Some(GpuFormat::R8)) => (PtxFormat::Etc1A8, None),
+        (GpuFormat::Etc1, Some(GpuFormat::Etc1)) => (PtxFormat::Etc1A8, Some(100)),
+        (GpuFormat::Pvrtc4Bpp, None) => (PtxFormat::Pvrtc4BppRgba, None),
+        (GpuFormat::Pvrtc4Bpp, Some(GpuFormat::R8)) => (PtxFormat::Pvrtc4BppRgbaA8, None),
+        (color_format, alpha_format) => {
+            return Err(RsbError::DeserializationError(format!(
+                "No PTX format stores {:?} with {:?} alpha",
+                color_format, alpha_format
+            )));
+        }
+    };
+
+    let mut data = Vec::new();
+    for (level, color_level) in color.levels.iter().enumerate() {
+        data.extend_from_slice(color_level);
+        if let Some(alpha) = alpha {
+            data.extend_from_slice(&alpha.levels[level]);
+        }
+    }
+
+    Ok(ImportedPtx {
+        data,
+        format,
+        format_code: format.code(),
+        alpha_format,
+        is_powervr: color.format == GpuFormat::Bgra8,
+        width: color.width,
+        height: color.height,
+        mip_count: color.levels.len() as u32,
+    })
+}
@@ -0,0 +1,143 @@
+//! PowerVR texture container, version 3.
+
+use crate::error::{Result, RsbError};
+use crate::ptx::container::{ContainerTexture, GpuFormat};
+use crate::ptx::mipmap::mip_dimensions;
+use byteorder::{LE, ReadBytesExt, WriteBytesExt};
+use std::io::Cursor;
+
+/// `PVR\x03` when read as little-endian bytes.
+pub const VERSION: u32 = 0x0352_5650;
+
+const HEADER_SIZE: u64 = 52;
+
+const PVRTC_4BPP_RGBA: u64 = 3;
+const ETC1: u64 = 6;
+
+const COLOUR_SPACE_LINEAR: u32 = 0;
+const CHANNEL_TYPE_UNSIGNED_BYTE_NORM: u32 = 0;
+const CHANNEL_TYPE_UNSIGNED_SHORT_NORM: u32 = 4;
+
+/// Uncompressed formats store channel names in the low four bytes and their bit
+/// widths in the high four, most significant channel first.
+const fn channels(names: [u8; 4], bits: [u8; 4]) -> u64 {
+    u64::from_le_bytes([
+        names[0], names[1], names[2], names[3], bits[0], bits[1], bits[2], bits[3],
+    ])
+}
+
+const RGBA8888: u64 = channels(*b"rgba", [8, 8, 8, 8]);
+const BGRA8888: u64 = channels(*b"bgra", [8, 8, 8, 8]);
+const RGBA4444: u64 = channels(*b"rgba", [4, 4, 4, 4]);
+const RGB565: u64 = channels([b'r', b'g', b'b', 0], [5, 6, 5, 0]);
+const RGBA5551: u64 = channels(*b"rgba", [5, 5, 5, 1]);
+const R8: u64 = channels([b'r', 0, 0, 0], [8, 0, 0, 0]);
+
+fn pixel_format(format: GpuFormat) -> u64 {
+    match format {
+        GpuFormat::Rgba8 => RGBA8888,
+        GpuFormat::Bgra8 => BGRA8888,
+        GpuFormat::Rgba4444 => RGBA4444,
+        GpuFormat::Rgb565 => RGB565,
+        GpuFormat::Rgba5551 => RGBA5551,
+        GpuFormat::R8 => R8,
+        GpuFormat::Etc1 => ETC1,
+        GpuFormat::Pvrtc4Bpp => PVRTC_4BPP_RGBA,
+    }
+}
+
+fn from_pixel_format(pixel_format: u64) -> Result<GpuFormat> {
+    Ok(match pixel_format {
+        RGBA8888 => GpuFormat::Rgba8,
+        BGRA8888 => GpuFormat::Bgra8,
+        RGBA4444 => GpuFormat::Rgba4444,
+        RGB565 => GpuFormat::Rgb565,
+        RGBA5551 => GpuFormat::Rgba5551,
+        R8 => GpuFormat::R8,
+        ETC1 => GpuFormat::Etc1,
+        PVRTC_4BPP_RGBA => GpuFormat::Pvrtc4Bpp,
+        n => {
+            return Err(RsbError::DeserializationError(format!(
+                "Unsupported PVR pixel format: 0x{:016X}",
+                n
+            )));
+        }
+    })
+}
+
+pub fn write(texture: &ContainerTexture) -> Result<Vec<u8>> {
+    let channel_type = match texture.format {
+        GpuFormat::Rgba4444 | GpuFormat::Rgb565 | GpuFormat::Rgba5551 => {
+            CHANNEL_TYPE_UNSIGNED_SHORT_NORM
+        }
+        _ => CHANNEL_TYPE_UNSIGNED_BYTE_NORM,
+    };
+
+    let mut out = Vec::new();
+    out.write_u32::<LE>(VERSION)?;
+    out.write_u32::<LE>(0)?; // flags
+    out.write_u64::<LE>(pixel_format(texture.format))?;
+    out.write_u32::<LE>(COLOUR_SPACE_LINEAR)?;
+    out.write_u32::<LE>(channel_type)?;
+    out.write_u32::<LE>(texture.height)?;
+    out.write_u32::<LE>(texture.width)?;
+    out.write_u32::<LE>(1)?; // depth
+    out.write_u32::<LE>(1)?; // surfaces
+    out.write_u32::<LE>(1)?; // faces
+    out.write_u32::<LE>(texture.levels.len() as u32)?;
+    out.write_u32::<LE>(0)?; // metadata size
+
+    for level in &texture.levels {
+        out.extend_from_slice(level);
+    }
+    Ok(out)
+}
+
+pub fn read(data: &[u8]) -> Result<ContainerTexture> {
+    let mut cursor = Cursor::new(data);
+    let version = cursor.read_u32::<LE>()?;
+    if version != VERSION {
+        return Err(RsbError::InvalidMagic(
+            format!("{:08X}", VERSION),
+            format!("{:08X}", version),
+        ));
+    }
+
+    let _flags = cursor.read_u32::<LE>()?;
+    let format = from_pixel_format(cursor.read_u64::<LE>()?)?;
+    let _colour_space = cursor.read_u32::<LE>()?;
+    let _channel_type = cursor.read_u32::<LE>()?;
+    let height = cursor.read_u32::<LE>()?.max(1);
+    let width = cursor.read_u32::<LE>()?;
+    let depth = cursor.read_u32::<LE>()?;
+    let surfaces = cursor.read_u32::<LE>()?;
+    let faces = cursor.read_u32::<LE>()?;
+    let level_count = cursor.read_u32::<LE>()?.max(1);
+    let metadata_size = cursor.read_u32::<LE>()?;
+    if depth > 1 || surfaces > 1 || faces > 1 {
+        return Err(RsbError::DeserializationError(
+            "Only single 2D PVR textures are supported".into(),
+        ));
+    }
+
+    super::check_level_count("PVR", width, height, level_count)?;
+
+    let mut offset = (HEADER_SIZE + metadata_size as u64) as usize;
+    let mut levels = Vec::with_capacity(level_count as usize);
+    for level in 0..level_count {
+        let (w, h) = mip_dimensions(width, height, level);
+        let size = format.level_size(w, h);
+        let level_data = data.get(offset..offset + size).ok_or_else(|| {
+            RsbError::DeserializationError("PVR level extends past end of file".into())
+        })?;
+        levels.push(level_data.to_vec());
+        offset += size;
+    }
+
+    Ok(ContainerTexture {
+        format,
+        width,
+        height,
+        levels,
+    })
+}
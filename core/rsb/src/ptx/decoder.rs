@@ -1,12 +1,105 @@
 use crate::error::{Result, RsbError};
-use crate::ptx::codec::etc1::{decode_etc1, decode_etc1_a8, decode_palette_alpha};
-use crate::ptx::codec::pvrtc::{decode_pvrtc_4bpp, decode_pvrtc_4bpp_a8};
-use crate::ptx::types::PtxFormat;
-use image::{DynamicImage, ImageBuffer, Rgba};
+use crate::ptx::mipmap::{full_mip_count, infer_mip_count, mip_chain_sizes, mip_dimensions};
+use crate::ptx::registry::{PtxPlatform, PtxRegistry};
+use image::DynamicImage;
 
 pub struct PtxDecoder;
 
 impl PtxDecoder {
+    /// Byte size of each level stored in `data_len` bytes of PTX data, base level first.
+    ///
+    /// With `mip_count` unset the chain length is inferred from the data size; data
+    /// that doesn't match any chain exactly is treated as a single surface.
+    pub fn mip_level_sizes(
+        data_len: usize,
+        width: u32,
+        height: u32,
+        format_code: i32,
+        alpha_format: Option<i32>,
+        is_powervr: bool,
+        mip_count: Option<u32>,
+    ) -> Result<Vec<usize>> {
+        if let Some(n) = mip_count
+            && n > full_mip_count(width, height)
+        {
+            return Err(RsbError::DeserializationError(format!(
+                "{} mip levels requested but a {}x{} texture has at most {}",
+                n,
+                width,
+                height,
+                full_mip_count(width, height)
+            )));
+        }
+
+        // Ambiguous ids are resolved per level by `decode`, so try each reading for the
+        // sizes, largest layout first as `PtxRegistry::decoder_for` does
+        let mut codecs =
+            PtxRegistry::global().codecs_for(format_code, PtxPlatform::from_powervr(is_powervr));
+        codecs.sort_by_key(|c| std::cmp::Reverse(c.data_size(width, height)));
+
+        let chain = codecs.iter().find_map(|codec| {
+            let count = match mip_count {
+                Some(n) => n,
+                None => infer_mip_count(codec.as_ref(), alpha_format, width, height, data_len)?,
+            };
+            let sizes = mip_chain_sizes(codec.as_ref(), alpha_format, width, height, count);
+            (sizes.iter().sum::<usize>() <= data_len).then_some(sizes)
+        });
+
+        match chain {
+            Some(sizes) if sizes.len() > 1 => Ok(sizes),
+            _ if mip_count.is_some_and(|n| n > 1) => Err(RsbError::DeserializationError(format!(
+                "Insufficient data for {} mip levels of format {}: got {}",
+                mip_count.unwrap_or(1),
+                format_code,
+                data_len
+            ))),
+            _ => Ok(vec![data_len]),
+        }
+    }
+
+    /// Decode a PTX that may hold a mip chain, returning every level (base level first).
+    ///
+    /// See `mip_level_sizes` for how the chain is split.
+    #[allow(clippy::too_many_arguments)]
+    pub fn decode_mips(
+        data: &[u8],
+        width: u32,
+        height: u32,
+        format_code: i32,
+        alpha_size: Option<i32>,
+        alpha_format: Option<i32>,
+        is_powervr: bool,
+        mip_count: Option<u32>,
+    ) -> Result<Vec<DynamicImage>> {
+        let sizes = Self::mip_level_sizes(
+            data.len(),
+            width,
+            height,
+            format_code,
+            alpha_format,
+            is_powervr,
+            mip_count,
+        )?;
+
+        let mut levels = Vec::with_capacity(sizes.len());
+        let mut offset = 0;
+        for (level, size) in sizes.into_iter().enumerate() {
+            let (w, h) = mip_dimensions(width, height, level as u32);
+            levels.push(Self::decode(
+                &data[offset..offset + size],
+                w,
+                h,
+                format_code,
+                alpha_size,
+                alpha_format,
+                is_powervr,
+            )?);
+            offset += size;
+        }
+        Ok(levels)
+    }
+
     pub fn decode(
         data: &[u8],
         width: u32,
@@ -16,294 +109,14 @@ impl PtxDecoder {
         _alpha_format: Option<i32>,
         is_powervr: bool,
     ) -> Result<DynamicImage> {
-        let mut format = PtxFormat::from(format_code);
-        let num_pixels = (width * height) as usize;
-
-        // Resolve ambiguous formats based on data size
-        if format_code == 30 {
-            // ID 30: PVRTC 4bpp (Opaque) OR ETC1 Palette
-            let expected_msg_size = (width * height) as usize / 2;
-            if data.len() > expected_msg_size + 16 {
-                // significantly larger
-                format = PtxFormat::Etc1Palette;
-            }
-        } else if format_code == 147 {
-            // ID 147: ETC1 (Opaque) OR ETC1 A8
-            let expected_opaque_size = (width * height) as usize / 2;
-            // Check for ETC1 + Uncompressed Alpha (1 byte/pixel)
-            if data.len() >= expected_opaque_size + (width * height) as usize {
-                format = PtxFormat::Etc1A8;
-            } else if data.len() >= expected_opaque_size * 2 {
-                // Check for ETC1 + Compressed Alpha (ETC1 block)
-                // Use Etc1A8 internally but handle splitting in decoder
-                format = PtxFormat::Etc1A8;
-            }
-        }
-
-        match format {
-            PtxFormat::Rgba8888 => {
-                if data.len() < num_pixels * 4 {
-                    return Err(RsbError::DeserializationError(format!(
-                        "Insufficient data for Rgba8888: expected {}, got {}",
-                        num_pixels * 4,
-                        data.len()
-                    )));
-                }
-
-                let mut img_buf = ImageBuffer::new(width, height);
-                for (i, pixel) in img_buf.pixels_mut().enumerate() {
-                    let offset = i * 4;
-                    let r = data[offset];
-                    let g = data[offset + 1];
-                    let b = data[offset + 2];
-                    let a = data[offset + 3];
-
-                    if is_powervr {
-                        // BGRA
-                        *pixel = Rgba([b, g, r, a]);
-                    } else {
-                        *pixel = Rgba([r, g, b, a]);
-                    }
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Rgba4444 => {
-                if data.len() < num_pixels * 2 {
-                    return Err(RsbError::DeserializationError(format!(
-                        "Insufficient data for Rgba4444: expected {}, got {}",
-                        num_pixels * 2,
-                        data.len()
-                    )));
-                }
-                let mut img_buf = ImageBuffer::new(width, height);
-                for (i, pixel) in img_buf.pixels_mut().enumerate() {
-                    let offset = i * 2;
-                    let val = u16::from_le_bytes([data[offset], data[offset + 1]]);
-                    // Sen: (val & 0xF000) >> 12 -> R
-                    let r = ((val & 0xF000) >> 12) as u8;
-                    let g = ((val & 0x0F00) >> 8) as u8;
-                    let b = ((val & 0x00F0) >> 4) as u8;
-                    let a = (val & 0x000F) as u8;
-
-                    let r8 = (r << 4) | r;
-                    let g8 = (g << 4) | g;
-                    let b8 = (b << 4) | b;
-                    let a8 = (a << 4) | a;
-
-                    *pixel = Rgba([r8, g8, b8, a8]);
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Rgb565 => {
-                if data.len() < num_pixels * 2 {
-                    return Err(RsbError::DeserializationError(format!(
-                        "Insufficient data for Rgb565: expected {}, got {}",
-                        num_pixels * 2,
-                        data.len()
-                    )));
-                }
-                let mut img_buf = ImageBuffer::new(width, height);
-                for (i, pixel) in img_buf.pixels_mut().enumerate() {
-                    let offset = i * 2;
-                    let val = u16::from_le_bytes([data[offset], data[offset + 1]]);
-                    let r = ((val & 0xF800) >> 11) as u8;
-                    let g = ((val & 0x07E0) >> 5) as u8;
-                    let b = (val & 0x001F) as u8;
-
-                    let r8 = (r << 3) | (r >> 2);
-                    let g8 = (g << 2) | (g >> 4);
-                    let b8 = (b << 3) | (b >> 2);
-
-                    *pixel = Rgba([r8, g8, b8, 255]);
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Rgba5551 => {
-                if data.len() < num_pixels * 2 {
-                    return Err(RsbError::DeserializationError(format!(
-                        "Insufficient data for Rgba5551: expected {}, got {}",
-                        num_pixels * 2,
-                        data.len()
-                    )));
-                }
-                let mut img_buf = ImageBuffer::new(width, height);
-                for (i, pixel) in img_buf.pixels_mut().enumerate() {
-                    let offset = i * 2;
-                    let val = u16::from_le_bytes([data[offset], data[offset + 1]]);
-                    let r = ((val & 0xF800) >> 11) as u8;
-                    let g = ((val & 0x07C0) >> 6) as u8;
-                    let b = ((val & 0x003E) >> 1) as u8;
-                    let a = (val & 0x0001) as u8;
-
-                    let r8 = (r << 3) | (r >> 2);
-                    let g8 = (g << 3) | (g >> 2);
-                    let b8 = (b << 3) | (b >> 2);
-                    let a8 = if a == 1 { 255 } else { 0 };
-
-                    *pixel = Rgba([r8, g8, b8, a8]);
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Rgba4444Block => {
-                let block_w = 32;
-                let block_h = 32;
-
-                let mut img_buf = ImageBuffer::new(width, height);
-                let blocks_x = width.div_ceil(block_w);
-                let blocks_y = height.div_ceil(block_h);
-
-                let mut offset = 0;
-
-                for by in 0..blocks_y {
-                    for bx in 0..blocks_x {
-                        for y_local in 0..block_h {
-                            for x_local in 0..block_w {
-                                let x = bx * block_w + x_local;
-                                let y = by * block_h + y_local;
-
-                                if x < width && y < height
-                                    && offset + 2 <= data.len() {
-                                        let val =
-                                            u16::from_le_bytes([data[offset], data[offset + 1]]);
-                                        let r = ((val & 0xF000) >> 12) as u8;
-                                        let g = ((val & 0x0F00) >> 8) as u8;
-                                        let b = ((val & 0x00F0) >> 4) as u8;
-                                        let a = (val & 0x000F) as u8;
-
-                                        let r8 = (r << 4) | r;
-                                        let g8 = (g << 4) | g;
-                                        let b8 = (b << 4) | b;
-                                        let a8 = (a << 4) | a;
-
-                                        img_buf.put_pixel(x, y, Rgba([r8, g8, b8, a8]));
-                                    }
-                                offset += 2;
-                            }
-                        }
-                    }
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Rgb565Block => {
-                let block_w = 32;
-                let block_h = 32;
-
-                let mut img_buf = ImageBuffer::new(width, height);
-                let blocks_x = width.div_ceil(block_w);
-                let blocks_y = height.div_ceil(block_h);
-
-                let mut offset = 0;
-
-                for by in 0..blocks_y {
-                    for bx in 0..blocks_x {
-                        for y_local in 0..block_h {
-                            for x_local in 0..block_w {
-                                let x = bx * block_w + x_local;
-                                let y = by * block_h + y_local;
-
-                                if x < width && y < height
-                                    && offset + 2 <= data.len() {
-                                        let val =
-                                            u16::from_le_bytes([data[offset], data[offset + 1]]);
-                                        let r = ((val & 0xF800) >> 11) as u8;
-                                        let g = ((val & 0x07E0) >> 5) as u8;
-                                        let b = (val & 0x001F) as u8;
-
-                                        let r8 = (r << 3) | (r >> 2);
-                                        let g8 = (g << 2) | (g >> 4);
-                                        let b8 = (b << 3) | (b >> 2);
-
-                                        img_buf.put_pixel(x, y, Rgba([r8, g8, b8, 255]));
-                                    }
-                                offset += 2;
-                            }
-                        }
-                    }
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Rgba5551Block => {
-                let block_w = 32;
-                let block_h = 32;
-
-                let mut img_buf = ImageBuffer::new(width, height);
-                let blocks_x = width.div_ceil(block_w);
-                let blocks_y = height.div_ceil(block_h);
-
-                let mut offset = 0;
-
-                for by in 0..blocks_y {
-                    for bx in 0..blocks_x {
-                        for y_local in 0..block_h {
-                            for x_local in 0..block_w {
-                                let x = bx * block_w + x_local;
-                                let y = by * block_h + y_local;
-
-                                if x < width && y < height
-                                    && offset + 2 <= data.len() {
-                                        let val =
-                                            u16::from_le_bytes([data[offset], data[offset + 1]]);
-                                        let r = ((val & 0xF800) >> 11) as u8;
-                                        let g = ((val & 0x07C0) >> 6) as u8;
-                                        let b = ((val & 0x003E) >> 1) as u8;
-                                        let a = (val & 0x0001) as u8;
-
-                                        let r8 = (r << 3) | (r >> 2);
-                                        let g8 = (g << 3) | (g >> 2);
-                                        let b8 = (b << 3) | (b >> 2);
-                                        let a8 = if a == 1 { 255 } else { 0 };
-
-                                        img_buf.put_pixel(x, y, Rgba([r8, g8, b8, a8]));
-                                    }
-                                offset += 2;
-                            }
-                        }
-                    }
-                }
-                Ok(DynamicImage::ImageRgba8(img_buf))
-            }
-            PtxFormat::Pvrtc4BppRgba => decode_pvrtc_4bpp(data, width, height),
-            PtxFormat::Etc1 => decode_etc1(data, width, height),
-            PtxFormat::Pvrtc4BppRgbaA8 => {
-                let alpha_size = (width * height) as usize;
-                if data.len() < alpha_size {
-                    return Err(RsbError::DeserializationError(
-                        "Data too small for PVRTC+A8".into(),
-                    ));
-                }
-                let offset = data.len().saturating_sub(alpha_size);
-                let pvrtc_data = &data[..offset];
-                let alpha_data = &data[offset..];
-
-                decode_pvrtc_4bpp_a8(pvrtc_data, alpha_data, width, height)
-            }
-            PtxFormat::Etc1A8 => {
-                let opaque_size = (width * height) as usize / 2;
-                // Check for Uncompressed Alpha (3x size) first
-                if data.len() >= opaque_size * 3 {
-                    // Uncompressed
-                    let offset = data.len().saturating_sub((width * height) as usize);
-                    let etc1_data = &data[..offset];
-                    let alpha_data = &data[offset..];
-                    decode_etc1_a8(etc1_data, alpha_data, width, height, false)
-                } else if data.len() >= opaque_size * 2 {
-                    // Likely dual-ETC1 (RGB + Alpha encoded as ETC1)
-                    let midpoint = data.len() / 2;
-                    let color_data = &data[..midpoint];
-                    let alpha_data = &data[midpoint..];
-                    decode_etc1_a8(color_data, alpha_data, width, height, true)
-                } else {
-                    let offset = data.len().saturating_sub((width * height) as usize);
-                    let etc1_data = &data[..offset];
-                    let alpha_data = &data[offset..];
-                    decode_etc1_a8(etc1_data, alpha_data, width, height, false)
-                }
-            }
-            PtxFormat::Etc1Palette => decode_palette_alpha(data, width, height),
-            PtxFormat::Unknown(n) => Err(RsbError::DeserializationError(format!(
-                "Unknown PTX format: {}",
-                n
-            ))),
-        }
+        // Ambiguous ids (30, 147) are resolved by the registry based on data size
+        let codec = PtxRegistry::global().decoder_for(
+            format_code,
+            PtxPlatform::from_powervr(is_powervr),
+            data.len(),
+            width,
+            height,
+        )?;
+        codec.decode(data, width, height)
     }
 }
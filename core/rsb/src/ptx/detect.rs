@@ -0,0 +1,324 @@
+use crate::error::Result;
+use crate::ptx::codec::PtxCodec;
+use crate::ptx::mipmap::level_data_size;
+use crate::ptx::registry::{PtxPlatform, PtxRegistry};
+use crate::ptx::types::PtxFormat;
+use crate::schema::types::RsbManifest;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Smallest and largest power-of-two edge considered when guessing from data length.
+const MIN_POT_EDGE: u32 = 4;
+const MAX_POT_EDGE: u32 = 8192;
+
+/// Score given to metadata read from `rsb_manifest.json`; always outranks a size guess.
+const MANIFEST_SCORE: i32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectSource {
+    /// Values taken from a neighbouring `rsb_manifest.json`.
+    Manifest,
+    /// Values inferred from the byte length of the PTX data.
+    DataLength,
+}
+
+/// A possible interpretation of a loose PTX file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PtxCandidate {
+    pub format: PtxFormat,
+    /// Format id as stored in the RSB, to be passed to `PtxDecoder::decode`.
+    pub format_code: i32,
+    pub width: u32,
+    pub height: u32,
+    pub alpha_size: Option<i32>,
+    pub alpha_format: Option<i32>,
+    /// Higher is more likely.
+    pub score: i32,
+    pub source: DetectSource,
+}
+
+/// Optional constraints narrowing down the guesses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DetectHint {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format_code: Option<i32>,
+}
+
+/// A byte layout a PTX payload may have, with a prior reflecting how common it is.
+struct Layout {
+    codec: Arc<dyn PtxCodec>,
+    format_code: i32,
+    alpha_format: Option<i32>,
+    prior: i32,
+    /// Alignment both edges must honour when they are not powers of two.
+    align: u32,
+}
+
+impl Layout {
+    fn size(&self, width: u32, height: u32) -> usize {
+        level_data_size(self.codec.as_ref(), self.alpha_format, width, height)
+    }
+}
+
+/// How common each format is in shipped RSBs; formats registered downstream rank last.
+fn prior(format: PtxFormat, alpha_format: Option<i32>) -> i32 {
+    match format {
+        PtxFormat::Etc1A8 if alpha_format == Some(100) => 20,
+        PtxFormat::Rgba8888 | PtxFormat::Etc1A8 => 30,
+        PtxFormat::Etc1 | PtxFormat::Pvrtc4BppRgba => 25,
+        PtxFormat::Rgba4444 | PtxFormat::Etc1Palette => 20,
+        PtxFormat::Rgba4444Block | PtxFormat::Pvrtc4BppRgbaA8 => 15,
+        PtxFormat::Rgb565 | PtxFormat::Rgba5551 => 10,
+        PtxFormat::Rgb565Block | PtxFormat::Rgba5551Block => 8,
+        PtxFormat::Unknown(_) => 5,
+    }
+}
+
+/// Every layout in the global registry, including the compressed-alpha variant of
+/// ETC1A8 that shares its codec.
+fn layouts() -> Vec<Layout> {
+    let registry = PtxRegistry::global();
+    let mut layouts: Vec<Layout> = Vec::new();
+
+    for entry in registry.entries() {
+        let format = entry.codec.format();
+        let duplicate = layouts
+            .iter()
+            .any(|l| l.format_code == entry.format_code && l.codec.name() == entry.codec.name());
+        // Both platforms register the same layouts; BGRA has the size of RGBA
+        if duplicate || (format == PtxFormat::Rgba8888 && entry.platform == PtxPlatform::Ios) {
+            continue;
+        }
+
+        // PVRTC is only defined on power-of-two textures
+        let align = match format {
+            PtxFormat::Pvrtc4BppRgba | PtxFormat::Pvrtc4BppRgbaA8 => 0,
+            _ => entry.codec.block_dims().0.max(1),
+        };
+        let mut alpha_formats = vec![None];
+        if format == PtxFormat::Etc1A8 {
+            alpha_formats.push(Some(100));
+        }
+        for alpha_format in alpha_formats {
+            layouts.push(Layout {
+                codec: entry.codec.clone(),
+                format_code: entry.format_code,
+                alpha_format,
+                prior: prior(format, alpha_format),
+                align,
+            });
+        }
+    }
+
+    layouts
+}
+
+pub struct PtxDetector;
+
+impl PtxDetector {
+    /// Rank every format/dimension combination whose expected size equals `data_len`.
+    ///
+    /// Without a width or height hint only power-of-two edges are tried; with one
+    /// edge known the other is solved for and only has to respect block alignment.
+    pub fn detect_from_len(data_len: usize, hint: DetectHint) -> Vec<PtxCandidate> {
+        let mut candidates = Vec::new();
+
+        for layout in &layouts() {
+            if hint
+                .format_code
+                .is_some_and(|code| code != layout.format_code)
+            {
+                continue;
+            }
+
+            for (w, h) in Self::dimension_pairs(layout, data_len, hint) {
+                if layout.size(w, h) != data_len {
+                    continue;
+                }
+
+                // Prefer square, then mildly elongated textures
+                let aspect = (w.ilog2() as i32 - h.ilog2() as i32).abs();
+                let mut score = layout.prior - aspect * 10;
+                if !w.is_power_of_two() || !h.is_power_of_two() {
+                    score -= 15;
+                }
+
+                candidates.push(PtxCandidate {
+                    format: layout.codec.format(),
+                    format_code: layout.format_code,
+                    width: w,
+                    height: h,
+                    alpha_size: None,
+                    alpha_format: layout.alpha_format,
+                    score,
+                    source: DetectSource::DataLength,
+                });
+            }
+        }
+
+        Self::rank(&mut candidates);
+        candidates
+    }
+
+    /// Look up a PTX in an `rsb_manifest.json`.
+    ///
+    /// `relative_path` is the path of the PTX relative to the manifest directory,
+    /// i.e. `<packet>/<resource path>` as laid out by `rsb unpack`.
+    pub fn detect_from_manifest(
+        manifest: &RsbManifest,
+        relative_path: &str,
+    ) -> Option<PtxCandidate> {
+        let wanted = relative_path.replace('\\', "/");
+
+        for group in &manifest.group {
+            for sub in &group.subgroup {
+                for res in &sub.packet_info.res {
+                    let path = format!("{}/{}", sub.name_packet, res.path.replace('\\', "/"));
+                    if !path.eq_ignore_ascii_case(&wanted) {
+                        continue;
+                    }
+                    let Some(ptx) = &res.ptx_info else {
+                        continue;
+                    };
+
+                    // Same precedence as `rsb unpack`: packet dimensions over the canvas size
+                    let (width, height) = match res
+                        .part1_info
+                        .as_ref()
+                        .filter(|p| p.width > 0 && p.height > 0)
+                    {
+                        Some(p1) => (p1.width, p1.height),
+                        None => (ptx.width as u32, ptx.height as u32),
+                    };
+
+                    return Some(PtxCandidate {
+                        format: PtxFormat::from(ptx.format),
+                        format_code: ptx.format,
+                        width,
+                        height,
+                        alpha_size: ptx.alpha_size,
+                        alpha_format: ptx.alpha_format,
+                        score: MANIFEST_SCORE,
+                        source: DetectSource::Manifest,
+                    });
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Walk up from `ptx_path` looking for an `rsb_manifest.json` that describes it.
+    /// Manifests that can't be read or parsed are skipped; the second value holds a
+    /// warning for each of them.
+    pub fn locate_in_manifest(ptx_path: &Path) -> Result<(Option<PtxCandidate>, Vec<String>)> {
+        let ptx_path = fs::canonicalize(ptx_path)?;
+        let mut warnings = Vec::new();
+
+        for dir in ptx_path.ancestors().skip(1) {
+            let manifest_path = dir.join("rsb_manifest.json");
+            if !manifest_path.is_file() {
+                continue;
+            }
+
+            // A broken manifest shouldn't stop detection; the size guess still applies
+            let manifest = match fs::read_to_string(&manifest_path)
+                .map_err(|e| e.to_string())
+                .and_then(|text| {
+                    serde_json::from_str::<RsbManifest>(&text).map_err(|e| e.to_string())
+                }) {
+                Ok(manifest) => manifest,
+                Err(e) => {
+                    warnings.push(format!(
+                        "ignoring unreadable {}: {}",
+                        manifest_path.display(),
+                        e
+                    ));
+                    continue;
+                }
+            };
+            let Ok(relative) = ptx_path.strip_prefix(dir) else {
+                continue;
+            };
+            let relative = relative.to_string_lossy();
+            if let Some(candidate) = Self::detect_from_manifest(&manifest, &relative) {
+                return Ok((Some(candidate), warnings));
+            }
+        }
+
+        Ok((None, warnings))
+    }
+
+    /// Combine manifest metadata (if any) with data-length guesses, best first,
+    /// along with the warnings of `locate_in_manifest`.
+    pub fn detect(
+        data: &[u8],
+        path: Option<&Path>,
+        hint: DetectHint,
+    ) -> Result<(Vec<PtxCandidate>, Vec<String>)> {
+        let mut candidates = Vec::new();
+        let mut warnings = Vec::new();
+
+        if let Some(path) = path {
+            let (found, skipped) = Self::locate_in_manifest(path)?;
+            candidates.extend(found);
+            warnings = skipped;
+        }
+        candidates.extend(Self::detect_from_len(data.len(), hint));
+
+        Self::rank(&mut candidates);
+        Ok((candidates, warnings))
+    }
+
+    fn rank(candidates: &mut [PtxCandidate]) {
+        candidates.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(b.width.cmp(&a.width))
+                .then(b.height.cmp(&a.height))
+        });
+    }
+
+    fn dimension_pairs(layout: &Layout, data_len: usize, hint: DetectHint) -> Vec<(u32, u32)> {
+        match (
+            hint.width.filter(|&w| w > 0),
+            hint.height.filter(|&h| h > 0),
+        ) {
+            (Some(w), Some(h)) => vec![(w, h)],
+            (Some(w), None) => Self::solve_edge(layout, data_len)
+                .into_iter()
+                .map(|h| (w, h))
+                .collect(),
+            (None, Some(h)) => Self::solve_edge(layout, data_len)
+                .into_iter()
+                .map(|w| (w, h))
+                .collect(),
+            (None, None) => {
+                let pot = Self::pot_edges();
+                pot.iter()
+                    .flat_map(|&w| pot.iter().map(move |&h| (w, h)))
+                    .collect()
+            }
+        }
+    }
+
+    /// Edge lengths worth trying for the unknown dimension.
+    fn solve_edge(layout: &Layout, data_len: usize) -> Vec<u32> {
+        // PVRTC is only defined on power-of-two textures
+        if layout.align == 0 {
+            return Self::pot_edges();
+        }
+        let max = (data_len as u32).min(MAX_POT_EDGE * 2);
+        (layout.align..=max)
+            .step_by(layout.align as usize)
+            .collect()
+    }
+
+    fn pot_edges() -> Vec<u32> {
+        (MIN_POT_EDGE.ilog2()..=MAX_POT_EDGE.ilog2())
+            .map(|e| 1 << e)
+            .collect()
+    }
+}
@@ -0,0 +1,115 @@
+use image::RgbaImage;
+
+/// How 8-bit channels are reduced to the bit depth of a 16-bit PTX format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DitherMode {
+    /// Drop the low bits (the historical behaviour).
+    #[default]
+    None,
+    /// Error diffusion, best for gradients.
+    FloydSteinberg,
+    /// 4x4 Bayer matrix, stable across frames and tiles.
+    Ordered,
+}
+
+/// Options for `PtxEncoder::encode_with_options`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PtxEncodeOptions {
+    pub dither: DitherMode,
+    /// Multiply RGB by alpha before encoding.
+    pub premultiply_alpha: bool,
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// Multiply colour channels by alpha in place.
+pub fn premultiply_alpha(image: &mut RgbaImage) {
+    for p in image.pixels_mut() {
+        let a = p[3] as u32;
+        for c in 0..3 {
+            p[c] = ((p[c] as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// Reduce every pixel to `bits` per channel (RGBA order), returning the levels in raster order.
+///
+/// A channel with 0 bits always yields level 0. Only the colour channels are dithered;
+/// alpha is always truncated, so a 1-bit alpha stays a clean threshold at 128 instead of
+/// stippling semi-transparent edges.
+pub fn quantize(image: &RgbaImage, bits: [u8; 4], mode: DitherMode) -> Vec<[u8; 4]> {
+    let (width, height) = image.dimensions();
+    let mut levels = vec![[0u8; 4]; (width * height) as usize];
+
+    let truncated = if mode == DitherMode::None { 0..4 } else { 3..4 };
+    for (level, p) in levels.iter_mut().zip(image.pixels()) {
+        for c in truncated.clone() {
+            if bits[c] > 0 {
+                level[c] = p[c] >> (8 - bits[c]);
+            }
+        }
+    }
+
+    match mode {
+        DitherMode::None => {}
+        DitherMode::Ordered => {
+            for (i, (level, p)) in levels.iter_mut().zip(image.pixels()).enumerate() {
+                let x = i as u32 % width;
+                let y = i as u32 / width;
+                let threshold = (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0;
+                for c in 0..3 {
+                    if bits[c] > 0 {
+                        let max = ((1u32 << bits[c]) - 1) as f32;
+                        let scaled = p[c] as f32 * max / 255.0;
+                        level[c] = (scaled + threshold - 0.5).round().clamp(0.0, max) as u8;
+                    }
+                }
+            }
+        }
+        DitherMode::FloydSteinberg => {
+            let mut work: Vec<[f32; 3]> = image
+                .pixels()
+                .map(|p| [p[0] as f32, p[1] as f32, p[2] as f32])
+                .collect();
+            let w = width as usize;
+            let h = height as usize;
+
+            for y in 0..h {
+                for x in 0..w {
+                    let i = y * w + x;
+                    for c in 0..3 {
+                        if bits[c] == 0 {
+                            continue;
+                        }
+                        let max = (1u32 << bits[c]) - 1;
+                        let value = work[i][c].clamp(0.0, 255.0);
+                        let q = (value * max as f32 / 255.0).round() as u32;
+                        levels[i][c] = q as u8;
+
+                        let error = value - expand(q, bits[c]) as f32;
+                        if x + 1 < w {
+                            work[i + 1][c] += error * 7.0 / 16.0;
+                        }
+                        if y + 1 < h {
+                            if x > 0 {
+                                work[i + w - 1][c] += error * 3.0 / 16.0;
+                            }
+                            work[i + w][c] += error * 5.0 / 16.0;
+                            if x + 1 < w {
+                                work[i + w + 1][c] += error / 16.0;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    levels
+}
+
+/// The 8-bit value a decoder reconstructs from a `bits`-wide level.
+fn expand(level: u32, bits: u8) -> u32 {
+    let max = (1u32 << bits) - 1;
+    (level * 255 + max / 2) / max
+}
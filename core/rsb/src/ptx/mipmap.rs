@@ -0,0 +1,143 @@
+use crate::ptx::codec::PtxCodec;
+use crate::ptx::types::PtxFormat;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+
+/// Downsampling filter used to build mip levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MipFilter {
+    /// 2x2 average of the previous level.
+    #[default]
+    Box,
+    /// Lanczos3 resample of the base level.
+    Lanczos3,
+}
+
+/// Number of levels in a full chain down to 1x1.
+pub fn full_mip_count(width: u32, height: u32) -> u32 {
+    width.max(height).max(1).ilog2() + 1
+}
+
+/// Dimensions of mip `level`, never smaller than 1x1.
+pub fn mip_dimensions(width: u32, height: u32, level: u32) -> (u32, u32) {
+    let shrink = |edge: u32| edge.checked_shr(level).unwrap_or(0).max(1);
+    (shrink(width), shrink(height))
+}
+
+/// Byte size of one surface in the layout `codec` produces.
+///
+/// `alpha_format == Some(100)` selects the ETC1-compressed alpha variant of format 147.
+pub fn level_data_size(
+    codec: &dyn PtxCodec,
+    alpha_format: Option<i32>,
+    width: u32,
+    height: u32,
+) -> usize {
+    if codec.format() == PtxFormat::Etc1A8 && alpha_format == Some(100) {
+        return width.div_ceil(4) as usize * height.div_ceil(4) as usize * 8 * 2;
+    }
+    codec.data_size(width, height)
+}
+
+/// Row pitch and alpha plane size an RSB records for a base level of `level_len` bytes:
+/// the bytes of one pixel row of the colour surface, and the bytes that follow that
+/// surface (the A8 plane, the compressed alpha or the palette and its indices).
+pub fn surface_layout(format: PtxFormat, width: u32, height: u32, level_len: usize) -> (i32, i32) {
+    let etc1 = width.div_ceil(4) as usize * height.div_ceil(4) as usize * 8;
+    let pvrtc = width.max(8) as usize * height.max(8) as usize / 2;
+    let (pitch, colour) = match format {
+        PtxFormat::Etc1 | PtxFormat::Etc1A8 | PtxFormat::Etc1Palette => {
+            (width.div_ceil(4) as usize * 2, etc1)
+        }
+        PtxFormat::Pvrtc4BppRgba | PtxFormat::Pvrtc4BppRgbaA8 => (width.max(8) as usize / 2, pvrtc),
+        PtxFormat::Rgba8888 => (width as usize * 4, level_len),
+        PtxFormat::Rgba4444 | PtxFormat::Rgb565 | PtxFormat::Rgba5551 => {
+            (width as usize * 2, level_len)
+        }
+        // 32x32 tiles
+        PtxFormat::Rgba4444Block | PtxFormat::Rgb565Block | PtxFormat::Rgba5551Block => {
+            (width.next_multiple_of(32) as usize * 2, level_len)
+        }
+        PtxFormat::Unknown(_) => (level_len / height.max(1) as usize, level_len),
+    };
+    (pitch as i32, level_len.saturating_sub(colour) as i32)
+}
+
+/// Byte size of every level in a chain of `count` levels.
+pub fn mip_chain_sizes(
+    codec: &dyn PtxCodec,
+    alpha_format: Option<i32>,
+    width: u32,
+    height: u32,
+    count: u32,
+) -> Vec<usize> {
+    (0..count)
+        .map(|level| {
+            let (w, h) = mip_dimensions(width, height, level);
+            level_data_size(codec, alpha_format, w, h)
+        })
+        .collect()
+}
+
+/// Find the chain length whose total size is exactly `data_len`.
+pub fn infer_mip_count(
+    codec: &dyn PtxCodec,
+    alpha_format: Option<i32>,
+    width: u32,
+    height: u32,
+    data_len: usize,
+) -> Option<u32> {
+    let mut total = 0;
+    for level in 0..full_mip_count(width, height) {
+        let (w, h) = mip_dimensions(width, height, level);
+        total += level_data_size(codec, alpha_format, w, h);
+        if total == data_len {
+            return Some(level + 1);
+        }
+        if total > data_len {
+            break;
+        }
+    }
+    None
+}
+
+/// Build `count` levels from `image`; level 0 is the image itself.
+pub fn generate_mips(image: &DynamicImage, count: u32, filter: MipFilter) -> Vec<DynamicImage> {
+    let base = image.to_rgba8();
+    let (width, height) = base.dimensions();
+    let mut levels = vec![DynamicImage::ImageRgba8(base.clone())];
+    let mut previous = base;
+
+    for level in 1..count.max(1) {
+        let (w, h) = mip_dimensions(width, height, level);
+        let next = match filter {
+            MipFilter::Box => box_downsample(&previous, w, h),
+            MipFilter::Lanczos3 => {
+                imageops::resize(levels[0].as_rgba8().unwrap(), w, h, FilterType::Lanczos3)
+            }
+        };
+        levels.push(DynamicImage::ImageRgba8(next.clone()));
+        previous = next;
+    }
+
+    levels
+}
+
+/// Average each 2x2 footprint of `src` (clamped at the edges) into a `w` x `h` image.
+fn box_downsample(src: &RgbaImage, w: u32, h: u32) -> RgbaImage {
+    let (sw, sh) = src.dimensions();
+    RgbaImage::from_fn(w, h, |x, y| {
+        let mut sum = [0u32; 4];
+        let xs = [(x * 2).min(sw - 1), (x * 2 + 1).min(sw - 1)];
+        let ys = [(y * 2).min(sh - 1), (y * 2 + 1).min(sh - 1)];
+        for &sy in &ys {
+            for &sx in &xs {
+                let p = src.get_pixel(sx, sy);
+                for c in 0..4 {
+                    sum[c] += p[c] as u32;
+                }
+            }
+        }
+        image::Rgba(sum.map(|s| ((s + 2) / 4) as u8))
+    })
+}
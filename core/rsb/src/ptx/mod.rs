@@ -1,11 +1,22 @@
 pub mod codec;
 pub mod color;
+pub mod container;
 pub mod decoder;
+pub mod detect;
+pub mod dither;
 pub mod encoder;
+pub mod mipmap;
+pub mod registry;
 pub mod types;
 
+pub use codec::PtxCodec;
+pub use container::{ContainerKind, ContainerTexture, GpuFormat};
 pub use decoder::PtxDecoder;
+pub use detect::{DetectHint, DetectSource, PtxCandidate, PtxDetector};
+pub use dither::{DitherMode, PtxEncodeOptions};
 pub use encoder::PtxEncoder;
+pub use mipmap::MipFilter;
+pub use registry::{PtxPlatform, PtxRegistry, register_codec};
 pub use types::PtxFormat;
 
 #[cfg(test)]
@@ -220,4 +231,404 @@ mod tests {
             "ID 147 with 2x size + 17 bytes should decode as ETC1 + Compressed Alpha with Header"
         );
     }
+
+    #[test]
+    fn test_detect_from_len() {
+        // 256x256 Etc1A8: 32768 (ETC1) + 65536 (alpha)
+        let candidates = PtxDetector::detect_from_len(98304, DetectHint::default());
+        let best = &candidates[0];
+        assert_eq!(best.format, PtxFormat::Etc1A8);
+        assert_eq!((best.width, best.height), (256, 256));
+
+        // Rgba8888 128x128 and Rgba4444 128x256 share a size; the square one wins
+        let candidates = PtxDetector::detect_from_len(65536, DetectHint::default());
+        assert_eq!(candidates[0].format, PtxFormat::Rgba8888);
+        assert_eq!((candidates[0].width, candidates[0].height), (128, 128));
+
+        // A known width pins down a non power-of-two height
+        let hint = DetectHint {
+            width: Some(100),
+            format_code: Some(0),
+            ..Default::default()
+        };
+        let candidates = PtxDetector::detect_from_len(100 * 60 * 4, hint);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].height, 60);
+    }
+
+    #[test]
+    fn test_detect_with_broken_manifest() {
+        let dir = std::env::temp_dir().join(format!("ptx_detect_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("atlases")).unwrap();
+        std::fs::write(dir.join("rsb_manifest.json"), "{ not json").unwrap();
+        let ptx_path = dir.join("atlases").join("page.ptx");
+        std::fs::write(&ptx_path, vec![0u8; 98304]).unwrap();
+
+        let located = PtxDetector::locate_in_manifest(&ptx_path);
+        let candidates = PtxDetector::detect(&[0u8; 98304], Some(&ptx_path), DetectHint::default());
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let (found, warnings) = located.unwrap();
+        assert!(found.is_none());
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("rsb_manifest.json"));
+        let (candidates, warnings) = candidates.unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(candidates[0].source, DetectSource::DataLength);
+        assert_eq!(candidates[0].format, PtxFormat::Etc1A8);
+    }
+
+    #[test]
+    fn test_dither_modes() {
+        // Horizontal gradient that 4-bit truncation flattens into bands
+        let mut img = image::RgbaImage::new(64, 4);
+        for (x, _, p) in img.enumerate_pixels_mut() {
+            *p = Rgba([x as u8 * 2, 0, 0, 255]);
+        }
+        let img = DynamicImage::ImageRgba8(img);
+
+        let plain = PtxEncoder::encode(&img, PtxFormat::Rgba4444, false).unwrap();
+
+        for dither in [DitherMode::FloydSteinberg, DitherMode::Ordered] {
+            let options = PtxEncodeOptions {
+                dither,
+                ..Default::default()
+            };
+            let data = PtxEncoder::encode_with_options(&img, PtxFormat::Rgba4444, false, &options)
+                .unwrap();
+            assert_eq!(data.len(), plain.len());
+            assert_ne!(data, plain, "{:?} should differ from truncation", dither);
+
+            // Average red over the row must stay close to the source
+            let decoded = PtxDecoder::decode(&data, 64, 4, 1, None, None, false)
+                .unwrap()
+                .to_rgba8();
+            let sum: u32 = (0..64).map(|x| decoded.get_pixel(x, 0)[0] as u32).sum();
+            let expected: u32 = (0..64).map(|x| x * 2).sum();
+            assert!(sum.abs_diff(expected) < 64 * 4, "{:?} drifted", dither);
+        }
+    }
+
+    #[test]
+    fn test_dither_none_matches_truncation() {
+        // Bytes written by the truncating encoders before dithering existed
+        let mut img = image::RgbaImage::new(4, 1);
+        img.put_pixel(0, 0, Rgba([0x12, 0x34, 0x56, 0x78]));
+        img.put_pixel(1, 0, Rgba([0xFF, 0x80, 0x7F, 0x80]));
+        img.put_pixel(2, 0, Rgba([0x08, 0xF7, 0x00, 0x7F]));
+        img.put_pixel(3, 0, Rgba([0xAB, 0xCD, 0xEF, 0xFF]));
+        let img = DynamicImage::ImageRgba8(img);
+
+        let golden: [(PtxFormat, [u8; 8]); 3] = [
+            (
+                PtxFormat::Rgba4444,
+                [0x57, 0x13, 0x78, 0xF8, 0x07, 0x0F, 0xEF, 0xAC],
+            ),
+            (
+                PtxFormat::Rgb565,
+                [0xAA, 0x11, 0x0F, 0xFC, 0xA0, 0x0F, 0x7D, 0xAE],
+            ),
+            (
+                PtxFormat::Rgba5551,
+                [0x94, 0x11, 0x1F, 0xFC, 0x80, 0x0F, 0x7B, 0xAE],
+            ),
+        ];
+        for (format, expected) in golden {
+            let data =
+                PtxEncoder::encode_with_options(&img, format, false, &PtxEncodeOptions::default())
+                    .unwrap();
+            assert_eq!(data, expected, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_dither_keeps_alpha_threshold() {
+        // Semi-transparent edge: 1-bit alpha must not be stippled by dithering
+        let mut img = image::RgbaImage::new(16, 16);
+        for (x, y, p) in img.enumerate_pixels_mut() {
+            let alpha = if x < 8 { 100 } else { 200 };
+            *p = Rgba([(x * 16) as u8, (y * 16) as u8, 128, alpha]);
+        }
+        for dither in [DitherMode::FloydSteinberg, DitherMode::Ordered] {
+            let levels = dither::quantize(&img, [5, 5, 5, 1], dither);
+            for (i, level) in levels.iter().enumerate() {
+                let expected = if i % 16 < 8 { 0 } else { 1 };
+                assert_eq!(level[3], expected, "{:?} pixel {}", dither, i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_premultiply_alpha() {
+        let img = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(
+            1,
+            1,
+            Rgba([200, 100, 50, 128]),
+        ));
+        let options = PtxEncodeOptions {
+            premultiply_alpha: true,
+            ..Default::default()
+        };
+        let data =
+            PtxEncoder::encode_with_options(&img, PtxFormat::Rgba8888, false, &options).unwrap();
+        assert_eq!(data, vec![100, 50, 25, 128]);
+    }
+
+    #[test]
+    fn test_mip_chain_round_trip() {
+        let img =
+            DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(16, 8, Rgba([10, 20, 30, 255])));
+        let options = PtxEncodeOptions::default();
+        let count = mipmap::full_mip_count(16, 8);
+        assert_eq!(count, 5);
+
+        for filter in [MipFilter::Box, MipFilter::Lanczos3] {
+            let data =
+                PtxEncoder::encode_mips(&img, PtxFormat::Rgba8888, false, &options, count, filter)
+                    .unwrap();
+            // 16x8 + 8x4 + 4x2 + 2x1 + 1x1 pixels
+            assert_eq!(data.len(), (128 + 32 + 8 + 2 + 1) * 4);
+
+            let levels = PtxDecoder::decode_mips(&data, 16, 8, 0, None, None, false, None).unwrap();
+            assert_eq!(levels.len(), 5);
+            assert_eq!((levels[4].width(), levels[4].height()), (1, 1));
+            assert_eq!(
+                levels[4].to_rgba8().get_pixel(0, 0),
+                &Rgba([10, 20, 30, 255])
+            );
+        }
+
+        // Etc1A8 chains must be split per level rather than by the alpha heuristic
+        let data =
+            PtxEncoder::encode_mips(&img, PtxFormat::Etc1A8, false, &options, 3, MipFilter::Box)
+                .unwrap();
+        let levels = PtxDecoder::decode_mips(&data, 16, 8, 147, None, None, false, None).unwrap();
+        assert_eq!(levels.len(), 3);
+        assert_eq!(levels[0].to_rgba8().get_pixel(0, 0)[3], 255);
+
+        // Single surfaces keep decoding as before
+        let single = PtxEncoder::encode(&img, PtxFormat::Rgba8888, false).unwrap();
+        let levels = PtxDecoder::decode_mips(&single, 16, 8, 0, None, None, false, None).unwrap();
+        assert_eq!(levels.len(), 1);
+    }
+
+    #[test]
+    fn test_registered_codec() {
+        use std::sync::Arc;
+
+        // 8-bit grey, not a real game format
+        struct Gray8;
+
+        impl PtxCodec for Gray8 {
+            fn name(&self) -> &str {
+                "Gray8"
+            }
+
+            fn format(&self) -> PtxFormat {
+                PtxFormat::Unknown(9000)
+            }
+
+            fn decode(
+                &self,
+                data: &[u8],
+                width: u32,
+                height: u32,
+            ) -> crate::error::Result<DynamicImage> {
+                let buf = image::GrayImage::from_raw(width, height, data.to_vec()).unwrap();
+                Ok(DynamicImage::ImageLuma8(buf))
+            }
+
+            fn encode(
+                &self,
+                image: &DynamicImage,
+                _options: &PtxEncodeOptions,
+            ) -> crate::error::Result<Vec<u8>> {
+                Ok(image.to_luma8().into_raw())
+            }
+
+            fn data_size(&self, width: u32, height: u32) -> usize {
+                (width * height) as usize
+            }
+
+            fn block_dims(&self) -> (u32, u32) {
+                (1, 1)
+            }
+        }
+
+        // A local registry keeps the test codec out of the global one other tests use
+        let mut registry = PtxRegistry::with_defaults();
+        registry.register(9000, PtxPlatform::Android, Arc::new(Gray8));
+
+        let img =
+            DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(8, 8, Rgba([90, 90, 90, 255])));
+        let encoder = registry
+            .encoder_for(PtxFormat::Unknown(9000), PtxPlatform::Android)
+            .unwrap();
+        let data = encoder.encode(&img, &PtxEncodeOptions::default()).unwrap();
+        assert_eq!(data.len(), 64);
+        let decoder = registry
+            .decoder_for(9000, PtxPlatform::Android, data.len(), 8, 8)
+            .unwrap();
+        let decoded = decoder.decode(&data, 8, 8).unwrap();
+        assert_eq!(decoded.to_rgba8().get_pixel(3, 3), &Rgba([90, 90, 90, 255]));
+
+        // Only registered for Android
+        assert!(registry.codecs_for(9000, PtxPlatform::Ios).is_empty());
+        assert!(
+            PtxRegistry::global()
+                .codecs_for(9000, PtxPlatform::Android)
+                .is_empty()
+        );
+
+        // Built-in ambiguity is resolved through the registry
+        let registry = PtxRegistry::with_defaults();
+        let pvrtc = registry
+            .decoder_for(30, PtxPlatform::Ios, 32, 8, 8)
+            .unwrap();
+        assert_eq!(pvrtc.format(), PtxFormat::Pvrtc4BppRgba);
+        let palette = registry
+            .decoder_for(30, PtxPlatform::Ios, 100, 8, 8)
+            .unwrap();
+        assert_eq!(palette.format(), PtxFormat::Etc1Palette);
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let mut img = image::RgbaImage::new(16, 8);
+        for (x, y, p) in img.enumerate_pixels_mut() {
+            *p = Rgba([(x * 16) as u8, (y * 32) as u8, 128, (x * y) as u8]);
+        }
+        let img = DynamicImage::ImageRgba8(img);
+
+        let cases = [
+            (PtxFormat::Etc1A8, 147, false, Some(GpuFormat::R8)),
+            (PtxFormat::Rgba8888, 0, true, None),
+            (PtxFormat::Rgb565, 2, false, None),
+        ];
+        for (format, code, powervr, alpha) in cases {
+            let data = PtxEncoder::encode_mips(
+                &img,
+                format,
+                powervr,
+                &PtxEncodeOptions::default(),
+                3,
+                MipFilter::Box,
+            )
+            .unwrap();
+            let surfaces = container::split_ptx(&data, 16, 8, code, None, powervr, None).unwrap();
+            assert_eq!(surfaces.color.levels.len(), 3);
+            assert_eq!(surfaces.alpha.as_ref().map(|a| a.format), alpha);
+
+            for kind in [
+                ContainerKind::Ktx,
+                ContainerKind::Ktx2,
+                ContainerKind::Dds,
+                ContainerKind::Pvr,
+            ] {
+                let wrapped = container::write_container(kind, &surfaces.color).unwrap();
+                assert_eq!(ContainerKind::from_magic(&wrapped), Some(kind));
+                let color = container::read_container(&wrapped).unwrap();
+                assert_eq!(color, surfaces.color);
+
+                let alpha = surfaces.alpha.as_ref().map(|a| {
+                    container::read_container(&container::write_container(kind, a).unwrap())
+                        .unwrap()
+                });
+                let imported = container::join_ptx(&color, alpha.as_ref()).unwrap();
+                assert_eq!(imported.data, data);
+                assert_eq!(imported.format_code, code);
+                assert_eq!(imported.is_powervr, powervr);
+                assert_eq!(imported.mip_count, 3);
+            }
+        }
+
+        // Tiled layouts have no container equivalent
+        let tiled = PtxEncoder::encode(&img, PtxFormat::Rgba4444Block, false).unwrap();
+        assert!(container::split_ptx(&tiled, 16, 8, 21, None, false, None).is_err());
+    }
+
+    #[test]
+    fn test_container_hostile_sizes() {
+        let texture = ContainerTexture {
+            format: GpuFormat::Rgba8,
+            width: 4,
+            height: 4,
+            levels: vec![vec![0x7F; 64]],
+        };
+
+        // KTX imageSize far past the end of the file
+        let mut ktx = container::write_container(ContainerKind::Ktx, &texture).unwrap();
+        ktx[64..68].copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        assert!(container::read_container(&ktx).is_err());
+        ktx.truncate(100);
+        assert!(container::read_container(&ktx).is_err());
+
+        // KTX2 level offset + length overflowing
+        let mut ktx2 = container::write_container(ContainerKind::Ktx2, &texture).unwrap();
+        ktx2[80..88].copy_from_slice(&(u64::MAX - 8).to_le_bytes());
+        assert!(container::read_container(&ktx2).is_err());
+    }
+
+    #[test]
+    fn test_container_hostile_level_counts() {
+        let texture = ContainerTexture {
+            format: GpuFormat::Rgba8,
+            width: 4,
+            height: 4,
+            levels: vec![vec![0x7F; 64]],
+        };
+
+        // Offset of the level count in each header; a 4x4 texture has at most 3 levels
+        for (kind, offset) in [
+            (ContainerKind::Dds, 28),
+            (ContainerKind::Ktx, 56),
+            (ContainerKind::Ktx2, 40),
+            (ContainerKind::Pvr, 44),
+        ] {
+            let mut data = container::write_container(kind, &texture).unwrap();
+            for count in [4, 40, u32::MAX] {
+                data[offset..offset + 4].copy_from_slice(&count.to_le_bytes());
+                let err = container::read_container(&data).unwrap_err();
+                assert!(
+                    err.to_string().contains("mip levels"),
+                    "{:?}: {}",
+                    kind,
+                    err
+                );
+            }
+        }
+
+        assert_eq!(mipmap::mip_dimensions(4096, 8, 40), (1, 1));
+        assert_eq!(mipmap::mip_dimensions(4096, 8, u32::MAX), (1, 1));
+        assert!(PtxDecoder::mip_level_sizes(64, 4, 4, 0, None, false, Some(u32::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_surface_layout() {
+        // 30x18 is not 4-aligned: ETC1 rounds up to 8x5 blocks of 8 bytes
+        let img = DynamicImage::new_rgba8(30, 18);
+        let cases = [
+            (PtxFormat::Rgba8888, 120, 0),
+            (PtxFormat::Rgba4444, 60, 0),
+            (PtxFormat::Rgb565Block, 64, 0),
+            (PtxFormat::Etc1, 16, 0),
+            (PtxFormat::Etc1A8, 16, 30 * 18),
+            // 1-byte count and 16-byte palette, then 4-bit indices
+            (PtxFormat::Etc1Palette, 16, 1 + 16 + 270),
+        ];
+        for (format, pitch, alpha_size) in cases {
+            let data = PtxEncoder::encode(&img, format, false).unwrap();
+            assert_eq!(
+                mipmap::surface_layout(format, 30, 18, data.len()),
+                (pitch, alpha_size),
+                "{:?}",
+                format
+            );
+        }
+        // PVRTC pads to at least 8x8 texels
+        assert_eq!(
+            mipmap::surface_layout(PtxFormat::Pvrtc4BppRgba, 4, 4, 32),
+            (4, 0)
+        );
+    }
 }
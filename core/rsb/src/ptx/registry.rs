@@ -0,0 +1,164 @@
+use crate::error::{Result, RsbError};
+use crate::ptx::codec::PtxCodec;
+use crate::ptx::codec::formats::{
+    Etc1A8Codec, Etc1Codec, Etc1PaletteCodec, Packed16Codec, Pvrtc4BppA8Codec, Pvrtc4BppCodec,
+    Rgba8888Codec,
+};
+use crate::ptx::types::PtxFormat;
+use std::sync::{Arc, OnceLock, RwLock};
+
+/// Target platform of an RSB, which decides how some format ids are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PtxPlatform {
+    Android,
+    /// PowerVR devices; RGBA8888 is stored as BGRA.
+    Ios,
+}
+
+impl PtxPlatform {
+    pub fn from_powervr(is_powervr: bool) -> Self {
+        if is_powervr {
+            PtxPlatform::Ios
+        } else {
+            PtxPlatform::Android
+        }
+    }
+}
+
+/// A codec registered under a format id.
+#[derive(Clone)]
+pub struct PtxRegistration {
+    pub format_code: i32,
+    pub platform: PtxPlatform,
+    pub codec: Arc<dyn PtxCodec>,
+}
+
+/// Codecs keyed by format id and platform.
+///
+/// Several codecs may share an id (30 is PVRTC or ETC1 with palette alpha, 147 is ETC1
+/// with or without alpha); `decoder_for` tells them apart by data length.
+#[derive(Clone, Default)]
+pub struct PtxRegistry {
+    entries: Vec<PtxRegistration>,
+}
+
+/// The global registry is copy-on-write: readers take a snapshot and never hold the
+/// lock, so `register_codec` can't deadlock against a lookup in progress.
+static GLOBAL: OnceLock<RwLock<Arc<PtxRegistry>>> = OnceLock::new();
+
+fn global_lock() -> &'static RwLock<Arc<PtxRegistry>> {
+    GLOBAL.get_or_init(|| RwLock::new(Arc::new(PtxRegistry::with_defaults())))
+}
+
+impl PtxRegistry {
+    /// The formats shipped with the game.
+    pub fn with_defaults() -> Self {
+        let mut registry = Self::default();
+        for platform in [PtxPlatform::Android, PtxPlatform::Ios] {
+            let codecs: [(i32, Arc<dyn PtxCodec>); 12] = [
+                (
+                    0,
+                    Arc::new(Rgba8888Codec {
+                        bgra: platform == PtxPlatform::Ios,
+                    }),
+                ),
+                (1, Arc::new(Packed16Codec::rgba4444(false))),
+                (2, Arc::new(Packed16Codec::rgb565(false))),
+                (3, Arc::new(Packed16Codec::rgba5551(false))),
+                (21, Arc::new(Packed16Codec::rgba4444(true))),
+                (22, Arc::new(Packed16Codec::rgb565(true))),
+                (23, Arc::new(Packed16Codec::rgba5551(true))),
+                (30, Arc::new(Pvrtc4BppCodec)),
+                (30, Arc::new(Etc1PaletteCodec)),
+                (147, Arc::new(Etc1Codec)),
+                (147, Arc::new(Etc1A8Codec)),
+                (148, Arc::new(Pvrtc4BppA8Codec)),
+            ];
+            for (format_code, codec) in codecs {
+                registry.register(format_code, platform, codec);
+            }
+        }
+        registry
+    }
+
+    /// Add a codec. Codecs registered later win over earlier ones of the same format.
+    pub fn register(&mut self, format_code: i32, platform: PtxPlatform, codec: Arc<dyn PtxCodec>) {
+        self.entries.push(PtxRegistration {
+            format_code,
+            platform,
+            codec,
+        });
+    }
+
+    pub fn entries(&self) -> &[PtxRegistration] {
+        &self.entries
+    }
+
+    /// All codecs sharing `format_code` on `platform`, latest registration first.
+    pub fn codecs_for(&self, format_code: i32, platform: PtxPlatform) -> Vec<Arc<dyn PtxCodec>> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| e.format_code == format_code && e.platform == platform)
+            .map(|e| e.codec.clone())
+            .collect()
+    }
+
+    /// Pick the codec for decoding `data_len` bytes of a `width` x `height` surface.
+    ///
+    /// The largest layout that accepts the data wins; if none does, the smallest is
+    /// used so its own size checks can report the problem.
+    pub fn decoder_for(
+        &self,
+        format_code: i32,
+        platform: PtxPlatform,
+        data_len: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<Arc<dyn PtxCodec>> {
+        let mut codecs = self.codecs_for(format_code, platform);
+        // Stable sort keeps later registrations ahead of equal-sized ones
+        codecs.sort_by_key(|c| std::cmp::Reverse(c.data_size(width, height)));
+
+        codecs
+            .iter()
+            .find(|c| c.accepts(data_len, width, height))
+            .or(codecs.last())
+            .cloned()
+            .ok_or_else(|| {
+                RsbError::DeserializationError(format!("Unknown PTX format: {}", format_code))
+            })
+    }
+
+    /// Pick the codec producing `format` on `platform`.
+    pub fn encoder_for(
+        &self,
+        format: PtxFormat,
+        platform: PtxPlatform,
+    ) -> Result<Arc<dyn PtxCodec>> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|e| e.platform == platform && e.codec.format() == format)
+            .map(|e| e.codec.clone())
+            .ok_or_else(|| {
+                RsbError::DeserializationError(format!("Encoding not implemented for {:?}", format))
+            })
+    }
+
+    /// Snapshot of the process-wide registry used by `PtxDecoder` and `PtxEncoder`.
+    /// Codecs registered after the call are not in it.
+    pub fn global() -> Arc<PtxRegistry> {
+        global_lock()
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+/// Register a codec in the global registry, e.g. for a format added by a game update.
+/// Safe to call while snapshots from `PtxRegistry::global` are alive.
+pub fn register_codec(format_code: i32, platform: PtxPlatform, codec: Arc<dyn PtxCodec>) {
+    let mut global = global_lock().write().unwrap_or_else(|e| e.into_inner());
+    Arc::make_mut(&mut global).register(format_code, platform, codec);
+}
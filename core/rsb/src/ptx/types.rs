@@ -32,3 +32,22 @@ impl From<i32> for PtxFormat {
         }
     }
 }
+
+impl PtxFormat {
+    /// Format id as stored in the RSB; ids shared between layouts map to the same value.
+    pub fn code(&self) -> i32 {
+        match self {
+            PtxFormat::Rgba8888 => 0,
+            PtxFormat::Rgba4444 => 1,
+            PtxFormat::Rgb565 => 2,
+            PtxFormat::Rgba5551 => 3,
+            PtxFormat::Rgba4444Block => 21,
+            PtxFormat::Rgb565Block => 22,
+            PtxFormat::Rgba5551Block => 23,
+            PtxFormat::Pvrtc4BppRgba | PtxFormat::Etc1Palette => 30,
+            PtxFormat::Etc1 | PtxFormat::Etc1A8 => 147,
+            PtxFormat::Pvrtc4BppRgbaA8 => 148,
+            PtxFormat::Unknown(n) => *n,
+        }
+    }
+}
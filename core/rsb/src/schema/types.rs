@@ -80,6 +80,9 @@ pub struct RsbPtxInfo {
     pub format: i32,
     pub alpha_size: Option<i32>,
     pub alpha_format: Option<i32>,
+    /// Number of mip levels stored in the PTX data; not part of the binary PTX info.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mip_count: Option<u32>,
 }
 
 // Structs for description.json serialization
@@ -180,4 +183,6 @@ pub struct ManifestPtxProperty {
     pub alpha_size: Option<i32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub alpha_format: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mip_count: Option<u32>,
 }
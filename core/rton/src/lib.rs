@@ -32,6 +32,15 @@ mod tests {
                 "key3".to_string(),
                 RtonValue::Array(vec![RtonValue::Bool(true), RtonValue::Bool(false)]),
             ),
+            (
+                "key4".to_string(),
+                RtonValue::Array(vec![
+                    RtonValue::Rtid(Rtid::Null),
+                    RtonValue::Rtid("RTID(Peashooter@PlantTypes)".parse().unwrap()),
+                    RtonValue::Rtid("RTID(1a.2b.0000003c@)".parse().unwrap()),
+                    RtonValue::Rtid("RTID(1a.2b.0000003c@Name)".parse().unwrap()),
+                ]),
+            ),
         ]);
 
         // Serialize to bytes (using default key/writer logic)
@@ -45,4 +54,37 @@ mod tests {
         // Verify equality
         assert_eq!(original, decoded);
     }
+
+    #[test]
+    fn test_replace_string() {
+        let mut value = RtonValue::Object(vec![
+            ("IMAGE_OLD".to_string(), RtonValue::Int32(1)),
+            ("id".to_string(), RtonValue::String("IMAGE_OLD".to_string())),
+            (
+                "list".to_string(),
+                RtonValue::Array(vec![
+                    RtonValue::String("IMAGE_OLD".to_string()),
+                    RtonValue::String("IMAGE_OLD_2".to_string()),
+                    RtonValue::Rtid("RTID(IMAGE_OLD@Sheet)".parse().unwrap()),
+                ]),
+            ),
+        ]);
+
+        assert_eq!(value.replace_string("IMAGE_OLD", "IMAGE_NEW"), 4);
+        assert_eq!(
+            value,
+            RtonValue::Object(vec![
+                ("IMAGE_NEW".to_string(), RtonValue::Int32(1)),
+                ("id".to_string(), RtonValue::String("IMAGE_NEW".to_string())),
+                (
+                    "list".to_string(),
+                    RtonValue::Array(vec![
+                        RtonValue::String("IMAGE_NEW".to_string()),
+                        RtonValue::String("IMAGE_OLD_2".to_string()),
+                        RtonValue::Rtid("RTID(IMAGE_NEW@Sheet)".parse().unwrap()),
+                    ]),
+                ),
+            ])
+        );
+    }
 }
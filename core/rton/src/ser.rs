@@ -55,6 +55,8 @@ pub struct RtonSerializer<W> {
     next_idx_92: u32,
     is_root: bool,
     pending_varint: PendingVarInt,
+    /// Inside an RTID, whose strings and numbers are written without identifiers
+    in_rtid: bool,
 }
 
 impl<W: Write> RtonSerializer<W> {
@@ -67,6 +69,7 @@ impl<W: Write> RtonSerializer<W> {
             next_idx_92: 0,
             is_root: true,
             pending_varint: PendingVarInt::None,
+            in_rtid: false,
         }
     }
 
@@ -162,7 +165,10 @@ impl<W: Write> ser::Serializer for &mut RtonSerializer<W> {
         match name {
             "RTID" => {
                 self.writer.write_u8(RtonIdentifier::Rtid as u8)?;
-                value.serialize(self)
+                self.in_rtid = true;
+                value.serialize(&mut *self)?;
+                self.in_rtid = false;
+                Ok(())
             }
             "VarIntI32" => {
                 self.pending_varint = PendingVarInt::I32;
@@ -207,6 +213,10 @@ impl<W: Write> ser::Serializer for &mut RtonSerializer<W> {
         Ok(())
     }
     fn serialize_u32(self, v: u32) -> Result<()> {
+        if self.in_rtid {
+            self.writer.write_u32::<LittleEndian>(v)?;
+            return Ok(());
+        }
         if self.pending_varint == PendingVarInt::U32 {
             self.writer.write_u8(RtonIdentifier::VarIntU32 as u8)?;
             self.writer.write_varint(v)?;
@@ -235,6 +245,10 @@ impl<W: Write> ser::Serializer for &mut RtonSerializer<W> {
         Ok(())
     }
     fn serialize_u64(self, v: u64) -> Result<()> {
+        if self.in_rtid {
+            self.writer.write_varint(v)?;
+            return Ok(());
+        }
         if self.pending_varint == PendingVarInt::U64 {
             self.writer.write_u8(RtonIdentifier::VarIntU64 as u8)?;
             self.writer.write_varint(v)?;
@@ -264,6 +278,9 @@ impl<W: Write> ser::Serializer for &mut RtonSerializer<W> {
     }
 
     fn serialize_str(self, v: &str) -> Result<()> {
+        if self.in_rtid {
+            return write_utf8_payload(&mut self.writer, v);
+        }
         if v == "*" {
             self.writer.write_u8(RtonIdentifier::StrNull as u8)?;
             return Ok(());
@@ -122,6 +122,32 @@ pub enum Rtid {
     },
 }
 
+impl Rtid {
+    /// The names around the `@`, leaving out the numeric parts of a UID
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            Rtid::Null => Vec::new(),
+            Rtid::Uid { name, .. } => name.iter().map(String::as_str).collect(),
+            Rtid::Raw { name, parent } => vec![name, parent],
+        }
+    }
+
+    /// Replace every name equal to `from` with `to`, returning how many were replaced
+    pub fn replace_name(&mut self, from: &str, to: &str) -> usize {
+        let names = match self {
+            Rtid::Null => Vec::new(),
+            Rtid::Uid { name, .. } => name.iter_mut().collect(),
+            Rtid::Raw { name, parent } => vec![name, parent],
+        };
+        let mut renamed = 0;
+        for name in names.into_iter().filter(|name| *name == from) {
+            *name = to.to_string();
+            renamed += 1;
+        }
+        renamed
+    }
+}
+
 impl fmt::Display for Rtid {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -138,7 +164,7 @@ impl fmt::Display for Rtid {
                     write!(f, "RTID({:x}.{:x}.{:08x}@)", id, group, obj)
                 }
             }
-            Rtid::Raw { name, parent } => write!(f, "RTID({}@{})", parent, name),
+            Rtid::Raw { name, parent } => write!(f, "RTID({}@{})", name, parent),
         }
     }
 }
@@ -234,10 +260,10 @@ impl Serialize for Rtid {
                 if let Some(n) = name {
                     let mut tup = serializer.serialize_tuple(5)?;
                     tup.serialize_element(&OverrideByte(2))?;
+                    tup.serialize_element(n)?;
                     tup.serialize_element(group)?;
                     tup.serialize_element(id)?;
                     tup.serialize_element(obj)?;
-                    tup.serialize_element(n)?;
                     tup.end()
                 } else {
                     let mut tup = serializer.serialize_tuple(4)?;
@@ -251,8 +277,8 @@ impl Serialize for Rtid {
             Rtid::Raw { name, parent } => {
                 let mut tup = serializer.serialize_tuple(3)?;
                 tup.serialize_element(&OverrideByte(3))?;
-                tup.serialize_element(parent)?;
                 tup.serialize_element(name)?;
+                tup.serialize_element(parent)?;
                 tup.end()
             }
         }
@@ -319,6 +345,33 @@ impl RtonValue {
             RtonValue::UInt64(v)
         }
     }
+
+    /// Replace every string value, object key and RTID name equal to `from` with `to`,
+    /// returning how many were replaced. Partial matches inside longer strings are left
+    /// alone.
+    pub fn replace_string(&mut self, from: &str, to: &str) -> usize {
+        match self {
+            RtonValue::String(s) if s == from => {
+                *s = to.to_string();
+                1
+            }
+            RtonValue::Rtid(rtid) => rtid.replace_name(from, to),
+            RtonValue::Array(values) => values.iter_mut().map(|v| v.replace_string(from, to)).sum(),
+            RtonValue::Object(entries) => entries
+                .iter_mut()
+                .map(|(key, value)| {
+                    let renamed = if key == from {
+                        *key = to.to_string();
+                        1
+                    } else {
+                        0
+                    };
+                    renamed + value.replace_string(from, to)
+                })
+                .sum(),
+            _ => 0,
+        }
+    }
 }
 
 impl Serialize for RtonValue {